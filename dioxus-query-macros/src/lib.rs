@@ -0,0 +1,280 @@
+//! Macros of [dioxus-query](https://github.com/marc2332/dioxus-query), enabled with its `macros` feature.
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::{format_ident, quote};
+use syn::{
+    parse::{ParseStream, Parser},
+    parse_macro_input,
+    punctuated::Punctuated,
+    spanned::Spanned,
+    FnArg, GenericArgument, Ident, ItemFn, LitStr, Pat, PathArguments, ReturnType, Token, Type,
+};
+
+/// The options given to `#[query(...)]`.
+#[derive(Default)]
+struct QueryArgs {
+    keys: Option<LitStr>,
+    value: Option<Type>,
+    error: Option<Type>,
+    key_type: Option<Type>,
+}
+
+/// Declare a query from an async function returning a `Result`, generating:
+/// - `use_<name>(cx, params...)`, a hook registering the query with the keys built from the parameters
+/// - `invalidate_<name>(client, params...)`, invalidating the query with those keys
+///
+/// `keys` lists the query keys, separated by commas, which are also patterns the parameters are taken back from.
+/// Every parameter of the function has to be bound by them. The query is identified by the path of the function,
+/// so it shares its cache entries with every call site.
+///
+/// The value and error types of the client default to the ones of the `Result`, and are converted with [From]
+/// otherwise, e.g to wrap the value in the enum of the app with `value = "QueryValue"`.
+/// The type of the keys defaults to the enum of the first key, or can be given with `key_type = "QueryKeys"`.
+///
+/// ## Example:
+///
+/// ```ignore
+/// #[query(keys = "QueryKeys::User(id)", value = "QueryValue", error = "QueryError")]
+/// async fn fetch_user(id: u64) -> Result<User, ApiError> {
+///     api::get_user(id).await
+/// }
+///
+/// let user = use_fetch_user(cx, *id);
+/// invalidate_fetch_user(client, *id);
+/// ```
+#[proc_macro_attribute]
+pub fn query(args: TokenStream, item: TokenStream) -> TokenStream {
+    let mut query_args = QueryArgs::default();
+    let args_parser = syn::meta::parser(|meta| {
+        if meta.path.is_ident("keys") {
+            query_args.keys = Some(meta.value()?.parse()?);
+        } else if meta.path.is_ident("value") {
+            query_args.value = Some(meta.value()?.parse::<LitStr>()?.parse()?);
+        } else if meta.path.is_ident("error") {
+            query_args.error = Some(meta.value()?.parse::<LitStr>()?.parse()?);
+        } else if meta.path.is_ident("key_type") {
+            query_args.key_type = Some(meta.value()?.parse::<LitStr>()?.parse()?);
+        } else {
+            return Err(meta.error("expected `keys`, `value`, `error` or `key_type`"));
+        }
+        Ok(())
+    });
+    parse_macro_input!(args with args_parser);
+    let query_fn = parse_macro_input!(item as ItemFn);
+
+    expand_query(query_args, query_fn)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn expand_query(args: QueryArgs, query_fn: ItemFn) -> syn::Result<proc_macro2::TokenStream> {
+    let signature = &query_fn.sig;
+    if signature.asyncness.is_none() {
+        return Err(syn::Error::new(
+            signature.fn_token.span(),
+            "#[query] functions must be async",
+        ));
+    }
+    if !signature.generics.params.is_empty() {
+        return Err(syn::Error::new(
+            signature.generics.span(),
+            "#[query] functions can't be generic",
+        ));
+    }
+
+    let Some(keys) = &args.keys else {
+        return Err(syn::Error::new(
+            Span::call_site(),
+            "#[query] needs the query keys, e.g `#[query(keys = \"QueryKeys::User(id)\")]`",
+        ));
+    };
+    let key_patterns = (|input: ParseStream| {
+        Punctuated::<Pat, Token![,]>::parse_terminated_with(input, Pat::parse_multi)
+    })
+    .parse_str(&keys.value())
+    .map_err(|err| syn::Error::new(keys.span(), format!("invalid query keys: {err}")))?;
+    if key_patterns.is_empty() {
+        return Err(syn::Error::new(
+            keys.span(),
+            "#[query] needs at least one query key",
+        ));
+    }
+
+    // Every parameter has to be taken back from the keys
+    let mut bindings = Vec::new();
+    for pattern in &key_patterns {
+        collect_bindings(pattern, &mut bindings);
+    }
+    let mut params = Vec::new();
+    let mut param_types = Vec::new();
+    for input in &signature.inputs {
+        let FnArg::Typed(input) = input else {
+            return Err(syn::Error::new(
+                input.span(),
+                "#[query] functions can't take `self`",
+            ));
+        };
+        let Pat::Ident(param) = &*input.pat else {
+            return Err(syn::Error::new(
+                input.pat.span(),
+                "#[query] function parameters must be plain names",
+            ));
+        };
+        if !bindings.contains(&param.ident) {
+            return Err(syn::Error::new(
+                param.ident.span(),
+                format!(
+                    "the parameter `{}` can't be derived from the query keys `{}`",
+                    param.ident,
+                    keys.value()
+                ),
+            ));
+        }
+        params.push(param.ident.clone());
+        param_types.push(input.ty.clone());
+    }
+
+    let (ok_type, err_type) = result_types(&signature.output);
+    let value_type = args.value.or(ok_type).ok_or_else(|| {
+        syn::Error::new(
+            signature.output.span(),
+            "#[query] functions must return a `Result`, or be given the `value` and `error` types",
+        )
+    })?;
+    let error_type = args.error.or(err_type).ok_or_else(|| {
+        syn::Error::new(
+            signature.output.span(),
+            "#[query] functions must return a `Result`, or be given the `value` and `error` types",
+        )
+    })?;
+    let key_type = match args.key_type {
+        Some(key_type) => key_type,
+        None => key_type_of(&key_patterns[0]).ok_or_else(|| {
+            syn::Error::new(
+                keys.span(),
+                "the type of the query keys can't be inferred, give it with `key_type`",
+            )
+        })?,
+    };
+
+    let vis = &query_fn.vis;
+    let name = &signature.ident;
+    let name_str = name.to_string();
+    let hook = format_ident!("use_{}", name);
+    let invalidate = format_ident!("invalidate_{}", name);
+    let key_patterns = key_patterns.iter().collect::<Vec<_>>();
+    let hook_doc = format!("Register a listener of the query of [{name_str}].");
+    let invalidate_doc = format!("Invalidate the query of [{name_str}], see [{hook}].");
+
+    Ok(quote! {
+        #query_fn
+
+        #[doc = #hook_doc]
+        #[allow(unused_variables)]
+        #vis fn #hook<'a>(
+            cx: &'a ::dioxus::prelude::ScopeState,
+            #(#params: #param_types),*
+        ) -> &'a ::dioxus_query::prelude::UseQuery<#value_type, #error_type, #key_type> {
+            ::dioxus_query::prelude::use_query_config(cx, move || {
+                ::dioxus_query::prelude::QueryConfig::new(
+                    ::std::vec![#(#key_patterns),*],
+                    |keys: ::std::vec::Vec<#key_type>| async move {
+                        match keys.as_slice() {
+                            [#(#key_patterns),*] => {
+                                #(let #params: #param_types = ::std::clone::Clone::clone(#params);)*
+                                match #name(#(#params),*).await {
+                                    ::std::result::Result::Ok(value) => ::dioxus_query::prelude::QueryResult::Ok(
+                                        <#value_type as ::std::convert::From<_>>::from(value),
+                                    ),
+                                    ::std::result::Result::Err(err) => ::dioxus_query::prelude::QueryResult::Err(
+                                        <#error_type as ::std::convert::From<_>>::from(err),
+                                    ),
+                                }
+                            }
+                            _ => ::std::unreachable!("the keys of `{}` don't match its declaration", #name_str),
+                        }
+                    },
+                )
+                .with_id(::std::concat!(::std::module_path!(), "::", #name_str))
+            })
+        }
+
+        #[doc = #invalidate_doc]
+        #vis fn #invalidate(
+            client: &::dioxus_query::prelude::UseQueryClient<#value_type, #error_type, #key_type>,
+            #(#params: #param_types),*
+        ) {
+            client.invalidate_queries(&[#(#key_patterns),*]);
+        }
+    })
+}
+
+/// Collect the names bound by a pattern of the keys.
+fn collect_bindings(pattern: &Pat, bindings: &mut Vec<Ident>) {
+    match pattern {
+        Pat::Ident(pattern) => bindings.push(pattern.ident.clone()),
+        Pat::TupleStruct(pattern) => pattern
+            .elems
+            .iter()
+            .for_each(|elem| collect_bindings(elem, bindings)),
+        Pat::Tuple(pattern) => pattern
+            .elems
+            .iter()
+            .for_each(|elem| collect_bindings(elem, bindings)),
+        Pat::Struct(pattern) => pattern
+            .fields
+            .iter()
+            .for_each(|field| collect_bindings(&field.pat, bindings)),
+        Pat::Paren(pattern) => collect_bindings(&pattern.pat, bindings),
+        _ => {}
+    }
+}
+
+/// Get the `T` and `E` of a `Result<T, E>` return type.
+fn result_types(output: &ReturnType) -> (Option<Type>, Option<Type>) {
+    let ReturnType::Type(_, ty) = output else {
+        return (None, None);
+    };
+    let Type::Path(path) = &**ty else {
+        return (None, None);
+    };
+    let Some(segment) = path.path.segments.last() else {
+        return (None, None);
+    };
+    let PathArguments::AngleBracketed(args) = &segment.arguments else {
+        return (None, None);
+    };
+    if segment.ident != "Result" {
+        return (None, None);
+    }
+    let mut types = args.args.iter().filter_map(|arg| match arg {
+        GenericArgument::Type(ty) => Some(ty.clone()),
+        _ => None,
+    });
+    (types.next(), types.next())
+}
+
+/// Get the enum of a key pattern like `QueryKeys::User(id)`, dropping the variant.
+fn key_type_of(pattern: &Pat) -> Option<Type> {
+    let path = match pattern {
+        Pat::TupleStruct(pattern) => &pattern.path,
+        Pat::Struct(pattern) => &pattern.path,
+        Pat::Path(pattern) => &pattern.path,
+        _ => return None,
+    };
+    let variant_position = path.segments.len().checked_sub(1).filter(|len| *len > 0)?;
+    let segments = path
+        .segments
+        .iter()
+        .take(variant_position)
+        .cloned()
+        .collect();
+    Some(Type::Path(syn::TypePath {
+        qself: None,
+        path: syn::Path {
+            leading_colon: path.leading_colon,
+            segments,
+        },
+    }))
+}
@@ -0,0 +1,61 @@
+//! Query `#[server]` functions of Dioxus fullstack.
+//! Build the server with the `ssr` feature of `dioxus-fullstack` and the client with its `web` feature.
+
+use dioxus::prelude::*;
+use dioxus_fullstack::prelude::*;
+use dioxus_query::prelude::*;
+
+fn main() {
+    LaunchBuilder::new(app).launch();
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+enum QueryKeys {
+    User(usize),
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+enum QueryError {
+    Server(String),
+}
+
+/// The errors of the server functions are errors of the queries
+impl From<ServerFnError> for QueryError {
+    fn from(err: ServerFnError) -> Self {
+        Self::Server(err.to_string())
+    }
+}
+
+#[server]
+async fn get_user_name(id: usize) -> Result<String, ServerFnError> {
+    match id {
+        0 => Ok("Marc".to_string()),
+        _ => Err(ServerFnError::ServerError(format!("User {id} not found"))),
+    }
+}
+
+#[allow(non_snake_case)]
+#[component]
+fn User(cx: Scope, id: usize) -> Element {
+    let user_name = use_server_query(cx, move || vec![QueryKeys::User(*id)], |keys| async move {
+        match keys.first() {
+            Some(QueryKeys::User(id)) => get_user_name(*id).await,
+            None => Err(ServerFnError::ServerError("Missing user id".to_string())),
+        }
+    });
+
+    render!( p { "{user_name.result().value():?}" } )
+}
+
+fn app(cx: Scope) -> Element {
+    use_init_query_client::<String, QueryError, QueryKeys>(cx);
+    let client = use_query_client::<String, QueryError, QueryKeys>(cx);
+
+    let refresh = |_| client.invalidate_query(QueryKeys::User(0));
+
+    render!(
+        User { id: 0 }
+        User { id: 1 }
+        button { onclick: refresh, label { "Refresh" } }
+    )
+}
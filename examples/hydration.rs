@@ -0,0 +1,85 @@
+#![cfg_attr(
+    all(not(debug_assertions), target_os = "windows"),
+    windows_subsystem = "windows"
+)]
+
+use dioxus_desktop::Config;
+use dioxus_query::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tokio::{runtime::Runtime, time::sleep};
+
+use dioxus::prelude::*;
+
+fn main() {
+    // On the server: fetch the data and dehydrate the cache
+    let state = Runtime::new().unwrap().block_on(async {
+        let client = UseQueryClient::<String, QueryError, QueryKeys>::new_detached();
+        client
+            .prefetch_query(vec![QueryKeys::User(0)], fetch_user)
+            .await;
+        client.dehydrate()
+    });
+    let state = serde_json::to_string(&state).unwrap();
+
+    // Render the page with the fetched data, it would be sent along with the state
+    let mut vdom = VirtualDom::new_with_props(
+        app,
+        AppProps {
+            state: state.clone(),
+        },
+    );
+    let _ = vdom.rebuild();
+    println!("Server rendered: {}", dioxus_ssr::render(&vdom));
+
+    // On the client: hydrate the cache with the state, the user is not fetched again
+    dioxus_desktop::launch_with_props(app, AppProps { state }, Config::default());
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+enum QueryKeys {
+    User(usize),
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+enum QueryError {
+    UserNotFound(usize),
+    Unknown,
+}
+
+async fn fetch_user(keys: Vec<QueryKeys>) -> QueryResult<String, QueryError> {
+    if let Some(QueryKeys::User(id)) = keys.first() {
+        println!("Fetching name of user {id}");
+        sleep(Duration::from_millis(650)).await;
+        match id {
+            0 => Ok("Marc".to_string()),
+            _ => Err(QueryError::UserNotFound(*id)),
+        }
+        .into()
+    } else {
+        QueryResult::Err(QueryError::Unknown)
+    }
+}
+
+#[allow(non_snake_case)]
+#[component]
+fn User(cx: Scope, id: usize) -> Element {
+    let user_name = use_query(cx, move || vec![QueryKeys::User(*id)], fetch_user);
+
+    render!( p { "{user_name.result().value():?}" } )
+}
+
+#[derive(Props, PartialEq)]
+struct AppProps {
+    /// The serialized [DehydratedState] sent by the server
+    state: String,
+}
+
+fn app(cx: Scope<AppProps>) -> Element {
+    let client = use_init_query_client_with_config::<String, QueryError, QueryKeys>(cx, || {
+        QueryClientConfig::default().stale_time(Duration::from_secs(60))
+    });
+    cx.use_hook(|| client.hydrate(serde_json::from_str(&cx.props.state).unwrap()));
+
+    render!( User { id: 0 } )
+}
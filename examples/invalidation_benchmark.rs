@@ -0,0 +1,65 @@
+//! Compare invalidating one query among thousands of cached ones by going through the whole cache
+//! against finding it by its keys. Run it with `cargo run --release --example invalidation_benchmark`.
+
+use dioxus_query::prelude::*;
+use std::time::{Duration, Instant};
+
+const ENTRIES: usize = 5_000;
+const INVALIDATIONS: usize = 200;
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+enum QueryKeys {
+    Row(usize),
+    Table,
+}
+
+async fn fetch_row(keys: Vec<QueryKeys>) -> QueryResult<usize, ()> {
+    match keys.first() {
+        Some(QueryKeys::Row(id)) => QueryResult::Ok(*id),
+        _ => QueryResult::Err(()),
+    }
+}
+
+fn main() {
+    tokio::runtime::Runtime::new().unwrap().block_on(async {
+        let client = UseQueryClient::<usize, (), QueryKeys>::new_detached();
+        let rows = (0..ENTRIES)
+            .map(|id| client.register_query(vec![QueryKeys::Row(id), QueryKeys::Table], fetch_row))
+            .collect::<Vec<_>>();
+        for row in &rows {
+            row.fetch().await;
+        }
+
+        let matching = time(|id| {
+            let client = client.clone();
+            async move {
+                client
+                    .manual_invalidate_queries_matching(|keys| keys.contains(&QueryKeys::Row(id)))
+                    .await
+            }
+        })
+        .await;
+        let indexed = time(|id| {
+            let client = client.clone();
+            async move { client.manual_invalidate_query(QueryKeys::Row(id)).await }
+        })
+        .await;
+
+        println!("{INVALIDATIONS} invalidations among {ENTRIES} entries");
+        println!("  going through the cache: {matching:?}");
+        println!("  finding the entries by their keys: {indexed:?}");
+    });
+}
+
+/// How long it takes to invalidate a different row each time.
+async fn time<F>(invalidate: impl Fn(usize) -> F) -> Duration
+where
+    F: std::future::Future<Output = Vec<InvalidationOutcome<QueryKeys, ()>>>,
+{
+    let start = Instant::now();
+    for i in 0..INVALIDATIONS {
+        let outcomes = invalidate(i * (ENTRIES / INVALIDATIONS)).await;
+        assert_eq!(outcomes.len(), 1);
+    }
+    start.elapsed()
+}
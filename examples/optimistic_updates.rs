@@ -0,0 +1,102 @@
+#![cfg_attr(
+    all(not(debug_assertions), target_os = "windows"),
+    windows_subsystem = "windows"
+)]
+
+use dioxus_query::prelude::*;
+use std::{sync::Mutex, time::Duration};
+use tokio::time::sleep;
+
+use dioxus::prelude::*;
+
+fn main() {
+    dioxus_desktop::launch(app);
+}
+
+/// The todos stored on the "server"
+static TODOS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+enum QueryKeys {
+    Todos,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+enum QueryError {}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+enum QueryValue {
+    Todos(Vec<String>),
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+enum MutationError {
+    Rejected(String),
+}
+
+async fn fetch_todos(_keys: Vec<QueryKeys>) -> QueryResult<QueryValue, QueryError> {
+    println!("Fetching todos");
+    sleep(Duration::from_millis(500)).await;
+    Ok(QueryValue::Todos(TODOS.lock().unwrap().clone())).into()
+}
+
+async fn add_todo(todo: String) -> MutationResult<(), MutationError> {
+    println!("Adding todo {todo}");
+    sleep(Duration::from_millis(1500)).await;
+    // The server rejects every other todo
+    let mut todos = TODOS.lock().unwrap();
+    if todos.len() % 2 == 1 {
+        return Err(MutationError::Rejected(todo)).into();
+    }
+    todos.push(todo);
+    Ok(()).into()
+}
+
+fn app(cx: Scope) -> Element {
+    use_init_query_client::<QueryValue, QueryError, QueryKeys>(cx);
+    let todos = use_query(cx, || vec![QueryKeys::Todos], fetch_todos);
+    let add = use_mutation_config(cx, || {
+        MutationConfig::new(add_todo)
+            .on_mutate(
+                // Show the new todo right away, keeping the previous ones to roll back
+                |client: &UseQueryClient<QueryValue, QueryError, QueryKeys>, todo: &String| {
+                    let previous = client.get_query_data(&[QueryKeys::Todos]);
+                    client.update_query_data(&[QueryKeys::Todos], |QueryValue::Todos(todos)| {
+                        todos.push(todo.clone())
+                    });
+                    previous
+                },
+                // Remove it if the server rejected it
+                |client, previous, err, _todo| {
+                    println!("Rolling back: {err:?}");
+                    if let Some(previous) = previous {
+                        client.update_query_data(&[QueryKeys::Todos], |todos| *todos = previous);
+                    }
+                },
+            )
+            // Get the todos as the server stores them once it's done
+            .invalidates(vec![QueryKeys::Todos])
+    });
+    let count = use_state(cx, || 0);
+
+    let onclick = move |_| {
+        count.modify(|count| count + 1);
+        add.mutate(format!("Todo {}", count.get()));
+    };
+
+    let todos = match todos.result().value() {
+        QueryResult::Ok(QueryValue::Todos(todos))
+        | QueryResult::Loading(Some(QueryValue::Todos(todos))) => todos.clone(),
+        _ => Vec::new(),
+    };
+
+    render!(
+        ul {
+            for todo in todos {
+                li { "{todo}" }
+            }
+        }
+        p { "{*add.result():?}" }
+        button { onclick: onclick, "Add todo" }
+    )
+}
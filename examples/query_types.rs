@@ -0,0 +1,73 @@
+#![cfg_attr(
+    all(not(debug_assertions), target_os = "windows"),
+    windows_subsystem = "windows"
+)]
+
+use dioxus_query::prelude::*;
+use std::time::Duration;
+use tokio::time::sleep;
+
+use dioxus::prelude::*;
+
+fn main() {
+    dioxus_desktop::launch(app);
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+enum QueryKeys {
+    User(usize),
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+enum QueryError {
+    UserNotFound(usize),
+    Unknown,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+enum QueryValue {
+    UserName(String),
+}
+
+define_query_types!(app_queries: Value = QueryValue, Error = QueryError, Keys = QueryKeys);
+
+async fn fetch_user(keys: Vec<QueryKeys>) -> app_queries::QueryResult {
+    if let Some(QueryKeys::User(id)) = keys.first() {
+        println!("Fetching name of user {id}");
+        sleep(Duration::from_millis(650)).await;
+        match id {
+            0 => Ok(QueryValue::UserName("Marc".to_string())),
+            _ => Err(QueryError::UserNotFound(*id)),
+        }
+        .into()
+    } else {
+        QueryResult::Err(QueryError::Unknown)
+    }
+}
+
+#[allow(non_snake_case)]
+#[component]
+fn UserName<'a>(cx: Scope<'a>, user: &'a app_queries::Query) -> Element<'a> {
+    render!( p { "{user.result().value():?}" } )
+}
+
+#[allow(non_snake_case)]
+#[component]
+fn User(cx: Scope, id: usize) -> Element {
+    let user = app_queries::use_query(cx, move || vec![QueryKeys::User(*id)], fetch_user);
+
+    render!( UserName { user: user } )
+}
+
+fn app(cx: Scope) -> Element {
+    app_queries::use_init_query_client(cx);
+    let client: &app_queries::QueryClient = app_queries::use_query_client(cx);
+
+    let refresh = |_| client.invalidate_query(QueryKeys::User(0));
+
+    render!(
+        User { id: 0 }
+        User { id: 1 }
+        button { onclick: refresh, label { "Refresh" } }
+    )
+}
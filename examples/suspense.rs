@@ -0,0 +1,68 @@
+#![cfg_attr(
+    all(not(debug_assertions), target_os = "windows"),
+    windows_subsystem = "windows"
+)]
+
+use dioxus_query::prelude::*;
+use std::time::Duration;
+use tokio::time::sleep;
+
+use dioxus::prelude::*;
+
+fn main() {
+    dioxus_desktop::launch(app);
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+enum QueryKeys {
+    User(usize),
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+enum QueryError {
+    UserNotFound(usize),
+    Unknown,
+}
+
+async fn fetch_user(keys: Vec<QueryKeys>) -> QueryResult<String, QueryError> {
+    if let Some(QueryKeys::User(id)) = keys.first() {
+        println!("Fetching name of user {id}");
+        sleep(Duration::from_millis(650)).await;
+        match id {
+            0 => Ok("Marc".to_string()),
+            _ => Err(QueryError::UserNotFound(*id)),
+        }
+        .into()
+    } else {
+        QueryResult::Err(QueryError::Unknown)
+    }
+}
+
+#[allow(non_snake_case)]
+#[component]
+fn User(cx: Scope, id: usize) -> Element {
+    let user_name = use_query(cx, move || vec![QueryKeys::User(*id)], fetch_user);
+
+    // Nothing is rendered until the user is loaded, no need to handle `Loading`
+    let user_name = user_name.suspend(cx)?;
+
+    println!("Showing user {id}");
+
+    match user_name {
+        Ok(user_name) => render!( p { "{user_name}" } ),
+        Err(err) => render!( p { "{err:?}" } ),
+    }
+}
+
+fn app(cx: Scope) -> Element {
+    use_init_query_client::<String, QueryError, QueryKeys>(cx);
+    let client = use_query_client::<String, QueryError, QueryKeys>(cx);
+
+    let refresh = |_| client.invalidate_query(QueryKeys::User(0));
+
+    render!(
+        User { id: 0 }
+        User { id: 1 }
+        button { onclick: refresh, label { "Refresh" } }
+    )
+}
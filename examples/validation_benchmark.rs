@@ -0,0 +1,53 @@
+//! Measure how long validating cached queries takes, the path that checks and marks an entry as fetching
+//! under a single lock. Run it with `cargo run --release --example validation_benchmark`,
+//! and with `--features sync` to measure it with the locks shared between threads.
+
+use dioxus_query::prelude::*;
+use std::time::{Duration, Instant};
+
+const ENTRIES: usize = 1_000;
+const ROUNDS: usize = 100;
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+enum QueryKeys {
+    Row(usize),
+}
+
+async fn fetch_row(keys: Vec<QueryKeys>) -> QueryResult<usize, ()> {
+    match keys.first() {
+        Some(QueryKeys::Row(id)) => QueryResult::Ok(*id),
+        _ => QueryResult::Err(()),
+    }
+}
+
+fn main() {
+    tokio::runtime::Runtime::new().unwrap().block_on(async {
+        // Fresh entries are only checked, stale ones are refetched every time
+        let fresh = time(Duration::from_secs(3600)).await;
+        let stale = time(Duration::ZERO).await;
+
+        println!("{ROUNDS} validations of {ENTRIES} entries");
+        println!("  fresh, only checked: {fresh:?}");
+        println!("  stale, refetched: {stale:?}");
+    });
+}
+
+/// How long it takes to validate every entry, over and over.
+async fn time(stale_time: Duration) -> Duration {
+    let client = UseQueryClient::<usize, (), QueryKeys>::new_detached()
+        .with_config(QueryClientConfig::default().stale_time(stale_time));
+    let rows = (0..ENTRIES)
+        .map(|id| client.register_query(vec![QueryKeys::Row(id)], fetch_row))
+        .collect::<Vec<_>>();
+    for row in &rows {
+        row.fetch().await;
+    }
+
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        for (id, row) in rows.iter().enumerate() {
+            assert_eq!(row.fetch().await, QueryResult::Ok(id));
+        }
+    }
+    start.elapsed()
+}
@@ -0,0 +1,60 @@
+use futures_util::future::poll_fn;
+use std::task::{Poll, Waker};
+
+use crate::sync::{Lock, Shared};
+
+#[derive(Default)]
+struct AbortState {
+    is_aborted: bool,
+    /// Tasks waiting for the fetch to be aborted
+    waiters: Vec<Waker>,
+}
+
+/// Lets a query function know that its fetch was abandoned, see [QueryConfig::new_with_abort_signal](crate::prelude::QueryConfig::new_with_abort_signal).
+///
+/// A fetch is abandoned once a newer fetch of the same query starts, or once its query is removed from the cache,
+/// e.g when its last listener unmounts. Its future is dropped right after, so the signal is only
+/// needed to stop the work it doesn't own, e.g a request made through the browser or a spawned task.
+/// It can be cheaply cloned.
+#[derive(Clone, Default)]
+pub struct QueryAbortSignal {
+    state: Shared<Lock<AbortState>>,
+}
+
+impl PartialEq for QueryAbortSignal {
+    fn eq(&self, other: &Self) -> bool {
+        Shared::ptr_eq(&self.state, &other.state)
+    }
+}
+
+impl QueryAbortSignal {
+    /// Check if the fetch was abandoned.
+    pub fn is_aborted(&self) -> bool {
+        self.state.borrow().is_aborted
+    }
+
+    /// Wait until the fetch is abandoned, e.g to race it against a request.
+    pub async fn aborted(&self) {
+        poll_fn(|cx| {
+            let mut state = self.state.borrow_mut();
+            if state.is_aborted {
+                return Poll::Ready(());
+            }
+            state.waiters.push(cx.waker().clone());
+            Poll::Pending
+        })
+        .await
+    }
+
+    /// Mark the fetch as abandoned, waking up the tasks waiting for it.
+    pub(crate) fn abort(&self) {
+        let waiters = {
+            let mut state = self.state.borrow_mut();
+            state.is_aborted = true;
+            std::mem::take(&mut state.waiters)
+        };
+        for waiter in waiters {
+            waiter.wake();
+        }
+    }
+}
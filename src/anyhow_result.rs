@@ -0,0 +1,31 @@
+use std::sync::Arc;
+
+use crate::{result::QueryResult, use_mutation::MutationResult};
+
+/// The result of a query whose function returns an [anyhow::Result].
+/// The error is wrapped in an [Arc] as the errors of the cache must be cloneable.
+///
+/// ## Example:
+///
+/// ```no_run
+/// async fn fetch_user(keys: Vec<QueryKeys>) -> AnyQueryResult<User> {
+///     let user = api::get_user(keys).await?;
+///     Ok(user).into()
+/// }
+/// ```
+pub type AnyQueryResult<T> = QueryResult<T, Arc<anyhow::Error>>;
+
+/// The result of a mutation whose function returns an [anyhow::Result], see [AnyQueryResult].
+pub type AnyMutationResult<T> = MutationResult<T, Arc<anyhow::Error>>;
+
+impl<T> From<anyhow::Result<T>> for QueryResult<T, Arc<anyhow::Error>> {
+    fn from(value: anyhow::Result<T>) -> Self {
+        value.map_err(Arc::new).into()
+    }
+}
+
+impl<T> From<anyhow::Result<T>> for MutationResult<T, Arc<anyhow::Error>> {
+    fn from(value: anyhow::Result<T>) -> Self {
+        value.map_err(Arc::new).into()
+    }
+}
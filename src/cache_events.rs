@@ -0,0 +1,81 @@
+use std::collections::BTreeMap;
+
+use crate::{
+    online_manager::OnlineStatus,
+    result::QueryResult,
+    sync::{Lock, Shared},
+};
+
+/// A change in the cache of a [UseQueryClient](crate::prelude::UseQueryClient),
+/// see [UseQueryClient::subscribe_cache_events](crate::prelude::UseQueryClient::subscribe_cache_events).
+#[derive(Debug, Clone, PartialEq)]
+pub enum QueryCacheEvent<'a, T, E, K> {
+    /// A new entry was created, either by a listener or a prefetch
+    EntryAdded { query_keys: &'a [K] },
+    /// The cached value of an entry changed
+    Updated {
+        query_keys: &'a [K],
+        result: &'a QueryResult<T, E>,
+    },
+    /// An entry was removed from the cache
+    Removed { query_keys: &'a [K] },
+    /// An entry without listeners was removed to keep the cache under
+    /// [QueryClientConfig::max_entries](crate::prelude::QueryClientConfig::max_entries)
+    Evicted { query_keys: &'a [K] },
+    /// The app went online or offline, see [OnlineManager](crate::prelude::OnlineManager)
+    OnlineStatusChanged { status: OnlineStatus },
+    /// The client was paused or resumed, see [UseQueryClient::pause_all](crate::prelude::UseQueryClient::pause_all)
+    PauseChanged { is_paused: bool },
+}
+
+#[cfg(not(feature = "sync"))]
+pub(crate) type CacheObserverFn<T, E, K> = dyn Fn(&QueryCacheEvent<'_, T, E, K>);
+#[cfg(feature = "sync")]
+pub(crate) type CacheObserverFn<T, E, K> = dyn Fn(&QueryCacheEvent<'_, T, E, K>) + Send + Sync;
+
+/// The observers subscribed to the cache events of a client, by order of subscription.
+pub(crate) struct CacheObservers<T, E, K> {
+    next_id: usize,
+    observers: BTreeMap<usize, Shared<CacheObserverFn<T, E, K>>>,
+}
+
+impl<T, E, K> Default for CacheObservers<T, E, K> {
+    fn default() -> Self {
+        Self {
+            next_id: 0,
+            observers: BTreeMap::new(),
+        }
+    }
+}
+
+impl<T, E, K> CacheObservers<T, E, K> {
+    pub(crate) fn insert(&mut self, observer: Shared<CacheObserverFn<T, E, K>>) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.observers.insert(id, observer);
+        id
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.observers.is_empty()
+    }
+
+    /// Clone the observers out, so they can be called without holding the lock.
+    pub(crate) fn to_vec(&self) -> Vec<Shared<CacheObserverFn<T, E, K>>> {
+        self.observers.values().cloned().collect()
+    }
+}
+
+/// Keeps a cache observer subscribed until dropped,
+/// see [UseQueryClient::subscribe_cache_events](crate::prelude::UseQueryClient::subscribe_cache_events).
+#[must_use = "the observer is unsubscribed as soon as this is dropped"]
+pub struct CacheEventsSubscription<T, E, K> {
+    pub(crate) observers: Shared<Lock<CacheObservers<T, E, K>>>,
+    pub(crate) id: usize,
+}
+
+impl<T, E, K> Drop for CacheEventsSubscription<T, E, K> {
+    fn drop(&mut self) {
+        self.observers.borrow_mut().observers.remove(&self.id);
+    }
+}
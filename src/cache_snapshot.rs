@@ -0,0 +1,149 @@
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{collections::HashSet, hash::Hash, time::Duration};
+
+use crate::{
+    cached_result::SerializableCachedResult, hydration::DehydratedQuery, result::QueryResult,
+    snapshot::QueryEntryStatus, sync, use_query::QueryChange, use_query_client::UseQueryClient,
+};
+
+/// An entry of an exported cache.
+#[derive(Serialize, Deserialize)]
+struct CacheSnapshotEntry<T, E, K> {
+    query_keys: Vec<K>,
+    /// Only informative, for whoever reads the snapshot
+    status: QueryEntryStatus,
+    result: SerializableCachedResult<T, E>,
+}
+
+/// An exported cache, with its entries left unparsed so they can be skipped one by one.
+#[derive(Deserialize)]
+struct RawCacheSnapshot {
+    queries: Vec<serde_json::Value>,
+}
+
+/// How [UseQueryClient::import_snapshot] treats the entries already in the cache.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SnapshotImport {
+    /// Keep the entries missing from the snapshot
+    #[default]
+    Merge,
+    /// Reset the entries missing from the snapshot, as [UseQueryClient::remove_queries] does
+    Replace,
+}
+
+/// An entry of a snapshot that couldn't be imported, see [UseQueryClient::import_snapshot].
+#[derive(Debug)]
+pub struct SkippedSnapshotEntry {
+    /// Position of the entry in the snapshot
+    pub index: usize,
+    /// Why it couldn't be read, e.g because the types changed since it was exported
+    pub error: serde_json::Error,
+}
+
+impl<T, E, K> UseQueryClient<T, E, K>
+where
+    T: 'static + Clone + Serialize + DeserializeOwned,
+    E: 'static + Clone + Serialize + DeserializeOwned,
+    K: 'static + Eq + Hash + Clone + Serialize + DeserializeOwned,
+{
+    /// Export the whole cache as JSON, including errors and loading entries, e.g to reproduce a bug report locally.
+    /// Instants are exported as how long ago they were. Only available with the `serde` feature.
+    /// See [UseQueryClient::import_snapshot].
+    pub fn export_snapshot(&self) -> String {
+        let queries = self
+            .queries_registry
+            .borrow()
+            .iter()
+            .map(|(entry, query_listeners)| {
+                let value = sync::read(&query_listeners.value);
+                CacheSnapshotEntry {
+                    query_keys: entry.query_keys.clone(),
+                    status: QueryEntryStatus::of(&value),
+                    result: SerializableCachedResult::from(&*value),
+                }
+            })
+            .collect::<Vec<_>>();
+        serde_json::json!({ "queries": queries }).to_string()
+    }
+
+    /// Import a cache exported with [UseQueryClient::export_snapshot], notifying the listeners of every imported entry.
+    /// Only available with the `serde` feature.
+    ///
+    /// Imported results are stale, so they are refetched by the next trigger, e.g a component mounting
+    /// or an invalidation, and the app heals itself once the bug is reproduced.
+    /// The results of queries that are not in the cache yet only keep their successful value, until they are created.
+    ///
+    /// Entries that can't be read, e.g because the types changed since they were exported, are skipped and returned,
+    /// it only fails if the snapshot itself can't be read.
+    ///
+    /// ## Example:
+    ///
+    /// ```no_run
+    /// let skipped = client.import_snapshot(&snapshot, SnapshotImport::Replace)?;
+    /// for SkippedSnapshotEntry { index, error } in skipped {
+    ///     println!("Skipped entry {index}: {error}");
+    /// }
+    /// ```
+    pub fn import_snapshot(
+        &self,
+        snapshot: &str,
+        mode: SnapshotImport,
+    ) -> Result<Vec<SkippedSnapshotEntry>, serde_json::Error> {
+        let RawCacheSnapshot { queries } = serde_json::from_str(snapshot)?;
+
+        let mut skipped = Vec::new();
+        let mut entries = Vec::new();
+        for (index, query) in queries.into_iter().enumerate() {
+            match serde_json::from_value::<CacheSnapshotEntry<T, E, K>>(query) {
+                Ok(mut entry) => {
+                    self.normalize_keys(&mut entry.query_keys);
+                    entries.push(entry);
+                }
+                Err(error) => skipped.push(SkippedSnapshotEntry { index, error }),
+            }
+        }
+
+        if mode == SnapshotImport::Replace {
+            let imported_keys = entries
+                .iter()
+                .map(|entry| entry.query_keys.clone())
+                .collect::<HashSet<_>>();
+            self.remove_queries_matching(|query_keys| !imported_keys.contains(query_keys));
+        }
+
+        let mut hydrated_queries = Vec::new();
+        for CacheSnapshotEntry {
+            query_keys, result, ..
+        } in entries
+        {
+            let cached_entries = self.get_entries_with_keys(&query_keys);
+            if cached_entries.is_empty() {
+                let age = result.data_elapsed_ms.map(Duration::from_millis);
+                if let (QueryResult::Ok(data), Some(age)) = (result.value, age) {
+                    hydrated_queries.push(DehydratedQuery {
+                        query_keys,
+                        data,
+                        age,
+                    });
+                }
+                continue;
+            }
+
+            for (_, query_listeners) in cached_entries {
+                // Make sure in-flight fetches and streams don't overwrite it
+                query_listeners.discard();
+                let value = &query_listeners.value;
+                sync::write(value).restore(result.clone());
+                query_listeners.settled.notify();
+                self.mark_used(&query_listeners.last_used);
+                self.emit_cache_updated(&query_keys, value);
+                let change = QueryChange::of(sync::read(value).value());
+                self.notify_entry_listeners(&query_keys, &query_listeners.listeners, value, change);
+            }
+        }
+        self.seed_queries(hydrated_queries, true);
+        self.notify_fetching_listeners();
+
+        Ok(skipped)
+    }
+}
@@ -1,16 +1,117 @@
-use instant::Instant;
-use std::{fmt::Debug, ops::Deref, time::Duration};
+use std::{
+    fmt::{Debug, Display},
+    mem,
+    ops::Deref,
+    time::Duration,
+};
 
-use crate::result::QueryResult;
+use crate::{
+    clock::{Instant, SharedClock},
+    result::QueryResult,
+    use_query_client::QueryEqFn,
+};
 
-const STALE_TIME: u64 = 100;
+/// How long a result is considered fresh by default.
+pub(crate) const STALE_TIME: u64 = 100;
+
+/// A serializable representation of a [CachedResult], e.g for test fixtures.
+/// Instants are stored as how long ago they were, in milliseconds.
+///
+/// It can be (de)serialized with the `serde` feature.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SerializableCachedResult<T, E> {
+    pub value: QueryResult<T, E>,
+    /// How long ago the result last changed
+    pub elapsed_ms: Option<u64>,
+    /// How long ago the result last got a successful value
+    pub data_elapsed_ms: Option<u64>,
+    /// How long ago the result last got an error
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub error_elapsed_ms: Option<u64>,
+    pub has_been_queried: bool,
+    pub has_been_invalidated: bool,
+    /// How long the result is considered fresh after being updated
+    pub stale_time_ms: u64,
+    pub previous_data: Option<T>,
+    pub previous_error: Option<E>,
+}
+
+/// Where the successful value shown by a query comes from, see [CachedResult::data_source].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DataSource {
+    /// Seeded with the initial value of the query, see [QueryConfig::initial](crate::prelude::QueryConfig::initial)
+    Initial,
+    /// Shown until the query gets a result, see [QueryConfig::placeholder_data](crate::prelude::QueryConfig::placeholder_data)
+    Placeholder,
+    /// Returned by the query function or its stream
+    Fetched,
+    /// Hydrated, restored from a persister or imported from a snapshot
+    Restored,
+}
 
 /// Cached result.
 #[derive(Debug, Clone, PartialEq)]
 pub struct CachedResult<T, E> {
     pub(crate) value: QueryResult<T, E>,
     pub(crate) instant: Option<Instant>,
+    pub(crate) data_instant: Option<Instant>,
+    pub(crate) error_instant: Option<Instant>,
     pub(crate) has_been_queried: bool,
+    pub(crate) has_been_invalidated: bool,
+    pub(crate) is_fetching: bool,
+    /// How long the result is considered fresh after being updated
+    pub(crate) stale_time: Duration,
+    /// How long the successful value can be shown, see [QueryConfig::max_age](crate::prelude::QueryConfig::max_age)
+    pub(crate) max_age: Option<Duration>,
+    /// Keep the successful value when a refetch fails, see [QueryConfig::keep_data_on_error](crate::prelude::QueryConfig::keep_data_on_error)
+    pub(crate) keep_data_on_error: bool,
+    /// Source of the current time, shared with the client
+    pub(crate) clock: SharedClock,
+    /// Last successful value, when `value` doesn't have one anymore
+    pub(crate) previous_data: Option<T>,
+    /// Last error, when `value` is not an error anymore
+    pub(crate) previous_error: Option<E>,
+    /// How many fetches have been stored
+    pub(crate) fetch_count: u32,
+    /// How many of the last stored fetches failed in a row
+    pub(crate) consecutive_failures: u32,
+    /// Where the successful value comes from, kept when a later fetch fails
+    pub(crate) data_source: Option<DataSource>,
+    /// Waiting for the app to be back online to be fetched, see [NetworkMode::OfflineFirst](crate::prelude::NetworkMode::OfflineFirst)
+    pub(crate) is_offline_paused: bool,
+}
+
+/// A snapshot of a [CachedResult] with both its last successful value and its last error.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryState<T, E> {
+    /// Last successful value, even if a later fetch failed
+    pub data: Option<T>,
+    /// Last error, even if a later fetch succeeded
+    pub error: Option<E>,
+    /// Whether the error is more recent than the data, see [CachedResult::has_newer_error]
+    pub has_newer_error: bool,
+    /// Whether a fetch is running
+    pub is_fetching: bool,
+    /// Whether the value should be refetched
+    pub is_stale: bool,
+    /// When the value was last updated
+    pub updated_at: Option<Instant>,
+    /// How many times the value has been fetched
+    pub fetch_count: u32,
+    /// How many of the last fetches failed in a row
+    pub consecutive_failures: u32,
+    /// Where the last successful value comes from
+    pub data_source: Option<DataSource>,
+    /// Whether a fetch is waiting for the app to be back online, see [CachedResult::is_offline_paused]
+    pub is_offline_paused: bool,
+}
+
+impl<T, E: Display> QueryState<T, E> {
+    /// Get the last error formatted for display, e.g to render it without downcasting it
+    pub fn error_string(&self) -> Option<String> {
+        self.error.as_ref().map(ToString::to_string)
+    }
 }
 
 impl<T, E> CachedResult<T, E> {
@@ -28,13 +129,289 @@ impl<T, E> CachedResult<T, E> {
 
     /// Check if this result has been mutated recently
     pub fn is_fresh(&self) -> bool {
-        if let Some(instant) = self.instant {
-            instant.elapsed().as_millis() < Duration::from_millis(STALE_TIME).as_millis()
+        if self.has_been_invalidated {
+            false
+        } else if let Some(instant) = self.instant {
+            self.clock.elapsed(instant) < self.stale_time
         } else {
             false
         }
     }
 
+    /// Get when this result last changed
+    pub fn updated_at(&self) -> Option<Instant> {
+        self.instant
+    }
+
+    /// Get how long ago this result last changed
+    pub fn elapsed(&self) -> Option<Duration> {
+        self.instant.map(|instant| self.clock.elapsed(instant))
+    }
+
+    /// Get when this result last got a successful value
+    pub fn data_updated_at(&self) -> Option<Instant> {
+        self.data_instant
+    }
+
+    /// Get when this result last got an error
+    pub fn error_updated_at(&self) -> Option<Instant> {
+        self.error_instant
+    }
+
+    /// Check if the last error is more recent than the last successful value,
+    /// e.g to show the stale data along with a notification of the error.
+    pub fn has_newer_error(&self) -> bool {
+        match (self.error_instant, self.data_instant) {
+            (Some(error_instant), Some(data_instant)) => error_instant > data_instant,
+            (error_instant, _) => error_instant.is_some(),
+        }
+    }
+
+    /// Check if the successful value is older than the max age of the query,
+    /// see [QueryConfig::max_age](crate::prelude::QueryConfig::max_age).
+    /// Expired values are discarded the next time the query is read.
+    pub fn is_expired(&self) -> bool {
+        match (self.max_age, self.data_instant) {
+            (Some(max_age), Some(data_instant)) => {
+                self.data().is_some() && self.clock.elapsed(data_instant) > max_age
+            }
+            _ => false,
+        }
+    }
+
+    /// Get the last successful value, even if a later fetch failed
+    pub fn data(&self) -> Option<&T> {
+        match &self.value {
+            QueryResult::Ok(data) | QueryResult::Loading(Some(data)) => Some(data),
+            _ => self.previous_data.as_ref(),
+        }
+    }
+
+    /// Get the last error, even if a later fetch succeeded
+    pub fn error(&self) -> Option<&E> {
+        match &self.value {
+            QueryResult::Err(error) => Some(error),
+            _ => self.previous_error.as_ref(),
+        }
+    }
+
+    /// Get the last error formatted for display, e.g to render it without downcasting it
+    pub fn error_string(&self) -> Option<String>
+    where
+        E: Display,
+    {
+        self.error().map(ToString::to_string)
+    }
+
+    /// Get a snapshot of this result
+    pub fn state(&self) -> QueryState<T, E>
+    where
+        T: Clone,
+        E: Clone,
+    {
+        QueryState {
+            data: self.data().cloned(),
+            error: self.error().cloned(),
+            has_newer_error: self.has_newer_error(),
+            is_fetching: self.is_fetching,
+            is_stale: !self.is_fresh(),
+            updated_at: self.instant,
+            fetch_count: self.fetch_count,
+            consecutive_failures: self.consecutive_failures,
+            data_source: self.data_source(),
+            is_offline_paused: self.is_offline_paused,
+        }
+    }
+
+    /// Get where the last successful value comes from, `None` if there is none,
+    /// e.g to only show an updating indicator over seeded data.
+    /// A failed refetch keeps the source of the value it didn't replace.
+    pub fn data_source(&self) -> Option<DataSource> {
+        self.data().and(self.data_source)
+    }
+
+    /// Get how many times this result has been fetched, whatever triggered the fetches
+    pub fn fetch_count(&self) -> u32 {
+        self.fetch_count
+    }
+
+    /// Get how many of the last fetches failed in a row, e.g to show which attempt is running.
+    /// It goes back to zero once a fetch succeeds.
+    pub fn consecutive_failures(&self) -> u32 {
+        self.consecutive_failures
+    }
+
+    /// Count a fetch of this result, successful or not.
+    pub(crate) fn count_fetch(&mut self, has_failed: bool) {
+        self.fetch_count = self.fetch_count.saturating_add(1);
+        self.consecutive_failures = if has_failed {
+            self.consecutive_failures.saturating_add(1)
+        } else {
+            0
+        };
+    }
+
+    /// Check if this result is being fetched, either for the first time or in the background
+    pub fn is_fetching(&self) -> bool {
+        self.is_fetching
+    }
+
+    /// Check if this result should have been fetched but the app is offline, so the cached result is served instead
+    /// until it's back online, e.g to show an offline badge rather than an error.
+    /// See [NetworkMode::OfflineFirst](crate::prelude::NetworkMode::OfflineFirst).
+    pub fn is_offline_paused(&self) -> bool {
+        self.is_offline_paused
+    }
+
+    /// Check if this result has never been fetched and isn't being fetched either,
+    /// e.g a lazy query that wasn't triggered yet, see [QueryConfig::fetch_on_mount](crate::prelude::QueryConfig::fetch_on_mount).
+    pub fn is_idle(&self) -> bool {
+        !self.has_been_queried && !self.is_fetching
+    }
+
+    /// Replace the value and everything known about it with the given serialized result, keeping its settings.
+    /// It's left stale, so it's fetched again by the next trigger.
+    pub(crate) fn restore(&mut self, result: SerializableCachedResult<T, E>) {
+        let now = self.clock.now();
+        let instant = |elapsed_ms: Option<u64>| {
+            elapsed_ms.map(|elapsed_ms| {
+                now.checked_sub(Duration::from_millis(elapsed_ms))
+                    .unwrap_or(now)
+            })
+        };
+        *self = Self {
+            value: result.value,
+            instant: instant(result.elapsed_ms),
+            data_instant: instant(result.data_elapsed_ms),
+            error_instant: instant(result.error_elapsed_ms),
+            has_been_queried: true,
+            has_been_invalidated: true,
+            previous_data: result.previous_data,
+            previous_error: result.previous_error,
+            data_source: Some(DataSource::Restored),
+            stale_time: self.stale_time,
+            max_age: self.max_age,
+            keep_data_on_error: self.keep_data_on_error,
+            clock: self.clock.clone(),
+            ..Default::default()
+        };
+    }
+
+    /// Discard the value and everything known about it, keeping its settings.
+    pub(crate) fn reset(&mut self) {
+        *self = Self {
+            stale_time: self.stale_time,
+            max_age: self.max_age,
+            keep_data_on_error: self.keep_data_on_error,
+            clock: self.clock.clone(),
+            ..Default::default()
+        };
+    }
+
+    /// Discard the successful value if it expired, leaving the result stale so it's fetched again.
+    /// Errors are kept, otherwise it goes back to `Loading(None)`.
+    pub(crate) fn expire(&mut self) {
+        if !self.is_expired() {
+            return;
+        }
+        if !self.value.is_err() {
+            self.value = QueryResult::Loading(None);
+        }
+        self.previous_data = None;
+        self.data_source = None;
+        self.has_been_invalidated = true;
+    }
+
+    /// Mark this result as being fetched.
+    /// With `loading` it also changes to `Loading`, keeping the cached value if there is any.
+    pub(crate) fn set_fetching(&mut self, loading: bool) {
+        self.is_fetching = true;
+        self.has_been_queried = true;
+        if loading {
+            // Keep the last successful value while loading
+            let cached_value = match mem::take(&mut self.value) {
+                QueryResult::Ok(data) | QueryResult::Loading(Some(data)) => Some(data),
+                QueryResult::Err(error) => {
+                    self.previous_error = Some(error);
+                    self.previous_data.take()
+                }
+                QueryResult::Loading(None) => self.previous_data.take(),
+            };
+            self.value = QueryResult::Loading(cached_value);
+        }
+    }
+
+    /// Replace the value, keeping aside the last successful value and the last error.
+    fn replace_value(&mut self, value: QueryResult<T, E>) {
+        let previous_value = mem::replace(&mut self.value, value);
+        let has_data = matches!(
+            self.value,
+            QueryResult::Ok(_) | QueryResult::Loading(Some(_))
+        );
+
+        match previous_value {
+            QueryResult::Ok(data) | QueryResult::Loading(Some(data)) if !has_data => {
+                self.previous_data = Some(data);
+            }
+            QueryResult::Err(error) if !self.value.is_err() => {
+                self.previous_error = Some(error);
+            }
+            _ => {}
+        }
+
+        if has_data {
+            self.previous_data = None;
+        }
+        if self.value.is_err() {
+            self.previous_error = None;
+        }
+    }
+
+    /// Store the result of a fetch.
+    /// Returns `false` if `is_equal` considers it the same as the cached one.
+    pub(crate) fn set_value(
+        &mut self,
+        value: QueryResult<T, E>,
+        is_equal: Option<&QueryEqFn<T, E>>,
+    ) -> bool {
+        let has_changed = !is_equal
+            .map(|is_equal| is_equal(&self.value, &value))
+            .unwrap_or_default();
+
+        let now = self.clock.now();
+        self.count_fetch(value.is_err());
+        self.is_fetching = false;
+
+        // Keep showing the successful value, with the error aside, and leave it stale so it's retried
+        if self.keep_data_on_error && self.data().is_some() {
+            if let QueryResult::Err(error) = value {
+                let data = match mem::take(&mut self.value) {
+                    QueryResult::Ok(data) | QueryResult::Loading(Some(data)) => Some(data),
+                    _ => self.previous_data.take(),
+                };
+                self.value = data.map_or(QueryResult::Loading(None), QueryResult::Ok);
+                self.previous_error = Some(error);
+                self.error_instant = Some(now);
+                self.has_been_queried = true;
+                self.has_been_invalidated = true;
+                return true;
+            }
+        }
+
+        if value.is_ok() {
+            self.data_instant = Some(now);
+            self.data_source = Some(DataSource::Fetched);
+        } else if value.is_err() {
+            self.error_instant = Some(now);
+        }
+        self.replace_value(value);
+        self.instant = Some(now);
+        self.has_been_queried = true;
+        self.has_been_invalidated = false;
+
+        has_changed
+    }
+
     /// Check if this result has been mutated at some point
     pub(crate) fn has_been_mutated(&self) -> bool {
         self.instant.is_some()
@@ -46,6 +423,59 @@ impl<T, E> CachedResult<T, E> {
     }
 }
 
+impl<T: Clone, E: Clone> From<&CachedResult<T, E>> for SerializableCachedResult<T, E> {
+    fn from(result: &CachedResult<T, E>) -> Self {
+        let elapsed_ms = |instant: Option<Instant>| {
+            instant.map(|instant| result.clock.elapsed(instant).as_millis() as u64)
+        };
+        Self {
+            value: result.value.clone(),
+            elapsed_ms: elapsed_ms(result.instant),
+            data_elapsed_ms: elapsed_ms(result.data_instant),
+            error_elapsed_ms: elapsed_ms(result.error_instant),
+            has_been_queried: result.has_been_queried,
+            has_been_invalidated: result.has_been_invalidated,
+            stale_time_ms: result.stale_time.as_millis() as u64,
+            previous_data: result.previous_data.clone(),
+            previous_error: result.previous_error.clone(),
+        }
+    }
+}
+
+impl<T, E> From<SerializableCachedResult<T, E>> for CachedResult<T, E> {
+    fn from(result: SerializableCachedResult<T, E>) -> Self {
+        let clock = SharedClock::default();
+        let now = clock.now();
+        let instant = |elapsed_ms: Option<u64>| {
+            elapsed_ms.map(|elapsed_ms| {
+                now.checked_sub(Duration::from_millis(elapsed_ms))
+                    .unwrap_or(now)
+            })
+        };
+        Self {
+            value: result.value,
+            instant: instant(result.elapsed_ms),
+            data_instant: instant(result.data_elapsed_ms),
+            error_instant: instant(result.error_elapsed_ms),
+            has_been_queried: result.has_been_queried,
+            has_been_invalidated: result.has_been_invalidated,
+            is_fetching: false,
+            stale_time: Duration::from_millis(result.stale_time_ms),
+            max_age: None,
+            keep_data_on_error: false,
+            clock,
+            previous_data: result.previous_data,
+            previous_error: result.previous_error,
+            fetch_count: 0,
+            consecutive_failures: 0,
+            data_source: Some(DataSource::Restored),
+            is_offline_paused: false,
+        }
+    }
+}
+
+/// Gives access to the accessors of the [QueryResult], e.g [QueryResult::ok], straight from the guard of a query result.
+/// Note that [CachedResult::data] also falls back to the previous data when the result errored.
 impl<T, E> Deref for CachedResult<T, E> {
     type Target = QueryResult<T, E>;
 
@@ -59,7 +489,80 @@ impl<T, E> Default for CachedResult<T, E> {
         Self {
             value: Default::default(),
             instant: None,
+            data_instant: None,
+            error_instant: None,
             has_been_queried: false,
+            has_been_invalidated: false,
+            is_fetching: false,
+            stale_time: Duration::from_millis(STALE_TIME),
+            max_age: None,
+            keep_data_on_error: false,
+            clock: SharedClock::default(),
+            previous_data: None,
+            previous_error: None,
+            fetch_count: 0,
+            consecutive_failures: 0,
+            data_source: None,
+            is_offline_paused: false,
         }
     }
 }
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::*;
+    use crate::{clock::MockClock, use_mutation::MutationResult};
+    use serde::{de::DeserializeOwned, Serialize};
+    use std::fmt::Debug;
+
+    fn round_trip<V: Serialize + DeserializeOwned + PartialEq + Debug>(value: V) {
+        let json = serde_json::to_string(&value).unwrap();
+        assert_eq!(serde_json::from_str::<V>(&json).unwrap(), value, "{json}");
+    }
+
+    #[test]
+    fn query_results_round_trip() {
+        round_trip(QueryResult::<usize, String>::Ok(1));
+        round_trip(QueryResult::<usize, String>::Err("failed".to_string()));
+        round_trip(QueryResult::<usize, String>::Loading(Some(1)));
+        round_trip(QueryResult::<usize, String>::Loading(None));
+    }
+
+    #[test]
+    fn mutation_results_round_trip() {
+        round_trip(MutationResult::<usize, String>::Ok(1));
+        round_trip(MutationResult::<usize, String>::Err("failed".to_string()));
+        round_trip(MutationResult::<usize, String>::Loading(Some(1)));
+        round_trip(MutationResult::<usize, String>::Loading(None));
+        round_trip(MutationResult::<usize, String>::Pending);
+        round_trip(MutationResult::<usize, String>::Queued(Some(1)));
+        round_trip(MutationResult::<usize, String>::Queued(None));
+    }
+
+    #[test]
+    fn cached_results_round_trip_with_their_age() {
+        let clock = MockClock::default();
+        let now = clock.now();
+        let result = CachedResult {
+            value: QueryResult::<usize, String>::Loading(Some(1)),
+            instant: Some(now),
+            data_instant: Some(now),
+            has_been_queried: true,
+            previous_error: Some("failed".to_string()),
+            clock: SharedClock::new(clock.clone()),
+            ..Default::default()
+        };
+        clock.advance(Duration::from_millis(1500));
+
+        let serializable = SerializableCachedResult::from(&result);
+        assert_eq!(serializable.elapsed_ms, Some(1500));
+        assert_eq!(serializable.data_elapsed_ms, Some(1500));
+        assert_eq!(serializable.error_elapsed_ms, None);
+        round_trip(serializable.clone());
+
+        let restored = CachedResult::from(serializable);
+        assert_eq!(restored.value(), result.value());
+        assert_eq!(restored.previous_error, result.previous_error);
+        assert!(restored.elapsed().unwrap() >= Duration::from_millis(1500));
+    }
+}
@@ -0,0 +1,170 @@
+//! Time source of the cache.
+//! It uses the `instant` crate so it also works on `wasm32-unknown-unknown`,
+//! where `std::time::Instant::now` panics.
+
+use futures_util::{future::poll_fn, Future};
+use std::{fmt::Debug, task::Poll, task::Waker, time::Duration};
+
+pub use instant::Instant;
+
+use crate::sync::{Lock, MaybeSend, MaybeSync, Shared};
+
+/// Source of the current time used to track the freshness of the cached results,
+/// see [QueryClientConfig::clock](crate::prelude::QueryClientConfig::clock).
+pub trait Clock: MaybeSend + MaybeSync {
+    /// Get the current time.
+    fn now(&self) -> Instant;
+
+    /// Wait for the given duration, e.g between the refetches of a
+    /// [QueryConfig::refetch_interval](crate::prelude::QueryConfig::refetch_interval).
+    /// By default it uses the timers of the `async-io` or `tokio` feature, or the browser timers of the `web` feature on `wasm32`.
+    fn sleep(&self, duration: Duration) -> Box<dyn Future<Output = ()>> {
+        sleep(duration)
+    }
+}
+
+/// Wait for the given duration with a timer of tokio, which needs to run within a tokio runtime.
+#[cfg(all(not(target_arch = "wasm32"), feature = "tokio"))]
+pub(crate) fn sleep(duration: Duration) -> Box<dyn Future<Output = ()>> {
+    Box::new(tokio::time::sleep(duration))
+}
+
+/// Wait for the given duration with a timer of async-io, which works with any executor.
+#[cfg(all(
+    not(target_arch = "wasm32"),
+    feature = "async-io",
+    not(feature = "tokio")
+))]
+pub(crate) fn sleep(duration: Duration) -> Box<dyn Future<Output = ()>> {
+    Box::new(async move {
+        async_io::Timer::after(duration).await;
+    })
+}
+
+#[cfg(all(
+    not(target_arch = "wasm32"),
+    not(any(feature = "tokio", feature = "async-io"))
+))]
+compile_error!(
+    "dioxus-query needs a timer on native targets, enable its `async-io` (default) or `tokio` feature"
+);
+
+/// Wait for the given duration with a browser timer.
+#[cfg(all(target_arch = "wasm32", feature = "web"))]
+pub(crate) fn sleep(duration: Duration) -> Box<dyn Future<Output = ()>> {
+    let millis = duration.as_millis().min(u32::MAX as u128) as u32;
+    Box::new(gloo_timers::future::TimeoutFuture::new(millis))
+}
+
+#[cfg(all(target_arch = "wasm32", not(feature = "web")))]
+compile_error!("dioxus-query needs the browser timers of its `web` feature on `wasm32`");
+
+/// The real clock, used by default.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// A clock that only moves when told to, so staleness and intervals can be tested without waiting.
+///
+/// ## Example:
+///
+/// ```no_run
+/// let clock = MockClock::default();
+/// let config = QueryClientConfig::default().clock(clock.clone());
+/// // ...
+/// clock.advance(Duration::from_secs(60));
+/// ```
+#[derive(Clone)]
+pub struct MockClock {
+    now: Shared<Lock<Instant>>,
+    /// Tasks waiting in [Clock::sleep]
+    sleepers: Shared<Lock<Vec<Waker>>>,
+}
+
+impl Default for MockClock {
+    fn default() -> Self {
+        Self {
+            now: Shared::new(Lock::new(Instant::now())),
+            sleepers: Shared::default(),
+        }
+    }
+}
+
+impl MockClock {
+    /// Move the clock forward, waking the sleeps that are over.
+    pub fn advance(&self, duration: Duration) {
+        *self.now.borrow_mut() += duration;
+        let sleepers = std::mem::take(&mut *self.sleepers.borrow_mut());
+        for sleeper in sleepers {
+            sleeper.wake();
+        }
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> Instant {
+        *self.now.borrow()
+    }
+
+    /// Wait until the clock is advanced past the given duration.
+    fn sleep(&self, duration: Duration) -> Box<dyn Future<Output = ()>> {
+        let deadline = self.now() + duration;
+        let clock = self.clone();
+        Box::new(poll_fn(move |cx| {
+            if clock.now() >= deadline {
+                return Poll::Ready(());
+            }
+            clock.sleepers.borrow_mut().push(cx.waker().clone());
+            Poll::Pending
+        }))
+    }
+}
+
+/// The clock shared by a client and its cached results.
+#[derive(Clone)]
+pub(crate) struct SharedClock(Shared<dyn Clock>);
+
+impl SharedClock {
+    pub(crate) fn new(clock: impl Clock + 'static) -> Self {
+        Self(Shared::new(clock))
+    }
+
+    /// Get the current time.
+    pub(crate) fn now(&self) -> Instant {
+        self.0.now()
+    }
+
+    /// Wait for the given duration.
+    pub(crate) fn sleep(&self, duration: Duration) -> Box<dyn Future<Output = ()>> {
+        self.0.sleep(duration)
+    }
+
+    /// Get how much time has passed since the given instant.
+    pub(crate) fn elapsed(&self, since: Instant) -> Duration {
+        self.now().saturating_duration_since(since)
+    }
+}
+
+impl Default for SharedClock {
+    fn default() -> Self {
+        Self::new(SystemClock)
+    }
+}
+
+impl Debug for SharedClock {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("SharedClock")
+    }
+}
+
+/// The clock doesn't take part in the comparison of cached results or configurations.
+impl PartialEq for SharedClock {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
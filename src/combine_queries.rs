@@ -0,0 +1,284 @@
+use std::hash::Hash;
+
+use crate::{
+    query_handle::QueryHandle, result::QueryResult, use_dependent_query::UseDependentQuery,
+    use_query::UseQuery,
+};
+
+/// A query listener whose current result can be combined with others, see [combine_queries].
+pub trait CombinableQuery {
+    type Data;
+    type Error;
+
+    /// Get a copy of the current result.
+    fn current_result(&self) -> QueryResult<Self::Data, Self::Error>;
+}
+
+impl<T: Clone, E: Clone> CombinableQuery for QueryResult<T, E> {
+    type Data = T;
+    type Error = E;
+
+    fn current_result(&self) -> QueryResult<T, E> {
+        self.clone()
+    }
+}
+
+impl<T: Clone, E: Clone, K: Eq + Hash> CombinableQuery for UseQuery<T, E, K> {
+    type Data = T;
+    type Error = E;
+
+    fn current_result(&self) -> QueryResult<T, E> {
+        self.result().value().clone()
+    }
+}
+
+impl<T: Clone, E: Clone, K: Eq + Hash> CombinableQuery for QueryHandle<T, E, K> {
+    type Data = T;
+    type Error = E;
+
+    fn current_result(&self) -> QueryResult<T, E> {
+        self.result().value().clone()
+    }
+}
+
+impl<T: Clone, E: Clone, K: Eq + Hash> CombinableQuery for UseDependentQuery<T, E, K> {
+    type Data = T;
+    type Error = E;
+
+    fn current_result(&self) -> QueryResult<T, E> {
+        self.result()
+    }
+}
+
+/// The state of several queries at once, see [combine_queries].
+#[derive(Clone, Debug, PartialEq)]
+pub struct CombinedQueries<D, E> {
+    any_loading: bool,
+    error: Option<E>,
+    data: Option<D>,
+}
+
+impl<D, E> CombinedQueries<D, E> {
+    /// Check if none of the queries is loading, whether they succeeded or errored.
+    pub fn all_settled(&self) -> bool {
+        !self.any_loading
+    }
+
+    /// Check if any of the queries is loading, including refetches showing their previous value.
+    pub fn any_loading(&self) -> bool {
+        self.any_loading
+    }
+
+    /// Get the error of the first query that errored, in the order they were given,
+    /// even if other queries are still loading.
+    pub fn first_error(&self) -> Option<&E> {
+        self.error.as_ref()
+    }
+
+    /// Get the values of every query, only once all of them succeeded.
+    pub fn data(&self) -> Option<&D> {
+        self.data.as_ref()
+    }
+
+    /// Get the values of every query, see [CombinedQueries::data].
+    pub fn into_data(self) -> Option<D> {
+        self.data
+    }
+
+    /// Get a single result for all the queries: the first error if any of them errored,
+    /// otherwise `Loading` if any of them is loading, otherwise the values of every query.
+    pub fn into_result(self) -> QueryResult<D, E> {
+        match (self.error, self.data) {
+            (Some(err), _) => QueryResult::Err(err),
+            (None, Some(data)) => QueryResult::Ok(data),
+            (None, None) => QueryResult::Loading(None),
+        }
+    }
+}
+
+impl<T, E> CombinedQueries<Vec<T>, E> {
+    /// Combine the given results, in order.
+    pub fn from_results(results: impl IntoIterator<Item = QueryResult<T, E>>) -> Self {
+        let mut any_loading = false;
+        let mut error = None;
+        let mut values = Some(Vec::new());
+        for result in results {
+            match result {
+                QueryResult::Ok(value) => {
+                    if let Some(values) = &mut values {
+                        values.push(value);
+                    }
+                }
+                QueryResult::Err(err) => {
+                    error.get_or_insert(err);
+                    values = None;
+                }
+                QueryResult::Loading(_) => {
+                    any_loading = true;
+                    values = None;
+                }
+            }
+        }
+        Self {
+            any_loading,
+            error,
+            data: values,
+        }
+    }
+}
+
+/// A tuple of queries that can be combined, see [combine_queries].
+/// It's implemented for tuples of up to 8 references to [CombinableQuery] with the same error type.
+pub trait QueryTuple {
+    type Data;
+    type Error;
+
+    /// Combine the current results of the queries.
+    fn combine(&self) -> CombinedQueries<Self::Data, Self::Error>;
+}
+
+macro_rules! impl_query_tuple {
+    ($($query:ident $result:ident),+) => {
+        impl<'a, E, $($query),+> QueryTuple for ($(&'a $query,)+)
+        where
+            $($query: CombinableQuery<Error = E> + ?Sized,)+
+        {
+            type Data = ($($query::Data,)+);
+            type Error = E;
+
+            fn combine(&self) -> CombinedQueries<Self::Data, E> {
+                let ($($result,)+) = self;
+                let mut any_loading = false;
+                let mut error = None;
+                $(
+                    let $result = match $result.current_result() {
+                        QueryResult::Ok(value) => Some(value),
+                        QueryResult::Err(err) => {
+                            error.get_or_insert(err);
+                            None
+                        }
+                        QueryResult::Loading(_) => {
+                            any_loading = true;
+                            None
+                        }
+                    };
+                )+
+                let data = match ($($result,)+) {
+                    ($(Some($result),)+) => Some(($($result,)+)),
+                    _ => None,
+                };
+                CombinedQueries {
+                    any_loading,
+                    error,
+                    data,
+                }
+            }
+        }
+    };
+}
+
+impl_query_tuple!(A a);
+impl_query_tuple!(A a, B b);
+impl_query_tuple!(A a, B b, C c);
+impl_query_tuple!(A a, B b, C c, D d);
+impl_query_tuple!(A a, B b, C c, D d, F f);
+impl_query_tuple!(A a, B b, C c, D d, F f, G g);
+impl_query_tuple!(A a, B b, C c, D d, F f, G g, H h);
+impl_query_tuple!(A a, B b, C c, D d, F f, G g, H h, I i);
+
+/// Combine the current results of several queries, e.g to render a screen only once all of them are loaded.
+/// It only reads the queries, the component is already re-rendered when any of them changes.
+///
+/// The values are only available once every query succeeded, and the first error wins over the queries still loading,
+/// see [CombinedQueries::into_result]. Use [UseQueries::combined](crate::prelude::UseQueries::combined) for a dynamic list.
+///
+/// ## Example:
+///
+/// ```no_run
+/// let user = use_query(cx, || vec![QueryKeys::User(id)], fetch_user);
+/// let posts = use_query(cx, || vec![QueryKeys::Posts(id)], fetch_posts);
+/// let combined = combine_queries((user, posts));
+///
+/// if let Some(err) = combined.first_error() {
+///     return render!( p { "{err}" } );
+/// }
+/// match combined.into_data() {
+///     Some((user, posts)) => render!( Profile { user: user, posts: posts } ),
+///     None => render!( p { "Loading..." } ),
+/// }
+/// ```
+pub fn combine_queries<Q: QueryTuple>(queries: Q) -> CombinedQueries<Q::Data, Q::Error> {
+    queries.combine()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type TestResult<T> = QueryResult<T, String>;
+
+    fn err<T>(error: &str) -> TestResult<T> {
+        QueryResult::Err(error.to_string())
+    }
+
+    #[test]
+    fn values_are_combined_once_every_query_succeeded() {
+        let (id, name): (TestResult<usize>, TestResult<&str>) =
+            (QueryResult::Ok(1), QueryResult::Ok("a"));
+        let combined = combine_queries((&id, &name));
+        assert!(combined.all_settled());
+        assert_eq!(combined.first_error(), None);
+        assert_eq!(combined.data(), Some(&(1, "a")));
+        assert_eq!(combined.into_result(), QueryResult::Ok((1, "a")));
+    }
+
+    #[test]
+    fn any_loading_query_hides_the_values() {
+        let ok: TestResult<usize> = QueryResult::Ok(1);
+        let loadings: [TestResult<usize>; 2] =
+            [QueryResult::Loading(None), QueryResult::Loading(Some(2))];
+        for loading in loadings {
+            let combined = combine_queries((&ok, &loading));
+            assert!(combined.any_loading());
+            assert_eq!(combined.data(), None);
+            assert_eq!(combined.into_result(), QueryResult::Loading(None));
+        }
+    }
+
+    #[test]
+    fn the_first_error_wins_over_the_loading_queries() {
+        let ok: TestResult<usize> = QueryResult::Ok(1);
+        let loading: TestResult<usize> = QueryResult::Loading(Some(2));
+        let combined = combine_queries((
+            &loading,
+            &ok,
+            &err::<usize>("first"),
+            &err::<usize>("second"),
+        ));
+        assert!(combined.any_loading());
+        assert!(!combined.all_settled());
+        assert_eq!(combined.first_error(), Some(&"first".to_string()));
+        assert_eq!(combined.data(), None);
+        assert_eq!(combined.into_result(), err("first"));
+
+        let combined = combine_queries((&ok, &err::<usize>("failed")));
+        assert!(combined.all_settled());
+        assert_eq!(combined.into_result(), err("failed"));
+    }
+
+    #[test]
+    fn results_are_combined_in_order() {
+        let combined = CombinedQueries::from_results([TestResult::Ok(1), TestResult::Ok(2)]);
+        assert_eq!(combined.into_result(), QueryResult::Ok(vec![1, 2]));
+
+        let combined = CombinedQueries::from_results([
+            QueryResult::Ok(1),
+            QueryResult::Loading(None),
+            err("first"),
+            err("second"),
+        ]);
+        assert!(combined.any_loading());
+        assert_eq!(combined.first_error(), Some(&"first".to_string()));
+        assert_eq!(combined.into_result(), err("first"));
+    }
+}
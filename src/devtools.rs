@@ -0,0 +1,111 @@
+use dioxus::prelude::*;
+use std::{fmt::Debug, hash::Hash};
+
+use crate::use_is_fetching::use_fetching_listener;
+
+/// A panel listing every query in the cache with its keys, status, age and listeners,
+/// and buttons to refetch or remove each of them. Only available with the `devtools` feature.
+///
+/// It is re-rendered whenever a query starts or finishes fetching, and whenever the cache changes.
+///
+/// ## Example:
+///
+/// ```no_run
+/// render!(
+///     App {}
+///     QueryDevtools::<QueryValue, QueryError, QueryKeys> {}
+/// )
+/// ```
+#[allow(non_snake_case)]
+pub fn QueryDevtools<T, E, K>(cx: Scope) -> Element
+where
+    T: 'static + Clone,
+    E: 'static + Clone,
+    K: 'static + Eq + Hash + Clone + Debug,
+{
+    let client = use_fetching_listener::<T, E, K>(cx);
+    cx.use_hook(|| {
+        let update = cx.schedule_update();
+        client.subscribe_cache_events(move |_| update())
+    });
+    let is_open = use_state(cx, || true);
+
+    let entries = client.snapshot();
+    let entries_count = entries.len();
+    let toggle_label = if **is_open {
+        "Hide queries"
+    } else {
+        "Show queries"
+    };
+    let paused_label = if client.is_paused() { ", paused" } else { "" };
+
+    render!(
+        div {
+            style: "position: fixed; bottom: 0; right: 0; max-height: 50%; overflow: auto; padding: 8px; background: white; color: black; border: 1px solid gray; font-family: monospace; font-size: 12px;",
+            button {
+                onclick: move |_| is_open.set(!is_open.get()),
+                "{toggle_label} ({entries_count}{paused_label})"
+            }
+            (**is_open).then(|| {
+                rsx!(
+                    table {
+                        tr {
+                            th { "Keys" }
+                            th { "Status" }
+                            th { "Age" }
+                            th { "Listeners" }
+                            th {}
+                        }
+                        entries.into_iter().map(|entry| {
+                            let keys = format!("{:?}", entry.query_keys);
+                            let status = if entry.is_fetching {
+                                format!("{:?} (fetching)", entry.status)
+                            } else {
+                                format!("{:?}", entry.status)
+                            };
+                            let age = entry
+                                .updated_at
+                                .map(|updated_at| format!("{}s", client.config.clock.elapsed(updated_at).as_secs()))
+                                .unwrap_or_else(|| "-".to_string());
+                            let listener_count = entry.listener_count;
+                            let meta = entry
+                                .meta
+                                .iter()
+                                .map(|(key, value)| format!("{key}: {value}"))
+                                .collect::<Vec<_>>()
+                                .join(", ");
+                            let refetch_keys = entry.query_keys.clone();
+                            let remove_keys = entry.query_keys;
+                            rsx!(
+                                tr {
+                                    td { title: "{meta}", "{keys}" }
+                                    td { "{status}" }
+                                    td { "{age}" }
+                                    td { "{listener_count}" }
+                                    td {
+                                        button {
+                                            onclick: move |_| {
+                                                let client = client.clone();
+                                                let query_keys = refetch_keys.clone();
+                                                spawn(async move {
+                                                    client
+                                                        .invalidate_queries_inner(|keys| keys == query_keys, true, false)
+                                                        .await;
+                                                });
+                                            },
+                                            "Refetch"
+                                        }
+                                        button {
+                                            onclick: move |_| client.remove_queries_matching(|keys| keys == remove_keys),
+                                            "Remove"
+                                        }
+                                    }
+                                }
+                            )
+                        })
+                    }
+                )
+            })
+        }
+    )
+}
@@ -0,0 +1,81 @@
+use futures_util::future::poll_fn;
+use std::{
+    fmt::Debug,
+    task::{Poll, Waker},
+};
+
+use crate::sync::{Lock, Shared};
+
+struct LimiterState {
+    /// How many more query functions can run right now
+    available: usize,
+    /// Fetches waiting for a query function to finish
+    waiters: Vec<Waker>,
+}
+
+/// Limits how many query functions run at once, see [QueryClientConfig::max_concurrent_fetches](crate::prelude::QueryClientConfig::max_concurrent_fetches).
+/// Its clones share the same limit.
+#[derive(Clone)]
+pub(crate) struct FetchLimiter {
+    state: Shared<Lock<LimiterState>>,
+}
+
+impl FetchLimiter {
+    pub(crate) fn new(max_concurrent_fetches: usize) -> Self {
+        Self {
+            state: Shared::new(Lock::new(LimiterState {
+                available: max_concurrent_fetches.max(1),
+                waiters: Vec::new(),
+            })),
+        }
+    }
+
+    /// Wait until a query function can run.
+    /// Dropping the returned future before it's ready, e.g when the fetch is aborted, doesn't take a permit.
+    pub(crate) async fn acquire(&self) -> FetchPermit {
+        poll_fn(|cx| {
+            let mut state = self.state.borrow_mut();
+            if state.available > 0 {
+                state.available -= 1;
+                return Poll::Ready(FetchPermit {
+                    state: self.state.clone(),
+                });
+            }
+            state.waiters.push(cx.waker().clone());
+            Poll::Pending
+        })
+        .await
+    }
+}
+
+impl Debug for FetchLimiter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("FetchLimiter")
+    }
+}
+
+/// The limiter doesn't take part in the comparison of configurations.
+impl PartialEq for FetchLimiter {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+/// Lets a query function run, until dropped.
+pub(crate) struct FetchPermit {
+    state: Shared<Lock<LimiterState>>,
+}
+
+impl Drop for FetchPermit {
+    fn drop(&mut self) {
+        // Every waiter is woken up, as the first ones might have been aborted in the meantime
+        let waiters = {
+            let mut state = self.state.borrow_mut();
+            state.available += 1;
+            std::mem::take(&mut state.waiters)
+        };
+        for waiter in waiters {
+            waiter.wake();
+        }
+    }
+}
@@ -0,0 +1,46 @@
+use std::{collections::HashMap, time::Duration};
+
+use crate::clock::Instant;
+
+/// The successful results cached by a [UseQueryClient](crate::prelude::UseQueryClient),
+/// e.g to send the data fetched while rendering on the server along with the HTML.
+/// See [UseQueryClient::dehydrate](crate::prelude::UseQueryClient::dehydrate)
+/// and [UseQueryClient::hydrate](crate::prelude::UseQueryClient::hydrate).
+///
+/// It can be (de)serialized with the `serde` feature.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DehydratedState<T, K> {
+    pub queries: Vec<DehydratedQuery<T, K>>,
+}
+
+impl<T, K> Default for DehydratedState<T, K> {
+    fn default() -> Self {
+        Self {
+            queries: Vec::new(),
+        }
+    }
+}
+
+/// A successful result of a [DehydratedState].
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DehydratedQuery<T, K> {
+    pub query_keys: Vec<K>,
+    pub data: T,
+    /// How long ago the result was updated when it was dehydrated,
+    /// so the hydrated result becomes stale as it would have in the original cache
+    pub age: Duration,
+}
+
+/// A hydrated or restored result waiting for an entry with its query keys to be created.
+pub(crate) struct HydratedQuery<T> {
+    pub(crate) data: T,
+    /// How long ago the result was updated when it was hydrated
+    pub(crate) age: Duration,
+    pub(crate) hydrated_at: Instant,
+    /// Whether to refetch it in the background right away
+    pub(crate) is_stale: bool,
+}
+
+pub(crate) type HydratedQueries<T, K> = HashMap<Vec<K>, HydratedQuery<T>>;
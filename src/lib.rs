@@ -59,18 +59,152 @@
 //! }
 //! ```
 //!
+//! ## Large values
+//!
+//! Cached values are only moved around while fetching, but reading them out of a query
+//! (e.g [UseQuery::cloned](crate::prelude::UseQuery::cloned) or [use_query_data](crate::prelude::use_query_data)) clones them.
+//! Values that are expensive to clone can be wrapped in an [Arc](std::sync::Arc) so those are pointer copies:
+//!
+//! ```rust
+//! #[derive(Clone, PartialEq, Debug)]
+//! enum QueryValue {
+//!     Document(Arc<ParsedDocument>),
+//! }
+//! ```
+//!
+
+/// Emit a `tracing` event with the `tracing` feature, or nothing at all without it.
+#[cfg(feature = "tracing")]
+macro_rules! query_event {
+    ($($arg:tt)*) => {
+        tracing::debug!($($arg)*)
+    };
+}
+#[cfg(not(feature = "tracing"))]
+macro_rules! query_event {
+    ($($arg:tt)*) => {};
+}
+
+/// Warn about a likely mistake in how the queries are used, with the `tracing` feature.
+#[cfg(feature = "tracing")]
+macro_rules! query_warning {
+    ($($arg:tt)*) => {
+        tracing::warn!($($arg)*)
+    };
+}
+#[cfg(not(feature = "tracing"))]
+macro_rules! query_warning {
+    ($($arg:tt)*) => {};
+}
 
+mod abort_signal;
+#[cfg(feature = "anyhow")]
+mod anyhow_result;
+mod cache_events;
+#[cfg(feature = "serde")]
+mod cache_snapshot;
 mod cached_result;
+mod clock;
+mod combine_queries;
+#[cfg(feature = "devtools")]
+mod devtools;
+mod fetch_limiter;
+mod hydration;
+mod manager_subscription;
+mod metrics;
+mod online_manager;
+mod persister;
+mod queries_registry;
+mod query_capability;
+mod query_client;
+mod query_collection;
+mod query_diagnostics;
+mod query_handle;
+mod query_ref;
+mod query_types;
+mod query_watcher;
+mod rate_limiter;
 mod result;
+mod retry_backoff;
+#[cfg(feature = "fullstack")]
+mod server_query;
+mod settle_notify;
+mod snapshot;
+mod sync;
+#[cfg(feature = "testing")]
+mod testing;
+mod use_dependent_query;
+mod use_infinite_query;
+mod use_is_fetching;
 mod use_mutation;
+mod use_mutation_progress;
+mod use_mutation_state;
+mod use_prefetcher;
+mod use_queries;
 mod use_query;
 mod use_query_client;
+mod use_query_data;
+mod use_query_signal;
+mod use_query_status;
+#[cfg(feature = "router")]
+mod use_route_refetch;
+mod visibility_manager;
 
 pub mod prelude {
+    pub use crate::abort_signal::QueryAbortSignal;
+    #[cfg(feature = "anyhow")]
+    pub use crate::anyhow_result::*;
+    pub use crate::cache_events::{CacheEventsSubscription, QueryCacheEvent};
+    #[cfg(feature = "serde")]
+    pub use crate::cache_snapshot::{SkippedSnapshotEntry, SnapshotImport};
     pub use crate::cached_result::*;
+    pub use crate::clock::{Clock, Instant, MockClock, SystemClock};
+    pub use crate::combine_queries::*;
+    pub use crate::define_query_types;
+    #[cfg(feature = "devtools")]
+    pub use crate::devtools::*;
+    pub use crate::hydration::*;
+    pub use crate::manager_subscription::ManagerSubscription;
+    pub use crate::metrics::*;
+    #[cfg(feature = "web")]
+    pub use crate::online_manager::NavigatorOnlineManager;
+    pub use crate::online_manager::{AlwaysOnline, OnlineChangeFn, OnlineManager, OnlineStatus};
+    pub use crate::persister::*;
+    pub use crate::query_capability::*;
+    pub use crate::query_client::*;
+    pub use crate::query_collection::QueryCollection;
+    #[cfg(debug_assertions)]
+    pub use crate::query_diagnostics::RegistrationConflict;
+    pub use crate::query_handle::*;
+    pub use crate::query_ref::QueryRef;
+    pub use crate::query_watcher::QueryWatcher;
     pub use crate::result::*;
+    pub use crate::retry_backoff::{ExponentialBackoff, Jitter, RetryRandom, SeededRandom};
+    #[cfg(feature = "fullstack")]
+    pub use crate::server_query::*;
+    pub use crate::snapshot::*;
+    pub use crate::sync::{MaybeSend, MaybeSync};
+    #[cfg(feature = "testing")]
+    pub use crate::testing::{MockResponses, QueryMock};
+    pub use crate::use_dependent_query::*;
+    pub use crate::use_infinite_query::*;
+    pub use crate::use_is_fetching::*;
     pub use crate::use_mutation::*;
+    pub use crate::use_mutation_progress::*;
+    pub use crate::use_mutation_state::use_mutation_state;
+    pub use crate::use_prefetcher::*;
+    pub use crate::use_queries::*;
     pub use crate::use_query::*;
     pub use crate::use_query_client::*;
+    pub use crate::use_query_data::*;
+    pub use crate::use_query_signal::*;
+    pub use crate::use_query_status::*;
+    #[cfg(feature = "router")]
+    pub use crate::use_route_refetch::*;
+    #[cfg(feature = "web")]
+    pub use crate::visibility_manager::DocumentVisibilityManager;
+    pub use crate::visibility_manager::{AlwaysVisible, VisibilityChangeFn, VisibilityManager};
+    #[cfg(feature = "macros")]
+    pub use dioxus_query_macros::query;
     pub use futures_util;
 }
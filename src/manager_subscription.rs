@@ -0,0 +1,32 @@
+use crate::sync::{MaybeSend, MaybeSync};
+
+#[cfg(not(feature = "sync"))]
+type UnsubscribeFn = dyn FnOnce();
+#[cfg(feature = "sync")]
+type UnsubscribeFn = dyn FnOnce() + Send + Sync;
+
+/// Keeps the function given to [OnlineManager::subscribe](crate::prelude::OnlineManager::subscribe)
+/// or [VisibilityManager::subscribe](crate::prelude::VisibilityManager::subscribe) subscribed until dropped,
+/// e.g so a manager doesn't keep the state of a client alive once the client is gone.
+#[must_use = "the function is unsubscribed as soon as this is dropped"]
+pub struct ManagerSubscription(Option<Box<UnsubscribeFn>>);
+
+impl ManagerSubscription {
+    /// Call `unsubscribe` once dropped, which should stop calling the subscribed function and drop it.
+    pub fn new(unsubscribe: impl FnOnce() + MaybeSend + MaybeSync + 'static) -> Self {
+        Self(Some(Box::new(unsubscribe)))
+    }
+
+    /// A subscription with nothing to undo, e.g of a manager whose status never changes.
+    pub fn empty() -> Self {
+        Self(None)
+    }
+}
+
+impl Drop for ManagerSubscription {
+    fn drop(&mut self) {
+        if let Some(unsubscribe) = self.0.take() {
+            unsubscribe();
+        }
+    }
+}
@@ -0,0 +1,156 @@
+use std::{collections::HashMap, hash::Hash, time::Duration};
+
+use crate::sync::{Lock, MaybeSend, MaybeSync};
+
+/// Collects how the cache of a client performs, see [UseQueryClient::with_metrics](crate::prelude::UseQueryClient::with_metrics).
+///
+/// [InMemoryMetrics] aggregates them so they can be read with
+/// [UseQueryClient::metrics_snapshot](crate::prelude::UseQueryClient::metrics_snapshot),
+/// other collectors (e.g forwarding them to a telemetry service) can be used by implementing this trait.
+pub trait QueryMetrics<K>: MaybeSend + MaybeSync {
+    /// A query was served from the cache instead of being fetched, as its result was fresh or already being fetched.
+    fn record_cache_hit(&self, query_keys: &[K]);
+
+    /// A query was fetched because its result was missing or stale.
+    fn record_cache_miss(&self, query_keys: &[K]) {
+        let _ = query_keys;
+    }
+
+    /// A query function finished, retries included, after `duration`.
+    fn record_fetch(&self, query_keys: &[K], duration: Duration, ok: bool);
+
+    /// Get what was collected so far, if the collector keeps it.
+    fn snapshot(&self) -> Option<QueryMetricsSnapshot<K>> {
+        None
+    }
+}
+
+/// Upper bounds of the buckets of [FetchHistogram], in milliseconds.
+const HISTOGRAM_BOUNDS: [u64; 8] = [10, 50, 100, 250, 500, 1000, 2500, 5000];
+
+/// How many fetches took how long.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FetchHistogram {
+    /// The fetches that took up to the given duration, and longer than the one of the previous bucket
+    pub buckets: Vec<(Duration, u64)>,
+    /// The fetches that took longer than the last bucket
+    pub overflow: u64,
+}
+
+impl FetchHistogram {
+    fn record(&mut self, duration: Duration) {
+        if self.buckets.is_empty() {
+            self.buckets = HISTOGRAM_BOUNDS
+                .iter()
+                .map(|bound| (Duration::from_millis(*bound), 0))
+                .collect();
+        }
+        match self
+            .buckets
+            .iter_mut()
+            .find(|(bound, _)| duration <= *bound)
+        {
+            Some((_, count)) => *count += 1,
+            None => self.overflow += 1,
+        }
+    }
+}
+
+/// What was collected for some query keys, see [QueryMetricsSnapshot].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct KeyMetrics {
+    pub cache_hits: u64,
+    pub cache_misses: u64,
+    pub fetches: u64,
+    pub failed_fetches: u64,
+    /// Time spent fetching, divide it by `fetches` to get the average
+    pub fetch_time: Duration,
+}
+
+/// What a [QueryMetrics] collector gathered, overall and by query keys.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct QueryMetricsSnapshot<K> {
+    pub total: KeyMetrics,
+    pub fetch_durations: FetchHistogram,
+    pub by_keys: HashMap<Vec<K>, KeyMetrics>,
+}
+
+impl<K> Default for QueryMetricsSnapshot<K> {
+    fn default() -> Self {
+        Self {
+            total: KeyMetrics::default(),
+            fetch_durations: FetchHistogram::default(),
+            by_keys: HashMap::default(),
+        }
+    }
+}
+
+impl<K: Eq + Hash + Clone> QueryMetricsSnapshot<K> {
+    fn record(&mut self, query_keys: &[K], record: impl Fn(&mut KeyMetrics)) {
+        record(&mut self.total);
+        match self.by_keys.get_mut(query_keys) {
+            Some(metrics) => record(metrics),
+            None => {
+                let mut metrics = KeyMetrics::default();
+                record(&mut metrics);
+                self.by_keys.insert(query_keys.to_vec(), metrics);
+            }
+        }
+    }
+}
+
+/// A [QueryMetrics] collector that keeps counts and fetch durations in memory.
+///
+/// ## Example:
+///
+/// ```no_run
+/// let client = UseQueryClient::<QueryValue, QueryError, QueryKeys>::new(cx.schedule_update_any())
+///     .with_metrics(InMemoryMetrics::default());
+///
+/// let metrics = client.metrics_snapshot().unwrap();
+/// println!("{} hits, {} misses", metrics.total.cache_hits, metrics.total.cache_misses);
+/// ```
+pub struct InMemoryMetrics<K> {
+    snapshot: Lock<QueryMetricsSnapshot<K>>,
+}
+
+impl<K> Default for InMemoryMetrics<K> {
+    fn default() -> Self {
+        Self {
+            snapshot: Lock::default(),
+        }
+    }
+}
+
+impl<K> QueryMetrics<K> for InMemoryMetrics<K>
+where
+    K: Eq + Hash + Clone + MaybeSend + MaybeSync,
+{
+    fn record_cache_hit(&self, query_keys: &[K]) {
+        self.snapshot
+            .borrow_mut()
+            .record(query_keys, |metrics| metrics.cache_hits += 1);
+    }
+
+    fn record_cache_miss(&self, query_keys: &[K]) {
+        self.snapshot
+            .borrow_mut()
+            .record(query_keys, |metrics| metrics.cache_misses += 1);
+    }
+
+    fn record_fetch(&self, query_keys: &[K], duration: Duration, ok: bool) {
+        let mut snapshot = self.snapshot.borrow_mut();
+        snapshot.fetch_durations.record(duration);
+        snapshot.record(query_keys, |metrics| {
+            metrics.fetches += 1;
+            if !ok {
+                metrics.failed_fetches += 1;
+            }
+            metrics.fetch_time += duration;
+        });
+    }
+
+    fn snapshot(&self) -> Option<QueryMetricsSnapshot<K>> {
+        Some(self.snapshot.borrow().clone())
+    }
+}
@@ -0,0 +1,147 @@
+use std::fmt::Debug;
+
+use crate::{
+    manager_subscription::ManagerSubscription,
+    sync::{MaybeSend, MaybeSync, Shared},
+};
+
+/// Whether the app can reach the network.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum OnlineStatus {
+    Online,
+    Offline,
+}
+
+impl OnlineStatus {
+    pub fn is_online(&self) -> bool {
+        matches!(self, OnlineStatus::Online)
+    }
+}
+
+impl From<bool> for OnlineStatus {
+    fn from(is_online: bool) -> Self {
+        if is_online {
+            OnlineStatus::Online
+        } else {
+            OnlineStatus::Offline
+        }
+    }
+}
+
+#[cfg(not(feature = "sync"))]
+pub type OnlineChangeFn = dyn Fn(bool);
+#[cfg(feature = "sync")]
+pub type OnlineChangeFn = dyn Fn(bool) + Send + Sync;
+
+/// The source of truth for whether the app is online,
+/// see [QueryClientConfig::online_manager](crate::prelude::QueryClientConfig::online_manager).
+pub trait OnlineManager: MaybeSend + MaybeSync {
+    /// Check if the app is online right now.
+    fn is_online(&self) -> bool;
+
+    /// Call `on_change` with the new status whenever it changes, until the returned subscription is dropped.
+    fn subscribe(&self, on_change: Box<OnlineChangeFn>) -> ManagerSubscription;
+}
+
+/// An [OnlineManager] that is always online, used by default.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct AlwaysOnline;
+
+impl OnlineManager for AlwaysOnline {
+    fn is_online(&self) -> bool {
+        true
+    }
+
+    fn subscribe(&self, _on_change: Box<OnlineChangeFn>) -> ManagerSubscription {
+        ManagerSubscription::empty()
+    }
+}
+
+/// The online manager of a client.
+#[derive(Clone)]
+pub(crate) struct SharedOnlineManager(Shared<dyn OnlineManager>);
+
+impl SharedOnlineManager {
+    pub(crate) fn new(online_manager: impl OnlineManager + 'static) -> Self {
+        Self(Shared::new(online_manager))
+    }
+
+    pub(crate) fn status(&self) -> OnlineStatus {
+        self.0.is_online().into()
+    }
+
+    pub(crate) fn subscribe(&self, on_change: Box<OnlineChangeFn>) -> ManagerSubscription {
+        self.0.subscribe(on_change)
+    }
+}
+
+impl Default for SharedOnlineManager {
+    fn default() -> Self {
+        Self::new(AlwaysOnline)
+    }
+}
+
+impl Debug for SharedOnlineManager {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("SharedOnlineManager")
+    }
+}
+
+/// The online manager doesn't take part in the comparison of configurations.
+impl PartialEq for SharedOnlineManager {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+#[cfg(feature = "web")]
+pub use navigator::NavigatorOnlineManager;
+
+#[cfg(feature = "web")]
+mod navigator {
+    use wasm_bindgen::{closure::Closure, JsCast};
+
+    use super::{OnlineChangeFn, OnlineManager};
+    use crate::{
+        manager_subscription::ManagerSubscription,
+        sync::{Lock, Shared},
+    };
+
+    /// An [OnlineManager] following `navigator.onLine` and the `online` and `offline` events of the window,
+    /// only available with the `web` feature.
+    #[derive(Clone, Copy, Debug, Default)]
+    pub struct NavigatorOnlineManager;
+
+    impl OnlineManager for NavigatorOnlineManager {
+        fn is_online(&self) -> bool {
+            web_sys::window()
+                .map(|window| window.navigator().on_line())
+                .unwrap_or(true)
+        }
+
+        fn subscribe(&self, on_change: Box<OnlineChangeFn>) -> ManagerSubscription {
+            let Some(window) = web_sys::window() else {
+                return ManagerSubscription::empty();
+            };
+            let on_change = Shared::new(Lock::new(Some(Shared::<OnlineChangeFn>::from(on_change))));
+            for (event, is_online) in [("online", true), ("offline", false)] {
+                let on_change = on_change.clone();
+                let listener = Closure::<dyn Fn()>::new(move || {
+                    // Cloned out first so it's free to unsubscribe
+                    let on_change = on_change.borrow().clone();
+                    if let Some(on_change) = on_change {
+                        on_change(is_online);
+                    }
+                });
+                window
+                    .add_event_listener_with_callback(event, listener.as_ref().unchecked_ref())
+                    .ok();
+                // The listener stays on the window, but the function is dropped once unsubscribed
+                listener.forget();
+            }
+            ManagerSubscription::new(move || {
+                on_change.borrow_mut().take();
+            })
+        }
+    }
+}
@@ -0,0 +1,154 @@
+use futures_util::Future;
+use std::collections::HashMap;
+
+use crate::{clock::Instant, hydration::DehydratedQuery, sync::MaybeSend, sync::MaybeSync};
+
+/// Stores the successful results of the queries so they survive a restart, e.g a page reload.
+/// See [UseQueryClient::with_persister](crate::prelude::UseQueryClient::with_persister).
+///
+/// With the `web` feature there is [LocalStoragePersister], other storages
+/// (e.g sled or sqlite on native targets) can be used by implementing this trait.
+pub trait QueryPersister<T, K>: MaybeSend + MaybeSync {
+    /// Store the latest successful result of a query, replacing the previous one.
+    /// Store when it was persisted too, so its age can be restored.
+    fn persist(&self, query: DehydratedQuery<T, K>);
+
+    /// Forget the result of a query.
+    fn remove(&self, query_keys: &[K]);
+
+    /// Get every stored result, called once when the client is created.
+    /// Their [DehydratedQuery::age] includes the time they spent in the storage,
+    /// so they are dropped once older than the max age of their query instead of being shown.
+    fn restore_all(&self) -> Vec<DehydratedQuery<T, K>>;
+
+    /// Wait before writing the pending changes, so rapid updates of the same query are only written once.
+    /// By default they are written as soon as the running tasks yield, return a timer to wait longer.
+    fn debounce(&self) -> Box<dyn Future<Output = ()>> {
+        Box::new(async {})
+    }
+}
+
+/// Results waiting to be persisted by their query keys, with when they were updated, `None` to remove them.
+pub(crate) type PendingWrites<T, K> = HashMap<Vec<K>, Option<(T, Instant)>>;
+
+#[cfg(feature = "web")]
+pub use local_storage::LocalStoragePersister;
+
+#[cfg(feature = "web")]
+mod local_storage {
+    use futures_util::Future;
+    use serde::{de::DeserializeOwned, Deserialize, Serialize};
+    use std::{marker::PhantomData, time::Duration};
+
+    use super::QueryPersister;
+    use crate::hydration::DehydratedQuery;
+
+    /// How long to wait before writing to the storage by default.
+    const DEBOUNCE_TIME: u64 = 1000;
+
+    /// A stored result, along with when it was stored.
+    #[derive(Serialize, Deserialize)]
+    struct PersistedQuery<T, K> {
+        #[serde(flatten)]
+        query: DehydratedQuery<T, K>,
+        /// In milliseconds since the Unix epoch, missing in the results stored by older versions
+        #[serde(default)]
+        persisted_at: Option<f64>,
+    }
+
+    /// A [QueryPersister] backed by the `localStorage` of the browser, only available with the `web` feature.
+    ///
+    /// Each query is stored as JSON under the given prefix followed by its query keys as JSON,
+    /// along with when it was stored.
+    pub struct LocalStoragePersister<T, K> {
+        prefix: String,
+        debounce_time: Duration,
+        phantom: PhantomData<fn() -> (T, K)>,
+    }
+
+    impl<T, K> LocalStoragePersister<T, K> {
+        pub fn new(prefix: impl Into<String>) -> Self {
+            Self {
+                prefix: prefix.into(),
+                debounce_time: Duration::from_millis(DEBOUNCE_TIME),
+                phantom: PhantomData,
+            }
+        }
+
+        /// Set how long to wait for more changes before writing to the storage.
+        pub fn debounce_time(mut self, debounce_time: Duration) -> Self {
+            self.debounce_time = debounce_time;
+            self
+        }
+
+        fn storage(&self) -> Option<web_sys::Storage> {
+            web_sys::window()?.local_storage().ok()?
+        }
+    }
+
+    impl<T, K> QueryPersister<T, K> for LocalStoragePersister<T, K>
+    where
+        T: Serialize + DeserializeOwned,
+        K: Serialize + DeserializeOwned,
+    {
+        fn persist(&self, query: DehydratedQuery<T, K>) {
+            let Some(storage) = self.storage() else {
+                return;
+            };
+            let query = PersistedQuery {
+                query,
+                persisted_at: Some(js_sys::Date::now()),
+            };
+            let (Ok(keys), Ok(query)) = (
+                serde_json::to_string(&query.query.query_keys),
+                serde_json::to_string(&query),
+            ) else {
+                return;
+            };
+            storage
+                .set_item(&format!("{}{keys}", self.prefix), &query)
+                .ok();
+        }
+
+        fn remove(&self, query_keys: &[K]) {
+            let (Some(storage), Ok(keys)) = (self.storage(), serde_json::to_string(query_keys))
+            else {
+                return;
+            };
+            storage.remove_item(&format!("{}{keys}", self.prefix)).ok();
+        }
+
+        fn restore_all(&self) -> Vec<DehydratedQuery<T, K>> {
+            let Some(storage) = self.storage() else {
+                return Vec::new();
+            };
+            let now = js_sys::Date::now();
+            let length = storage.length().unwrap_or_default();
+            (0..length)
+                .filter_map(|index| storage.key(index).ok().flatten())
+                .filter(|key| key.starts_with(&self.prefix))
+                .filter_map(|key| storage.get_item(&key).ok().flatten())
+                // Skip the entries that can't be read anymore, e.g because the types changed
+                .filter_map(|query| serde_json::from_str::<PersistedQuery<T, K>>(&query).ok())
+                .map(
+                    |PersistedQuery {
+                         mut query,
+                         persisted_at,
+                     }| {
+                        // Results of unknown age are only shown until they are refetched
+                        let stored_for = persisted_at
+                            .map(|persisted_at| (now - persisted_at).max(0.0))
+                            .unwrap_or_default();
+                        query.age += Duration::from_millis(stored_for as u64);
+                        query
+                    },
+                )
+                .collect()
+        }
+
+        fn debounce(&self) -> Box<dyn Future<Output = ()>> {
+            let millis = self.debounce_time.as_millis().min(u32::MAX as u128) as u32;
+            Box::new(gloo_timers::future::TimeoutFuture::new(millis))
+        }
+    }
+}
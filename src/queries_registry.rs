@@ -0,0 +1,286 @@
+use std::{
+    collections::{HashMap, HashSet},
+    hash::Hash,
+};
+
+use crate::use_query_client::{QueryListeners, RegistryEntry};
+
+/// The cache entries of a client, indexed by each of their query keys and tags,
+/// so the entries of some keys are found without going through the whole cache, e.g to invalidate them.
+///
+/// Every insertion and removal goes through here, so the index can't get out of sync with the entries,
+/// whether they are removed by their last listener, evicted or garbage collected.
+pub(crate) struct QueriesRegistry<T, E, K> {
+    entries: HashMap<RegistryEntry<K>, QueryListeners<T, E, K>>,
+    /// The entries each query key is part of
+    entries_by_key: HashMap<K, HashSet<RegistryEntry<K>>>,
+    /// The entries tagged with each tag, which never change once an entry is created
+    entries_by_tag: HashMap<K, HashSet<RegistryEntry<K>>>,
+}
+
+impl<T, E, K> Default for QueriesRegistry<T, E, K> {
+    fn default() -> Self {
+        Self {
+            entries: HashMap::new(),
+            entries_by_key: HashMap::new(),
+            entries_by_tag: HashMap::new(),
+        }
+    }
+}
+
+impl<T, E, K: Eq + Hash> QueriesRegistry<T, E, K> {
+    pub(crate) fn get(&self, entry: &RegistryEntry<K>) -> Option<&QueryListeners<T, E, K>> {
+        self.entries.get(entry)
+    }
+
+    pub(crate) fn contains_key(&self, entry: &RegistryEntry<K>) -> bool {
+        self.entries.contains_key(entry)
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub(crate) fn keys(&self) -> impl Iterator<Item = &RegistryEntry<K>> {
+        self.entries.keys()
+    }
+
+    pub(crate) fn iter(
+        &self,
+    ) -> impl Iterator<Item = (&RegistryEntry<K>, &QueryListeners<T, E, K>)> {
+        self.entries.iter()
+    }
+
+    /// Get the entries whose query keys contain any of the given keys, each of them once.
+    pub(crate) fn with_any_key<'a>(
+        &'a self,
+        keys: &'a [K],
+    ) -> impl Iterator<Item = (&'a RegistryEntry<K>, &'a QueryListeners<T, E, K>)> {
+        let mut seen = HashSet::new();
+        keys.iter()
+            .filter_map(move |key| self.entries_by_key.get(key))
+            .flatten()
+            .filter(move |entry| seen.insert(*entry))
+            .filter_map(move |entry| self.entries.get_key_value(entry))
+    }
+
+    /// Get the entries tagged with the given tag, see [crate::use_query::QueryConfig::tags].
+    pub(crate) fn with_tag<'a>(
+        &'a self,
+        tag: &K,
+    ) -> impl Iterator<Item = (&'a RegistryEntry<K>, &'a QueryListeners<T, E, K>)> {
+        self.entries_by_tag
+            .get(tag)
+            .into_iter()
+            .flatten()
+            .filter_map(move |entry| self.entries.get_key_value(entry))
+    }
+
+    /// Get the entries whose query keys are exactly the given ones, one per query function.
+    pub(crate) fn with_keys<'a>(
+        &'a self,
+        query_keys: &'a [K],
+    ) -> impl Iterator<Item = (&'a RegistryEntry<K>, &'a QueryListeners<T, E, K>)> {
+        // Entries without keys aren't in the index
+        let entries: Box<dyn Iterator<Item = _> + 'a> = match query_keys.first() {
+            Some(key) => Box::new(
+                self.entries_by_key
+                    .get(key)
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|entry| self.entries.get_key_value(entry)),
+            ),
+            None => Box::new(self.entries.iter()),
+        };
+        entries.filter(move |(entry, _)| entry.query_keys == query_keys)
+    }
+
+    pub(crate) fn remove(&mut self, entry: &RegistryEntry<K>) -> Option<QueryListeners<T, E, K>> {
+        let query_listeners = self.entries.remove(entry)?;
+        unindex(&mut self.entries_by_key, &entry.query_keys, entry);
+        unindex(&mut self.entries_by_tag, &query_listeners.tags, entry);
+        Some(query_listeners)
+    }
+}
+
+impl<T, E, K: Eq + Hash + Clone> QueriesRegistry<T, E, K> {
+    /// Remove the entries satisfying the given predicate, and get them back.
+    /// Only the removed entries are touched, the others stay where they are.
+    pub(crate) fn remove_where(
+        &mut self,
+        mut should_remove: impl FnMut(&RegistryEntry<K>, &QueryListeners<T, E, K>) -> bool,
+    ) -> Vec<(RegistryEntry<K>, QueryListeners<T, E, K>)> {
+        let matching = self
+            .entries
+            .iter()
+            .filter(|(entry, query_listeners)| should_remove(entry, query_listeners))
+            .map(|(entry, _)| entry.clone())
+            .collect::<Vec<_>>();
+        matching
+            .into_iter()
+            .filter_map(|entry| {
+                let query_listeners = self.remove(&entry)?;
+                Some((entry, query_listeners))
+            })
+            .collect()
+    }
+
+    pub(crate) fn insert(
+        &mut self,
+        entry: RegistryEntry<K>,
+        query_listeners: QueryListeners<T, E, K>,
+    ) -> Option<QueryListeners<T, E, K>> {
+        // Its tags might not be the same as those of the entry it replaces
+        let previous = self.remove(&entry);
+        index(&mut self.entries_by_key, &entry.query_keys, &entry);
+        index(&mut self.entries_by_tag, &query_listeners.tags, &entry);
+        self.entries.insert(entry, query_listeners);
+        previous
+    }
+
+    /// Get the listeners of the given entry, creating it with the given function if it's not in the cache.
+    pub(crate) fn get_or_insert_with(
+        &mut self,
+        entry: RegistryEntry<K>,
+        create: impl FnOnce() -> QueryListeners<T, E, K>,
+    ) -> &mut QueryListeners<T, E, K> {
+        self.entries.entry(entry).or_insert_with_key(|entry| {
+            let query_listeners = create();
+            index(&mut self.entries_by_key, &entry.query_keys, entry);
+            index(&mut self.entries_by_tag, &query_listeners.tags, entry);
+            query_listeners
+        })
+    }
+}
+
+fn index<K: Eq + Hash + Clone>(
+    entries_by_key: &mut HashMap<K, HashSet<RegistryEntry<K>>>,
+    keys: &[K],
+    entry: &RegistryEntry<K>,
+) {
+    for key in keys {
+        entries_by_key
+            .entry(key.clone())
+            .or_default()
+            .insert(entry.clone());
+    }
+}
+
+fn unindex<K: Eq + Hash>(
+    entries_by_key: &mut HashMap<K, HashSet<RegistryEntry<K>>>,
+    keys: &[K],
+    entry: &RegistryEntry<K>,
+) {
+    for key in keys {
+        if let Some(entries) = entries_by_key.get_mut(key) {
+            entries.remove(entry);
+            if entries.is_empty() {
+                entries_by_key.remove(key);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{result::QueryResult, use_query::QueryConfig, use_query_client::QueryClientConfig};
+    use futures_util::future::ready;
+
+    type TestRegistry = QueriesRegistry<usize, String, &'static str>;
+
+    fn insert(registry: &mut TestRegistry, query_keys: &[&'static str]) {
+        let config = QueryConfig::new(query_keys.to_vec(), |_| ready(QueryResult::Ok(0)));
+        let query_listeners = QueryListeners::from_config(&config, &QueryClientConfig::default());
+        registry.insert(config.registry_entry, query_listeners);
+    }
+
+    fn registry() -> TestRegistry {
+        let mut registry = TestRegistry::default();
+        for query_keys in [&["a", "b"][..], &["b", "c"], &["c"], &[]] {
+            insert(&mut registry, query_keys);
+        }
+        registry
+    }
+
+    fn sorted<'a>(
+        entries: impl Iterator<
+            Item = (
+                &'a RegistryEntry<&'static str>,
+                &'a QueryListeners<usize, String, &'static str>,
+            ),
+        >,
+    ) -> Vec<Vec<&'static str>> {
+        let mut query_keys = entries
+            .map(|(entry, _)| entry.query_keys.clone())
+            .collect::<Vec<_>>();
+        query_keys.sort();
+        query_keys
+    }
+
+    #[test]
+    fn the_index_finds_the_same_entries_as_a_scan() {
+        let registry = registry();
+        for keys in [&["a"][..], &["b"], &["b", "c"], &["a", "c"], &["d"], &[]] {
+            let scanned = registry
+                .iter()
+                .filter(|(entry, _)| entry.query_keys.iter().any(|key| keys.contains(key)));
+            assert_eq!(sorted(registry.with_any_key(keys)), sorted(scanned));
+        }
+    }
+
+    #[test]
+    fn only_the_entries_with_the_exact_keys_are_found() {
+        let registry = registry();
+        assert_eq!(
+            sorted(registry.with_keys(&["b", "c"])),
+            vec![vec!["b", "c"]]
+        );
+        assert_eq!(
+            sorted(registry.with_keys(&["c", "b"])),
+            Vec::<Vec<_>>::new()
+        );
+        assert_eq!(sorted(registry.with_keys(&["b"])), Vec::<Vec<_>>::new());
+        // Entries without keys aren't in the index
+        assert_eq!(sorted(registry.with_keys(&[])), vec![Vec::new()]);
+    }
+
+    #[test]
+    fn removed_entries_are_removed_from_the_index() {
+        let mut registry = registry();
+        let entry = registry
+            .keys()
+            .find(|entry| entry.query_keys == ["a", "b"])
+            .cloned()
+            .unwrap();
+        assert!(registry.remove(&entry).is_some());
+        assert!(!registry.entries_by_key.contains_key("a"));
+        assert_eq!(
+            sorted(registry.with_any_key(&["a", "b"])),
+            vec![vec!["b", "c"]]
+        );
+
+        let removed = registry.remove_where(|entry, _| entry.query_keys.contains(&"c"));
+        assert_eq!(removed.len(), 2);
+        assert!(registry.entries_by_key.is_empty());
+        assert_eq!(registry.len(), 1);
+        assert_eq!(
+            sorted(registry.with_any_key(&["b", "c"])),
+            Vec::<Vec<_>>::new()
+        );
+    }
+
+    #[test]
+    fn tagged_entries_are_found_until_removed() {
+        let mut registry = registry();
+        let config = QueryConfig::new(vec!["a"], |_| ready(QueryResult::Ok(0))).tags(vec!["t"]);
+        let query_listeners = QueryListeners::from_config(&config, &QueryClientConfig::default());
+        registry.insert(config.registry_entry.clone(), query_listeners);
+        assert_eq!(sorted(registry.with_tag(&"t")), vec![vec!["a"]]);
+        assert_eq!(sorted(registry.with_tag(&"a")), Vec::<Vec<_>>::new());
+
+        registry.remove(&config.registry_entry);
+        assert!(registry.entries_by_tag.is_empty());
+        assert_eq!(sorted(registry.with_tag(&"t")), Vec::<Vec<_>>::new());
+    }
+}
@@ -0,0 +1,95 @@
+use dioxus::prelude::*;
+use futures_util::Future;
+use std::{hash::Hash, sync::Arc, time::Duration};
+
+use crate::{
+    result::QueryResult,
+    sync::{MaybeSend, MaybeSync},
+    use_query::{use_query_config, QueryConfig, UseQuery},
+    use_query_client::QueryFnId,
+};
+
+/// A query defined as a type, as an alternative to a query function closure.
+///
+/// Queries of the same type share their cached entries, no matter where they are used,
+/// as the type itself identifies the query. Being plain types, they can also be called directly in tests.
+///
+/// ## Example:
+///
+/// ```no_run
+/// #[derive(Default)]
+/// struct UsersQuery;
+///
+/// impl QueryCapability for UsersQuery {
+///     type Ok = Vec<User>;
+///     type Err = QueryError;
+///     type Keys = QueryKeys;
+///
+///     const STALE_TIME: Option<Duration> = Some(Duration::from_secs(30));
+///
+///     async fn run(&self, keys: &[QueryKeys]) -> Result<Vec<User>, QueryError> {
+///         fetch_users(keys).await
+///     }
+/// }
+///
+/// let users = use_query_of::<UsersQuery>(cx, || vec![QueryKeys::Users]);
+/// ```
+pub trait QueryCapability: 'static + MaybeSend + MaybeSync {
+    /// The data returned by the query
+    type Ok: 'static + Clone;
+    /// The error returned by the query
+    type Err: 'static + Clone;
+    /// The type of the query keys
+    type Keys: 'static + Eq + Hash + Clone + MaybeSend + MaybeSync;
+
+    /// Same as [QueryConfig::stale_time], `None` to use the default of the [UseQueryClient](crate::prelude::UseQueryClient).
+    const STALE_TIME: Option<Duration> = None;
+    /// Same as [QueryConfig::retry], `None` to use the default of the [UseQueryClient](crate::prelude::UseQueryClient).
+    const RETRY: Option<u32> = None;
+
+    /// Run the query for the given query keys.
+    fn run(
+        &self,
+        keys: &[Self::Keys],
+    ) -> impl Future<Output = Result<Self::Ok, Self::Err>> + MaybeSend;
+}
+
+impl<T, E, K> QueryConfig<T, E, K>
+where
+    T: 'static + Clone,
+    E: 'static + Clone,
+    K: 'static + Eq + Hash + Clone,
+{
+    /// Create the configuration of a query defined as a [QueryCapability],
+    /// applying its associated configuration. It can be further customized like any other configuration.
+    pub fn of<Q>(query_keys: Vec<K>) -> Self
+    where
+        Q: QueryCapability<Ok = T, Err = E, Keys = K> + Default,
+    {
+        let capability = Arc::new(Q::default());
+        let mut config = Self::new(query_keys, move |keys: Vec<K>| {
+            let capability = capability.clone();
+            async move { QueryResult::from(capability.run(&keys).await) }
+        });
+        config.registry_entry.query_fn_id = QueryFnId::of::<Q>();
+        if let Some(stale_time) = Q::STALE_TIME {
+            config = config.stale_time(stale_time);
+        }
+        if let Some(retry) = Q::RETRY {
+            config = config.retry(retry);
+        }
+        config
+    }
+}
+
+/// Register a query listener for a query defined as a [QueryCapability].
+/// See [UseQuery] on how to use it.
+pub fn use_query_of<Q>(
+    cx: &ScopeState,
+    query_keys: impl FnOnce() -> Vec<Q::Keys>,
+) -> &UseQuery<Q::Ok, Q::Err, Q::Keys>
+where
+    Q: QueryCapability + Default,
+{
+    use_query_config(cx, || QueryConfig::of::<Q>(query_keys()))
+}
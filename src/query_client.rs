@@ -0,0 +1,186 @@
+use dioxus::prelude::*;
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    hash::Hash,
+    sync::Arc,
+};
+
+use crate::{
+    sync::{Lock, MaybeSend, MaybeSync, Shared},
+    use_query_client::{
+        InvalidateFn, QueryClientConfig, QueryInvalidator, Scheduler, UseQueryClient,
+    },
+};
+
+#[cfg(not(feature = "sync"))]
+type AnyClient = dyn Any;
+#[cfg(feature = "sync")]
+type AnyClient = dyn Any + Send + Sync;
+
+/// A [UseQueryClient] of some value and error types, with its type erased.
+struct ErasedClient<K> {
+    client: Box<AnyClient>,
+    invalidate: Shared<InvalidateFn<K>>,
+}
+
+/// A client for the queries of different value and error types that share the same type of keys,
+/// so e.g invalidating everything about a user is a single call no matter what each query returns.
+///
+/// It keeps a [UseQueryClient] per combination of value and error types,
+/// which the query hooks use as usual once it's provided with [provide_shared_query_client].
+/// It can be cheaply cloned.
+///
+/// ## Example:
+///
+/// ```no_run
+/// provide_shared_query_client(cx, || {
+///     QueryClient::<QueryKeys>::new(cx.schedule_update_any())
+///         .serve::<User, ApiError>()
+///         .serve::<Vec<Post>, ApiError>()
+/// });
+///
+/// // Refetches both the user and their posts
+/// let client = use_shared_query_client::<QueryKeys>(cx);
+/// client.invalidate_query(QueryKeys::User(42));
+/// ```
+pub struct QueryClient<K> {
+    config: QueryClientConfig,
+    scheduler: Arc<Scheduler>,
+    /// The typed clients, by the types of their values and errors
+    clients: Shared<Lock<HashMap<TypeId, ErasedClient<K>>>>,
+}
+
+impl<K> Clone for QueryClient<K> {
+    fn clone(&self) -> Self {
+        Self {
+            config: self.config.clone(),
+            scheduler: self.scheduler.clone(),
+            clients: self.clients.clone(),
+        }
+    }
+}
+
+impl<K: 'static + Clone> QueryClient<K> {
+    /// Create a new client with an empty cache.
+    /// The scheduler is used to re-render the listeners, e.g `cx.schedule_update_any()`.
+    pub fn new(scheduler: Arc<Scheduler>) -> Self {
+        Self {
+            config: QueryClientConfig::default(),
+            scheduler,
+            clients: Shared::default(),
+        }
+    }
+
+    /// Set the default options for the queries of the types served after this call.
+    pub fn with_config(mut self, config: QueryClientConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Get the client of the given value and error types, if it was created already.
+    pub fn get<T, E>(&self) -> Option<UseQueryClient<T, E, K>>
+    where
+        T: 'static + Clone,
+        E: 'static + Clone,
+    {
+        self.clients
+            .borrow()
+            .get(&TypeId::of::<(T, E)>())
+            .and_then(|erased| erased.client.downcast_ref::<UseQueryClient<T, E, K>>())
+            .cloned()
+    }
+
+    /// Invalidate the queries of every type matching the given key, in the **background**.
+    pub fn invalidate_query(&self, key_to_invalidate: K) {
+        self.invalidate_queries(&[key_to_invalidate]);
+    }
+
+    /// Invalidate the queries of every type matching any of the given keys, in the **background**,
+    /// see [UseQueryClient::invalidate_queries].
+    pub fn invalidate_queries(&self, keys_to_invalidate: &[K]) {
+        self.invalidate_queries_except(keys_to_invalidate, &[]);
+    }
+
+    /// Invalidate the queries of every type as [QueryClient::invalidate_queries] would,
+    /// except the entries with the given query keys.
+    pub(crate) fn invalidate_queries_except(&self, keys_to_invalidate: &[K], except: &[Vec<K>]) {
+        let invalidators = self
+            .clients
+            .borrow()
+            .values()
+            .map(|erased| erased.invalidate.clone())
+            .collect::<Vec<_>>();
+        for invalidate in invalidators {
+            invalidate(keys_to_invalidate, except);
+        }
+    }
+}
+
+impl<K> QueryClient<K>
+where
+    K: 'static + Eq + Hash + Clone + MaybeSend + MaybeSync,
+{
+    /// Serve the queries of the given value and error types,
+    /// so the query hooks of the descendants of [provide_shared_query_client] find their client.
+    pub fn serve<T, E>(self) -> Self
+    where
+        T: 'static + Clone + MaybeSend + MaybeSync,
+        E: 'static + Clone + MaybeSend + MaybeSync,
+    {
+        self.typed::<T, E>();
+        self
+    }
+
+    /// Get the client of the given value and error types, creating it if it doesn't exist yet.
+    pub fn typed<T, E>(&self) -> UseQueryClient<T, E, K>
+    where
+        T: 'static + Clone + MaybeSend + MaybeSync,
+        E: 'static + Clone + MaybeSend + MaybeSync,
+    {
+        if let Some(client) = self.get::<T, E>() {
+            return client;
+        }
+
+        let client =
+            UseQueryClient::<T, E, K>::new(self.scheduler.clone()).with_config(self.config.clone());
+        let invalidate: Shared<InvalidateFn<K>> = Shared::new({
+            let client = client.clone();
+            move |keys: &[K], except: &[Vec<K>]| client.invalidate_queries_except(keys, except)
+        });
+        self.clients.borrow_mut().insert(
+            TypeId::of::<(T, E)>(),
+            ErasedClient {
+                client: Box::new(client.clone()),
+                invalidate,
+            },
+        );
+        client
+    }
+}
+
+/// Provide the given [QueryClient] to the descendants of this component.
+/// Their query hooks use the client it serves for their value and error types,
+/// unless an ancestor provides a [UseQueryClient] of those types itself,
+/// and mutations invalidate the queries of every type.
+pub fn provide_shared_query_client<K>(
+    cx: &ScopeState,
+    client: impl FnOnce() -> QueryClient<K>,
+) -> &QueryClient<K>
+where
+    K: 'static + Eq + Hash + Clone + MaybeSend + MaybeSync,
+{
+    let client = use_context_provider(cx, client);
+    use_context_provider(cx, || {
+        let client = client.clone();
+        QueryInvalidator(Shared::new(move |keys: &[K], except: &[Vec<K>]| {
+            client.invalidate_queries_except(keys, except)
+        }))
+    });
+    client
+}
+
+/// Get access to the [QueryClient] provided by the closest ancestor.
+pub fn use_shared_query_client<K: 'static>(cx: &ScopeState) -> &QueryClient<K> {
+    use_context(cx).unwrap()
+}
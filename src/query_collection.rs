@@ -0,0 +1,159 @@
+use std::{collections::VecDeque, hash::Hash};
+
+use crate::use_query_client::UseQueryClient;
+
+/// A cached value holding a list of items, so it can be edited optimistically
+/// with [UseQueryClient::append_to_query], [UseQueryClient::remove_from_query] and [UseQueryClient::replace_in_query].
+///
+/// It's implemented for [Vec] and [VecDeque], implement it for the query value to edit the collections it holds.
+///
+/// ## Example:
+///
+/// ```no_run
+/// impl QueryCollection for QueryValue {
+///     type Item = Todo;
+///
+///     fn append_item(&mut self, item: Todo) {
+///         if let QueryValue::Todos(todos) = self {
+///             todos.push(item);
+///         }
+///     }
+///
+///     fn retain_items(&mut self, keep: impl FnMut(&Todo) -> bool) {
+///         if let QueryValue::Todos(todos) = self {
+///             todos.retain(keep);
+///         }
+///     }
+///
+///     fn for_each_item_mut(&mut self, f: impl FnMut(&mut Todo)) {
+///         if let QueryValue::Todos(todos) = self {
+///             todos.iter_mut().for_each(f);
+///         }
+///     }
+/// }
+/// ```
+pub trait QueryCollection {
+    type Item;
+
+    /// Add the given item at the end of the collection.
+    fn append_item(&mut self, item: Self::Item);
+
+    /// Keep only the items for which `keep` returns `true`, in order.
+    fn retain_items(&mut self, keep: impl FnMut(&Self::Item) -> bool);
+
+    /// Call `f` with every item of the collection, in order.
+    fn for_each_item_mut(&mut self, f: impl FnMut(&mut Self::Item));
+}
+
+impl<I> QueryCollection for Vec<I> {
+    type Item = I;
+
+    fn append_item(&mut self, item: I) {
+        self.push(item);
+    }
+
+    fn retain_items(&mut self, keep: impl FnMut(&I) -> bool) {
+        self.retain(keep);
+    }
+
+    fn for_each_item_mut(&mut self, f: impl FnMut(&mut I)) {
+        self.iter_mut().for_each(f);
+    }
+}
+
+impl<I> QueryCollection for VecDeque<I> {
+    type Item = I;
+
+    fn append_item(&mut self, item: I) {
+        self.push_back(item);
+    }
+
+    fn retain_items(&mut self, keep: impl FnMut(&I) -> bool) {
+        self.retain(keep);
+    }
+
+    fn for_each_item_mut(&mut self, f: impl FnMut(&mut I)) {
+        self.iter_mut().for_each(f);
+    }
+}
+
+impl<T, E, K> UseQueryClient<T, E, K>
+where
+    T: 'static + Clone + QueryCollection,
+    E: 'static + Clone,
+    K: 'static + Eq + Hash + Clone,
+{
+    /// Add the given item at the end of the cached collection of the query with the given keys,
+    /// notifying its listeners once, see [UseQueryClient::update_query_data].
+    /// Returns the previous value to roll it back with [UseQueryClient::set_query_data],
+    /// or `None` without changing anything if there is no value to edit.
+    ///
+    /// ## Example:
+    ///
+    /// ```no_run
+    /// MutationConfig::new(add_todo)
+    ///     .on_mutate(
+    ///         |client: &UseQueryClient<QueryValue, QueryError, QueryKeys>, todo: &Todo| {
+    ///             client.append_to_query(&[QueryKeys::Todos], todo.clone())
+    ///         },
+    ///         |client, previous, _err, _todo| {
+    ///             if let Some(previous) = previous {
+    ///                 client.set_query_data(&[QueryKeys::Todos], previous);
+    ///             }
+    ///         },
+    ///     )
+    /// ```
+    pub fn append_to_query(&self, keys: &[K], item: T::Item) -> Option<T> {
+        self.edit_collection(keys, |data| data.append_item(item))
+    }
+
+    /// Remove the items for which `is_match` returns `true` from the cached collection of the query with the given keys,
+    /// see [UseQueryClient::append_to_query].
+    ///
+    /// ## Example:
+    ///
+    /// ```no_run
+    /// let previous = client.remove_from_query(&[QueryKeys::Todos], |todo| todo.id == id);
+    /// ```
+    pub fn remove_from_query(
+        &self,
+        keys: &[K],
+        mut is_match: impl FnMut(&T::Item) -> bool,
+    ) -> Option<T> {
+        self.edit_collection(keys, |data| data.retain_items(|item| !is_match(item)))
+    }
+
+    /// Replace the first item for which `is_match` returns `true` in the cached collection of the query with the given keys,
+    /// see [UseQueryClient::append_to_query].
+    ///
+    /// ## Example:
+    ///
+    /// ```no_run
+    /// let previous = client.replace_in_query(&[QueryKeys::Todos], |item| item.id == todo.id, todo.clone());
+    /// ```
+    pub fn replace_in_query(
+        &self,
+        keys: &[K],
+        mut is_match: impl FnMut(&T::Item) -> bool,
+        new_item: T::Item,
+    ) -> Option<T> {
+        let mut new_item = Some(new_item);
+        self.edit_collection(keys, |data| {
+            data.for_each_item_mut(|item| {
+                if new_item.is_some() && is_match(item) {
+                    *item = new_item.take().unwrap();
+                }
+            })
+        })
+    }
+
+    /// Edit the cached collection in place, returning a copy of it from before the edit.
+    fn edit_collection(&self, keys: &[K], edit: impl FnOnce(&mut T)) -> Option<T> {
+        let mut previous = None;
+        self.update_query_data(keys, |data| {
+            previous = Some(data.clone());
+            edit(data);
+        });
+        previous
+    }
+}
@@ -0,0 +1,189 @@
+use std::{
+    fmt::Debug,
+    hash::{Hash, Hasher},
+};
+
+#[cfg(debug_assertions)]
+use crate::{
+    queries_registry::QueriesRegistry,
+    sync,
+    use_query::QueryConfig,
+    use_query_client::{QueryFnId, UseQueryClient},
+};
+
+/// The type name of a query function, only kept in debug builds to tell the functions apart in the diagnostics.
+/// It doesn't take part in the comparison of query functions, their types do.
+#[derive(Clone, Copy)]
+pub(crate) struct QueryFnName {
+    #[cfg(debug_assertions)]
+    name: &'static str,
+}
+
+impl QueryFnName {
+    pub(crate) fn of<F>() -> Self {
+        Self {
+            #[cfg(debug_assertions)]
+            name: std::any::type_name::<F>(),
+        }
+    }
+}
+
+impl Debug for QueryFnName {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        #[cfg(debug_assertions)]
+        return f.write_str(self.name);
+        #[cfg(not(debug_assertions))]
+        f.write_str("QueryFnName")
+    }
+}
+
+impl PartialEq for QueryFnName {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for QueryFnName {}
+
+impl Hash for QueryFnName {
+    fn hash<H: Hasher>(&self, _: &mut H) {}
+}
+
+/// A registration of a query that probably isn't what was intended, see [UseQueryClient::registration_conflicts].
+/// Only available in debug builds.
+#[cfg(debug_assertions)]
+#[derive(Clone, Debug, PartialEq)]
+pub enum RegistrationConflict<K> {
+    /// The same query keys were registered with different query functions, e.g two closures calling the same endpoint,
+    /// so they got separate cache entries. Use the same function, or the same [QueryConfig::with_id].
+    QueryFn {
+        query_keys: Vec<K>,
+        /// The function of the entry that was already there
+        existing_query_fn: String,
+        /// The function of the new entry
+        new_query_fn: String,
+    },
+    /// The same query was registered with different options, only the ones of the registration that created its entry are used.
+    Options {
+        query_keys: Vec<K>,
+        query_fn: String,
+        /// Names of the options that differ, e.g `stale_time`
+        options: Vec<&'static str>,
+    },
+}
+
+/// Describe a query function for the diagnostics, by its type name or its explicit id.
+#[cfg(debug_assertions)]
+fn describe_query_fn(query_fn_id: &QueryFnId) -> String {
+    match query_fn_id {
+        QueryFnId::Type(_, name) => format!("{name:?}"),
+        QueryFnId::Explicit(id) => format!("id \"{id}\""),
+    }
+}
+
+#[cfg(debug_assertions)]
+impl<T, E, K> UseQueryClient<T, E, K>
+where
+    T: 'static + Clone,
+    E: 'static + Clone,
+    K: 'static + Eq + Hash + Clone,
+{
+    /// Get the suspicious registrations of queries seen so far, e.g the same query keys with different query functions.
+    /// They are also logged as `tracing` warnings with the `tracing` feature. Only available in debug builds.
+    ///
+    /// ## Example:
+    ///
+    /// ```no_run
+    /// for conflict in client.registration_conflicts() {
+    ///     println!("{conflict:?}");
+    /// }
+    /// ```
+    pub fn registration_conflicts(&self) -> Vec<RegistrationConflict<K>> {
+        self.registration_conflicts.borrow().clone()
+    }
+
+    /// Look for conflicts between the given registration and the entries already in the cache.
+    pub(crate) fn check_registration(
+        &self,
+        queries_registry: &QueriesRegistry<T, E, K>,
+        config: &QueryConfig<T, E, K>,
+    ) {
+        let registry_entry = &config.registry_entry;
+        let conflict = match queries_registry.get(registry_entry) {
+            // A new entry, for keys that might be cached already
+            None => {
+                let Some(existing_entry) = queries_registry.keys().find(|entry| {
+                    entry.query_keys == registry_entry.query_keys
+                        && entry.query_fn_id != registry_entry.query_fn_id
+                }) else {
+                    return;
+                };
+                let existing_query_fn = describe_query_fn(&existing_entry.query_fn_id);
+                let new_query_fn = describe_query_fn(&registry_entry.query_fn_id);
+                query_warning!(
+                    keys = %self.fmt_keys(&registry_entry.query_keys),
+                    existing = %existing_query_fn,
+                    new = %new_query_fn,
+                    "query keys registered with another query function, they have separate cache entries"
+                );
+                RegistrationConflict::QueryFn {
+                    query_keys: registry_entry.query_keys.clone(),
+                    existing_query_fn,
+                    new_query_fn,
+                }
+            }
+            // Another listener of an entry, whose options were set by the first one
+            Some(query_listeners) => {
+                let defaults = &self.config;
+                let value = sync::read(&query_listeners.value);
+                let stale_time = config.stale_time.unwrap_or(defaults.stale_time);
+                let options = [
+                    ("stale_time", stale_time != value.stale_time),
+                    (
+                        "max_age",
+                        config.max_age.map(|max_age| max_age.max(stale_time)) != value.max_age,
+                    ),
+                    (
+                        "retry",
+                        config.retry.unwrap_or(defaults.retry) != query_listeners.retry,
+                    ),
+                    (
+                        "keep_data_on_error",
+                        config.keep_data_on_error != value.keep_data_on_error,
+                    ),
+                    (
+                        "loading_on_refetch",
+                        config.loading_on_refetch != query_listeners.loading_on_refetch,
+                    ),
+                    (
+                        "min_fetch_interval",
+                        config.min_fetch_interval != query_listeners.min_fetch_interval,
+                    ),
+                ]
+                .into_iter()
+                .filter_map(|(option, differs)| differs.then_some(option))
+                .collect::<Vec<_>>();
+                if options.is_empty() {
+                    return;
+                }
+                let query_fn = describe_query_fn(&registry_entry.query_fn_id);
+                query_warning!(
+                    keys = %self.fmt_keys(&registry_entry.query_keys),
+                    query_fn = %query_fn,
+                    options = ?options,
+                    "query registered with options that differ from its entry, they are ignored"
+                );
+                RegistrationConflict::Options {
+                    query_keys: registry_entry.query_keys.clone(),
+                    query_fn,
+                    options,
+                }
+            }
+        };
+
+        let mut conflicts = self.registration_conflicts.borrow_mut();
+        if !conflicts.contains(&conflict) {
+            conflicts.push(conflict);
+        }
+    }
+}
@@ -0,0 +1,53 @@
+use std::{hash::Hash, sync::RwLockReadGuard};
+
+use crate::{
+    cached_result::{CachedResult, QueryState},
+    result::QueryResult,
+    use_query_client::{QueryValue, RegistryEntry, UseQueryClient},
+};
+
+/// A query listener that is not tied to any component,
+/// e.g to test query functions and the cache without a `VirtualDom`.
+/// Created with [UseQueryClient::register_query].
+///
+/// Unlike [UseQuery](crate::prelude::UseQuery) it doesn't fetch by itself, call [QueryHandle::fetch].
+pub struct QueryHandle<T, E, K: Eq + Hash> {
+    pub(crate) client: UseQueryClient<T, E, K>,
+    pub(crate) value: QueryValue<CachedResult<T, E>>,
+    pub(crate) registry_entry: RegistryEntry<K>,
+}
+
+impl<T, E, K: Eq + Hash> QueryHandle<T, E, K> {
+    /// Get the current result from the query.
+    pub fn result(&self) -> RwLockReadGuard<CachedResult<T, E>> {
+        self.client.read_entry(&self.registry_entry, &self.value)
+    }
+
+    /// Get a snapshot of the query with both its last successful value and its last error.
+    pub fn state(&self) -> QueryState<T, E>
+    where
+        T: Clone,
+        E: Clone,
+    {
+        self.result().state()
+    }
+}
+
+impl<T, E, K> QueryHandle<T, E, K>
+where
+    T: 'static + Clone,
+    E: 'static + Clone,
+    K: 'static + Eq + Hash + Clone,
+{
+    /// Fetch the query unless its cached result is still fresh, and get the result.
+    pub async fn fetch(&self) -> QueryResult<T, E> {
+        self.client.run_new_query(&self.registry_entry).await;
+        self.result().value().clone()
+    }
+}
+
+impl<T, E, K: Eq + Hash> Drop for QueryHandle<T, E, K> {
+    fn drop(&mut self) {
+        self.client.unregister_listener(&self.registry_entry, None);
+    }
+}
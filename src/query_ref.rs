@@ -0,0 +1,149 @@
+use std::{
+    hash::Hash,
+    sync::{Arc, RwLockReadGuard},
+};
+
+use crate::{
+    cached_result::{CachedResult, QueryState},
+    clock::Instant,
+    sync,
+    use_query_client::{QueryValue, RegistryEntry, UseQueryClient},
+};
+
+/// An owned handle to the cached result of a query, see [UseQuery::handle](crate::prelude::UseQuery::handle).
+///
+/// Unlike [UseQuery](crate::prelude::UseQuery), it isn't a listener: it can be cloned, moved into
+/// event handlers and spawned tasks, or passed as a prop, and dropping it doesn't affect the query.
+/// The component that took it stays the listener, so it's still re-rendered as the query changes.
+/// Once that component unmounts, the handle keeps the last cached result and refetching does nothing.
+/// For a listener that isn't tied to any component, see [QueryHandle](crate::prelude::QueryHandle).
+///
+/// Two handles are equal if they come from the same query and were taken from the same result,
+/// so memoized children taking a handle as a prop are only re-rendered when the result changes.
+///
+/// ## Example:
+///
+/// ```no_run
+/// let user = use_query(cx, || vec![QueryKeys::User(id)], fetch_user).handle();
+///
+/// let refresh = {
+///     let user = user.clone();
+///     move |_| user.refetch()
+/// };
+/// cx.spawn({
+///     let user = user.clone();
+///     async move {
+///         sleep(Duration::from_secs(5)).await;
+///         if user.error_cloned().is_some() {
+///             user.refetch();
+///         }
+///     }
+/// });
+///
+/// render!(
+///     UserName { user: user }
+///     button { onclick: refresh, "Refresh" }
+/// )
+/// ```
+pub struct QueryRef<T, E, K> {
+    client: UseQueryClient<T, E, K>,
+    value: QueryValue<CachedResult<T, E>>,
+    registry_entry: RegistryEntry<K>,
+    /// When the result was last updated and whether it was being fetched, as the handle was taken
+    taken_from: (Option<Instant>, bool),
+}
+
+impl<T, E, K> QueryRef<T, E, K> {
+    pub(crate) fn new(
+        client: UseQueryClient<T, E, K>,
+        value: QueryValue<CachedResult<T, E>>,
+        registry_entry: RegistryEntry<K>,
+    ) -> Self {
+        let taken_from = {
+            let result = sync::read(&value);
+            (result.instant, result.is_fetching)
+        };
+        Self {
+            client,
+            value,
+            registry_entry,
+            taken_from,
+        }
+    }
+}
+
+impl<T, E, K: Eq + Hash> QueryRef<T, E, K> {
+    /// Get the current result from the query, see [UseQuery::result](crate::prelude::UseQuery::result).
+    pub fn result(&self) -> RwLockReadGuard<CachedResult<T, E>> {
+        self.client.read_entry(&self.registry_entry, &self.value)
+    }
+
+    /// Get a copy of the current value of the query, if there is any.
+    pub fn cloned(&self) -> Option<T>
+    where
+        T: Clone,
+    {
+        self.result().value().data().cloned()
+    }
+
+    /// Get a copy of the current error of the query, if it errored.
+    pub fn error_cloned(&self) -> Option<E>
+    where
+        E: Clone,
+    {
+        self.result().value().err().cloned()
+    }
+
+    /// Get a snapshot of the query with both its last successful value and its last error.
+    pub fn state(&self) -> QueryState<T, E>
+    where
+        T: Clone,
+        E: Clone,
+    {
+        self.result().state()
+    }
+}
+
+impl<T, E, K> QueryRef<T, E, K>
+where
+    T: 'static + Clone,
+    E: 'static + Clone,
+    K: 'static + Eq + Hash + Clone,
+{
+    /// Fetch the query in the **background**, even if its cached result is fresh,
+    /// see [UseQuery::trigger](crate::prelude::UseQuery::trigger).
+    pub fn refetch(&self) {
+        self.spawn_refetch(false);
+    }
+
+    /// Same as [QueryRef::refetch] but even while the client is paused,
+    /// see [UseQueryClient::pause_all](crate::prelude::UseQueryClient::pause_all).
+    pub fn force_refetch(&self) {
+        self.spawn_refetch(true);
+    }
+
+    fn spawn_refetch(&self, force: bool) {
+        let client = self.client.clone();
+        let registry_entry = self.registry_entry.clone();
+        self.client.spawn_task(async move {
+            client.trigger_query(&registry_entry, force).await;
+        });
+    }
+}
+
+impl<T: Clone, E: Clone, K: Clone> Clone for QueryRef<T, E, K> {
+    fn clone(&self) -> Self {
+        Self {
+            client: self.client.clone(),
+            value: self.value.clone(),
+            registry_entry: self.registry_entry.clone(),
+            taken_from: self.taken_from,
+        }
+    }
+}
+
+impl<T, E, K> PartialEq for QueryRef<T, E, K> {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.value, &other.value) && self.taken_from == other.taken_from
+    }
+}
@@ -0,0 +1,74 @@
+/// Define a module with aliases of the query types and hooks of an app, so the value, error and keys types
+/// don't have to be given on every call. They are plain aliases and wrappers of the generic ones.
+///
+/// The module contains:
+/// - `QueryClient`, `Query`, `QueryConfig` and `QueryResult`, aliases of [UseQueryClient](crate::prelude::UseQueryClient),
+///   [UseQuery](crate::prelude::UseQuery), [QueryConfig](crate::prelude::QueryConfig) and [QueryResult](crate::prelude::QueryResult)
+/// - `use_init_query_client`, `use_query_client`, `use_query` and `use_query_config`, wrappers of the hooks with the same names
+///
+/// ## Example:
+///
+/// ```no_run
+/// define_query_types!(pub app: Value = QueryValue, Error = QueryError, Keys = QueryKeys);
+///
+/// #[component]
+/// fn User(cx: Scope, id: usize) -> Element {
+///     let user = app::use_query(cx, || vec![QueryKeys::User(*id)], fetch_user);
+///     ...
+/// }
+///
+/// #[derive(Props)]
+/// struct UserNameProps<'a> {
+///     user: &'a app::Query,
+/// }
+/// ```
+#[macro_export]
+macro_rules! define_query_types {
+    ($vis:vis $name:ident: Value = $value:ty, Error = $error:ty, Keys = $keys:ty $(,)?) => {
+        $vis mod $name {
+            #![allow(dead_code, unused_imports)]
+            use super::*;
+
+            pub type QueryClient = $crate::prelude::UseQueryClient<$value, $error, $keys>;
+            pub type Query = $crate::prelude::UseQuery<$value, $error, $keys>;
+            pub type QueryConfig = $crate::prelude::QueryConfig<$value, $error, $keys>;
+            pub type QueryResult = $crate::prelude::QueryResult<$value, $error>;
+
+            /// Same as `dioxus_query::prelude::use_init_query_client`, with the types of the app.
+            pub fn use_init_query_client(cx: &::dioxus::prelude::ScopeState) -> &QueryClient {
+                $crate::prelude::use_init_query_client(cx)
+            }
+
+            /// Same as `dioxus_query::prelude::use_query_client`, with the types of the app.
+            pub fn use_query_client(cx: &::dioxus::prelude::ScopeState) -> &QueryClient {
+                $crate::prelude::use_query_client(cx)
+            }
+
+            /// Same as `dioxus_query::prelude::use_query`, with the types of the app.
+            pub fn use_query<Q, F>(
+                cx: &::dioxus::prelude::ScopeState,
+                query_keys: impl FnOnce() -> Vec<$keys>,
+                query_fn: Q,
+            ) -> &Query
+            where
+                Q: 'static
+                    + Fn(Vec<$keys>) -> F
+                    + $crate::prelude::MaybeSend
+                    + $crate::prelude::MaybeSync,
+                F: 'static
+                    + $crate::prelude::futures_util::Future<Output = QueryResult>
+                    + $crate::prelude::MaybeSend,
+            {
+                $crate::prelude::use_query(cx, query_keys, query_fn)
+            }
+
+            /// Same as `dioxus_query::prelude::use_query_config`, with the types of the app.
+            pub fn use_query_config(
+                cx: &::dioxus::prelude::ScopeState,
+                config: impl FnOnce() -> QueryConfig,
+            ) -> &Query {
+                $crate::prelude::use_query_config(cx, config)
+            }
+        }
+    };
+}
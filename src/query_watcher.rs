@@ -0,0 +1,102 @@
+use futures_util::future::poll_fn;
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    task::{Poll, Waker},
+};
+
+use crate::{
+    cached_result::CachedResult,
+    sync::{self, Lock, Shared},
+    use_query_client::UseQueryClient,
+};
+
+/// How many times the watched query keys changed, and the task waiting for the next change.
+#[derive(Default)]
+pub(crate) struct WatcherState {
+    changes: usize,
+    waker: Option<Waker>,
+}
+
+impl WatcherState {
+    /// Let the watcher know its query keys changed.
+    pub(crate) fn notify(&mut self) {
+        self.changes += 1;
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+/// The watchers of the client, by the query keys they watch.
+pub(crate) type QueryWatchers<K> = HashMap<Vec<K>, Vec<Shared<Lock<WatcherState>>>>;
+
+/// Follows the cached value of some query keys outside of any component,
+/// e.g from a background task that mirrors it somewhere else.
+/// Created with [UseQueryClient::watch].
+///
+/// It keeps the entries of its query keys in the cache like a listener does,
+/// but it never fetches them. Once dropped, they are collected like any entry without listeners.
+pub struct QueryWatcher<T, E, K: Eq + Hash> {
+    pub(crate) client: UseQueryClient<T, E, K>,
+    pub(crate) query_keys: Vec<K>,
+    pub(crate) state: Shared<Lock<WatcherState>>,
+    /// The changes that were already seen
+    pub(crate) seen: usize,
+}
+
+impl<T, E, K> QueryWatcher<T, E, K>
+where
+    T: 'static + Clone,
+    E: 'static + Clone,
+    K: 'static + Eq + Hash + Clone,
+{
+    /// Get a copy of the cached result, `None` if nothing is cached for these keys.
+    /// If multiple query functions are cached with the same keys, the most recently updated one is used.
+    pub fn cached(&self) -> Option<CachedResult<T, E>> {
+        self.client
+            .get_entry_with_keys(&self.query_keys)
+            .map(|query_listeners| sync::read(&query_listeners.value).clone())
+    }
+
+    /// Wait for the cached value to change, and get it. Changes that happen in between calls
+    /// are not lost, but they are collapsed into a single one.
+    ///
+    /// ## Example:
+    ///
+    /// ```no_run
+    /// let mut watcher = client.watch(&[QueryKeys::User(42)]);
+    /// spawn(async move {
+    ///     loop {
+    ///         if let Some(user) = watcher.changed().await {
+    ///             mirror.store(user.value());
+    ///         }
+    ///     }
+    /// });
+    /// ```
+    pub async fn changed(&mut self) -> Option<CachedResult<T, E>> {
+        poll_fn(|cx| {
+            let mut state = self.state.borrow_mut();
+            if state.changes != self.seen {
+                self.seen = state.changes;
+                return Poll::Ready(());
+            }
+            state.waker = Some(cx.waker().clone());
+            Poll::Pending
+        })
+        .await;
+        self.cached()
+    }
+}
+
+impl<T, E, K: Eq + Hash> Drop for QueryWatcher<T, E, K> {
+    fn drop(&mut self) {
+        let mut watchers = self.client.watchers.borrow_mut();
+        if let Some(states) = watchers.get_mut(&self.query_keys) {
+            states.retain(|state| !Shared::ptr_eq(state, &self.state));
+            if states.is_empty() {
+                watchers.remove(&self.query_keys);
+            }
+        }
+    }
+}
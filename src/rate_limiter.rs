@@ -0,0 +1,250 @@
+use futures_util::future::poll_fn;
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt::Debug,
+    task::{Poll, Waker},
+    time::Duration,
+};
+
+use crate::{
+    clock::{Instant, SharedClock},
+    sync::{Lock, Shared},
+};
+
+struct BucketState {
+    /// How many query functions can start right now
+    tokens: usize,
+    /// When the last token was added, `None` until the first call
+    last_refill: Option<Instant>,
+    /// Tickets of the fetches waiting for a token, in the order they arrived
+    queue: VecDeque<usize>,
+    /// Wakers of the fetches waiting for their turn, by their tickets
+    waiters: HashMap<usize, Waker>,
+    next_ticket: usize,
+}
+
+impl BucketState {
+    /// Add the tokens earned since the last refill, up to the capacity.
+    fn refill(&mut self, now: Instant, capacity: usize, refill_interval: Duration) {
+        let Some(last_refill) = self.last_refill else {
+            self.last_refill = Some(now);
+            return;
+        };
+        if self.tokens >= capacity || refill_interval.is_zero() {
+            self.tokens = capacity;
+            self.last_refill = Some(now);
+            return;
+        }
+        let elapsed = now.saturating_duration_since(last_refill);
+        let earned = (elapsed.as_nanos() / refill_interval.as_nanos()) as usize;
+        if earned == 0 {
+            return;
+        }
+        self.tokens = (self.tokens + earned).min(capacity);
+        self.last_refill = Some(if self.tokens == capacity {
+            now
+        } else {
+            last_refill + refill_interval * earned as u32
+        });
+    }
+
+    /// Leave the queue, letting the next fetch know it's its turn.
+    fn leave(&mut self, ticket: usize) {
+        self.waiters.remove(&ticket);
+        let was_first = self.queue.front() == Some(&ticket);
+        self.queue.retain(|queued| *queued != ticket);
+        if was_first {
+            if let Some(waiter) = self
+                .queue
+                .front()
+                .and_then(|next| self.waiters.remove(next))
+            {
+                waiter.wake();
+            }
+        }
+    }
+}
+
+/// Limits how often query functions are called with a token bucket, see [QueryClientConfig::rate_limit](crate::prelude::QueryClientConfig::rate_limit).
+/// Fetches get their token in the order they asked for it. Its clones share the same bucket.
+#[derive(Clone)]
+pub(crate) struct RateLimiter {
+    capacity: usize,
+    refill_interval: Duration,
+    state: Shared<Lock<BucketState>>,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(capacity: usize, refill_interval: Duration) -> Self {
+        let capacity = capacity.max(1);
+        Self {
+            capacity,
+            refill_interval,
+            state: Shared::new(Lock::new(BucketState {
+                tokens: capacity,
+                last_refill: None,
+                queue: VecDeque::new(),
+                waiters: HashMap::new(),
+                next_ticket: 0,
+            })),
+        }
+    }
+
+    /// Wait until a query function can be called, taking a token.
+    /// Dropping the returned future before it's ready, e.g when the fetch is aborted, gives up its turn.
+    pub(crate) async fn acquire(&self, clock: &SharedClock) {
+        let ticket = {
+            let mut state = self.state.borrow_mut();
+            let ticket = state.next_ticket;
+            state.next_ticket += 1;
+            state.queue.push_back(ticket);
+            ticket
+        };
+        let mut turn = QueueTurn {
+            state: &self.state,
+            ticket,
+            is_done: false,
+        };
+
+        loop {
+            // Wait for the fetches that arrived earlier
+            poll_fn(|cx| {
+                let mut state = self.state.borrow_mut();
+                if state.queue.front() == Some(&ticket) {
+                    return Poll::Ready(());
+                }
+                state.waiters.insert(ticket, cx.waker().clone());
+                Poll::Pending
+            })
+            .await;
+
+            // Wait for the next token
+            let next_refill = {
+                let mut state = self.state.borrow_mut();
+                let now = clock.now();
+                state.refill(now, self.capacity, self.refill_interval);
+                if state.tokens > 0 {
+                    state.tokens -= 1;
+                    state.leave(ticket);
+                    turn.is_done = true;
+                    return;
+                }
+                let last_refill = state.last_refill.unwrap_or(now);
+                (last_refill + self.refill_interval).saturating_duration_since(now)
+            };
+            Box::into_pin(clock.sleep(next_refill)).await;
+        }
+    }
+}
+
+impl Debug for RateLimiter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RateLimiter")
+            .field("capacity", &self.capacity)
+            .field("refill_interval", &self.refill_interval)
+            .finish()
+    }
+}
+
+/// The limiter doesn't take part in the comparison of configurations.
+impl PartialEq for RateLimiter {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+/// The place of a fetch in the queue, given up if it stops waiting before getting its token.
+struct QueueTurn<'a> {
+    state: &'a Lock<BucketState>,
+    ticket: usize,
+    is_done: bool,
+}
+
+impl Drop for QueueTurn<'_> {
+    fn drop(&mut self) {
+        if !self.is_done {
+            self.state.borrow_mut().leave(self.ticket);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::MockClock;
+    use std::{cell::RefCell, rc::Rc};
+    use tokio::task::{spawn_local, yield_now, JoinHandle, LocalSet};
+
+    async fn settle() {
+        for _ in 0..10 {
+            yield_now().await;
+        }
+    }
+
+    /// Wait for a token in the background, recording when it's taken.
+    fn waiter(
+        limiter: &RateLimiter,
+        clock: &SharedClock,
+        acquired: &Rc<RefCell<Vec<usize>>>,
+        id: usize,
+    ) -> JoinHandle<()> {
+        let (limiter, clock, acquired) = (limiter.clone(), clock.clone(), acquired.clone());
+        spawn_local(async move {
+            limiter.acquire(&clock).await;
+            acquired.borrow_mut().push(id);
+        })
+    }
+
+    #[tokio::test]
+    async fn fetches_get_their_token_in_the_order_they_asked_for_it() {
+        LocalSet::new()
+            .run_until(async {
+                let mock_clock = MockClock::default();
+                let clock = SharedClock::new(mock_clock.clone());
+                let limiter = RateLimiter::new(1, Duration::from_secs(1));
+                let acquired = Rc::new(RefCell::new(Vec::new()));
+
+                // The bucket starts full
+                limiter.acquire(&clock).await;
+                let waiters = (0..3)
+                    .map(|id| waiter(&limiter, &clock, &acquired, id))
+                    .collect::<Vec<_>>();
+                settle().await;
+                assert!(acquired.borrow().is_empty());
+
+                // One token per interval, to the one that has waited the longest
+                for expected in [vec![0], vec![0, 1], vec![0, 1, 2]] {
+                    mock_clock.advance(Duration::from_secs(1));
+                    settle().await;
+                    assert_eq!(*acquired.borrow(), expected);
+                }
+                for waiter in waiters {
+                    waiter.await.unwrap();
+                }
+            })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn aborted_fetches_give_up_their_turn() {
+        LocalSet::new()
+            .run_until(async {
+                let mock_clock = MockClock::default();
+                let clock = SharedClock::new(mock_clock.clone());
+                let limiter = RateLimiter::new(1, Duration::from_secs(1));
+                let acquired = Rc::new(RefCell::new(Vec::new()));
+
+                limiter.acquire(&clock).await;
+                let first = waiter(&limiter, &clock, &acquired, 0);
+                let second = waiter(&limiter, &clock, &acquired, 1);
+                settle().await;
+                first.abort();
+                settle().await;
+
+                mock_clock.advance(Duration::from_secs(1));
+                second.await.unwrap();
+                assert_eq!(*acquired.borrow(), vec![1]);
+            })
+            .await;
+    }
+}
@@ -2,6 +2,7 @@ use crate::cached_result::CachedResult;
 
 /// The result of a query.
 #[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum QueryResult<T, E> {
     /// Contains a successful state
     Ok(T),
@@ -23,6 +24,84 @@ impl<T, E> QueryResult<T, E> {
     pub fn is_loading(&self) -> bool {
         matches!(self, QueryResult::Loading(..))
     }
+
+    /// Get the successful value, `None` if loading or errored.
+    pub fn ok(&self) -> Option<&T> {
+        match self {
+            QueryResult::Ok(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Get the error, `None` if successful or loading.
+    pub fn err(&self) -> Option<&E> {
+        match self {
+            QueryResult::Err(e) => Some(e),
+            _ => None,
+        }
+    }
+
+    /// Get the cached value shown while loading, `None` if not loading or there is none.
+    pub fn loading_value(&self) -> Option<&T> {
+        match self {
+            QueryResult::Loading(v) => v.as_ref(),
+            _ => None,
+        }
+    }
+
+    /// Get the value there is data for, either the successful value or the cached value shown while loading.
+    pub fn data(&self) -> Option<&T> {
+        match self {
+            QueryResult::Ok(v) | QueryResult::Loading(Some(v)) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Convert into a [Result], with the data of the result if any, see [QueryResult::data].
+    pub fn to_result(self) -> Result<Option<T>, E> {
+        match self {
+            QueryResult::Ok(v) => Ok(Some(v)),
+            QueryResult::Err(e) => Err(e),
+            QueryResult::Loading(v) => Ok(v),
+        }
+    }
+
+    /// Convert from `&QueryResult<T, E>` to `QueryResult<&T, &E>`, e.g to transform it without cloning it.
+    pub fn as_ref(&self) -> QueryResult<&T, &E> {
+        match self {
+            QueryResult::Ok(v) => QueryResult::Ok(v),
+            QueryResult::Err(e) => QueryResult::Err(e),
+            QueryResult::Loading(v) => QueryResult::Loading(v.as_ref()),
+        }
+    }
+
+    /// Transform the successful value, including the cached one of a loading state.
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> QueryResult<U, E> {
+        match self {
+            QueryResult::Ok(v) => QueryResult::Ok(f(v)),
+            QueryResult::Err(e) => QueryResult::Err(e),
+            QueryResult::Loading(v) => QueryResult::Loading(v.map(f)),
+        }
+    }
+
+    /// Transform the error.
+    pub fn map_err<F>(self, f: impl FnOnce(E) -> F) -> QueryResult<T, F> {
+        match self {
+            QueryResult::Ok(v) => QueryResult::Ok(v),
+            QueryResult::Err(e) => QueryResult::Err(f(e)),
+            QueryResult::Loading(v) => QueryResult::Loading(v),
+        }
+    }
+
+    /// Transform the successful value with a fallible function.
+    /// For a loading state the cached value is transformed too, and dropped if `f` doesn't return a value.
+    pub fn and_then<U>(self, f: impl FnOnce(T) -> QueryResult<U, E>) -> QueryResult<U, E> {
+        match self {
+            QueryResult::Ok(v) => f(v),
+            QueryResult::Err(e) => QueryResult::Err(e),
+            QueryResult::Loading(v) => QueryResult::Loading(v.and_then(|v| f(v).into())),
+        }
+    }
 }
 
 impl<T, E> Default for QueryResult<T, E> {
@@ -31,9 +110,9 @@ impl<T, E> Default for QueryResult<T, E> {
     }
 }
 
-impl<T, E> From<CachedResult<T, E>> for Option<T> {
-    fn from(result: CachedResult<T, E>) -> Self {
-        match result.value {
+impl<T, E> From<QueryResult<T, E>> for Option<T> {
+    fn from(result: QueryResult<T, E>) -> Self {
+        match result {
             QueryResult::Ok(v) => Some(v),
             QueryResult::Err(_) => None,
             QueryResult::Loading(v) => v,
@@ -41,6 +120,12 @@ impl<T, E> From<CachedResult<T, E>> for Option<T> {
     }
 }
 
+impl<T, E> From<CachedResult<T, E>> for Option<T> {
+    fn from(result: CachedResult<T, E>) -> Self {
+        result.value.into()
+    }
+}
+
 impl<T, E> From<Result<T, E>> for QueryResult<T, E> {
     fn from(value: Result<T, E>) -> Self {
         match value {
@@ -55,3 +140,135 @@ impl<T, E> From<T> for QueryResult<T, E> {
         QueryResult::Ok(value)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type TestResult = QueryResult<usize, String>;
+
+    fn variants() -> [TestResult; 4] {
+        [
+            QueryResult::Ok(2),
+            QueryResult::Err("failed".to_string()),
+            QueryResult::Loading(Some(2)),
+            QueryResult::Loading(None),
+        ]
+    }
+
+    #[test]
+    fn accessors_get_what_each_variant_holds() {
+        let [ok, err, loading, empty] = variants();
+        assert_eq!(
+            [ok.ok(), err.ok(), loading.ok(), empty.ok()],
+            [Some(&2), None, None, None]
+        );
+        assert_eq!(
+            [ok.err(), err.err(), loading.err(), empty.err()],
+            [None, Some(&"failed".to_string()), None, None]
+        );
+        assert_eq!(
+            [
+                ok.loading_value(),
+                err.loading_value(),
+                loading.loading_value(),
+                empty.loading_value()
+            ],
+            [None, None, Some(&2), None]
+        );
+        // Only `Ok` and `Loading(Some)` have data
+        assert_eq!(
+            [ok.data(), err.data(), loading.data(), empty.data()],
+            [Some(&2), None, Some(&2), None]
+        );
+    }
+
+    #[test]
+    fn to_result_keeps_the_data_or_the_error() {
+        assert_eq!(
+            variants().map(TestResult::to_result),
+            [
+                Ok(Some(2)),
+                Err("failed".to_string()),
+                Ok(Some(2)),
+                Ok(None)
+            ]
+        );
+    }
+
+    #[test]
+    fn cached_results_have_the_same_accessors() {
+        let cached = CachedResult::new(TestResult::Loading(Some(2)));
+        assert_eq!(cached.ok(), None);
+        assert_eq!(cached.err(), None);
+        assert_eq!(cached.loading_value(), Some(&2));
+    }
+
+    #[test]
+    fn as_ref_borrows_every_variant() {
+        let [ok, err, loading, empty] = variants();
+        assert_eq!(ok.as_ref(), QueryResult::Ok(&2));
+        assert_eq!(err.as_ref(), QueryResult::Err(&"failed".to_string()));
+        assert_eq!(loading.as_ref(), QueryResult::Loading(Some(&2)));
+        assert_eq!(empty.as_ref(), QueryResult::Loading(None));
+    }
+
+    #[test]
+    fn map_transforms_the_value_of_every_variant() {
+        let mapped = variants().map(|result| result.map(|v| v * 10));
+        assert_eq!(
+            mapped,
+            [
+                QueryResult::Ok(20),
+                QueryResult::Err("failed".to_string()),
+                QueryResult::Loading(Some(20)),
+                QueryResult::Loading(None),
+            ]
+        );
+    }
+
+    #[test]
+    fn map_err_only_transforms_the_error() {
+        let mapped = variants().map(|result| result.map_err(|e| e.len()));
+        assert_eq!(
+            mapped,
+            [
+                QueryResult::Ok(2),
+                QueryResult::Err(6),
+                QueryResult::Loading(Some(2)),
+                QueryResult::Loading(None),
+            ]
+        );
+    }
+
+    #[test]
+    fn and_then_chains_every_variant() {
+        let halve = |v: usize| -> TestResult {
+            if v % 2 == 0 {
+                QueryResult::Ok(v / 2)
+            } else {
+                QueryResult::Err("odd".to_string())
+            }
+        };
+        let chained = variants().map(|result| result.and_then(halve));
+        assert_eq!(
+            chained,
+            [
+                QueryResult::Ok(1),
+                QueryResult::Err("failed".to_string()),
+                QueryResult::Loading(Some(1)),
+                QueryResult::Loading(None),
+            ]
+        );
+
+        // The cached value of a loading state is dropped if it can't be transformed
+        assert_eq!(
+            QueryResult::Ok(3).and_then(halve),
+            QueryResult::Err("odd".to_string())
+        );
+        assert_eq!(
+            QueryResult::Loading(Some(3)).and_then(halve),
+            QueryResult::Loading(None)
+        );
+    }
+}
@@ -0,0 +1,158 @@
+use std::{
+    collections::hash_map::RandomState,
+    fmt::Debug,
+    hash::{BuildHasher, Hasher},
+    time::Duration,
+};
+
+use crate::sync::{Lock, MaybeSend, MaybeSync, Shared};
+
+/// How the delays of an [ExponentialBackoff] are randomized, so clients failing at the same time
+/// don't all retry at the same time too.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Jitter {
+    /// Wait for the computed delay exactly
+    #[default]
+    None,
+    /// Wait for anything between zero and the computed delay
+    Full,
+    /// Wait for at least half the computed delay, and anything up to all of it
+    Equal,
+}
+
+/// Source of the randomness of the [Jitter], see [ExponentialBackoff::with_random].
+pub trait RetryRandom: MaybeSend + MaybeSync {
+    /// Get a random number in `0.0..1.0`.
+    fn next_fraction(&self) -> f64;
+}
+
+/// A pseudo-random [RetryRandom] that always yields the same numbers for the same seed,
+/// e.g so tests of the retry timings are deterministic. It can be cheaply cloned.
+///
+/// ## Example:
+///
+/// ```no_run
+/// let backoff = ExponentialBackoff::new(Duration::from_millis(100), Duration::from_secs(10))
+///     .with_jitter(Jitter::Full)
+///     .with_random(SeededRandom::new(42));
+/// ```
+#[derive(Clone)]
+pub struct SeededRandom {
+    state: Shared<Lock<u64>>,
+}
+
+impl SeededRandom {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            // The state of a xorshift generator must not be zero
+            state: Shared::new(Lock::new(seed.max(1))),
+        }
+    }
+
+    /// Seed it with the random keys of the standard library, so each client gets different numbers.
+    fn from_entropy() -> Self {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u64(0);
+        Self::new(hasher.finish())
+    }
+}
+
+impl RetryRandom for SeededRandom {
+    fn next_fraction(&self) -> f64 {
+        let mut state = self.state.borrow_mut();
+        *state ^= *state << 13;
+        *state ^= *state >> 7;
+        *state ^= *state << 17;
+        // Keep the 53 bits a `f64` can represent exactly
+        (*state >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// The randomness shared by the clones of an [ExponentialBackoff].
+#[derive(Clone)]
+struct SharedRandom(Shared<dyn RetryRandom>);
+
+impl Debug for SharedRandom {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("SharedRandom")
+    }
+}
+
+/// The randomness doesn't take part in the comparison of configurations.
+impl PartialEq for SharedRandom {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+/// How long to wait before each retry, see [QueryConfig::retry_delay](crate::prelude::QueryConfig::retry_delay)
+/// and [MutationConfig::retry_with_backoff](crate::prelude::MutationConfig::retry_with_backoff).
+///
+/// The delay starts at `initial` and is multiplied by the factor (2 by default) after every attempt, up to `max`.
+/// With a [Jitter] each delay is randomized within that bound.
+///
+/// ## Example:
+///
+/// ```no_run
+/// // Wait up to 100ms, 200ms, 400ms... up to 10s
+/// let backoff = ExponentialBackoff::new(Duration::from_millis(100), Duration::from_secs(10))
+///     .with_jitter(Jitter::Full);
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExponentialBackoff {
+    initial: Duration,
+    factor: u32,
+    max: Duration,
+    jitter: Jitter,
+    random: SharedRandom,
+}
+
+impl ExponentialBackoff {
+    pub fn new(initial: Duration, max: Duration) -> Self {
+        Self {
+            initial,
+            factor: 2,
+            max,
+            jitter: Jitter::None,
+            random: SharedRandom(Shared::new(SeededRandom::from_entropy())),
+        }
+    }
+
+    /// Wait for the same delay before every retry.
+    pub fn constant(delay: Duration) -> Self {
+        Self::new(delay, delay).factor(1)
+    }
+
+    /// Set how much the delay is multiplied by after every attempt.
+    pub fn factor(mut self, factor: u32) -> Self {
+        self.factor = factor;
+        self
+    }
+
+    /// Randomize every delay within its bound, see [Jitter].
+    pub fn with_jitter(mut self, jitter: Jitter) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Set the source of the randomness of the [Jitter], e.g a [SeededRandom] in tests.
+    /// By default each backoff gets its own random seed.
+    pub fn with_random(mut self, random: impl RetryRandom + 'static) -> Self {
+        self.random = SharedRandom(Shared::new(random));
+        self
+    }
+
+    /// Get how long to wait after the given failed attempt, starting at 1.
+    pub(crate) fn delay(&self, attempt: u32) -> Duration {
+        let multiplier = self.factor.saturating_pow(attempt.saturating_sub(1));
+        let bound = self.initial.saturating_mul(multiplier).min(self.max);
+        match self.jitter {
+            Jitter::None => bound,
+            Jitter::Full => bound.mul_f64(self.random.0.next_fraction()),
+            Jitter::Equal => {
+                let half = bound / 2;
+                half + half.mul_f64(self.random.0.next_fraction())
+            }
+        }
+    }
+}
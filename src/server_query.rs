@@ -0,0 +1,84 @@
+use dioxus::prelude::*;
+use dioxus_fullstack::prelude::ServerFnError;
+use futures_util::Future;
+use std::{hash::Hash, time::Duration};
+
+use crate::{
+    result::QueryResult,
+    sync::{MaybeSend, MaybeSync},
+    use_query::{use_query_config, QueryConfig, UseQuery},
+};
+
+/// How long the results of server functions are considered fresh by default, in milliseconds.
+/// Long enough for the results hydrated from the server not to be fetched again as the page starts.
+const SERVER_QUERY_STALE_TIME: u64 = 30_000;
+
+/// Create the configuration of a query calling a `#[server]` function, see [use_server_query].
+/// Its options can still be changed, e.g with [QueryConfig::stale_time].
+pub fn server_query_config<T, E, K, V, Q, F>(
+    query_keys: Vec<K>,
+    server_fn: Q,
+) -> QueryConfig<T, E, K>
+where
+    T: 'static + From<V>,
+    E: 'static + From<ServerFnError>,
+    K: 'static,
+    Q: 'static + Fn(Vec<K>) -> F + MaybeSend + MaybeSync,
+    F: 'static + Future<Output = Result<V, ServerFnError>> + MaybeSend,
+{
+    QueryConfig::new(query_keys, move |keys| {
+        let fut = server_fn(keys);
+        async move {
+            match fut.await {
+                Ok(value) => QueryResult::Ok(T::from(value)),
+                Err(err) => QueryResult::Err(E::from(err)),
+            }
+        }
+    })
+    .stale_time(Duration::from_millis(SERVER_QUERY_STALE_TIME))
+}
+
+/// Register a query listener calling a `#[server]` function of Dioxus fullstack.
+/// Its value is converted into the value of the client with [From], and so is the [ServerFnError] into the error.
+/// Only available with the `fullstack` feature.
+///
+/// Its results are fresh for 30 seconds, so the ones rendered on the server and hydrated on the client
+/// with [UseQueryClient::hydrate](crate::prelude::UseQueryClient::hydrate) are not fetched again while the page starts.
+/// Use [server_query_config] with [use_query_config] for other options.
+///
+/// ## Example:
+///
+/// ```no_run
+/// #[server]
+/// async fn get_user(id: usize) -> Result<User, ServerFnError> {
+///     db::user(id).await
+/// }
+///
+/// let user = use_server_query(cx, || vec![QueryKeys::User(id)], |keys| async move {
+///     match keys.first() {
+///         Some(QueryKeys::User(id)) => get_user(*id).await,
+///         _ => Err(ServerFnError::ServerError("Unexpected keys".to_string())),
+///     }
+/// });
+/// ```
+pub fn use_server_query<T, E, K, V, Q, F>(
+    cx: &ScopeState,
+    query_keys: impl FnOnce() -> Vec<K>,
+    server_fn: Q,
+) -> &UseQuery<T, E, K>
+where
+    T: 'static + Clone + From<V>,
+    E: 'static + Clone + From<ServerFnError>,
+    K: 'static + Eq + Hash + Clone,
+    Q: 'static + Fn(Vec<K>) -> F + MaybeSend + MaybeSync,
+    F: 'static + Future<Output = Result<V, ServerFnError>> + MaybeSend,
+{
+    let mut server_fn = Some(server_fn);
+    let query = use_query_config(cx, || {
+        server_query_config(query_keys(), server_fn.take().unwrap())
+    });
+    if let Some(server_fn) = server_fn {
+        query.refresh_query_fn(server_query_config(Vec::new(), server_fn));
+    }
+    query
+}
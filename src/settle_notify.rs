@@ -0,0 +1,79 @@
+use futures_util::future::poll_fn;
+use std::{
+    collections::HashMap,
+    task::{Poll, Waker},
+};
+
+use crate::sync::Lock;
+
+#[derive(Default)]
+struct SettleState {
+    /// Bumped whenever a result is written to the entry
+    settles: usize,
+    /// Wakers of the futures waiting for the next settle, by their ids
+    waiters: HashMap<usize, Waker>,
+    next_waiter: usize,
+}
+
+/// Lets futures wait for the cached result of an entry to be written, see [UseQuery::wait](crate::prelude::UseQuery::wait).
+/// It only holds the wakers of the futures waiting right now, not the entry.
+#[derive(Default)]
+pub(crate) struct SettleNotify {
+    state: Lock<SettleState>,
+}
+
+impl SettleNotify {
+    /// Wake every future waiting for the entry to settle.
+    pub(crate) fn notify(&self) {
+        let waiters = {
+            let mut state = self.state.borrow_mut();
+            state.settles = state.settles.wrapping_add(1);
+            std::mem::take(&mut state.waiters)
+        };
+        for waiter in waiters.into_values() {
+            waiter.wake();
+        }
+    }
+
+    /// Get how many times the entry was written, to wait for the next write with [SettleNotify::settled_since].
+    pub(crate) fn settles(&self) -> usize {
+        self.state.borrow().settles
+    }
+
+    /// Wait until the entry is written after the given number of writes.
+    /// Dropping the returned future before it's ready removes its waker.
+    pub(crate) async fn settled_since(&self, settles: usize) {
+        let id = {
+            let mut state = self.state.borrow_mut();
+            let id = state.next_waiter;
+            state.next_waiter = state.next_waiter.wrapping_add(1);
+            id
+        };
+        let _waiter = Waiter {
+            state: &self.state,
+            id,
+        };
+
+        poll_fn(|cx| {
+            let mut state = self.state.borrow_mut();
+            if state.settles != settles {
+                return Poll::Ready(());
+            }
+            state.waiters.insert(id, cx.waker().clone());
+            Poll::Pending
+        })
+        .await;
+    }
+}
+
+/// A future waiting for the entry to settle, which stops being woken once dropped.
+struct Waiter<'a> {
+    state: &'a Lock<SettleState>,
+    id: usize,
+}
+
+impl Drop for Waiter<'_> {
+    fn drop(&mut self) {
+        self.state.borrow_mut().waiters.remove(&self.id);
+    }
+}
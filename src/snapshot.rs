@@ -0,0 +1,99 @@
+use std::{borrow::Cow, fmt::Debug};
+
+use crate::{
+    cached_result::CachedResult, clock::Instant, result::QueryResult, use_mutation::MutationResult,
+    use_query::QueryMeta,
+};
+
+/// The status of a cache entry, see [QueryEntrySnapshot].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum QueryEntryStatus {
+    /// It has no value yet
+    Loading,
+    /// Its last fetch failed
+    Error,
+    /// It has a value that won't be refetched yet
+    Fresh,
+    /// It has a value that will be refetched
+    Stale,
+}
+
+impl QueryEntryStatus {
+    pub(crate) fn of<T, E>(cached_result: &CachedResult<T, E>) -> Self {
+        match cached_result.value() {
+            QueryResult::Loading(_) => Self::Loading,
+            QueryResult::Err(_) => Self::Error,
+            QueryResult::Ok(_) if cached_result.is_fresh() => Self::Fresh,
+            QueryResult::Ok(_) => Self::Stale,
+        }
+    }
+}
+
+/// A snapshot of a cache entry, for debugging purposes.
+/// See [UseQueryClient::snapshot](crate::prelude::UseQueryClient::snapshot).
+#[derive(Clone, Debug, PartialEq)]
+pub struct QueryEntrySnapshot<K> {
+    /// Keys of the entry
+    pub query_keys: Vec<K>,
+    /// Status of the entry
+    pub status: QueryEntryStatus,
+    /// Whether a fetch is running
+    pub is_fetching: bool,
+    /// When the value was last updated
+    pub updated_at: Option<Instant>,
+    /// How many components and handles are listening to it
+    pub listener_count: usize,
+    /// Metadata of the query, see [QueryConfig::meta](crate::prelude::QueryConfig::meta)
+    pub meta: QueryMeta,
+    pub(crate) debug_value: Option<String>,
+}
+
+impl<K> QueryEntrySnapshot<K> {
+    /// Get the value of the entry formatted with [Debug],
+    /// only available in snapshots taken with [UseQueryClient::snapshot_with_values](crate::prelude::UseQueryClient::snapshot_with_values).
+    pub fn debug_value(&self) -> Option<&str> {
+        self.debug_value.as_deref()
+    }
+}
+
+/// The status of a mutation, see [MutationSnapshot].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MutationStatus {
+    /// It has not been called yet, or it was reset
+    Pending,
+    /// A call is running
+    Loading,
+    /// Its last call succeeded
+    Success,
+    /// Its last call failed
+    Error,
+    /// Its last call failed because the app is offline and will be replayed
+    Queued,
+}
+
+impl MutationStatus {
+    pub(crate) fn of<T, E>(result: &MutationResult<T, E>) -> Self {
+        match result {
+            MutationResult::Pending => Self::Pending,
+            MutationResult::Loading(_) => Self::Loading,
+            MutationResult::Ok(_) => Self::Success,
+            MutationResult::Err(_) => Self::Error,
+            MutationResult::Queued(_) => Self::Queued,
+        }
+    }
+}
+
+/// A snapshot of a mutation used by a component, for debugging purposes or e.g to show that changes are syncing.
+/// See [MutationClient::active_mutations](crate::prelude::MutationClient::active_mutations).
+#[derive(Clone, Debug, PartialEq)]
+pub struct MutationSnapshot {
+    /// Identifies the mutation for as long as it's used
+    pub id: usize,
+    /// Key of the mutation, see [MutationConfig::with_key](crate::prelude::MutationConfig::with_key)
+    pub key: Option<Cow<'static, str>>,
+    /// Status of the mutation
+    pub status: MutationStatus,
+    /// When its last call started
+    pub started_at: Option<Instant>,
+}
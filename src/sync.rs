@@ -0,0 +1,168 @@
+//! Primitives used to share the state of the queries and mutations.
+//! They are thread-safe with the `sync` feature, so the client can be moved to other threads.
+
+use std::sync::{PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard, TryLockError};
+
+#[cfg(not(feature = "sync"))]
+pub(crate) use std::rc::Rc as Shared;
+#[cfg(feature = "sync")]
+pub(crate) use std::sync::Arc as Shared;
+
+#[cfg(not(feature = "sync"))]
+pub(crate) type ReadGuard<'a, T> = std::cell::Ref<'a, T>;
+#[cfg(feature = "sync")]
+pub(crate) type ReadGuard<'a, T> = std::sync::RwLockReadGuard<'a, T>;
+
+#[cfg(not(feature = "sync"))]
+pub(crate) type WriteGuard<'a, T> = std::cell::RefMut<'a, T>;
+#[cfg(feature = "sync")]
+pub(crate) type WriteGuard<'a, T> = std::sync::RwLockWriteGuard<'a, T>;
+
+/// Implemented by every type, or only by `Send` types with the `sync` feature.
+#[cfg(not(feature = "sync"))]
+pub trait MaybeSend {}
+#[cfg(not(feature = "sync"))]
+impl<T: ?Sized> MaybeSend for T {}
+
+/// Implemented by every type, or only by `Send` types with the `sync` feature.
+#[cfg(feature = "sync")]
+pub trait MaybeSend: Send {}
+#[cfg(feature = "sync")]
+impl<T: ?Sized + Send> MaybeSend for T {}
+
+/// Implemented by every type, or only by `Sync` types with the `sync` feature.
+#[cfg(not(feature = "sync"))]
+pub trait MaybeSync {}
+#[cfg(not(feature = "sync"))]
+impl<T: ?Sized> MaybeSync for T {}
+
+/// Implemented by every type, or only by `Sync` types with the `sync` feature.
+#[cfg(feature = "sync")]
+pub trait MaybeSync: Sync {}
+#[cfg(feature = "sync")]
+impl<T: ?Sized + Sync> MaybeSync for T {}
+
+/// A value that can be mutated through a shared reference.
+#[derive(Default)]
+pub(crate) struct Lock<T> {
+    #[cfg(not(feature = "sync"))]
+    inner: std::cell::RefCell<T>,
+    #[cfg(feature = "sync")]
+    inner: std::sync::RwLock<T>,
+}
+
+impl<T> Lock<T> {
+    pub(crate) fn new(value: T) -> Self {
+        Self {
+            inner: value.into(),
+        }
+    }
+
+    #[cfg(not(feature = "sync"))]
+    pub(crate) fn borrow(&self) -> ReadGuard<'_, T> {
+        self.inner.borrow()
+    }
+
+    #[cfg(feature = "sync")]
+    pub(crate) fn borrow(&self) -> ReadGuard<'_, T> {
+        self.inner.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    #[cfg(not(feature = "sync"))]
+    pub(crate) fn borrow_mut(&self) -> WriteGuard<'_, T> {
+        self.inner.borrow_mut()
+    }
+
+    #[cfg(feature = "sync")]
+    pub(crate) fn borrow_mut(&self) -> WriteGuard<'_, T> {
+        self.inner.write().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Lock the given value for reading.
+/// A panic while it was locked for writing doesn't poison it, so the value is still readable.
+pub(crate) fn read<T: ?Sized>(lock: &RwLock<T>) -> RwLockReadGuard<'_, T> {
+    lock.read().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Lock the given value for writing.
+/// A panic while it was locked for writing doesn't poison it, so the value is still writable.
+pub(crate) fn write<T: ?Sized>(lock: &RwLock<T>) -> RwLockWriteGuard<'_, T> {
+    lock.write().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Lock the given value for writing if nothing else has it locked, without waiting, e.g a guard
+/// still held by the same thread. A panic while it was locked for writing doesn't poison it.
+pub(crate) fn try_write<T: ?Sized>(lock: &RwLock<T>) -> Option<RwLockWriteGuard<'_, T>> {
+    match lock.try_write() {
+        Ok(guard) => Some(guard),
+        Err(TryLockError::Poisoned(err)) => Some(err.into_inner()),
+        Err(TryLockError::WouldBlock) => None,
+    }
+}
+
+/// A number that can be changed through a shared reference.
+#[derive(Default)]
+pub(crate) struct Counter {
+    #[cfg(not(feature = "sync"))]
+    inner: std::cell::Cell<usize>,
+    #[cfg(feature = "sync")]
+    inner: std::sync::atomic::AtomicUsize,
+}
+
+impl Counter {
+    #[cfg(not(feature = "sync"))]
+    pub(crate) fn get(&self) -> usize {
+        self.inner.get()
+    }
+
+    #[cfg(feature = "sync")]
+    pub(crate) fn get(&self) -> usize {
+        self.inner.load(std::sync::atomic::Ordering::SeqCst)
+    }
+
+    #[cfg(not(feature = "sync"))]
+    pub(crate) fn set(&self, value: usize) {
+        self.inner.set(value)
+    }
+
+    #[cfg(feature = "sync")]
+    pub(crate) fn set(&self, value: usize) {
+        self.inner.store(value, std::sync::atomic::Ordering::SeqCst)
+    }
+
+    /// Add one in a single step, so concurrent increments aren't lost, and get the new value.
+    #[cfg(not(feature = "sync"))]
+    pub(crate) fn increment(&self) -> usize {
+        let value = self.inner.get() + 1;
+        self.inner.set(value);
+        value
+    }
+
+    /// Add one in a single step, so concurrent increments aren't lost, and get the new value.
+    #[cfg(feature = "sync")]
+    pub(crate) fn increment(&self) -> usize {
+        self.inner.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1
+    }
+
+    /// Subtract one in a single step, stopping at zero, and get the new value.
+    #[cfg(not(feature = "sync"))]
+    pub(crate) fn decrement(&self) -> usize {
+        let value = self.inner.get().saturating_sub(1);
+        self.inner.set(value);
+        value
+    }
+
+    /// Subtract one in a single step, stopping at zero, and get the new value.
+    #[cfg(feature = "sync")]
+    pub(crate) fn decrement(&self) -> usize {
+        use std::sync::atomic::Ordering;
+        let previous = self
+            .inner
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |value| {
+                Some(value.saturating_sub(1))
+            })
+            .unwrap_or_default();
+        previous.saturating_sub(1)
+    }
+}
@@ -0,0 +1,306 @@
+//! Scripted responses for the queries of component tests, see [QueryMock].
+//!
+//! ## Example:
+//!
+//! ```no_run
+//! #[tokio::test]
+//! async fn shows_the_user_once_the_retry_succeeds() {
+//!     let mock = QueryMock::<QueryValue, QueryError, QueryKeys>::new();
+//!     mock.on(vec![QueryKeys::User(1)])
+//!         .respond(QueryResult::Err(QueryError::Unknown))
+//!         .respond(QueryResult::Ok(QueryValue::UserName("Marc".to_string())))
+//!         .after(Duration::from_millis(10));
+//!
+//!     #[derive(Props, PartialEq)]
+//!     struct AppProps {
+//!         mock: QueryMock<QueryValue, QueryError, QueryKeys>,
+//!     }
+//!
+//!     fn app(cx: Scope<AppProps>) -> Element {
+//!         provide_query_client(cx, || {
+//!             UseQueryClient::new(cx.schedule_update_any()).with_mock(cx.props.mock.clone())
+//!         });
+//!         let user = use_query_config(cx, || {
+//!             QueryConfig::new(vec![QueryKeys::User(1)], fetch_user).retry(1)
+//!         });
+//!         match user.result().value() {
+//!             QueryResult::Ok(QueryValue::UserName(name)) => render!( p { "{name}" } ),
+//!             _ => render!( p { "Loading..." } ),
+//!         }
+//!     }
+//!
+//!     let mut dom = VirtualDom::new_with_props(app, AppProps { mock: mock.clone() });
+//!     let _ = dom.rebuild();
+//!     tokio::time::timeout(Duration::from_secs(1), dom.wait_for_work()).await.ok();
+//!     tokio::time::timeout(Duration::from_secs(1), dom.wait_for_work()).await.ok();
+//!     dom.render_immediate();
+//!
+//!     assert_eq!(dioxus_ssr::render(&dom), "<p>Marc</p>");
+//!     assert_eq!(mock.fetch_count(&[QueryKeys::User(1)]), 2);
+//! }
+//! ```
+
+use std::{collections::VecDeque, hash::Hash, time::Duration};
+
+use crate::{
+    result::QueryResult,
+    sync::{Lock, MaybeSend, MaybeSync, Shared},
+    use_query_client::UseQueryClient,
+};
+
+#[cfg(not(feature = "sync"))]
+type MockMatchFn<K> = dyn Fn(&[K]) -> bool;
+#[cfg(feature = "sync")]
+type MockMatchFn<K> = dyn Fn(&[K]) -> bool + Send + Sync;
+
+/// A result served by a [QueryMock], once its delay elapsed.
+struct MockResponse<T, E> {
+    result: QueryResult<T, E>,
+    delay: Duration,
+}
+
+/// The responses of the query keys matching a rule, in order.
+struct MockRule<T, E, K> {
+    is_match: Box<MockMatchFn<K>>,
+    /// The last one is served again once the others are used up
+    responses: VecDeque<MockResponse<T, E>>,
+}
+
+struct MockState<T, E, K> {
+    /// Checked in the order they were added, the first matching rule wins
+    rules: Vec<MockRule<T, E, K>>,
+    /// The query keys of every fetch, in order
+    fetches: Vec<Vec<K>>,
+}
+
+/// Serves scripted results instead of calling the query functions, e.g to test components without controlling
+/// the timing of real query functions. Install it with [UseQueryClient::with_mock]. Its clones share the same script,
+/// so a clone can be kept by the test to check the fetches. Only available with the `testing` feature.
+///
+/// Fetches of query keys without a matching rule call the query function as usual.
+/// Retries are fetches too, so a sequence of responses can fail and then succeed.
+/// Streams and the pages of infinite queries are not served by the mock.
+pub struct QueryMock<T, E, K> {
+    state: Shared<Lock<MockState<T, E, K>>>,
+}
+
+impl<T, E, K> Clone for QueryMock<T, E, K> {
+    fn clone(&self) -> Self {
+        Self {
+            state: self.state.clone(),
+        }
+    }
+}
+
+/// Every clone of a mock is the same mock, so components taking it as a prop don't re-render for it.
+impl<T, E, K> PartialEq for QueryMock<T, E, K> {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl<T, E, K> Default for QueryMock<T, E, K> {
+    fn default() -> Self {
+        Self {
+            state: Shared::new(Lock::new(MockState {
+                rules: Vec::new(),
+                fetches: Vec::new(),
+            })),
+        }
+    }
+}
+
+impl<T, E, K> QueryMock<T, E, K> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Script the responses of the fetches of exactly the given query keys.
+    ///
+    /// ## Example:
+    ///
+    /// ```no_run
+    /// mock.on(vec![QueryKeys::User(1)])
+    ///     .respond(QueryResult::Ok(QueryValue::UserName("Marc".to_string())))
+    ///     .after(Duration::from_millis(200));
+    /// ```
+    pub fn on(&self, query_keys: Vec<K>) -> MockResponses<T, E, K>
+    where
+        K: 'static + PartialEq + MaybeSend + MaybeSync,
+    {
+        self.on_matching(move |keys| keys == query_keys)
+    }
+
+    /// Script the responses of the fetches of the query keys for which `is_match` returns `true`,
+    /// e.g every user with `|keys| matches!(keys, [QueryKeys::User(_)])`.
+    pub fn on_matching(
+        &self,
+        is_match: impl Fn(&[K]) -> bool + MaybeSend + MaybeSync + 'static,
+    ) -> MockResponses<T, E, K> {
+        let mut state = self.state.borrow_mut();
+        state.rules.push(MockRule {
+            is_match: Box::new(is_match),
+            responses: VecDeque::new(),
+        });
+        MockResponses {
+            mock: self.clone(),
+            rule: state.rules.len() - 1,
+        }
+    }
+
+    /// Get how many times exactly the given query keys were fetched, retries included, whether they were served or not.
+    pub fn fetch_count(&self, query_keys: &[K]) -> usize
+    where
+        K: PartialEq,
+    {
+        self.fetch_count_matching(|keys| keys == query_keys)
+    }
+
+    /// Get how many times query keys for which `is_match` returns `true` were fetched, see [QueryMock::fetch_count].
+    pub fn fetch_count_matching(&self, is_match: impl Fn(&[K]) -> bool) -> usize {
+        self.state
+            .borrow()
+            .fetches
+            .iter()
+            .filter(|keys| is_match(keys))
+            .count()
+    }
+
+    /// Get the query keys of every fetch so far, in order.
+    pub fn fetches(&self) -> Vec<Vec<K>>
+    where
+        K: Clone,
+    {
+        self.state.borrow().fetches.clone()
+    }
+
+    /// Record a fetch of the given query keys, getting the response of the first matching rule.
+    fn serve(&self, query_keys: &[K]) -> Option<(QueryResult<T, E>, Duration)>
+    where
+        T: Clone,
+        E: Clone,
+        K: Clone,
+    {
+        let mut state = self.state.borrow_mut();
+        state.fetches.push(query_keys.to_vec());
+        let rule = state
+            .rules
+            .iter_mut()
+            .find(|rule| (rule.is_match)(query_keys))?;
+        let response = if rule.responses.len() > 1 {
+            rule.responses.pop_front()?
+        } else {
+            let response = rule.responses.front()?;
+            MockResponse {
+                result: response.result.clone(),
+                delay: response.delay,
+            }
+        };
+        Some((response.result, response.delay))
+    }
+}
+
+/// The responses of a rule of a [QueryMock], see [QueryMock::on].
+pub struct MockResponses<T, E, K> {
+    mock: QueryMock<T, E, K>,
+    rule: usize,
+}
+
+impl<T, E, K> MockResponses<T, E, K> {
+    /// Serve the given result to the next fetch, after the ones already given.
+    /// The last result is served to every fetch once the others are used up.
+    pub fn respond(self, result: impl Into<QueryResult<T, E>>) -> Self {
+        self.mock.state.borrow_mut().rules[self.rule]
+            .responses
+            .push_back(MockResponse {
+                result: result.into(),
+                delay: Duration::ZERO,
+            });
+        self
+    }
+
+    /// Wait for the given duration before serving the last result given, according to the clock of the client,
+    /// see [QueryClientConfig::clock](crate::prelude::QueryClientConfig::clock).
+    pub fn after(self, delay: Duration) -> Self {
+        if let Some(response) = self.mock.state.borrow_mut().rules[self.rule]
+            .responses
+            .back_mut()
+        {
+            response.delay = delay;
+        }
+        self
+    }
+}
+
+impl<T, E, K> UseQueryClient<T, E, K>
+where
+    T: 'static + Clone + MaybeSend + MaybeSync,
+    E: 'static + Clone + MaybeSend + MaybeSync,
+    K: 'static + Clone + Eq + Hash + MaybeSend + MaybeSync,
+{
+    /// Serve the results scripted in the given mock instead of calling the query functions, see [QueryMock].
+    /// Only available with the `testing` feature.
+    pub fn with_mock(mut self, mock: QueryMock<T, E, K>) -> Self {
+        self.interceptor = Some(std::sync::Arc::new(Box::new(move |query_keys: &[K]| {
+            mock.serve(query_keys)
+        })));
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+    use dioxus::prelude::*;
+    use std::time::Duration;
+
+    type TestMock = QueryMock<String, String, usize>;
+
+    async fn fetch_user(_: Vec<usize>) -> QueryResult<String, String> {
+        panic!("the mock serves every fetch of the user")
+    }
+
+    #[derive(Props, PartialEq)]
+    struct AppProps {
+        mock: TestMock,
+    }
+
+    fn app(cx: Scope<AppProps>) -> Element {
+        provide_query_client(cx, || {
+            UseQueryClient::new(cx.schedule_update_any()).with_mock(cx.props.mock.clone())
+        });
+        let user = use_query_config(cx, || QueryConfig::new(vec![1], fetch_user).retry(1));
+        match user.result().value() {
+            QueryResult::Ok(name) => render!( p { "{name}" } ),
+            _ => render!( p { "Loading..." } ),
+        }
+    }
+
+    #[tokio::test]
+    async fn components_show_the_scripted_results() {
+        let mock = TestMock::new();
+        mock.on(vec![1])
+            .respond(QueryResult::Err("unavailable".to_string()))
+            .respond(QueryResult::Ok("Marc".to_string()))
+            .after(Duration::from_millis(10));
+
+        let mut dom = VirtualDom::new_with_props(app, AppProps { mock: mock.clone() });
+        let _ = dom.rebuild();
+        assert_eq!(dioxus_ssr::render(&dom), "<p>Loading...</p>");
+
+        // The failed fetch is retried, and the retry is served after its delay
+        let rendered = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                dom.wait_for_work().await;
+                dom.render_immediate();
+                let rendered = dioxus_ssr::render(&dom);
+                if rendered != "<p>Loading...</p>" {
+                    return rendered;
+                }
+            }
+        })
+        .await;
+        assert_eq!(rendered.as_deref(), Ok("<p>Marc</p>"));
+        assert_eq!(mock.fetch_count(&[1]), 2);
+    }
+}
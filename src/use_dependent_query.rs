@@ -0,0 +1,129 @@
+use dioxus::prelude::*;
+use futures_util::Future;
+use std::{
+    cell::{Cell, RefCell},
+    hash::Hash,
+    rc::Rc,
+};
+
+use crate::{
+    result::QueryResult,
+    sync::{MaybeSend, MaybeSync},
+    use_query::{QueryConfig, UseQuery},
+    use_query_client::use_query_client,
+};
+
+/// A query listener whose query keys are derived from the data of another query.
+/// See [use_dependent_query].
+pub struct UseDependentQuery<T, E, K: Eq + Hash> {
+    /// The listener of the dependent query along with its keys, `None` while the other query has no data
+    query: RefCell<Option<(Vec<K>, Rc<UseQuery<T, E, K>>)>>,
+    /// Whether the other query was loading, as of the last render
+    is_upstream_loading: Cell<bool>,
+}
+
+impl<T, E, K: Eq + Hash> UseDependentQuery<T, E, K> {
+    /// Get the listener of the dependent query, `None` while the query it depends on has no data.
+    pub fn query(&self) -> Option<Rc<UseQuery<T, E, K>>> {
+        self.query.borrow().as_ref().map(|(_, query)| query.clone())
+    }
+
+    /// Check if the dependent query is waiting for the query it depends on to succeed.
+    pub fn is_idle(&self) -> bool {
+        self.query.borrow().is_none()
+    }
+
+    /// Get the current result of the dependent query, `Loading(None)` while it's idle.
+    pub fn result(&self) -> QueryResult<T, E>
+    where
+        T: Clone,
+        E: Clone,
+    {
+        match self.query() {
+            Some(query) => query.result().value().clone(),
+            None => QueryResult::Loading(None),
+        }
+    }
+
+    /// Check if either of the queries is loading, so a single spinner can cover both.
+    pub fn is_loading(&self) -> bool {
+        self.is_upstream_loading.get()
+            || self
+                .query()
+                .map(|query| query.result().is_loading())
+                .unwrap_or_default()
+    }
+}
+
+/// Register a query listener whose query keys are derived from the successful data of another query,
+/// e.g the projects of the organization of a user. It stays idle while the other query has no data or errored,
+/// and moves to the new keys whenever the data of the other query changes, e.g after invalidating it.
+/// While the other query is refetching the dependent query keeps its current keys.
+///
+/// ## Example:
+///
+/// ```no_run
+/// let user = use_query(cx, || vec![QueryKeys::User(id)], fetch_user);
+/// let projects = use_dependent_query(
+///     cx,
+///     user,
+///     |user: &User| vec![QueryKeys::Projects(user.org_id)],
+///     fetch_projects,
+/// );
+///
+/// if projects.is_loading() {
+///     return render!( p { "Loading..." } );
+/// }
+/// ```
+pub fn use_dependent_query<'a, T, E, K, U, UE, UK, Q, F>(
+    cx: &'a ScopeState,
+    upstream: &UseQuery<U, UE, UK>,
+    query_keys: impl FnOnce(&U) -> Vec<K>,
+    query_fn: Q,
+) -> &'a UseDependentQuery<T, E, K>
+where
+    T: 'static + Clone,
+    E: 'static + Clone,
+    K: 'static + Eq + Hash + Clone,
+    UK: Eq + Hash,
+    Q: 'static + Fn(Vec<K>) -> F + MaybeSend + MaybeSync,
+    F: 'static + Future<Output = QueryResult<T, E>> + MaybeSend,
+{
+    let client = use_query_client(cx);
+    let dependent = cx.use_hook(|| UseDependentQuery {
+        query: RefCell::default(),
+        is_upstream_loading: Cell::new(true),
+    });
+
+    let query_keys = {
+        let upstream = upstream.result();
+        dependent
+            .is_upstream_loading
+            .set(upstream.value().is_loading());
+        match upstream.value() {
+            QueryResult::Ok(data) => Some(query_keys(data)),
+            QueryResult::Loading(Some(_)) => return dependent,
+            _ => None,
+        }
+    };
+
+    let mut query = dependent.query.borrow_mut();
+    match query_keys {
+        Some(mut query_keys) => {
+            client.normalize_keys(&mut query_keys);
+            let has_changed = query
+                .as_ref()
+                .map(|(current_keys, _)| *current_keys != query_keys)
+                .unwrap_or(true);
+            if has_changed {
+                // The previous listener is unregistered once dropped
+                let config = QueryConfig::new(query_keys.clone(), query_fn);
+                *query = Some((query_keys, Rc::new(UseQuery::new(cx, client, config))));
+            }
+        }
+        None => *query = None,
+    }
+    drop(query);
+
+    dependent
+}
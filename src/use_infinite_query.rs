@@ -0,0 +1,308 @@
+use dioxus::prelude::*;
+use futures_util::Future;
+use std::{
+    hash::Hash,
+    sync::{Arc, RwLockReadGuard},
+};
+
+use crate::{
+    cached_result::{CachedResult, DataSource},
+    result::QueryResult,
+    sync::{self, MaybeSend, MaybeSync},
+    use_query::{QueryChange, QueryConfig, UseQuery},
+    use_query_client::{
+        use_query_client, QueryFnId, QueryListeners, RegistryEntry, UseQueryClient,
+    },
+};
+
+#[cfg(not(feature = "sync"))]
+pub type PageFn<T, E, K, P> = dyn Fn(Vec<K>, P) -> Box<dyn Future<Output = QueryResult<T, E>>>;
+#[cfg(feature = "sync")]
+pub type PageFn<T, E, K, P> =
+    dyn Fn(Vec<K>, P) -> Box<dyn Future<Output = QueryResult<T, E>> + Send> + Send + Sync;
+
+#[cfg(not(feature = "sync"))]
+pub type NextPageParamFn<T, P> = dyn Fn(&T) -> Option<P>;
+#[cfg(feature = "sync")]
+pub type NextPageParamFn<T, P> = dyn Fn(&T) -> Option<P> + Send + Sync;
+
+/// The pages loaded by an infinite query.
+#[derive(Clone, PartialEq, Debug)]
+pub struct InfiniteData<T, P> {
+    /// Loaded pages, in order
+    pub pages: Vec<T>,
+    /// Params each page was fetched with
+    pub page_params: Vec<P>,
+    /// Param of the next page, `None` when there are no more pages
+    pub next_page_param: Option<P>,
+    /// Whether the next page is being fetched
+    pub is_fetching_next_page: bool,
+}
+
+impl<T, P> InfiniteData<T, P> {
+    fn new(first_page_param: P) -> Self {
+        Self {
+            pages: Vec::new(),
+            page_params: Vec::new(),
+            next_page_param: Some(first_page_param),
+            is_fetching_next_page: false,
+        }
+    }
+
+    fn push_page(&mut self, page: T, page_param: P, get_next_page_param: &NextPageParamFn<T, P>) {
+        self.next_page_param = get_next_page_param(&page);
+        self.pages.push(page);
+        self.page_params.push(page_param);
+    }
+}
+
+/// An infinite query listener.
+///
+/// Its pages are cached as [InfiniteData], so it requires a query client for that type,
+/// e.g `use_init_query_client::<InfiniteData<UsersPage, usize>, QueryError, QueryKeys>(cx)`.
+pub struct UseInfiniteQuery<T, E, K: Eq + Hash, P> {
+    query: UseQuery<InfiniteData<T, P>, E, K>,
+    client: UseQueryClient<InfiniteData<T, P>, E, K>,
+    registry_entry: RegistryEntry<K>,
+    page_fn: Arc<Box<PageFn<T, E, K, P>>>,
+    get_next_page_param: Arc<Box<NextPageParamFn<T, P>>>,
+}
+
+impl<T, E, K, P> UseInfiniteQuery<T, E, K, P>
+where
+    T: 'static + Clone,
+    E: 'static + Clone,
+    K: 'static + Eq + Hash + Clone,
+    P: 'static + Clone,
+{
+    /// Get the current result from the query.
+    pub fn result(&self) -> RwLockReadGuard<CachedResult<InfiniteData<T, P>, E>> {
+        self.query.result()
+    }
+
+    /// Get the loaded pages.
+    pub fn pages(&self) -> Vec<T> {
+        self.result()
+            .data()
+            .map(|data| data.pages.clone())
+            .unwrap_or_default()
+    }
+
+    /// Check if there is a next page to fetch.
+    pub fn has_next_page(&self) -> bool {
+        self.result()
+            .data()
+            .map(|data| data.next_page_param.is_some())
+            .unwrap_or_default()
+    }
+
+    /// Check if the next page is being fetched, as opposed to the first one.
+    pub fn is_fetching_next_page(&self) -> bool {
+        self.result()
+            .data()
+            .map(|data| data.is_fetching_next_page)
+            .unwrap_or_default()
+    }
+
+    /// Fetch the next page, in the **background**.
+    pub fn fetch_next_page(&self) {
+        let client = self.client.clone();
+        let registry_entry = self.registry_entry.clone();
+        let page_fn = self.page_fn.clone();
+        let get_next_page_param = self.get_next_page_param.clone();
+        spawn(async move {
+            Self::inner_fetch_next_page(&client, &registry_entry, &page_fn, &get_next_page_param)
+                .await;
+        });
+    }
+
+    /// Fetch the next page.
+    pub async fn manual_fetch_next_page(&self) {
+        Self::inner_fetch_next_page(
+            &self.client,
+            &self.registry_entry,
+            &self.page_fn,
+            &self.get_next_page_param,
+        )
+        .await;
+    }
+
+    async fn inner_fetch_next_page(
+        client: &UseQueryClient<InfiniteData<T, P>, E, K>,
+        registry_entry: &RegistryEntry<K>,
+        page_fn: &Arc<Box<PageFn<T, E, K, P>>>,
+        get_next_page_param: &Arc<Box<NextPageParamFn<T, P>>>,
+    ) {
+        let Some(QueryListeners {
+            value,
+            generation,
+            last_used,
+            settled,
+            ..
+        }) = client.get_entry(registry_entry)
+        else {
+            return;
+        };
+
+        // Only fetch the next page once the previous ones are loaded
+        let page_param = {
+            let mut value = sync::write(&value);
+            match &mut value.value {
+                QueryResult::Ok(data) if !data.is_fetching_next_page => {
+                    let Some(page_param) = data.next_page_param.clone() else {
+                        return;
+                    };
+                    data.is_fetching_next_page = true;
+                    page_param
+                }
+                _ => return,
+            }
+        };
+        client.notify_listeners(registry_entry, QueryChange::Loading);
+
+        // Fetch the page
+        let fetch_generation = generation.increment();
+        if let Some(rate_limiter) = &client.config.rate_limiter {
+            rate_limiter.acquire(&client.config.clock).await;
+        }
+        let permit = match &client.config.fetch_limiter {
+            Some(limiter) => Some(limiter.acquire().await),
+            None => None,
+        };
+        let fut = (page_fn)(registry_entry.query_keys.clone(), page_param.clone());
+        let fut = Box::into_pin(fut);
+        let page = fut.await;
+        drop(permit);
+
+        // Discard the page if a newer fetch was started or the cached value was removed in the meantime
+        if generation.get() != fetch_generation {
+            return;
+        }
+
+        {
+            let mut value = sync::write(&value);
+            let QueryResult::Ok(data) = &mut value.value else {
+                return;
+            };
+            data.is_fetching_next_page = false;
+
+            match page {
+                QueryResult::Ok(page) => {
+                    data.push_page(page, page_param, get_next_page_param.as_ref().as_ref());
+                    value.count_fetch(false);
+                    let now = value.clock.now();
+                    value.instant = Some(now);
+                    value.data_instant = Some(now);
+                    value.data_source = Some(DataSource::Fetched);
+                }
+                QueryResult::Err(err) => {
+                    value.set_value(QueryResult::Err(err), None);
+                }
+                QueryResult::Loading(_) => {}
+            }
+        }
+        settled.notify();
+
+        client.mark_used(&last_used);
+        client.emit_cache_updated(&registry_entry.query_keys, &value);
+        let change = QueryChange::of(sync::read(&value).value());
+        client.notify_listeners(registry_entry, change);
+    }
+}
+
+/// Register an infinite query listener.
+/// The query function is called with the query keys and the param of the page to fetch,
+/// starting with `first_page_param`, while `get_next_page_param` derives the param of the next page
+/// from the last loaded page, returning `None` when there are no more pages.
+///
+/// Invalidating the query refetches all its loaded pages, in order.
+///
+/// ## Example:
+///
+/// ```no_run
+/// let users = use_infinite_query(
+///     cx,
+///     || vec![QueryKeys::Users],
+///     0,
+///     fetch_users_page,
+///     |page| page.next_cursor,
+/// );
+/// ```
+pub fn use_infinite_query<T, E, K, P, Q, F>(
+    cx: &ScopeState,
+    query_keys: impl FnOnce() -> Vec<K>,
+    first_page_param: P,
+    query_fn: Q,
+    get_next_page_param: impl Fn(&T) -> Option<P> + MaybeSend + MaybeSync + 'static,
+) -> &UseInfiniteQuery<T, E, K, P>
+where
+    T: 'static + PartialEq + Clone + MaybeSend + MaybeSync,
+    E: 'static + PartialEq + Clone + MaybeSend + MaybeSync,
+    K: 'static + Eq + Hash + Clone + MaybeSend + MaybeSync,
+    P: 'static + PartialEq + Clone + MaybeSend + MaybeSync,
+    Q: 'static + Fn(Vec<K>, P) -> F + MaybeSend + MaybeSync,
+    F: 'static + Future<Output = QueryResult<T, E>> + MaybeSend,
+{
+    let client = use_query_client::<InfiniteData<T, P>, E, K>(cx);
+    cx.use_hook(|| {
+        let page_fn: Arc<Box<PageFn<T, E, K, P>>> = Arc::new(Box::new(move |keys, page_param| {
+            let fut = query_fn(keys, page_param);
+            Box::new(fut)
+        }));
+        let get_next_page_param: Arc<Box<NextPageParamFn<T, P>>> =
+            Arc::new(Box::new(get_next_page_param));
+        let mut query_keys = query_keys();
+        client.normalize_keys(&mut query_keys);
+        let registry_entry = RegistryEntry {
+            query_keys,
+            query_fn_id: QueryFnId::of::<F>(),
+        };
+
+        // (Re)fetch as many pages as there were loaded, starting from the first one
+        let query_fn = {
+            to_owned![client, registry_entry, page_fn, get_next_page_param];
+            move |query_keys: Vec<K>| {
+                let loaded_pages = client
+                    .get_entry(&registry_entry)
+                    .and_then(|entry| {
+                        let value = sync::read(&entry.value);
+                        value.data().map(|data| data.pages.len())
+                    })
+                    .unwrap_or_default()
+                    .max(1);
+                to_owned![page_fn, get_next_page_param, first_page_param];
+                async move {
+                    let mut data = InfiniteData::new(first_page_param);
+                    while data.pages.len() < loaded_pages {
+                        let Some(page_param) = data.next_page_param.take() else {
+                            break;
+                        };
+                        let fut = (page_fn)(query_keys.clone(), page_param.clone());
+                        match Box::into_pin(fut).await {
+                            QueryResult::Ok(page) => data.push_page(
+                                page,
+                                page_param,
+                                get_next_page_param.as_ref().as_ref(),
+                            ),
+                            QueryResult::Err(err) => return QueryResult::Err(err),
+                            QueryResult::Loading(_) => return QueryResult::Loading(None),
+                        }
+                    }
+                    QueryResult::Ok(data)
+                }
+            }
+        };
+
+        let mut config =
+            QueryConfig::new(registry_entry.query_keys.clone(), query_fn).compare_results(true);
+        config.registry_entry = registry_entry.clone();
+
+        UseInfiniteQuery {
+            query: UseQuery::new(cx, client, config),
+            client: client.clone(),
+            registry_entry,
+            page_fn,
+            get_next_page_param,
+        }
+    })
+}
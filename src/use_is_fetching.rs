@@ -0,0 +1,64 @@
+use dioxus::prelude::*;
+use std::hash::Hash;
+
+use crate::use_query_client::{use_query_client, UseQueryClient};
+
+struct FetchingListener<T, E, K: Eq + Hash> {
+    client: UseQueryClient<T, E, K>,
+    scope_id: ScopeId,
+}
+
+impl<T, E, K: Eq + Hash> Drop for FetchingListener<T, E, K> {
+    fn drop(&mut self) {
+        self.client
+            .fetching_listeners
+            .borrow_mut()
+            .remove(&self.scope_id);
+    }
+}
+
+pub(crate) fn use_fetching_listener<T, E, K>(cx: &ScopeState) -> &UseQueryClient<T, E, K>
+where
+    T: 'static + Clone,
+    E: 'static + Clone,
+    K: 'static + Eq + Hash + Clone,
+{
+    let client = use_query_client::<T, E, K>(cx);
+    cx.use_hook(|| {
+        client.fetching_listeners.borrow_mut().insert(cx.scope_id());
+        FetchingListener {
+            client: client.clone(),
+            scope_id: cx.scope_id(),
+        }
+    });
+    client
+}
+
+/// Get how many queries are being fetched, e.g to show a global loading indicator.
+/// The component is re-rendered whenever a query starts or finishes fetching.
+///
+/// ## Example:
+///
+/// ```no_run
+/// let is_fetching = use_is_fetching::<QueryValue, QueryError, QueryKeys>(cx) > 0;
+/// ```
+pub fn use_is_fetching<T, E, K>(cx: &ScopeState) -> usize
+where
+    T: 'static + Clone,
+    E: 'static + Clone,
+    K: 'static + Eq + Hash + Clone,
+{
+    use_fetching_listener::<T, E, K>(cx).count_fetching(|_| true)
+}
+
+/// Get how many queries containing any of the given keys are being fetched.
+/// See [use_is_fetching].
+pub fn use_is_fetching_for<T, E, K>(cx: &ScopeState, keys: &[K]) -> usize
+where
+    T: 'static + Clone,
+    E: 'static + Clone,
+    K: 'static + Eq + Hash + Clone,
+{
+    use_fetching_listener::<T, E, K>(cx)
+        .count_fetching(|query_keys| query_keys.iter().any(|k| keys.contains(k)))
+}
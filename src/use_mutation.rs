@@ -1,128 +1,856 @@
 use dioxus::prelude::*;
-use futures_util::Future;
-use std::{fmt::Debug, rc::Rc, sync::Arc};
+use futures_util::{
+    future::{poll_fn, AbortHandle, Abortable},
+    Future,
+};
+use std::{
+    any::Any,
+    borrow::Cow,
+    collections::{hash_map::DefaultHasher, HashMap, HashSet, VecDeque},
+    fmt::Debug,
+    hash::{Hash, Hasher},
+    sync::Arc,
+    task::{Poll, Waker},
+    time::Duration,
+};
 
+use crate::{
+    clock::{sleep, Instant},
+    retry_backoff::ExponentialBackoff,
+    snapshot::{MutationSnapshot, MutationStatus},
+    sync::{Counter, Lock, MaybeSend, MaybeSync, ReadGuard, Shared},
+    use_mutation_state::{
+        InFlightMutations, LastSettled, MutationCall, MutationRegistry, MutationSubscription,
+        SharedMutation,
+    },
+    use_query_client::{QueryInvalidator, Scheduler, UseQueryClient},
+};
+
+#[cfg(not(feature = "sync"))]
 pub type MutationFn<T, E, A> = dyn Fn(A) -> Box<dyn Future<Output = MutationResult<T, E>>>;
+#[cfg(feature = "sync")]
+pub type MutationFn<T, E, A> =
+    dyn Fn(A) -> Box<dyn Future<Output = MutationResult<T, E>> + Send> + Send + Sync;
 
-/// A query mutation.
+#[cfg(not(feature = "sync"))]
+pub(crate) type IsOfflineFn<E> = dyn Fn(&E) -> bool;
+#[cfg(feature = "sync")]
+pub(crate) type IsOfflineFn<E> = dyn Fn(&E) -> bool + Send + Sync;
+
+#[cfg(not(feature = "sync"))]
+pub(crate) type OnMutationSuccessFn<T, A> = dyn Fn(&T, &A);
+#[cfg(feature = "sync")]
+pub(crate) type OnMutationSuccessFn<T, A> = dyn Fn(&T, &A) + Send + Sync;
+
+#[cfg(not(feature = "sync"))]
+pub(crate) type OnMutationErrorFn<E, A> = dyn Fn(&E, &A);
+#[cfg(feature = "sync")]
+pub(crate) type OnMutationErrorFn<E, A> = dyn Fn(&E, &A) + Send + Sync;
+
+#[cfg(not(feature = "sync"))]
+pub(crate) type OnMutationSettledFn<T, E, A> = dyn Fn(&MutationResult<T, E>, &A);
+#[cfg(feature = "sync")]
+pub(crate) type OnMutationSettledFn<T, E, A> = dyn Fn(&MutationResult<T, E>, &A) + Send + Sync;
+
+#[cfg(not(feature = "sync"))]
+pub(crate) type OnMutateFn<T, E, A> = dyn Fn(&A) -> Box<OnMutateEndFn<T, E, A>>;
+#[cfg(feature = "sync")]
+pub(crate) type OnMutateFn<T, E, A> = dyn Fn(&A) -> Box<OnMutateEndFn<T, E, A>> + Send + Sync;
+
+#[cfg(not(feature = "sync"))]
+pub(crate) type OnMutateEndFn<T, E, A> = dyn FnOnce(&MutationResult<T, E>, &A);
+#[cfg(feature = "sync")]
+pub(crate) type OnMutateEndFn<T, E, A> = dyn FnOnce(&MutationResult<T, E>, &A) + Send;
+
+#[cfg(not(feature = "sync"))]
+pub(crate) type DedupeHashFn<A> = dyn Fn(&A) -> u64;
+#[cfg(feature = "sync")]
+pub(crate) type DedupeHashFn<A> = dyn Fn(&A) -> u64 + Send + Sync;
+
+/// Adds the handlers that need the component using the mutation, e.g to get its query client.
+pub(crate) type ScopedHandlersFn<T, E, A> = dyn FnOnce(&ScopeState, &mut MutationHandlers<T, E, A>);
+
+#[cfg(not(feature = "sync"))]
+type AnyQueryKeys = dyn Any;
+#[cfg(feature = "sync")]
+type AnyQueryKeys = dyn Any + Send + Sync;
+
+/// The query keys of the entries written by [MutationConfig::updates] with the result of the current call,
+/// by type of keys, so [MutationConfig::invalidates] doesn't refetch them too.
+#[derive(Default)]
+pub(crate) struct UpdatedQueries(Lock<Vec<Box<AnyQueryKeys>>>);
+
+impl UpdatedQueries {
+    fn clear(&self) {
+        self.0.borrow_mut().clear();
+    }
+
+    fn push<K: 'static + MaybeSend + MaybeSync>(&self, query_keys: Vec<Vec<K>>) {
+        self.0.borrow_mut().push(Box::new(query_keys));
+    }
+
+    fn get<K: 'static + Clone>(&self) -> Vec<Vec<K>> {
+        self.0
+            .borrow()
+            .iter()
+            .filter_map(|query_keys| query_keys.downcast_ref::<Vec<Vec<K>>>())
+            .flatten()
+            .cloned()
+            .collect()
+    }
+}
+
+/// The arguments of the mutations that failed because the app was offline,
+/// see [MutationConfig::offline_queue].
+pub(crate) struct OfflineQueue<E, A> {
+    queued: Lock<VecDeque<A>>,
+    /// Whether an error means the app is offline
+    is_offline: Box<IsOfflineFn<E>>,
+}
+
+/// What to do with the result of a mutation, besides storing it.
+pub(crate) struct MutationHandlers<T, E, A> {
+    /// Write the result into the query caches, before the `on_success` callbacks run
+    cache_updates: Vec<Box<OnMutationSuccessFn<T, A>>>,
+    updated_queries: Shared<UpdatedQueries>,
+    on_success: Vec<Box<OnMutationSuccessFn<T, A>>>,
+    on_error: Vec<Box<OnMutationErrorFn<E, A>>>,
+    on_settled: Vec<Box<OnMutationSettledFn<T, E, A>>>,
+    on_mutate: Vec<Box<OnMutateFn<T, E, A>>>,
+    offline_queue: Option<OfflineQueue<E, A>>,
+    /// How many times the mutation function is called again when it returns an error
+    retry: u32,
+    retry_delay: ExponentialBackoff,
+    concurrency: MutationConcurrency,
+}
+
+impl<T, E, A> MutationHandlers<T, E, A> {
+    /// Run the `on_mutate` handlers before calling the mutation function,
+    /// returning what to do once its result is known.
+    fn start(&self, arg: &A) -> Vec<Box<OnMutateEndFn<T, E, A>>> {
+        self.on_mutate.iter().map(|f| f(arg)).collect()
+    }
+
+    /// Run the callbacks with the result of a mutation and its argument.
+    fn run_callbacks(&self, result: &MutationResult<T, E>, arg: &A) {
+        match result {
+            MutationResult::Ok(data) => {
+                self.updated_queries.clear();
+                self.cache_updates.iter().for_each(|f| f(data, arg));
+                self.on_success.iter().for_each(|f| f(data, arg));
+            }
+            MutationResult::Err(err) => self.on_error.iter().for_each(|f| f(err, arg)),
+            _ => {}
+        }
+        self.on_settled.iter().for_each(|f| f(result, arg));
+    }
+
+    /// Check if the mutation failed because the app is offline and should be queued.
+    fn is_offline(&self, result: &MutationResult<T, E>) -> bool {
+        match (result, &self.offline_queue) {
+            (MutationResult::Err(err), Some(offline_queue)) => (offline_queue.is_offline)(err),
+            _ => false,
+        }
+    }
+}
+
+/// What to do when a mutation is called while a previous call is still running, see [MutationConfig::concurrency].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MutationConcurrency {
+    /// Run every call right away, the result of the last one to finish is kept
+    #[default]
+    Parallel,
+    /// Ignore new calls while one is running
+    DropWhileInFlight,
+    /// Run every call right away but only keep the result of the last one to start,
+    /// the results of the previous ones are discarded once they finish, although their callbacks still run
+    Latest,
+    /// Run the calls one after the other, in the order they were made
+    Queue,
+}
+
+/// Lets the calls of mutations with the same key share a single call of the mutation function,
+/// see [MutationConfig::dedupe_in_flight].
+struct MutationDedupe<T, E, A> {
+    key: Cow<'static, str>,
+    /// Hashes the variables of a call, so only the calls with the same variables are deduplicated
+    hash: Box<DedupeHashFn<A>>,
+    calls: InFlightMutations<T, E>,
+}
+
+/// Keeps track of the calls of a mutation to apply its [MutationConcurrency].
+#[derive(Default)]
+pub(crate) struct MutationRuns {
+    /// How many calls are running or waiting for their turn
+    in_flight: Counter,
+    /// How many calls were started, used to number them
+    started: Counter,
+    /// The numbers of the queued calls in the order they were made, the first one is running
+    queue: Lock<VecDeque<usize>>,
+    /// How many times the mutation was cancelled, so the queued calls know they were cancelled while waiting
+    cancellations: Counter,
+    /// The queued calls waiting for their turn
+    waiters: Lock<Vec<Waker>>,
+    /// How to abort the running calls, by number, see [UseMutation::cancel]
+    aborts: Lock<HashMap<usize, AbortHandle>>,
+}
+
+/// A call of a mutation, marked as finished once dropped, even if its task was cancelled.
+/// A queued call leaves the queue once dropped, so it only lets the next one run if it was running itself.
+struct MutationRun {
+    runs: Shared<MutationRuns>,
+    number: usize,
+}
+
+impl Drop for MutationRun {
+    fn drop(&mut self) {
+        let runs = &self.runs;
+        runs.in_flight.decrement();
+        runs.queue
+            .borrow_mut()
+            .retain(|number| *number != self.number);
+        runs.aborts.borrow_mut().remove(&self.number);
+        for waker in runs.waiters.borrow_mut().drain(..) {
+            waker.wake();
+        }
+    }
+}
+
+/// What the [MutationClient] knows about a mutation used by a component.
+struct MutationRecord {
+    key: Option<Cow<'static, str>>,
+    status: MutationStatus,
+    started_at: Option<Instant>,
+}
+
+/// Keeps track of the mutations used and running anywhere in the app, regardless of their types.
+/// Get it with [use_mutation_client].
 #[derive(Clone)]
+pub struct MutationClient {
+    mutating: Shared<Counter>,
+    /// The mutations used by the mounted components, by id
+    mutations: Shared<Lock<HashMap<usize, MutationRecord>>>,
+    /// Bumped whenever a mutation is registered, to give it an id
+    registered: Shared<Counter>,
+    listeners: Shared<Lock<HashSet<ScopeId>>>,
+    scheduler: Arc<Scheduler>,
+}
+
+impl MutationClient {
+    /// Get the app's mutation client, creating it the first time.
+    pub(crate) fn get(cx: &ScopeState) -> Self {
+        cx.consume_context().unwrap_or_else(|| {
+            cx.provide_root_context(MutationClient {
+                mutating: Shared::default(),
+                mutations: Shared::default(),
+                registered: Shared::default(),
+                listeners: Shared::default(),
+                scheduler: cx.schedule_update_any(),
+            })
+        })
+    }
+
+    /// Get a snapshot of every mutation used by the mounted components, in the order they were created,
+    /// e.g to show that changes are still syncing with the ones that are [MutationStatus::Loading].
+    pub fn active_mutations(&self) -> Vec<MutationSnapshot> {
+        let mut snapshots = self
+            .mutations
+            .borrow()
+            .iter()
+            .map(|(id, record)| MutationSnapshot {
+                id: *id,
+                key: record.key.clone(),
+                status: record.status,
+                started_at: record.started_at,
+            })
+            .collect::<Vec<_>>();
+        snapshots.sort_by_key(|snapshot| snapshot.id);
+        snapshots
+    }
+
+    /// Keep track of a new mutation until the returned registration is dropped.
+    fn register(&self, key: Option<Cow<'static, str>>) -> MutationRegistration {
+        let id = self.registered.increment();
+        self.mutations.borrow_mut().insert(
+            id,
+            MutationRecord {
+                key,
+                status: MutationStatus::Pending,
+                started_at: None,
+            },
+        );
+        self.notify_listeners();
+        MutationRegistration {
+            client: self.clone(),
+            id,
+        }
+    }
+
+    fn notify_listeners(&self) {
+        let listeners = self.listeners.borrow().clone();
+        for listener in listeners {
+            (self.scheduler)(listener);
+        }
+    }
+
+    /// Count a mutation as running until the returned guard is dropped.
+    fn start_mutation(&self) -> MutatingGuard {
+        self.mutating.increment();
+        self.notify_listeners();
+        MutatingGuard {
+            client: self.clone(),
+        }
+    }
+}
+
+/// Stops keeping track of a mutation once dropped, along with the last clone of its [UseMutation].
+struct MutationRegistration {
+    client: MutationClient,
+    id: usize,
+}
+
+impl MutationRegistration {
+    /// Report the new result of the mutation.
+    fn update<T, E>(&self, result: &MutationResult<T, E>) {
+        let status = MutationStatus::of(result);
+        let has_changed = {
+            let mut mutations = self.client.mutations.borrow_mut();
+            let Some(record) = mutations.get_mut(&self.id) else {
+                return;
+            };
+            if status == MutationStatus::Loading && record.status != MutationStatus::Loading {
+                record.started_at = Some(Instant::now());
+            }
+            std::mem::replace(&mut record.status, status) != status
+        };
+        if has_changed {
+            self.client.notify_listeners();
+        }
+    }
+}
+
+impl Drop for MutationRegistration {
+    fn drop(&mut self) {
+        self.client.mutations.borrow_mut().remove(&self.id);
+        self.client.notify_listeners();
+    }
+}
+
+/// Stops counting a mutation as running once dropped, even if its task was cancelled.
+struct MutatingGuard {
+    client: MutationClient,
+}
+
+impl Drop for MutatingGuard {
+    fn drop(&mut self) {
+        self.client.mutating.decrement();
+        self.client.notify_listeners();
+    }
+}
+
+/// A query mutation.
+///
+/// Cloning it is cheap and gives an owned handle to the same mutation, which can be moved into a task spawned
+/// with `cx.spawn` or given to a child component as a prop. Every method works the same from a clone,
+/// the ones running in the **background** spawn their task in the component they are called from.
+/// Two handles are equal if they are clones of each other, so props holding them don't break memoization.
+///
+/// ## Example:
+///
+/// ```no_run
+/// let rename = use_mutation(cx, rename_user).clone();
+///
+/// cx.spawn(async move {
+///     if rename.manual_mutate((id, name)).await.is_ok() {
+///         rename.reset();
+///     }
+/// });
+/// ```
 pub struct UseMutation<T, E, A> {
-    value: Rc<RefCell<MutationResult<T, E>>>,
-    mutation_fn: Arc<Box<MutationFn<T, E, A>>>,
-    scheduler: Arc<dyn Fn(ScopeId)>,
-    scope_id: ScopeId,
+    value: Shared<Lock<MutationResult<T, E>>>,
+    last_settled: Shared<Lock<LastSettled<T, E>>>,
+    /// Replaced whenever the component re-renders with a new closure, so it doesn't capture stale values
+    mutation_fn: Shared<Lock<Arc<Box<MutationFn<T, E, A>>>>>,
+    scheduler: Arc<Scheduler>,
+    /// Scopes re-rendered whenever the mutation changes, more than one if it is shared by a key
+    listeners: Shared<Lock<HashSet<ScopeId>>>,
+    mutation_client: MutationClient,
+    registration: Shared<MutationRegistration>,
+    dedupe: Option<Shared<MutationDedupe<T, E, A>>>,
+    handlers: Shared<MutationHandlers<T, E, A>>,
+    variables: Shared<Lock<Option<A>>>,
+    attempts: Shared<Counter>,
+    runs: Shared<MutationRuns>,
+}
+
+impl<T, E, A> Clone for UseMutation<T, E, A> {
+    fn clone(&self) -> Self {
+        Self {
+            value: self.value.clone(),
+            last_settled: self.last_settled.clone(),
+            mutation_fn: self.mutation_fn.clone(),
+            scheduler: self.scheduler.clone(),
+            listeners: self.listeners.clone(),
+            mutation_client: self.mutation_client.clone(),
+            registration: self.registration.clone(),
+            dedupe: self.dedupe.clone(),
+            handlers: self.handlers.clone(),
+            variables: self.variables.clone(),
+            attempts: self.attempts.clone(),
+            runs: self.runs.clone(),
+        }
+    }
+}
+
+/// The state of a mutation shared by a key is also shared by the handles of other components,
+/// so they are told apart by their registration, which is only shared by clones.
+impl<T, E, A> PartialEq for UseMutation<T, E, A> {
+    fn eq(&self, other: &Self) -> bool {
+        Shared::ptr_eq(&self.registration, &other.registration)
+    }
 }
 
 impl<T, E, A> UseMutation<T, E, A>
 where
     T: Clone,
     E: Clone,
+    A: Clone,
 {
+    fn notify(&self) {
+        let listeners = self.listeners.borrow().clone();
+        for listener in listeners {
+            (self.scheduler)(listener);
+        }
+    }
+
     /// Get the current result from the query mutation.
-    pub fn result(&self) -> Ref<'_, MutationResult<T, E>> {
+    pub fn result(&self) -> ReadGuard<'_, MutationResult<T, E>> {
         self.value.borrow()
     }
 
-    async fn inner_mutate(
+    /// Get the last successful value of the mutation, even if a later call failed or is still running.
+    /// `None` if it never succeeded since the last reset.
+    ///
+    /// ## Example:
+    ///
+    /// ```no_run
+    /// let save = use_mutation(cx, save_form);
+    /// let last_saved = save.data().map(|saved| saved.at.to_string());
+    /// let error = save.error().map(|err| err.to_string());
+    /// ```
+    pub fn data(&self) -> Option<T> {
+        self.last_settled.borrow().data.clone()
+    }
+
+    /// Get the error of the last failed call, until a later call succeeds.
+    /// `None` if no call failed since the last success or reset.
+    pub fn error(&self) -> Option<E> {
+        self.last_settled.borrow().error.clone()
+    }
+
+    /// Store the result of the mutation, keeping its last successful value and last error apart.
+    fn set_value(&self, value: MutationResult<T, E>) {
+        {
+            let mut last_settled = self.last_settled.borrow_mut();
+            match &value {
+                MutationResult::Ok(data) => {
+                    last_settled.data = Some(data.clone());
+                    last_settled.error = None;
+                }
+                MutationResult::Err(err) => last_settled.error = Some(err.clone()),
+                _ => {}
+            }
+        }
+        self.registration.update(&value);
+        *self.value.borrow_mut() = value;
+    }
+
+    /// Get the arguments of the last call to the mutation function, `None` if it was never called since the last reset.
+    pub fn variables(&self) -> Option<A> {
+        self.variables.borrow().clone()
+    }
+
+    /// Get how many times the mutation function was called by the last mutation, retries included, see [MutationConfig::retry].
+    pub fn attempts(&self) -> usize {
+        self.attempts.get()
+    }
+
+    /// Call the mutation function, or wait for the result of the call with the same key
+    /// and variables already in flight if the mutation is deduplicated, see [MutationConfig::dedupe_in_flight].
+    async fn call_mutation_fn(&self, arg: A, notify: bool) -> MutationResult<T, E> {
+        let Some(dedupe) = &self.dedupe else {
+            return self.call_with_retries(arg, notify).await;
+        };
+        match dedupe.calls.join(dedupe.key.clone(), (dedupe.hash)(&arg)) {
+            MutationCall::Lead(call) => {
+                let result = self.call_with_retries(arg, notify).await;
+                call.settle(result.clone());
+                result
+            }
+            MutationCall::Follow(call) => match call.wait().await {
+                Some(result) => result,
+                // The call it was waiting for got cancelled
+                None => self.call_with_retries(arg, notify).await,
+            },
+        }
+    }
+
+    /// Call the mutation function, calling it again on errors as many times as configured.
+    /// Errors meaning the app is offline are not retried.
+    async fn call_with_retries(&self, arg: A, notify: bool) -> MutationResult<T, E> {
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            self.attempts.set(attempt);
+            let mutation_fn = self.mutation_fn.borrow().clone();
+            let fut = mutation_fn(arg.clone());
+            let fut = Box::into_pin(fut);
+            let value = fut.await;
+            let should_retry = value.is_err()
+                && attempt <= self.handlers.retry as usize
+                && !self.handlers.is_offline(&value);
+            if !should_retry {
+                return value;
+            }
+            Box::into_pin(sleep(self.handlers.retry_delay.delay(attempt as u32))).await;
+            if notify {
+                self.notify();
+            }
+        }
+    }
+
+    /// Call the mutation function, `None` if the call is cancelled in the meantime.
+    async fn call_cancellable(
+        &self,
         arg: A,
-        value: &Rc<RefCell<MutationResult<T, E>>>,
-        scheduler: &Arc<dyn Fn(ScopeId)>,
-        scope_id: ScopeId,
-        mutation_fn: &Arc<Box<MutationFn<T, E, A>>>,
-    ) {
-        let cached_value = value.borrow().clone().into();
+        notify: bool,
+        run: &MutationRun,
+    ) -> Option<MutationResult<T, E>> {
+        let (abort_handle, abort_registration) = AbortHandle::new_pair();
+        self.runs
+            .aborts
+            .borrow_mut()
+            .insert(run.number, abort_handle);
+        Abortable::new(self.call_mutation_fn(arg, notify), abort_registration)
+            .await
+            .ok()
+    }
+
+    /// Cancel the running calls of the mutation, retries included, as well as the queued ones waiting for their turn,
+    /// and go back to the last successful result, or [MutationResult::Pending] if there is none.
+    /// The callbacks of the cancelled calls don't run and their optimistic updates are not rolled back.
+    /// Does nothing if the mutation is not running.
+    pub fn cancel(&self) {
+        let runs = &self.runs;
+        if runs.in_flight.get() == 0 {
+            return;
+        }
+        runs.cancellations.increment();
+        let aborts = runs
+            .aborts
+            .borrow_mut()
+            .drain()
+            .map(|(_, abort_handle)| abort_handle)
+            .collect::<Vec<_>>();
+        for abort_handle in aborts {
+            abort_handle.abort();
+        }
+        let waiters = runs.waiters.borrow_mut().drain(..).collect::<Vec<_>>();
+        for waker in waiters {
+            waker.wake();
+        }
+
+        let previous_data: Option<T> = self.value.borrow().clone().into();
+        self.set_value(
+            previous_data
+                .map(MutationResult::Ok)
+                .unwrap_or(MutationResult::Pending),
+        );
+        self.notify();
+    }
+
+    /// Wait for the turn of a new call of the mutation, `None` if it must be ignored or was cancelled while queued.
+    async fn start_run(&self) -> Option<MutationRun> {
+        let runs = &self.runs;
+        let concurrency = self.handlers.concurrency;
+        if concurrency == MutationConcurrency::DropWhileInFlight && runs.in_flight.get() > 0 {
+            return None;
+        }
+
+        let number = runs.started.increment() - 1;
+        runs.in_flight.increment();
+        let run = MutationRun {
+            runs: runs.clone(),
+            number,
+        };
+
+        if concurrency == MutationConcurrency::Queue {
+            runs.queue.borrow_mut().push_back(number);
+            let cancellations = runs.cancellations.get();
+            let is_cancelled = poll_fn(|cx| {
+                if runs.cancellations.get() != cancellations {
+                    return Poll::Ready(true);
+                }
+                if runs.queue.borrow().front() == Some(&number) {
+                    return Poll::Ready(false);
+                }
+                runs.waiters.borrow_mut().push(cx.waker().clone());
+                Poll::Pending
+            })
+            .await;
+            if is_cancelled {
+                return None;
+            }
+        }
+
+        Some(run)
+    }
+
+    /// Check if the result of a call must be discarded because a newer call was started.
+    fn is_superseded(&self, run: &MutationRun) -> bool {
+        self.handlers.concurrency == MutationConcurrency::Latest
+            && self.runs.started.get() != run.number + 1
+    }
+
+    /// Run the mutation, returning its result, and its argument back if it failed because the app is offline.
+    async fn inner_mutate(&self, arg: A) -> (MutationResult<T, E>, Option<A>) {
+        let Some(run) = self.start_run().await else {
+            return (self.value.borrow().clone(), None);
+        };
+        let _mutating = self.mutation_client.start_mutation();
+        let cached_value = self.value.borrow().clone().into();
+        *self.variables.borrow_mut() = Some(arg.clone());
+        let arg_copy = arg.clone();
 
         // Set state to loading and notify
-        *value.borrow_mut() = MutationResult::Loading(cached_value);
+        self.set_value(MutationResult::Loading(cached_value));
 
         // TODO optimization: Check if the value was already loading
         // to decide to call the scheduler or not
-        (scheduler)(scope_id);
+        self.notify();
+
+        let on_mutate_end = self.handlers.start(&arg);
 
         // Trigger the mutation function
-        let fut = (mutation_fn)(arg);
-        let fut = Box::into_pin(fut);
-        let new_value = fut.await;
+        let Some(new_value) = self.call_cancellable(arg, true, &run).await else {
+            return (self.value.borrow().clone(), None);
+        };
+
+        // Roll back the optimistic updates, even if the mutation is queued as it will update them again once replayed
+        on_mutate_end
+            .into_iter()
+            .for_each(|f| f(&new_value, &arg_copy));
+
+        // A newer call owns the stored result
+        let is_superseded = self.is_superseded(&run);
+
+        // Keep the argument to replay the mutation later if the app is offline
+        if self.handlers.is_offline(&new_value) {
+            // Keep the result the mutation had before
+            let queued_value = MutationResult::Queued(self.value.borrow().clone().into());
+            if !is_superseded {
+                self.set_value(queued_value.clone());
+                self.notify();
+            }
+            return (queued_value, Some(arg_copy));
+        }
 
-        // Set state to the new value and notify
-        *value.borrow_mut() = new_value;
+        if !is_superseded {
+            // Set state to the new value and notify
+            self.set_value(new_value.clone());
 
-        // TODO optimization: Check if the previous and new value are
-        // different to decide to call the scheduler or not
-        (scheduler)(scope_id);
+            // TODO optimization: Check if the previous and new value are
+            // different to decide to call the scheduler or not
+            self.notify();
+        }
+
+        self.handlers.run_callbacks(&new_value, &arg_copy);
+        (new_value, None)
     }
 
-    async fn inner_silent_mutate(
-        arg: A,
-        value: &Rc<RefCell<MutationResult<T, E>>>,
-        mutation_fn: &Arc<Box<MutationFn<T, E, A>>>,
-    ) {
-        let cached_value = value.borrow().clone().into();
+    async fn inner_replay_queued(&self) {
+        let Some(offline_queue) = &self.handlers.offline_queue else {
+            return;
+        };
+        loop {
+            let Some(arg) = offline_queue.queued.borrow_mut().pop_front() else {
+                return;
+            };
+            let (_, offline_arg) = self.inner_mutate(arg).await;
+
+            // Still offline, keep it first in line for the next replay
+            if let Some(arg) = offline_arg {
+                offline_queue.queued.borrow_mut().push_front(arg);
+                return;
+            }
+        }
+    }
+
+    async fn inner_silent_mutate(&self, arg: A) -> MutationResult<T, E> {
+        let Some(run) = self.start_run().await else {
+            return self.value.borrow().clone();
+        };
+        let _mutating = self.mutation_client.start_mutation();
+        let cached_value = self.value.borrow().clone().into();
+        *self.variables.borrow_mut() = Some(arg.clone());
+        let arg_copy = arg.clone();
 
         // Set state to loading
-        *value.borrow_mut() = MutationResult::Loading(cached_value);
+        self.set_value(MutationResult::Loading(cached_value));
+
+        let on_mutate_end = self.handlers.start(&arg);
 
         // Trigger the mutation function
-        let fut = (mutation_fn)(arg);
-        let fut = Box::into_pin(fut);
-        let new_value = fut.await;
+        let Some(new_value) = self.call_cancellable(arg, false, &run).await else {
+            return self.value.borrow().clone();
+        };
+
+        // Set state to the new value, unless a newer call owns it
+        if !self.is_superseded(&run) {
+            self.set_value(new_value.clone());
+        }
 
-        // Set state to the new value
-        *value.borrow_mut() = new_value;
+        on_mutate_end
+            .into_iter()
+            .for_each(|f| f(&new_value, &arg_copy));
+        self.handlers.run_callbacks(&new_value, &arg_copy);
+        new_value
     }
 
     /// Call the mutation function with a set of arguments, in the **background**.
+    /// It is spawned as a task of the current component, so it can be called right from an event handler.
+    /// Overlapping calls are handled as configured with [MutationConfig::concurrency],
+    /// use [UseMutation::manual_mutate] to get the result of a specific call.
     pub fn mutate(&self, arg: A)
     where
         T: 'static,
         E: 'static,
         A: 'static,
     {
-        let value = self.value.clone();
-        let scheduler = self.scheduler.clone();
-        let scope_id = self.scope_id;
-        let mutation_fn = self.mutation_fn.clone();
-        spawn(
-            async move { Self::inner_mutate(arg, &value, &scheduler, scope_id, &mutation_fn).await },
-        );
+        let mutation = self.clone();
+        spawn(async move {
+            mutation.manual_mutate(arg).await;
+        });
     }
 
     /// Call the mutation function with a set of arguments.
-    pub async fn manual_mutate(&self, arg: A) {
-        Self::inner_mutate(
-            arg,
-            &self.value,
-            &self.scheduler,
-            self.scope_id,
-            &self.mutation_fn,
-        )
-        .await;
+    /// Resolves with a copy of its result, which is also stored for [UseMutation::result].
+    /// If the call is ignored because of [MutationConcurrency::DropWhileInFlight], it resolves right away with the current result.
+    pub async fn manual_mutate(&self, arg: A) -> MutationResult<T, E> {
+        let (result, offline_arg) = self.inner_mutate(arg).await;
+        if let (Some(arg), Some(offline_queue)) = (offline_arg, &self.handlers.offline_queue) {
+            offline_queue.queued.borrow_mut().push_back(arg);
+        }
+        result
+    }
+
+    /// Call the mutation function again with the arguments of the last call, in the **background**.
+    /// Does nothing if it was never called since the last reset, see [UseMutation::variables].
+    pub fn retry(&self)
+    where
+        T: 'static,
+        E: 'static,
+        A: 'static,
+    {
+        if let Some(arg) = self.variables() {
+            self.mutate(arg);
+        }
+    }
+
+    /// Call the mutation function again with the arguments of the last call, see [UseMutation::retry].
+    /// Resolves with a copy of its result, `None` if it was never called since the last reset.
+    pub async fn manual_retry(&self) -> Option<MutationResult<T, E>> {
+        let arg = self.variables()?;
+        Some(self.manual_mutate(arg).await)
+    }
+
+    /// Go back to [MutationResult::Pending] and forget the arguments of the last call,
+    /// along with the last successful value and the last error.
+    pub fn reset(&self) {
+        self.set_value(MutationResult::Pending);
+        *self.last_settled.borrow_mut() = LastSettled::default();
+        *self.variables.borrow_mut() = None;
+        self.notify();
+    }
+
+    /// Replay the mutations queued while offline, in the **background**, see [MutationConfig::offline_queue].
+    /// They run one after the other in the order they were queued,
+    /// stopping at the first one that fails because the app is still offline.
+    pub fn replay_queued(&self)
+    where
+        T: 'static,
+        E: 'static,
+        A: 'static,
+    {
+        let mutation = self.clone();
+        spawn(async move {
+            mutation.inner_replay_queued().await;
+        });
+    }
+
+    /// Replay the mutations queued while offline, see [UseMutation::replay_queued].
+    pub async fn manual_replay_queued(&self) {
+        self.inner_replay_queued().await;
+    }
+
+    /// Get the arguments of the mutations queued while offline, in the order they will be replayed.
+    pub fn queued(&self) -> Vec<A> {
+        self.handlers
+            .offline_queue
+            .as_ref()
+            .map(|offline_queue| offline_queue.queued.borrow().iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Drop the queued mutation at the given position, e.g because it conflicts with newer changes.
+    pub fn remove_queued(&self, index: usize) -> Option<A> {
+        self.handlers
+            .offline_queue
+            .as_ref()?
+            .queued
+            .borrow_mut()
+            .remove(index)
+    }
+
+    /// Drop every queued mutation.
+    pub fn clear_queued(&self) {
+        if let Some(offline_queue) = &self.handlers.offline_queue {
+            offline_queue.queued.borrow_mut().clear();
+        }
+    }
+
+    /// Queue mutations to be replayed, e.g the ones persisted before the app was closed.
+    pub fn restore_queued(&self, args: impl IntoIterator<Item = A>) {
+        if let Some(offline_queue) = &self.handlers.offline_queue {
+            offline_queue.queued.borrow_mut().extend(args);
+        }
     }
 
     /// Call the mutation function silently with a set of arguments, in the **background**.
     /// This will not make the component re run.
-    pub async fn mutate_silent(&self, arg: A)
+    pub fn mutate_silent(&self, arg: A)
     where
         T: 'static,
         E: 'static,
         A: 'static,
     {
-        let value = self.value.clone();
-        let mutation_fn = self.mutation_fn.clone();
+        let mutation = self.clone();
         spawn(async move {
-            Self::inner_silent_mutate(arg, &value, &mutation_fn).await;
+            mutation.inner_silent_mutate(arg).await;
         });
     }
 
     /// Call the mutation function silently with a set of arguments.
     /// This will not make the component re run.
-    pub async fn manual_mutate_silent(&self, arg: A) {
-        Self::inner_silent_mutate(arg, &self.value, &self.mutation_fn).await;
+    /// Resolves with a copy of its result, which is also stored for [UseMutation::result].
+    pub async fn manual_mutate_silent(&self, arg: A) -> MutationResult<T, E> {
+        self.inner_silent_mutate(arg).await
     }
 }
 
 /// The result of a mutation.
 #[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum MutationResult<T, E> {
     /// Mutation was successful
     Ok(T),
@@ -132,6 +860,8 @@ pub enum MutationResult<T, E> {
     Loading(Option<T>),
     /// Mutation has not been triggered yet
     Pending,
+    /// Mutation failed because the app is offline and will be replayed, may or not have a previous result
+    Queued(Option<T>),
 }
 
 impl<T, E> MutationResult<T, E> {
@@ -150,6 +880,53 @@ impl<T, E> MutationResult<T, E> {
     pub fn is_pending(&self) -> bool {
         matches!(self, MutationResult::Pending)
     }
+
+    pub fn is_queued(&self) -> bool {
+        matches!(self, MutationResult::Queued(..))
+    }
+
+    /// Whether the mutation is done, either successful or errored.
+    pub fn is_settled(&self) -> bool {
+        matches!(self, MutationResult::Ok(..) | MutationResult::Err(..))
+    }
+
+    /// Get the successful value, `None` if not successful.
+    pub fn ok(&self) -> Option<&T> {
+        match self {
+            MutationResult::Ok(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Get the error, `None` if not errored.
+    pub fn err(&self) -> Option<&E> {
+        match self {
+            MutationResult::Err(e) => Some(e),
+            _ => None,
+        }
+    }
+
+    /// Transform the successful value, including the previous one of a loading or queued state.
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> MutationResult<U, E> {
+        match self {
+            MutationResult::Ok(v) => MutationResult::Ok(f(v)),
+            MutationResult::Err(e) => MutationResult::Err(e),
+            MutationResult::Loading(v) => MutationResult::Loading(v.map(f)),
+            MutationResult::Pending => MutationResult::Pending,
+            MutationResult::Queued(v) => MutationResult::Queued(v.map(f)),
+        }
+    }
+
+    /// Transform the error.
+    pub fn map_err<F>(self, f: impl FnOnce(E) -> F) -> MutationResult<T, F> {
+        match self {
+            MutationResult::Ok(v) => MutationResult::Ok(v),
+            MutationResult::Err(e) => MutationResult::Err(f(e)),
+            MutationResult::Loading(v) => MutationResult::Loading(v),
+            MutationResult::Pending => MutationResult::Pending,
+            MutationResult::Queued(v) => MutationResult::Queued(v),
+        }
+    }
 }
 
 impl<T, E> From<Result<T, E>> for MutationResult<T, E> {
@@ -161,6 +938,19 @@ impl<T, E> From<Result<T, E>> for MutationResult<T, E> {
     }
 }
 
+/// The successful value, or the previous one while loading or queued, and `Ok(None)` if pending.
+impl<T, E> From<MutationResult<T, E>> for Result<Option<T>, E> {
+    fn from(result: MutationResult<T, E>) -> Self {
+        match result {
+            MutationResult::Ok(v) => Ok(Some(v)),
+            MutationResult::Err(e) => Err(e),
+            MutationResult::Loading(v) => Ok(v),
+            MutationResult::Pending => Ok(None),
+            MutationResult::Queued(v) => Ok(v),
+        }
+    }
+}
+
 impl<T, E> From<MutationResult<T, E>> for Option<T> {
     fn from(result: MutationResult<T, E>) -> Self {
         match result {
@@ -168,26 +958,877 @@ impl<T, E> From<MutationResult<T, E>> for Option<T> {
             MutationResult::Err(_) => None,
             MutationResult::Loading(v) => v,
             MutationResult::Pending => None,
+            MutationResult::Queued(v) => v,
         }
     }
 }
 
+impl<T: 'static, E: 'static, A> UseMutation<T, E, A> {
+    fn new(
+        cx: &ScopeState,
+        config: MutationConfig<T, E, A>,
+        mutation: SharedMutation<T, E>,
+    ) -> Self {
+        let MutationConfig {
+            mutation_fn,
+            key,
+            dedupe,
+            mut handlers,
+            scoped_handlers,
+        } = config;
+        let dedupe = key.clone().zip(dedupe).map(|(key, hash)| {
+            Shared::new(MutationDedupe {
+                key,
+                hash,
+                calls: InFlightMutations::get(cx),
+            })
+        });
+        for add_handlers in scoped_handlers {
+            add_handlers(cx, &mut handlers);
+        }
+        let mutation_client = MutationClient::get(cx);
+        let registration = mutation_client.register(key);
+        registration.update(&*mutation.value.borrow());
+        UseMutation {
+            value: mutation.value,
+            last_settled: mutation.last_settled,
+            mutation_fn: Shared::new(Lock::new(mutation_fn)),
+            scheduler: cx.schedule_update_any(),
+            listeners: mutation.listeners,
+            mutation_client,
+            registration: Shared::new(registration),
+            dedupe,
+            handlers: Shared::new(handlers),
+            variables: Shared::default(),
+            attempts: Shared::default(),
+            runs: Shared::default(),
+        }
+    }
+
+    /// Call the mutation function of the given configuration from now on,
+    /// e.g the closure of the current render, so it doesn't keep the values captured by the first one.
+    pub(crate) fn refresh_mutation_fn(&self, config: MutationConfig<T, E, A>) {
+        *self.mutation_fn.borrow_mut() = config.mutation_fn;
+    }
+}
+
+/// The configuration of a mutation, see [use_mutation_config].
+pub struct MutationConfig<T, E, A> {
+    pub(crate) mutation_fn: Arc<Box<MutationFn<T, E, A>>>,
+    pub(crate) key: Option<Cow<'static, str>>,
+    pub(crate) dedupe: Option<Box<DedupeHashFn<A>>>,
+    pub(crate) handlers: MutationHandlers<T, E, A>,
+    pub(crate) scoped_handlers: Vec<Box<ScopedHandlersFn<T, E, A>>>,
+}
+
+impl<T, E, A> MutationConfig<T, E, A> {
+    pub fn new<M, F>(mutation_fn: M) -> Self
+    where
+        M: Fn(A) -> F + MaybeSend + MaybeSync + 'static,
+        F: Future<Output = MutationResult<T, E>> + MaybeSend + 'static,
+    {
+        Self {
+            mutation_fn: Arc::new(Box::new(move |p| {
+                let fut = mutation_fn(p);
+                Box::new(fut)
+            })),
+            key: None,
+            dedupe: None,
+            handlers: MutationHandlers {
+                cache_updates: Vec::new(),
+                updated_queries: Shared::default(),
+                on_success: Vec::new(),
+                on_error: Vec::new(),
+                on_settled: Vec::new(),
+                on_mutate: Vec::new(),
+                offline_queue: None,
+                retry: 0,
+                retry_delay: ExponentialBackoff::constant(Duration::ZERO),
+                concurrency: MutationConcurrency::default(),
+            },
+            scoped_handlers: Vec::new(),
+        }
+    }
+
+    /// Same as [MutationConfig::new] but with a mutation function returning a [Result].
+    pub fn from_result_fn<M, F>(mutation_fn: M) -> Self
+    where
+        T: 'static,
+        E: 'static,
+        A: 'static,
+        M: Fn(A) -> F + MaybeSend + MaybeSync + 'static,
+        F: Future<Output = Result<T, E>> + MaybeSend + 'static,
+    {
+        Self::new(move |p| {
+            let fut = mutation_fn(p);
+            async move { MutationResult::from(fut.await) }
+        })
+    }
+
+    /// Name the mutation, e.g to tell it apart in [MutationClient::active_mutations].
+    pub fn with_key(mut self, key: impl Into<Cow<'static, str>>) -> Self {
+        self.key = Some(key.into());
+        self
+    }
+
+    /// Don't call the mutation function again when the mutation is called while a call of a mutation
+    /// with the same key is in flight anywhere in the app, wait for the result of that call instead,
+    /// e.g so double-clicking a button doesn't send the same request twice.
+    /// Only mutations named with [MutationConfig::with_key] are deduplicated, and only those that are idempotent should be.
+    ///
+    /// ## Example:
+    ///
+    /// ```no_run
+    /// MutationConfig::new(follow_user).with_key("follow").dedupe_in_flight()
+    /// ```
+    pub fn dedupe_in_flight(mut self) -> Self {
+        self.dedupe = Some(Box::new(|_: &A| 0));
+        self
+    }
+
+    /// Same as [MutationConfig::dedupe_in_flight] but only for the calls with the same variables.
+    pub fn dedupe_in_flight_by_variables(mut self) -> Self
+    where
+        A: Hash,
+    {
+        self.dedupe = Some(Box::new(|arg: &A| {
+            let mut hasher = DefaultHasher::new();
+            arg.hash(&mut hasher);
+            hasher.finish()
+        }));
+        self
+    }
+
+    /// Call a callback with the value and the argument of every successful mutation, once its result is stored.
+    pub fn on_success(
+        mut self,
+        on_success: impl Fn(&T, &A) + MaybeSend + MaybeSync + 'static,
+    ) -> Self {
+        self.handlers.on_success.push(Box::new(on_success));
+        self
+    }
+
+    /// Call a callback with the error and the argument of every failed mutation, once its result is stored.
+    pub fn on_error(mut self, on_error: impl Fn(&E, &A) + MaybeSend + MaybeSync + 'static) -> Self {
+        self.handlers.on_error.push(Box::new(on_error));
+        self
+    }
+
+    /// Call a callback with the result and the argument of every mutation, successful or not, once its result is stored.
+    pub fn on_settled(
+        mut self,
+        on_settled: impl Fn(&MutationResult<T, E>, &A) + MaybeSend + MaybeSync + 'static,
+    ) -> Self {
+        self.handlers.on_settled.push(Box::new(on_settled));
+        self
+    }
+
+    /// Invalidate the queries with any of the given keys every time the mutation succeeds,
+    /// as [UseQueryClient::invalidate_queries](crate::prelude::UseQueryClient::invalidate_queries) would.
+    /// Nothing is invalidated when it fails, and the queries written with [MutationConfig::updates] are not refetched.
+    ///
+    /// The queries belong to the [UseQueryClient](crate::prelude::UseQueryClient) provided to the component using the mutation.
+    pub fn invalidates<K>(self, keys: Vec<K>) -> Self
+    where
+        K: 'static + Clone + MaybeSend + MaybeSync,
+    {
+        self.invalidates_with(move |_, _| keys.clone())
+    }
+
+    /// Same as [MutationConfig::invalidates] but with keys derived from the value and the argument of the mutation.
+    ///
+    /// ## Example:
+    ///
+    /// ```no_run
+    /// MutationConfig::new(rename_user)
+    ///     .invalidates_with(|user: &User, _| vec![QueryKeys::User(user.id), QueryKeys::Users])
+    /// ```
+    pub fn invalidates_with<K>(
+        mut self,
+        invalidates: impl Fn(&T, &A) -> Vec<K> + MaybeSend + MaybeSync + 'static,
+    ) -> Self
+    where
+        K: 'static + Clone,
+    {
+        self.scoped_handlers.push(Box::new(move |cx, handlers| {
+            let QueryInvalidator(invalidate) = cx
+                .consume_context::<QueryInvalidator<K>>()
+                .expect("Mutations that invalidate queries need a query client");
+            let updated_queries = handlers.updated_queries.clone();
+            handlers.on_success.push(Box::new(move |data, arg| {
+                invalidate(&invalidates(data, arg), &updated_queries.get::<K>())
+            }));
+        }));
+        self
+    }
+
+    /// Write the value of the mutation into the cached values of the queries with any of the given keys
+    /// every time it succeeds, instead of refetching them, e.g when it returns the updated entity.
+    /// The queries are considered fresh and their listeners are notified, nothing happens to the ones without a value.
+    /// Can be called multiple times for different keys.
+    ///
+    /// The queries belong to the [UseQueryClient] provided to the component using the mutation.
+    ///
+    /// ## Example:
+    ///
+    /// ```no_run
+    /// MutationConfig::new(rename_user)
+    ///     .updates(vec![QueryKeys::User(id)], |cached: &mut QueryValue, user: &User| {
+    ///         *cached = QueryValue::User(user.clone())
+    ///     })
+    ///     .invalidates(vec![QueryKeys::Users])
+    /// ```
+    pub fn updates<QT, QE, QK>(
+        self,
+        keys: Vec<QK>,
+        updater: impl Fn(&mut QT, &T) + MaybeSend + MaybeSync + 'static,
+    ) -> Self
+    where
+        QT: 'static + Clone + MaybeSend + MaybeSync,
+        QE: 'static + Clone + MaybeSend + MaybeSync,
+        QK: 'static + Eq + Hash + Clone + MaybeSend + MaybeSync,
+    {
+        self.update_queries::<QT, QE, QK>(keys, updater, false)
+    }
+
+    /// Same as [MutationConfig::updates] but the matching queries without a value to write into,
+    /// e.g because their last fetch failed, are invalidated instead.
+    pub fn updates_or_invalidates<QT, QE, QK>(
+        self,
+        keys: Vec<QK>,
+        updater: impl Fn(&mut QT, &T) + MaybeSend + MaybeSync + 'static,
+    ) -> Self
+    where
+        QT: 'static + Clone + MaybeSend + MaybeSync,
+        QE: 'static + Clone + MaybeSend + MaybeSync,
+        QK: 'static + Eq + Hash + Clone + MaybeSend + MaybeSync,
+    {
+        self.update_queries::<QT, QE, QK>(keys, updater, true)
+    }
+
+    fn update_queries<QT, QE, QK>(
+        mut self,
+        keys: Vec<QK>,
+        updater: impl Fn(&mut QT, &T) + MaybeSend + MaybeSync + 'static,
+        invalidate_missed: bool,
+    ) -> Self
+    where
+        QT: 'static + Clone + MaybeSend + MaybeSync,
+        QE: 'static + Clone + MaybeSend + MaybeSync,
+        QK: 'static + Eq + Hash + Clone + MaybeSend + MaybeSync,
+    {
+        self.scoped_handlers.push(Box::new(move |cx, handlers| {
+            let client = cx
+                .consume_context::<UseQueryClient<QT, QE, QK>>()
+                .expect("Mutations that update queries need a query client");
+            let updated_queries = handlers.updated_queries.clone();
+            handlers.cache_updates.push(Box::new(move |data, _| {
+                let (mut updated, missed) =
+                    client.update_matching_data(&keys, |cached| updater(cached, data));
+                if invalidate_missed && !missed.is_empty() {
+                    client.invalidate_entries_with_keys(&missed);
+                    updated.extend(missed);
+                }
+                updated_queries.push(updated);
+            }));
+        }));
+        self
+    }
+
+    /// Update the cache of the query client optimistically before calling the mutation function,
+    /// and roll it back if the mutation fails.
+    ///
+    /// `on_mutate` gets the [UseQueryClient] provided to the component using the mutation and the argument of the mutation,
+    /// and returns a context, e.g the previous data of the queries it updated.
+    /// If the mutation fails, `on_error` gets that context back along with the error to restore the previous data.
+    /// Use [MutationConfig::on_settled] or [MutationConfig::invalidates] to reconcile the cache with the actual result.
+    ///
+    /// Mutations update the cache in the order they were started, and each rollback restores the data its context holds,
+    /// so with concurrent mutations of the same query the last write wins.
+    /// Mutations queued while offline are rolled back too, `on_mutate` runs again once they are replayed.
+    ///
+    /// ## Example:
+    ///
+    /// ```no_run
+    /// MutationConfig::new(add_todo)
+    ///     .on_mutate(
+    ///         |client: &UseQueryClient<QueryValue, QueryError, QueryKeys>, todo: &Todo| {
+    ///             let previous = client.get_query_data(&[QueryKeys::Todos]);
+    ///             client.update_query_data(&[QueryKeys::Todos], |todos| todos.push(todo.clone()));
+    ///             previous
+    ///         },
+    ///         |client, previous, _err, _todo| {
+    ///             if let Some(previous) = previous {
+    ///                 client.update_query_data(&[QueryKeys::Todos], |todos| *todos = previous);
+    ///             }
+    ///         },
+    ///     )
+    ///     .invalidates(vec![QueryKeys::Todos])
+    /// ```
+    pub fn on_mutate<QT, QE, QK, C>(
+        mut self,
+        on_mutate: impl Fn(&UseQueryClient<QT, QE, QK>, &A) -> C + MaybeSend + MaybeSync + 'static,
+        on_error: impl Fn(&UseQueryClient<QT, QE, QK>, C, &E, &A) + MaybeSend + MaybeSync + 'static,
+    ) -> Self
+    where
+        QT: 'static + Clone + MaybeSend + MaybeSync,
+        QE: 'static + Clone + MaybeSend + MaybeSync,
+        QK: 'static + Clone + MaybeSend + MaybeSync,
+        C: 'static + MaybeSend,
+    {
+        self.scoped_handlers.push(Box::new(move |cx, handlers| {
+            let client = cx
+                .consume_context::<UseQueryClient<QT, QE, QK>>()
+                .expect("Mutations that update the cache optimistically need a query client");
+            let on_error = Shared::new(on_error);
+            handlers.on_mutate.push(Box::new(move |arg| {
+                let context = on_mutate(&client, arg);
+                let client = client.clone();
+                let on_error = on_error.clone();
+                Box::new(move |result, arg| {
+                    if let MutationResult::Err(err) = result {
+                        on_error(&client, context, err, arg);
+                    }
+                })
+            }));
+        }));
+        self
+    }
+
+    /// Call the mutation function again, after `delay`, when it returns an error, up to `retry` times.
+    /// The result stays [MutationResult::Loading] meanwhile, see [UseMutation::attempts].
+    /// Mutations are not retried by default as they might not be idempotent.
+    pub fn retry(self, retry: u32, delay: Duration) -> Self {
+        self.retry_with_backoff(retry, ExponentialBackoff::constant(delay))
+    }
+
+    /// Same as [MutationConfig::retry] but waiting for the delays of the given backoff,
+    /// e.g with a [Jitter](crate::prelude::Jitter) so clients failing together don't retry together.
+    pub fn retry_with_backoff(mut self, retry: u32, backoff: ExponentialBackoff) -> Self {
+        self.handlers.retry = retry;
+        self.handlers.retry_delay = backoff;
+        self
+    }
+
+    /// Choose what to do when the mutation is called while a previous call is still running,
+    /// by default every call runs right away, see [MutationConcurrency].
+    pub fn concurrency(mut self, concurrency: MutationConcurrency) -> Self {
+        self.handlers.concurrency = concurrency;
+        self
+    }
+
+    /// Queue the arguments of the mutations that fail because the app is offline, as told by `is_offline`,
+    /// instead of losing them. The result is then [MutationResult::Queued]
+    /// until the queued mutations are replayed with [UseMutation::replay_queued].
+    /// Callbacks only run once a queued mutation is replayed.
+    pub fn offline_queue(
+        mut self,
+        is_offline: impl Fn(&E) -> bool + MaybeSend + MaybeSync + 'static,
+    ) -> Self {
+        self.handlers.offline_queue = Some(OfflineQueue {
+            queued: Lock::new(VecDeque::new()),
+            is_offline: Box::new(is_offline),
+        });
+        self
+    }
+}
+
+/// Create mutation with the given configuration. See [UseMutation] on how to use it.
+///
+/// ## Example:
+///
+/// ```no_run
+/// let rename_user = use_mutation_config(cx, || {
+///     MutationConfig::new(rename_user)
+///         .on_success(|_, (id, _)| client.invalidate_query(QueryKeys::User(*id)))
+///         .on_error(|err, _| println!("Couldn't rename the user: {err:?}"))
+/// });
+/// ```
+pub fn use_mutation_config<T, E, A>(
+    cx: &ScopeState,
+    config: impl FnOnce() -> MutationConfig<T, E, A>,
+) -> &UseMutation<T, E, A>
+where
+    T: 'static + PartialEq,
+    E: 'static + PartialEq,
+    A: 'static + Clone,
+{
+    cx.use_hook(|| {
+        let mutation = SharedMutation {
+            value: Shared::new(Lock::new(MutationResult::Pending)),
+            last_settled: Shared::default(),
+            listeners: Shared::new(Lock::new(HashSet::from([cx.scope_id()]))),
+        };
+        UseMutation::new(cx, config(), mutation)
+    })
+}
+
+/// Create a mutation sharing its state with every component using the same key,
+/// so a component can show the progress of a mutation called by another one, see [use_mutation_state].
+/// Mutations with different keys are independent.
+///
+/// ## Example:
+///
+/// ```no_run
+/// let submit = use_mutation_with_key(cx, MutationKeys::SubmitForm, submit_form);
+/// ```
+pub fn use_mutation_with_key<K, T, E, A, M, F>(
+    cx: &ScopeState,
+    key: K,
+    mutation_fn: M,
+) -> &UseMutation<T, E, A>
+where
+    K: 'static + Eq + Hash + Clone,
+    T: 'static + Clone,
+    E: 'static + Clone,
+    A: 'static + Clone,
+    M: Fn(A) -> F + MaybeSend + MaybeSync + 'static,
+    F: Future<Output = MutationResult<T, E>> + MaybeSend + 'static,
+{
+    let mut mutation_fn = Some(mutation_fn);
+    let (mutation, _subscription): &(UseMutation<T, E, A>, MutationSubscription<K, T, E>) = cx
+        .use_hook(|| {
+            let subscription = MutationRegistry::get(cx).subscribe(key, cx.scope_id());
+            let config = MutationConfig::new(mutation_fn.take().unwrap());
+            let mutation = UseMutation::new(cx, config, subscription.mutation.clone());
+            (mutation, subscription)
+        });
+    if let Some(mutation_fn) = mutation_fn {
+        mutation.refresh_mutation_fn(MutationConfig::new(mutation_fn));
+    }
+    mutation
+}
+
 /// Create mutation. See [UseMutation] on how to use it.
+///
+/// The mutation function is the closure of the last render, so the values it captures are never stale.
 pub fn use_mutation<T, E, A, M, F>(cx: &ScopeState, mutation_fn: M) -> &UseMutation<T, E, A>
 where
     T: 'static + PartialEq,
     E: 'static + PartialEq,
-    A: 'static,
-    M: Fn(A) -> F + 'static,
-    F: Future<Output = MutationResult<T, E>> + 'static,
+    A: 'static + Clone,
+    M: Fn(A) -> F + MaybeSend + MaybeSync + 'static,
+    F: Future<Output = MutationResult<T, E>> + MaybeSend + 'static,
 {
-    cx.use_hook(|| UseMutation {
-        value: Rc::new(RefCell::new(MutationResult::Pending)),
-        mutation_fn: Arc::new(Box::new(move |p| {
-            let fut = mutation_fn(p);
-            Box::new(fut)
-        })),
-        scheduler: cx.schedule_update_any(),
-        scope_id: cx.scope_id(),
-    })
+    let mut mutation_fn = Some(mutation_fn);
+    let mutation = use_mutation_config(cx, || MutationConfig::new(mutation_fn.take().unwrap()));
+    if let Some(mutation_fn) = mutation_fn {
+        mutation.refresh_mutation_fn(MutationConfig::new(mutation_fn));
+    }
+    mutation
+}
+
+/// Same as [use_mutation] but with a mutation function returning a [Result].
+///
+/// ## Example:
+///
+/// ```no_run
+/// async fn rename_user((id, name): (usize, String)) -> Result<User, ApiError> {
+///     api::rename_user(id, name).await
+/// }
+///
+/// let rename = use_mutation_result(cx, rename_user);
+/// ```
+pub fn use_mutation_result<T, E, A, M, F>(cx: &ScopeState, mutation_fn: M) -> &UseMutation<T, E, A>
+where
+    T: 'static + PartialEq,
+    E: 'static + PartialEq,
+    A: 'static + Clone,
+    M: Fn(A) -> F + MaybeSend + MaybeSync + 'static,
+    F: Future<Output = Result<T, E>> + MaybeSend + 'static,
+{
+    let mut mutation_fn = Some(mutation_fn);
+    let mutation = use_mutation_config(cx, || {
+        MutationConfig::from_result_fn(mutation_fn.take().unwrap())
+    });
+    if let Some(mutation_fn) = mutation_fn {
+        mutation.refresh_mutation_fn(MutationConfig::from_result_fn(mutation_fn));
+    }
+    mutation
+}
+
+/// Create a mutation that queues its arguments when it fails because the app is offline,
+/// see [MutationConfig::offline_queue].
+///
+/// ## Example:
+///
+/// ```no_run
+/// let save_note = use_mutation_with_offline_queue(cx, save_note, |err| {
+///     matches!(err, MutationError::Network)
+/// });
+/// ```
+pub fn use_mutation_with_offline_queue<T, E, A, M, F>(
+    cx: &ScopeState,
+    mutation_fn: M,
+    is_offline: impl Fn(&E) -> bool + MaybeSend + MaybeSync + 'static,
+) -> &UseMutation<T, E, A>
+where
+    T: 'static + PartialEq,
+    E: 'static + PartialEq,
+    A: 'static + Clone,
+    M: Fn(A) -> F + MaybeSend + MaybeSync + 'static,
+    F: Future<Output = MutationResult<T, E>> + MaybeSend + 'static,
+{
+    let mut mutation_fn = Some(mutation_fn);
+    let mutation = use_mutation_config(cx, || {
+        MutationConfig::new(mutation_fn.take().unwrap()).offline_queue(is_offline)
+    });
+    if let Some(mutation_fn) = mutation_fn {
+        mutation.refresh_mutation_fn(MutationConfig::new(mutation_fn));
+    }
+    mutation
+}
+
+struct MutatingListener {
+    client: MutationClient,
+    scope_id: ScopeId,
+}
+
+impl Drop for MutatingListener {
+    fn drop(&mut self) {
+        self.client.listeners.borrow_mut().remove(&self.scope_id);
+    }
+}
+
+/// Get how many mutations are running anywhere in the app, including silent ones,
+/// e.g to disable a button while saving.
+/// The component is re-rendered whenever a mutation starts or finishes.
+///
+/// ## Example:
+///
+/// ```no_run
+/// let is_mutating = use_is_mutating(cx) > 0;
+/// ```
+pub fn use_is_mutating(cx: &ScopeState) -> usize {
+    let listener = cx.use_hook(|| {
+        let client = MutationClient::get(cx);
+        client.listeners.borrow_mut().insert(cx.scope_id());
+        MutatingListener {
+            client,
+            scope_id: cx.scope_id(),
+        }
+    });
+    listener.client.mutating.get()
+}
+
+/// Get the app's [MutationClient], e.g to check which mutations are running from an event handler.
+pub fn use_mutation_client(cx: &ScopeState) -> &MutationClient {
+    cx.use_hook(|| MutationClient::get(cx))
+}
+
+/// Get a snapshot of every mutation used by the mounted components, see [MutationClient::active_mutations].
+/// The component is re-rendered whenever a mutation is created, changes its status or is dropped.
+///
+/// ## Example:
+///
+/// ```no_run
+/// let is_syncing = use_active_mutations(cx)
+///     .iter()
+///     .any(|mutation| mutation.status == MutationStatus::Loading);
+///
+/// render!( if is_syncing { "Syncing your changes..." } )
+/// ```
+pub fn use_active_mutations(cx: &ScopeState) -> Vec<MutationSnapshot> {
+    let listener = cx.use_hook(|| {
+        let client = MutationClient::get(cx);
+        client.listeners.borrow_mut().insert(cx.scope_id());
+        MutatingListener {
+            client,
+            scope_id: cx.scope_id(),
+        }
+    });
+    listener.client.active_mutations()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::future::{ready, BoxFuture, FutureExt};
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    };
+    use tokio::{
+        sync::oneshot,
+        task::{spawn_local, yield_now, JoinHandle, LocalSet},
+    };
+
+    /// A mutation outside of any component, with a scheduler counting how many times it re-renders its component.
+    fn detached_mutation<A>(
+        config: MutationConfig<usize, String, A>,
+    ) -> (UseMutation<usize, String, A>, Arc<AtomicUsize>) {
+        let renders = Arc::new(AtomicUsize::new(0));
+        let scheduler: Arc<Scheduler> = Arc::new({
+            let renders = renders.clone();
+            move |_: ScopeId| {
+                renders.fetch_add(1, Ordering::SeqCst);
+            }
+        });
+        let mutation_client = MutationClient {
+            mutating: Shared::default(),
+            mutations: Shared::default(),
+            registered: Shared::default(),
+            listeners: Shared::default(),
+            scheduler: scheduler.clone(),
+        };
+        let registration = mutation_client.register(config.key.clone());
+        let mutation = UseMutation {
+            value: Shared::new(Lock::new(MutationResult::Pending)),
+            last_settled: Shared::default(),
+            mutation_fn: Shared::new(Lock::new(config.mutation_fn)),
+            scheduler,
+            listeners: Shared::new(Lock::new(HashSet::from([ScopeId(0)]))),
+            mutation_client,
+            registration: Shared::new(registration),
+            dedupe: None,
+            handlers: Shared::new(config.handlers),
+            variables: Shared::default(),
+            attempts: Shared::default(),
+            runs: Shared::default(),
+        };
+        (mutation, renders)
+    }
+
+    /// A mutation function resolving each of its calls with its argument once the matching gate is opened,
+    /// in the order they were made, and how many times it was called.
+    fn gated_mutation_fn(
+        calls: usize,
+    ) -> (
+        Vec<Option<oneshot::Sender<()>>>,
+        Arc<AtomicUsize>,
+        impl Fn(usize) -> BoxFuture<'static, MutationResult<usize, String>>,
+    ) {
+        let (gates, receivers): (Vec<_>, VecDeque<_>) = (0..calls)
+            .map(|_| {
+                let (gate, receiver) = oneshot::channel();
+                (Some(gate), receiver)
+            })
+            .unzip();
+        let receivers = Mutex::new(receivers);
+        let started = Arc::new(AtomicUsize::new(0));
+        let mutation_fn = {
+            let started = started.clone();
+            move |arg: usize| {
+                started.fetch_add(1, Ordering::SeqCst);
+                let receiver = receivers
+                    .lock()
+                    .unwrap()
+                    .pop_front()
+                    .expect("the mutation function was called more times than expected");
+                async move {
+                    match receiver.await {
+                        Ok(()) => MutationResult::Ok(arg),
+                        Err(_) => MutationResult::Err("gate dropped".to_string()),
+                    }
+                }
+                .boxed()
+            }
+        };
+        (gates, started, mutation_fn)
+    }
+
+    fn spawn_call(
+        mutation: &UseMutation<usize, String, usize>,
+        arg: usize,
+    ) -> JoinHandle<MutationResult<usize, String>> {
+        let mutation = mutation.clone();
+        spawn_local(async move { mutation.manual_mutate(arg).await })
+    }
+
+    /// Let the spawned tasks run until they are all waiting for something.
+    async fn settle() {
+        for _ in 0..10 {
+            yield_now().await;
+        }
+    }
+
+    /// What happened to two overlapping calls of a mutation.
+    #[derive(Debug, PartialEq)]
+    struct Overlap {
+        first: MutationResult<usize, String>,
+        second: MutationResult<usize, String>,
+        stored: MutationResult<usize, String>,
+        /// How many calls of the mutation function started before either of them finished
+        started_together: usize,
+        calls: usize,
+        renders: usize,
+    }
+
+    /// Call the mutation twice before the first call finishes, then let the calls of its function finish
+    /// in the given order.
+    async fn overlapping_calls(
+        concurrency: MutationConcurrency,
+        finish_order: [usize; 2],
+    ) -> Overlap {
+        LocalSet::new()
+            .run_until(async move {
+                let (mut gates, calls, mutation_fn) = gated_mutation_fn(2);
+                let (mutation, renders) =
+                    detached_mutation(MutationConfig::new(mutation_fn).concurrency(concurrency));
+                let first = spawn_call(&mutation, 1);
+                settle().await;
+                let second = spawn_call(&mutation, 2);
+                settle().await;
+                let started_together = calls.load(Ordering::SeqCst);
+
+                for gate in finish_order {
+                    let _ = gates[gate].take().unwrap().send(());
+                    settle().await;
+                }
+                Overlap {
+                    first: first.await.unwrap(),
+                    second: second.await.unwrap(),
+                    stored: mutation.result().clone(),
+                    started_together,
+                    calls: calls.load(Ordering::SeqCst),
+                    renders: renders.load(Ordering::SeqCst),
+                }
+            })
+            .await
+    }
+
+    #[tokio::test]
+    async fn parallel_calls_keep_the_result_of_the_last_one_to_finish() {
+        assert_eq!(
+            overlapping_calls(MutationConcurrency::Parallel, [1, 0]).await,
+            Overlap {
+                first: MutationResult::Ok(1),
+                second: MutationResult::Ok(2),
+                stored: MutationResult::Ok(1),
+                started_together: 2,
+                calls: 2,
+                renders: 4,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn calls_made_while_in_flight_are_dropped() {
+        assert_eq!(
+            overlapping_calls(MutationConcurrency::DropWhileInFlight, [0, 1]).await,
+            Overlap {
+                first: MutationResult::Ok(1),
+                second: MutationResult::Loading(None),
+                stored: MutationResult::Ok(1),
+                started_together: 1,
+                calls: 1,
+                renders: 2,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn latest_calls_supersede_the_previous_ones() {
+        // The first call finishing last doesn't overwrite the result of the second one
+        assert_eq!(
+            overlapping_calls(MutationConcurrency::Latest, [1, 0]).await,
+            Overlap {
+                first: MutationResult::Ok(1),
+                second: MutationResult::Ok(2),
+                stored: MutationResult::Ok(2),
+                started_together: 2,
+                calls: 2,
+                renders: 3,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn queued_calls_run_one_after_the_other() {
+        // The second call can only finish once the first one did, even if its gate opens first
+        assert_eq!(
+            overlapping_calls(MutationConcurrency::Queue, [1, 0]).await,
+            Overlap {
+                first: MutationResult::Ok(1),
+                second: MutationResult::Ok(2),
+                stored: MutationResult::Ok(2),
+                started_together: 1,
+                calls: 2,
+                renders: 4,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn dropping_a_queued_call_does_not_start_the_next_one_early() {
+        LocalSet::new()
+            .run_until(async {
+                let (mut gates, calls, mutation_fn) = gated_mutation_fn(2);
+                let (mutation, _) = detached_mutation(
+                    MutationConfig::new(mutation_fn).concurrency(MutationConcurrency::Queue),
+                );
+                let first = spawn_call(&mutation, 1);
+                settle().await;
+                let second = spawn_call(&mutation, 2);
+                settle().await;
+                let third = spawn_call(&mutation, 3);
+                settle().await;
+
+                // The last call gives up while waiting, the second one keeps waiting for the first one
+                third.abort();
+                settle().await;
+                assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+                let _ = gates[0].take().unwrap().send(());
+                settle().await;
+                assert_eq!(calls.load(Ordering::SeqCst), 2);
+                let _ = gates[1].take().unwrap().send(());
+                assert_eq!(first.await.unwrap(), MutationResult::Ok(1));
+                assert_eq!(second.await.unwrap(), MutationResult::Ok(2));
+                assert_eq!(*mutation.result(), MutationResult::Ok(2));
+            })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn cancelling_also_cancels_the_queued_calls() {
+        LocalSet::new()
+            .run_until(async {
+                let (_gates, calls, mutation_fn) = gated_mutation_fn(2);
+                let (mutation, _) = detached_mutation(
+                    MutationConfig::new(mutation_fn).concurrency(MutationConcurrency::Queue),
+                );
+                let first = spawn_call(&mutation, 1);
+                settle().await;
+                let second = spawn_call(&mutation, 2);
+                settle().await;
+
+                mutation.cancel();
+                assert_eq!(first.await.unwrap(), MutationResult::Pending);
+                assert_eq!(second.await.unwrap(), MutationResult::Pending);
+                assert_eq!(calls.load(Ordering::SeqCst), 1);
+                assert_eq!(*mutation.result(), MutationResult::Pending);
+            })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn awaited_calls_return_owned_results() {
+        let (mutation, _) = detached_mutation(MutationConfig::new(|n: usize| {
+            ready(MutationResult::Ok(n * 2))
+        }));
+
+        // Neither result borrows the mutation, so it can be called again while they are kept
+        let first = mutation.manual_mutate(1).await;
+        let second = mutation.manual_mutate(2).await;
+        assert_eq!(first, MutationResult::Ok(2));
+        assert_eq!(second, MutationResult::Ok(4));
+        assert_eq!(*mutation.result(), second);
+
+        let silent = mutation.manual_mutate_silent(3).await;
+        assert_eq!(silent, MutationResult::Ok(6));
+        assert_eq!(first, MutationResult::Ok(2));
+        assert_eq!(*mutation.result(), silent);
+    }
+
+    #[tokio::test]
+    async fn calls_use_the_refreshed_mutation_function() {
+        let adding = |offset: usize| move |n: usize| ready(MutationResult::Ok(n + offset));
+        let (mutation, _) = detached_mutation(MutationConfig::new(adding(1)));
+        assert_eq!(mutation.manual_mutate(1).await, MutationResult::Ok(2));
+
+        // A re-render with a closure capturing a newer value
+        mutation.refresh_mutation_fn(MutationConfig::new(adding(10)));
+        assert_eq!(mutation.manual_mutate(1).await, MutationResult::Ok(11));
+    }
 }
@@ -0,0 +1,122 @@
+use dioxus::prelude::*;
+use futures_util::Future;
+use std::{ops::Deref, sync::Arc};
+
+use crate::{
+    sync::{Lock, MaybeSend, MaybeSync, Shared},
+    use_mutation::{use_mutation_config, MutationConfig, MutationResult, UseMutation},
+    use_query_client::Scheduler,
+};
+
+/// Lets a mutation function report its progress, see [use_mutation_with_progress].
+pub struct ProgressSender<P> {
+    progress: Shared<Lock<Option<P>>>,
+    scheduler: Arc<Scheduler>,
+    scope_id: ScopeId,
+}
+
+impl<P> Clone for ProgressSender<P> {
+    fn clone(&self) -> Self {
+        Self {
+            progress: self.progress.clone(),
+            scheduler: self.scheduler.clone(),
+            scope_id: self.scope_id,
+        }
+    }
+}
+
+impl<P> ProgressSender<P> {
+    /// Store the current progress of the mutation and re-render the component using it.
+    pub fn send(&self, progress: P) {
+        *self.progress.borrow_mut() = Some(progress);
+        (self.scheduler)(self.scope_id);
+    }
+}
+
+/// A mutation reporting its progress while it runs, see [use_mutation_with_progress].
+/// It can be used as a [UseMutation].
+#[derive(Clone)]
+pub struct UseMutationWithProgress<T, E, A, P> {
+    mutation: UseMutation<T, E, A>,
+    progress: Shared<Lock<Option<P>>>,
+}
+
+impl<T, E, A, P> Deref for UseMutationWithProgress<T, E, A, P> {
+    type Target = UseMutation<T, E, A>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.mutation
+    }
+}
+
+impl<T, E, A, P> UseMutationWithProgress<T, E, A, P>
+where
+    T: Clone,
+    E: Clone,
+    A: Clone,
+{
+    /// Get the last progress sent by the running mutation, `None` once it has settled.
+    pub fn progress(&self) -> Option<P>
+    where
+        P: Clone,
+    {
+        self.progress.borrow().clone()
+    }
+
+    /// Same as [UseMutation::reset], also forgetting the progress.
+    pub fn reset(&self) {
+        *self.progress.borrow_mut() = None;
+        self.mutation.reset();
+    }
+
+    /// Same as [UseMutation::cancel], also forgetting the progress.
+    pub fn cancel(&self) {
+        *self.progress.borrow_mut() = None;
+        self.mutation.cancel();
+    }
+}
+
+/// Create a mutation whose function can report its progress, e.g to show a progress bar while uploading a file.
+/// The component is re-rendered on every progress update.
+///
+/// ## Example:
+///
+/// ```no_run
+/// let upload = use_mutation_with_progress(cx, |file: File, progress: ProgressSender<f32>| async move {
+///     for (i, chunk) in file.chunks().enumerate() {
+///         send_chunk(chunk).await;
+///         progress.send(i as f32 / file.chunks_count() as f32);
+///     }
+///     MutationResult::Ok(())
+/// });
+///
+/// render!( if let Some(progress) = upload.progress() { progress { value: "{progress}" } } )
+/// ```
+pub fn use_mutation_with_progress<T, E, A, P, M, F>(
+    cx: &ScopeState,
+    mutation_fn: M,
+) -> &UseMutationWithProgress<T, E, A, P>
+where
+    T: 'static + Clone + PartialEq,
+    E: 'static + Clone + PartialEq,
+    A: 'static + Clone,
+    P: 'static + MaybeSend + MaybeSync,
+    M: Fn(A, ProgressSender<P>) -> F + MaybeSend + MaybeSync + 'static,
+    F: Future<Output = MutationResult<T, E>> + MaybeSend + 'static,
+{
+    let progress = cx.use_hook(|| Shared::new(Lock::new(None)));
+    let mutation = use_mutation_config(cx, || {
+        let sender = ProgressSender {
+            progress: progress.clone(),
+            scheduler: cx.schedule_update_any(),
+            scope_id: cx.scope_id(),
+        };
+        let settled_progress = progress.clone();
+        MutationConfig::new(move |arg| mutation_fn(arg, sender.clone()))
+            .on_settled(move |_, _| *settled_progress.borrow_mut() = None)
+    });
+    cx.use_hook(|| UseMutationWithProgress {
+        mutation: mutation.clone(),
+        progress: progress.clone(),
+    })
+}
@@ -0,0 +1,274 @@
+use dioxus::prelude::*;
+use futures_util::future::poll_fn;
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    hash::Hash,
+    task::{Poll, Waker},
+};
+
+use crate::{
+    sync::{Lock, Shared},
+    use_mutation::MutationResult,
+};
+
+/// The state of a keyed mutation, shared by every component using or observing it.
+pub(crate) struct SharedMutation<T, E> {
+    pub(crate) value: Shared<Lock<MutationResult<T, E>>>,
+    pub(crate) last_settled: Shared<Lock<LastSettled<T, E>>>,
+    /// Scopes re-rendered whenever the mutation changes
+    pub(crate) listeners: Shared<Lock<HashSet<ScopeId>>>,
+}
+
+impl<T, E> Clone for SharedMutation<T, E> {
+    fn clone(&self) -> Self {
+        Self {
+            value: self.value.clone(),
+            last_settled: self.last_settled.clone(),
+            listeners: self.listeners.clone(),
+        }
+    }
+}
+
+/// The last successful value and the last error of a mutation,
+/// kept apart from its result so neither is lost by the next call, see [UseMutation::data](crate::prelude::UseMutation::data).
+pub(crate) struct LastSettled<T, E> {
+    pub(crate) data: Option<T>,
+    pub(crate) error: Option<E>,
+}
+
+impl<T, E> Default for LastSettled<T, E> {
+    fn default() -> Self {
+        Self {
+            data: None,
+            error: None,
+        }
+    }
+}
+
+/// The keyed mutations of the app with the same types of keys, values and errors,
+/// see [use_mutation_with_key](crate::prelude::use_mutation_with_key).
+pub(crate) struct MutationRegistry<K, T, E> {
+    mutations: Shared<Lock<HashMap<K, SharedMutation<T, E>>>>,
+}
+
+impl<K, T, E> Clone for MutationRegistry<K, T, E> {
+    fn clone(&self) -> Self {
+        Self {
+            mutations: self.mutations.clone(),
+        }
+    }
+}
+
+impl<K, T, E> MutationRegistry<K, T, E>
+where
+    K: 'static + Eq + Hash + Clone,
+    T: 'static,
+    E: 'static,
+{
+    /// Get the app's registry for these types, creating it the first time.
+    pub(crate) fn get(cx: &ScopeState) -> Self {
+        cx.consume_context().unwrap_or_else(|| {
+            cx.provide_root_context(MutationRegistry {
+                mutations: Shared::new(Lock::new(HashMap::new())),
+            })
+        })
+    }
+
+    /// Register a listener of the mutation with the given key, creating it if there is none yet.
+    pub(crate) fn subscribe(&self, key: K, scope_id: ScopeId) -> MutationSubscription<K, T, E> {
+        let mutation = self
+            .mutations
+            .borrow_mut()
+            .entry(key.clone())
+            .or_insert_with(|| SharedMutation {
+                value: Shared::new(Lock::new(MutationResult::Pending)),
+                last_settled: Shared::default(),
+                listeners: Shared::default(),
+            })
+            .clone();
+        mutation.listeners.borrow_mut().insert(scope_id);
+        MutationSubscription {
+            registry: self.clone(),
+            mutation,
+            key,
+            scope_id,
+        }
+    }
+}
+
+/// Keeps a component listening to a keyed mutation until dropped.
+/// The mutation is forgotten once it has no listeners left.
+pub(crate) struct MutationSubscription<K: Eq + Hash, T, E> {
+    registry: MutationRegistry<K, T, E>,
+    pub(crate) mutation: SharedMutation<T, E>,
+    key: K,
+    scope_id: ScopeId,
+}
+
+impl<K: Eq + Hash, T, E> Drop for MutationSubscription<K, T, E> {
+    fn drop(&mut self) {
+        let is_unused = {
+            let mut listeners = self.mutation.listeners.borrow_mut();
+            listeners.remove(&self.scope_id);
+            listeners.is_empty()
+        };
+        if is_unused {
+            self.registry.mutations.borrow_mut().remove(&self.key);
+        }
+    }
+}
+
+/// Get the result of the mutation with the given key, see [use_mutation_with_key](crate::prelude::use_mutation_with_key).
+/// The component is re-rendered whenever any component using the mutation calls it.
+///
+/// ## Example:
+///
+/// ```no_run
+/// let submit = use_mutation_state::<MutationKeys, FormValue, FormError>(cx, MutationKeys::SubmitForm);
+///
+/// render!( if submit.is_loading() { "Submitting..." } )
+/// ```
+pub fn use_mutation_state<K, T, E>(cx: &ScopeState, key: K) -> MutationResult<T, E>
+where
+    K: 'static + Eq + Hash + Clone,
+    T: 'static + Clone,
+    E: 'static + Clone,
+{
+    let subscription =
+        cx.use_hook(|| MutationRegistry::<K, T, E>::get(cx).subscribe(key, cx.scope_id()));
+    let value = subscription.mutation.value.borrow().clone();
+    value
+}
+
+/// A deduplicated call of a mutation, see [MutationConfig::dedupe_in_flight](crate::prelude::MutationConfig::dedupe_in_flight).
+enum InFlightCall<T, E> {
+    /// The mutation function is running, with the calls waiting for its result
+    Running(Vec<Waker>),
+    Settled(MutationResult<T, E>),
+    /// The call was cancelled before its result was known
+    Abandoned,
+}
+
+type InFlightCallKey = (Cow<'static, str>, u64);
+
+/// The deduplicated calls running in the app with the same types of values and errors,
+/// by the key of their mutation and the hash of their variables.
+pub(crate) struct InFlightMutations<T, E> {
+    calls: Shared<Lock<HashMap<InFlightCallKey, Shared<Lock<InFlightCall<T, E>>>>>>,
+}
+
+impl<T, E> Clone for InFlightMutations<T, E> {
+    fn clone(&self) -> Self {
+        Self {
+            calls: self.calls.clone(),
+        }
+    }
+}
+
+/// Either the call that runs the mutation function, or a call waiting for its result.
+pub(crate) enum MutationCall<T, E> {
+    Lead(LeadingCall<T, E>),
+    Follow(FollowingCall<T, E>),
+}
+
+impl<T: 'static, E: 'static> InFlightMutations<T, E> {
+    /// Get the app's in-flight calls for these types, creating them the first time.
+    pub(crate) fn get(cx: &ScopeState) -> Self {
+        cx.consume_context().unwrap_or_else(|| {
+            cx.provide_root_context(InFlightMutations {
+                calls: Shared::default(),
+            })
+        })
+    }
+}
+
+impl<T, E> InFlightMutations<T, E> {
+    /// Join the call running with the same key and variables hash, or lead a new one if there is none.
+    pub(crate) fn join(&self, key: Cow<'static, str>, hash: u64) -> MutationCall<T, E> {
+        let key = (key, hash);
+        let mut calls = self.calls.borrow_mut();
+        if let Some(call) = calls.get(&key) {
+            return MutationCall::Follow(FollowingCall { call: call.clone() });
+        }
+        let call = Shared::new(Lock::new(InFlightCall::Running(Vec::new())));
+        calls.insert(key.clone(), call.clone());
+        MutationCall::Lead(LeadingCall {
+            calls: self.clone(),
+            key,
+            call,
+        })
+    }
+}
+
+/// Runs the mutation function for every call with the same key and variables hash,
+/// its followers are let go once it's settled or dropped.
+pub(crate) struct LeadingCall<T, E> {
+    calls: InFlightMutations<T, E>,
+    key: InFlightCallKey,
+    call: Shared<Lock<InFlightCall<T, E>>>,
+}
+
+impl<T, E> LeadingCall<T, E> {
+    /// Share the result of the mutation function with the followers.
+    pub(crate) fn settle(self, result: MutationResult<T, E>) {
+        let waiters =
+            match std::mem::replace(&mut *self.call.borrow_mut(), InFlightCall::Settled(result)) {
+                InFlightCall::Running(waiters) => waiters,
+                _ => Vec::new(),
+            };
+        for waiter in waiters {
+            waiter.wake();
+        }
+    }
+}
+
+impl<T, E> Drop for LeadingCall<T, E> {
+    fn drop(&mut self) {
+        {
+            let mut calls = self.calls.calls.borrow_mut();
+            if calls
+                .get(&self.key)
+                .is_some_and(|call| Shared::ptr_eq(call, &self.call))
+            {
+                calls.remove(&self.key);
+            }
+        }
+
+        // Cancelled before settling
+        let waiters = {
+            let mut call = self.call.borrow_mut();
+            match &mut *call {
+                InFlightCall::Running(waiters) => {
+                    let waiters = std::mem::take(waiters);
+                    *call = InFlightCall::Abandoned;
+                    waiters
+                }
+                _ => Vec::new(),
+            }
+        };
+        for waiter in waiters {
+            waiter.wake();
+        }
+    }
+}
+
+/// Waits for the result of the [LeadingCall] with the same key and variables hash.
+pub(crate) struct FollowingCall<T, E> {
+    call: Shared<Lock<InFlightCall<T, E>>>,
+}
+
+impl<T: Clone, E: Clone> FollowingCall<T, E> {
+    /// Wait for the result of the leading call, `None` if it was cancelled.
+    pub(crate) async fn wait(&self) -> Option<MutationResult<T, E>> {
+        poll_fn(|cx| match &mut *self.call.borrow_mut() {
+            InFlightCall::Running(waiters) => {
+                waiters.push(cx.waker().clone());
+                Poll::Pending
+            }
+            InFlightCall::Settled(result) => Poll::Ready(Some(result.clone())),
+            InFlightCall::Abandoned => Poll::Ready(None),
+        })
+        .await
+    }
+}
@@ -0,0 +1,150 @@
+use dioxus::prelude::*;
+use futures_util::Future;
+use std::{
+    collections::{HashMap, HashSet},
+    hash::Hash,
+    time::Duration,
+};
+
+use crate::{
+    result::QueryResult,
+    sync::{self, Lock, MaybeSend, MaybeSync, Shared},
+    use_query::QueryConfig,
+    use_query_client::{use_query_client, UseQueryClient},
+};
+
+/// How long the pointer has to rest on an element before prefetching, by default.
+const PREFETCH_DEBOUNCE: u64 = 100;
+
+/// The prefetches of a [Prefetcher], by their query keys.
+struct Prefetches<K> {
+    /// Bumped on every request, so only the last one of a burst prefetches
+    requests: HashMap<Vec<K>, usize>,
+    /// Keys being prefetched right now
+    in_flight: HashSet<Vec<K>>,
+}
+
+impl<K> Default for Prefetches<K> {
+    fn default() -> Self {
+        Self {
+            requests: HashMap::default(),
+            in_flight: HashSet::default(),
+        }
+    }
+}
+
+/// Prefetches queries in the **background**, e.g when hovering a link to the view that needs them,
+/// see [use_prefetcher]. It can be cheaply cloned.
+///
+/// Requests for the same keys within the debounce of each other are merged into one,
+/// and nothing is fetched if the cached result is fresh or already being prefetched.
+/// The prefetched result is picked up by the queries registered later with the same keys and function.
+#[derive(Clone)]
+pub struct Prefetcher<T, E, K> {
+    client: UseQueryClient<T, E, K>,
+    debounce: Duration,
+    prefetches: Shared<Lock<Prefetches<K>>>,
+}
+
+impl<T, E, K> Prefetcher<T, E, K>
+where
+    T: 'static + Clone,
+    E: 'static + Clone,
+    K: 'static + Eq + Hash + Clone,
+{
+    /// Prefetch the given query, in the **background**.
+    pub fn prefetch<Q, F>(&self, query_keys: Vec<K>, query_fn: Q)
+    where
+        Q: 'static + Fn(Vec<K>) -> F + MaybeSend + MaybeSync,
+        F: 'static + Future<Output = QueryResult<T, E>> + MaybeSend,
+    {
+        self.prefetch_config(QueryConfig::new(query_keys, query_fn));
+    }
+
+    /// Prefetch the query with the given configuration, in the **background**,
+    /// e.g with the same [QueryConfig::stale_time] as the query that will use it, so it isn't fetched again.
+    pub fn prefetch_config(&self, mut config: QueryConfig<T, E, K>) {
+        self.client
+            .normalize_keys(&mut config.registry_entry.query_keys);
+        let query_keys = config.registry_entry.query_keys.clone();
+
+        let is_fresh = self
+            .client
+            .get_entry(&config.registry_entry)
+            .map(|query_listeners| sync::read(&query_listeners.value).is_fresh())
+            .unwrap_or_default();
+        let request = {
+            let mut prefetches = self.prefetches.borrow_mut();
+            if is_fresh || prefetches.in_flight.contains(&query_keys) {
+                return;
+            }
+            let request = prefetches.requests.entry(query_keys.clone()).or_default();
+            *request += 1;
+            *request
+        };
+
+        let prefetcher = self.clone();
+        spawn(async move {
+            // Wait for the requests to settle, giving up if another one arrived in the meantime
+            Box::into_pin(prefetcher.client.config.clock.sleep(prefetcher.debounce)).await;
+            {
+                let mut prefetches = prefetcher.prefetches.borrow_mut();
+                if prefetches.requests.get(&query_keys) != Some(&request) {
+                    return;
+                }
+                prefetches.requests.remove(&query_keys);
+                prefetches.in_flight.insert(query_keys.clone());
+            }
+
+            prefetcher.client.prefetch_query_config(config).await;
+            prefetcher
+                .prefetches
+                .borrow_mut()
+                .in_flight
+                .remove(&query_keys);
+        });
+    }
+}
+
+/// Get a [Prefetcher] for the queries of the client,
+/// which waits for the pointer to rest for 100ms before prefetching.
+///
+/// ## Example:
+///
+/// ```no_run
+/// let prefetcher = use_prefetcher::<QueryValue, QueryError, QueryKeys>(cx);
+///
+/// render!(
+///     Link {
+///         to: "/users/{id}",
+///         onmouseenter: move |_| prefetcher.prefetch(vec![QueryKeys::User(id)], fetch_user),
+///         "See user"
+///     }
+/// )
+/// ```
+pub fn use_prefetcher<T, E, K>(cx: &ScopeState) -> &Prefetcher<T, E, K>
+where
+    T: 'static + Clone,
+    E: 'static + Clone,
+    K: 'static + Eq + Hash + Clone,
+{
+    use_prefetcher_with_debounce(cx, Duration::from_millis(PREFETCH_DEBOUNCE))
+}
+
+/// Same as [use_prefetcher] but waiting for the pointer to rest for `debounce` before prefetching.
+pub fn use_prefetcher_with_debounce<T, E, K>(
+    cx: &ScopeState,
+    debounce: Duration,
+) -> &Prefetcher<T, E, K>
+where
+    T: 'static + Clone,
+    E: 'static + Clone,
+    K: 'static + Eq + Hash + Clone,
+{
+    let client = use_query_client(cx);
+    cx.use_hook(|| Prefetcher {
+        client: client.clone(),
+        debounce,
+        prefetches: Shared::default(),
+    })
+}
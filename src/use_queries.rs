@@ -0,0 +1,132 @@
+use dioxus::prelude::*;
+use std::{collections::HashMap, hash::Hash, rc::Rc};
+
+use crate::{
+    cached_result::QueryState,
+    combine_queries::CombinedQueries,
+    result::QueryResult,
+    use_query::{QueryConfig, UseQuery},
+    use_query_client::{use_query_client, RegistryEntry, UseQueryClient},
+};
+
+/// A dynamic list of query listeners.
+pub struct UseQueries<T, E, K: Eq + Hash> {
+    client: UseQueryClient<T, E, K>,
+    queries: RefCell<Vec<Rc<UseQuery<T, E, K>>>>,
+}
+
+impl<T, E, K: Eq + Hash> UseQueries<T, E, K> {
+    /// Get the number of queries.
+    pub fn len(&self) -> usize {
+        self.queries.borrow().len()
+    }
+
+    /// Check if there are no queries.
+    pub fn is_empty(&self) -> bool {
+        self.queries.borrow().is_empty()
+    }
+
+    /// Get the current results of the queries, in the order of their configurations.
+    pub fn results(&self) -> Vec<QueryResult<T, E>>
+    where
+        T: Clone,
+        E: Clone,
+    {
+        self.queries
+            .borrow()
+            .iter()
+            .map(|query| query.result().value().clone())
+            .collect()
+    }
+
+    /// Get snapshots of the queries, in the order of their configurations.
+    pub fn states(&self) -> Vec<QueryState<T, E>>
+    where
+        T: Clone,
+        E: Clone,
+    {
+        self.queries
+            .borrow()
+            .iter()
+            .map(|query| query.state())
+            .collect()
+    }
+
+    /// Combine the current results of the queries, in the order of their configurations,
+    /// e.g to show a single spinner until all of them are loaded. See [combine_queries](crate::prelude::combine_queries).
+    pub fn combined(&self) -> CombinedQueries<Vec<T>, E>
+    where
+        T: Clone,
+        E: Clone,
+    {
+        CombinedQueries::from_results(self.results())
+    }
+}
+
+impl<T, E, K> UseQueries<T, E, K>
+where
+    T: 'static + Clone,
+    E: 'static + Clone,
+    K: 'static + Eq + Hash + Clone,
+{
+    /// Sync the listeners with the given configurations,
+    /// keeping the ones that are still used and dropping the rest.
+    fn update(&self, cx: &ScopeState, configs: Vec<QueryConfig<T, E, K>>) {
+        let mut queries = self.queries.borrow_mut();
+        let mut previous_queries = queries
+            .drain(..)
+            .map(|query| (query.registry_entry.clone(), query))
+            .collect::<HashMap<RegistryEntry<K>, _>>();
+        let mut current_queries = HashMap::<RegistryEntry<K>, Rc<UseQuery<T, E, K>>>::new();
+
+        for mut config in configs {
+            self.client
+                .normalize_keys(&mut config.registry_entry.query_keys);
+
+            // Share the listener between configurations of the same query
+            let query = current_queries
+                .entry(config.registry_entry.clone())
+                .or_insert_with(|| {
+                    previous_queries
+                        .remove(&config.registry_entry)
+                        .unwrap_or_else(|| Rc::new(UseQuery::new(cx, &self.client, config)))
+                })
+                .clone();
+            queries.push(query);
+        }
+
+        // The listeners left in `previous_queries` are unregistered once dropped
+    }
+}
+
+/// Register a query listener for each of the given configurations, in parallel.
+///
+/// The configurations are read on every render, listeners of queries that are no longer
+/// in the list are unregistered and new ones are registered.
+///
+/// ## Example:
+///
+/// ```no_run
+/// let users = use_queries(cx, || {
+///     ids.iter()
+///         .map(|id| QueryConfig::new(vec![QueryKeys::User(*id)], fetch_user))
+///         .collect()
+/// });
+/// ```
+pub fn use_queries<T, E, K>(
+    cx: &ScopeState,
+    configs: impl FnOnce() -> Vec<QueryConfig<T, E, K>>,
+) -> &UseQueries<T, E, K>
+where
+    T: 'static + Clone,
+    E: 'static + Clone,
+    K: 'static + Eq + Hash + Clone,
+{
+    let client = use_query_client(cx);
+    let queries = cx.use_hook(|| UseQueries {
+        client: client.clone(),
+        queries: RefCell::default(),
+    });
+    queries.update(cx, configs());
+    queries
+}
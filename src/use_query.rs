@@ -1,17 +1,27 @@
 use dioxus::prelude::*;
-use futures_util::Future;
+use futures_util::{Future, Stream};
 use std::{
-    any::TypeId,
-    collections::HashSet,
+    borrow::Cow,
+    cell::Cell,
+    collections::HashMap,
+    fmt::Debug,
     hash::Hash,
     sync::{Arc, RwLock, RwLockReadGuard},
+    time::Duration,
 };
 
 use crate::{
-    cached_result::CachedResult,
+    abort_signal::QueryAbortSignal,
+    cached_result::{CachedResult, DataSource, QueryState},
+    clock::Instant,
+    query_ref::QueryRef,
     result::QueryResult,
+    retry_backoff::ExponentialBackoff,
+    sync::{self, Lock, MaybeSend, MaybeSync, Shared},
     use_query_client::{
-        use_query_client, QueryFn, QueryListeners, QueryValue, RegistryEntry, UseQueryClient,
+        use_query_client, EnabledFn, EntryCallbacks, ListenerFilterFn, PrefetchNextFn, QueryEqFn,
+        QueryFn, QueryFnId, QueryValue, RefetchIntervalFn, RegistryEntry, RetryIfFn, StreamFn,
+        UseQueryClient,
     },
 };
 
@@ -19,68 +29,1158 @@ use crate::{
 pub struct UseQuery<T, E, K: Eq + Hash> {
     client: UseQueryClient<T, E, K>,
     value: QueryValue<CachedResult<T, E>>,
-    registry_entry: RegistryEntry<K>,
+    pub(crate) registry_entry: RegistryEntry<K>,
     scope_id: ScopeId,
+    placeholder: Option<T>,
+    has_dismissed_placeholder: Cell<bool>,
+    enabled: Option<Arc<Box<EnabledFn>>>,
+    throw_error: Option<ThrowErrorFn<E>>,
+    throw_on_refetch_error: bool,
+    is_paused: Cell<bool>,
+    refetch_on_mount: RefetchOnMount,
 }
 
+/// Throws an error to the nearest error boundary, see [QueryConfig::throw_on_error].
+pub(crate) type ThrowErrorFn<E> = fn(&ScopeState, &E);
+
 impl<T, E, K: Eq + Hash> UseQuery<T, E, K> {
     /// Get the current result from the query.
+    ///
+    /// The guard can be kept to borrow the value without cloning it, e.g with [CachedResult::data] and [CachedResult::error].
+    /// Don't hold it across an `.await`, as the query can't be updated while it's alive.
+    ///
+    /// ## Example:
+    ///
+    /// ```no_run
+    /// let result = user.result();
+    /// let name = result.data().map(|user| user.name.as_str()).unwrap_or("Loading...");
+    ///
+    /// render!( p { "{name}" } )
+    /// ```
     pub fn result(&self) -> RwLockReadGuard<CachedResult<T, E>> {
-        self.value.read().expect("Query value is already borrowed")
+        self.client.read_entry(&self.registry_entry, &self.value)
+    }
+
+    /// Get a copy of the current value of the query, if there is any.
+    /// Like converting the result into an [Option], the cached value is returned while loading.
+    pub fn cloned(&self) -> Option<T>
+    where
+        T: Clone,
+    {
+        self.result().value().data().cloned()
+    }
+
+    /// Get a copy of the current error of the query, if it errored.
+    pub fn error_cloned(&self) -> Option<E>
+    where
+        E: Clone,
+    {
+        self.result().value().err().cloned()
+    }
+
+    /// Check if the placeholder data is being shown, see [QueryConfig::placeholder_data].
+    /// Once the query gets a real result the placeholder never shows again.
+    pub fn is_placeholder(&self) -> bool {
+        if self.placeholder.is_none() || self.has_dismissed_placeholder.get() {
+            return false;
+        }
+        let result = self.result();
+        let is_placeholder =
+            !result.has_been_mutated() && matches!(result.value(), QueryResult::Loading(None));
+        if !is_placeholder {
+            self.has_dismissed_placeholder.set(true);
+        }
+        is_placeholder
+    }
+
+    /// Get where the value shown by [UseQuery::result_or_placeholder] comes from, `None` if there is none,
+    /// e.g to show an updating indicator over seeded data but not over fetched data.
+    ///
+    /// ## Example:
+    ///
+    /// ```no_run
+    /// let is_seeded = matches!(
+    ///     user.data_source(),
+    ///     Some(DataSource::Initial | DataSource::Placeholder)
+    /// );
+    /// ```
+    pub fn data_source(&self) -> Option<DataSource> {
+        if self.is_placeholder() {
+            return Some(DataSource::Placeholder);
+        }
+        self.result().data_source()
+    }
+
+    /// Get the current result from the query, or `Loading` with the placeholder data
+    /// if there is no result yet. See [QueryConfig::placeholder_data].
+    pub fn result_or_placeholder(&self) -> QueryResult<T, E>
+    where
+        T: Clone,
+        E: Clone,
+    {
+        match &self.placeholder {
+            Some(placeholder) if self.is_placeholder() => {
+                QueryResult::Loading(Some(placeholder.clone()))
+            }
+            _ => self.result().value().clone(),
+        }
+    }
+
+    /// Suspend the component while the query has no value yet, see [ScopeState::suspend].
+    /// Cached values are returned right away, even while they are being refetched in the background.
+    ///
+    /// ## Example:
+    ///
+    /// ```no_run
+    /// let user = use_query(cx, || vec![QueryKeys::User(id)], fetch_user);
+    /// let user = user.suspend(cx)?;
+    /// ```
+    pub fn suspend(&self, cx: &ScopeState) -> Option<Result<T, E>>
+    where
+        T: Clone,
+        E: Clone,
+    {
+        let result = self.result();
+        match (result.value(), result.data()) {
+            (QueryResult::Err(err), _) => Some(Err(err.clone())),
+            (_, Some(data)) => Some(Ok(data.clone())),
+            _ => {
+                cx.suspend();
+                None
+            }
+        }
+    }
+
+    /// Get a snapshot of the query with both its last successful value and its last error.
+    pub fn state(&self) -> QueryState<T, E>
+    where
+        T: Clone,
+        E: Clone,
+    {
+        self.result().state()
     }
 }
 
-impl<T, E, K: Eq + Hash> Drop for UseQuery<T, E, K> {
-    fn drop(&mut self) {
-        let was_last_listener = {
-            let mut queries_registry = self.client.queries_registry.borrow_mut();
-            let query_listeners = queries_registry.get_mut(&self.registry_entry).unwrap();
-            // Remove this listener
-            query_listeners.listeners.remove(&self.scope_id);
-            query_listeners.listeners.is_empty()
-        };
+impl<T, E, K> UseQuery<T, E, K>
+where
+    T: 'static + Clone,
+    E: 'static + Clone,
+    K: 'static + Eq + Hash + Clone,
+{
+    /// Fetch the entry of the query with the function of the given configuration from now on,
+    /// e.g the closure of the current render, so it doesn't keep the values captured by the first one.
+    pub(crate) fn refresh_query_fn(&self, config: QueryConfig<T, E, K>) {
+        self.client
+            .set_query_fn(&self.registry_entry, config.query_fn);
+    }
+
+    /// Get an owned handle to the cached result of the query, which can be moved into event handlers
+    /// and spawned tasks, or passed as a prop. The component stays the listener of the query, see [QueryRef].
+    pub fn handle(&self) -> QueryRef<T, E, K> {
+        QueryRef::new(
+            self.client.clone(),
+            self.value.clone(),
+            self.registry_entry.clone(),
+        )
+    }
+
+    /// Fetch the query in the **background**, even if its cached result is fresh,
+    /// e.g to start a lazy query, see [QueryConfig::fetch_on_mount].
+    pub fn trigger(&self) {
+        let client = self.client.clone();
+        let registry_entry = self.registry_entry.clone();
+        spawn(async move {
+            client.trigger_query(&registry_entry, false).await;
+        });
+    }
+
+    /// Same as [UseQuery::trigger] but even while the client is paused,
+    /// e.g for a refresh asked by the user, see [UseQueryClient::pause_all](crate::prelude::UseQueryClient::pause_all).
+    pub fn force_trigger(&self) {
+        let client = self.client.clone();
+        let registry_entry = self.registry_entry.clone();
+        spawn(async move {
+            client.trigger_query(&registry_entry, true).await;
+        });
+    }
+
+    /// Wait for the query to settle, getting its value or its error.
+    /// It's ready right away if the query has a result and isn't being fetched,
+    /// otherwise it waits for the fetch in flight, or the next one, to store its result.
+    ///
+    /// The future doesn't borrow the query so it can be spawned, and dropping it stops the waiting
+    /// without keeping the query in the cache.
+    ///
+    /// ## Example:
+    ///
+    /// ```no_run
+    /// let user = use_query(cx, || vec![QueryKeys::User(id)], fetch_user);
+    /// cx.spawn({
+    ///     let user = user.wait();
+    ///     async move {
+    ///         if let Ok(user) = user.await {
+    ///             track_visit(user.id);
+    ///         }
+    ///     }
+    /// });
+    /// ```
+    pub fn wait(&self) -> impl Future<Output = Result<T, E>> {
+        let value = self.value.clone();
+        let settled = self
+            .client
+            .get_entry(&self.registry_entry)
+            .map(|query_listeners| query_listeners.settled)
+            .unwrap_or_default();
+        async move {
+            loop {
+                let settles = settled.settles();
+                {
+                    let result = sync::read(&value);
+                    if !result.is_fetching() {
+                        match result.value() {
+                            QueryResult::Ok(data) => return Ok(data.clone()),
+                            QueryResult::Err(err) => return Err(err.clone()),
+                            QueryResult::Loading(_) => {}
+                        }
+                    }
+                }
+                settled.settled_since(settles).await;
+            }
+        }
+    }
 
-        // Clear the queries registry of this listener if it was the last one
-        if was_last_listener {
+    /// Stop re-rendering the component when the query changes, without unmounting it,
+    /// e.g while the panel showing it is collapsed. The cached result can still be read.
+    ///
+    /// While every listener of the query is paused it isn't refetched, neither when invalidated nor on an interval,
+    /// but it's kept in the cache until the component unmounts.
+    pub fn pause(&self) {
+        if !self.is_paused.replace(true) {
             self.client
-                .queries_registry
-                .borrow_mut()
-                .remove(&self.registry_entry);
+                .pause_listener(&self.registry_entry, self.scope_id);
+        }
+    }
+
+    /// Re-render the component when the query changes again, after [UseQuery::pause].
+    /// The query is fetched in the **background** if it went stale in the meantime,
+    /// according to [QueryConfig::refetch_on_mount].
+    pub fn resume(&self) {
+        if !self.is_paused.replace(false) {
+            return;
+        }
+        self.client
+            .resume_listener(&self.registry_entry, self.scope_id);
+
+        let client = self.client.clone();
+        let registry_entry = self.registry_entry.clone();
+        let refetch_on_mount = self.refetch_on_mount;
+        spawn(async move {
+            client
+                .run_mount_query(&registry_entry, refetch_on_mount)
+                .await;
+        });
+        (self.client.scheduler)(self.scope_id);
+    }
+
+    /// Pause or resume the component, see [UseQuery::pause] and [UseQuery::resume].
+    pub fn set_active(&self, is_active: bool) {
+        if is_active {
+            self.resume();
+        } else {
+            self.pause();
+        }
+    }
+
+    /// Check if the component is paused, see [UseQuery::pause].
+    pub fn is_paused(&self) -> bool {
+        self.is_paused.get()
+    }
+
+    /// Check if the query is waiting for the app to be back online to be fetched, see [CachedResult::is_offline_paused].
+    pub fn is_offline_paused(&self) -> bool {
+        self.result().is_offline_paused()
+    }
+
+    /// Register the scope as a listener of the query with the given configuration.
+    pub(crate) fn new(
+        cx: &ScopeState,
+        client: &UseQueryClient<T, E, K>,
+        mut config: QueryConfig<T, E, K>,
+    ) -> Self {
+        client.normalize_keys(&mut config.registry_entry.query_keys);
+        let value = client.register_listener(&config, Some(cx.scope_id()));
+        let registry_entry = config.registry_entry.clone();
+
+        // Asynchronously initialize the query value
+        let refetch_on_mount = config.refetch_on_mount;
+        cx.spawn({
+            to_owned![client, registry_entry];
+            async move {
+                client
+                    .run_mount_query(&registry_entry, refetch_on_mount)
+                    .await;
+            }
+        });
+
+        // Poll the query, the task is dropped along with the component
+        if let Some(refetch_interval) = config.refetch_interval.clone() {
+            let in_background = config.refetch_interval_in_background;
+            cx.spawn({
+                to_owned![client, registry_entry];
+                async move {
+                    client
+                        .run_refetch_interval(&registry_entry, &**refetch_interval, in_background)
+                        .await;
+                }
+            });
+        }
+
+        UseQuery {
+            client: client.clone(),
+            value,
+            registry_entry,
+            scope_id: cx.scope_id(),
+            placeholder: config
+                .placeholder_data
+                .map(|placeholder_data| placeholder_data()),
+            has_dismissed_placeholder: Cell::new(false),
+            enabled: config.enabled,
+            throw_error: config.throw_error,
+            throw_on_refetch_error: config.throw_on_refetch_error,
+            is_paused: Cell::new(false),
+            refetch_on_mount,
         }
     }
+
+    /// Throw the error of the query to the nearest error boundary once it settles to one,
+    /// see [QueryConfig::throw_on_error].
+    pub(crate) fn throw_if_errored(&self, cx: &ScopeState) {
+        let Some(throw_error) = self.throw_error else {
+            return;
+        };
+        let result = self.result();
+        if let QueryResult::Err(err) = result.value() {
+            // Refetches that failed while there was data to show
+            if self.throw_on_refetch_error || result.data().is_none() {
+                throw_error(cx, err);
+            }
+        }
+    }
+
+    /// Fetch the query once it gets enabled, e.g when the component re-renders
+    /// because the data it depends on arrived. See [QueryConfig::enabled_fn].
+    pub(crate) fn fetch_once_enabled(&self, cx: &ScopeState) {
+        let Some(enabled) = &self.enabled else {
+            return;
+        };
+        if self.is_paused.get() {
+            return;
+        }
+        if enabled() && sync::read(&self.value).is_idle() {
+            cx.spawn({
+                let client = self.client.clone();
+                let registry_entry = self.registry_entry.clone();
+                async move {
+                    client.run_new_query(&registry_entry).await;
+                }
+            });
+        }
+    }
+}
+
+impl<T, E, K: Eq + Hash> Drop for UseQuery<T, E, K> {
+    fn drop(&mut self) {
+        self.client
+            .unregister_listener(&self.registry_entry, Some(self.scope_id));
+    }
 }
 
 /// The configuration for a given query listener.
 pub struct QueryConfig<T, E, K> {
-    query_fn: Arc<Box<QueryFn<T, E, K>>>,
-    initial_value: Option<QueryResult<T, E>>,
-    registry_entry: RegistryEntry<K>,
+    pub(crate) query_fn: Arc<Box<QueryFn<T, E, K>>>,
+    pub(crate) stream_fn: Option<Arc<Box<StreamFn<T, E, K>>>>,
+    pub(crate) initial_value: Option<QueryResult<T, E>>,
+    pub(crate) initial_updated_at: Option<Instant>,
+    pub(crate) placeholder_data: Option<Box<dyn FnOnce() -> T>>,
+    pub(crate) registry_entry: RegistryEntry<K>,
+    pub(crate) is_equal: Option<Arc<Box<QueryEqFn<T, E>>>>,
+    pub(crate) loading_on_refetch: bool,
+    pub(crate) stale_time: Option<Duration>,
+    pub(crate) retry: Option<u32>,
+    pub(crate) retry_if: Option<Arc<Box<RetryIfFn<E>>>>,
+    pub(crate) retry_delay: Option<ExponentialBackoff>,
+    pub(crate) meta: Arc<QueryMeta>,
+    pub(crate) tags: Arc<Vec<K>>,
+    pub(crate) refetch_interval: Option<Arc<Box<RefetchIntervalFn<T, E>>>>,
+    pub(crate) refetch_interval_in_background: bool,
+    pub(crate) debounce: Option<Duration>,
+    pub(crate) debounce_invalidations: bool,
+    pub(crate) min_fetch_interval: Option<Duration>,
+    pub(crate) keep_data_on_error: bool,
+    pub(crate) max_age: Option<Duration>,
+    pub(crate) refetch_on_mount: RefetchOnMount,
+    pub(crate) fetch_on_mount: bool,
+    pub(crate) network_mode: NetworkMode,
+    pub(crate) enabled: Option<Arc<Box<EnabledFn>>>,
+    pub(crate) subscription: Option<SubscriptionEnd<E>>,
+    pub(crate) throw_error: Option<ThrowErrorFn<E>>,
+    pub(crate) throw_on_refetch_error: bool,
+    pub(crate) notify_on: NotifyOn,
+    pub(crate) entry_callbacks: EntryCallbacks<T, E>,
+    pub(crate) listener_filter: Option<Arc<Box<ListenerFilterFn<T, E>>>>,
+    pub(crate) prefetch_next: Option<Arc<Box<PrefetchNextFn<T, K>>>>,
+}
+
+/// Inert data about a query, e.g a human-readable name for the logs, see [QueryConfig::meta].
+pub type QueryMeta = HashMap<String, String>;
+
+/// What a subscription does when its stream ends, see [QueryConfig::subscription].
+#[derive(Clone, Debug, PartialEq)]
+pub enum SubscriptionEnd<E> {
+    /// Subscribe again after the given delay, e.g to reconnect a websocket
+    Resubscribe(Duration),
+    /// Cache the given error, staying unsubscribed until the entry is created again
+    Fail(E),
+}
+
+/// Which changes of a query re-render a component using it, see [QueryConfig::notify_on].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NotifyOn {
+    /// Every change, including the query starting or stopping to fetch
+    #[default]
+    All,
+    /// Only when a fetch settles, with either a value or an error
+    Settled,
+    /// Only when the value of the query changes
+    DataChanged,
+}
+
+/// A change of a query that its listeners may be notified of.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum QueryChange {
+    /// The query started or stopped loading, or was reset
+    Loading,
+    /// A fetch settled with an error
+    Error,
+    /// The value of the query changed
+    Data,
+}
+
+impl QueryChange {
+    /// The change of a query that now has the given result.
+    pub(crate) fn of<T, E>(result: &QueryResult<T, E>) -> Self {
+        match result {
+            QueryResult::Ok(_) => Self::Data,
+            QueryResult::Err(_) => Self::Error,
+            QueryResult::Loading(_) => Self::Loading,
+        }
+    }
+}
+
+impl NotifyOn {
+    /// Whether a listener is notified of the given change.
+    pub(crate) fn accepts(self, change: QueryChange) -> bool {
+        match self {
+            Self::All => true,
+            Self::Settled => change != QueryChange::Loading,
+            Self::DataChanged => change == QueryChange::Data,
+        }
+    }
+}
+
+/// How a query behaves while the app is offline, according to the
+/// [OnlineManager](crate::prelude::OnlineManager) of the client, see [QueryConfig::network_mode].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NetworkMode {
+    /// Fetch it whenever it's triggered, even if the fetch is likely to fail
+    #[default]
+    Online,
+    /// Don't fetch it while offline, serve whatever is cached instead, even if stale,
+    /// and fetch it once the app is back online. See [CachedResult::is_offline_paused]
+    OfflineFirst,
+}
+
+/// Whether a query that already has a cached result is fetched when a component using it mounts,
+/// see [QueryConfig::refetch_on_mount].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RefetchOnMount {
+    /// Fetch it on every mount, even if the result is fresh
+    Always,
+    /// Fetch it only if the result is stale, see [QueryConfig::stale_time]
+    #[default]
+    IfStale,
+    /// Never fetch it again on mount, only when invalidated
+    Never,
 }
 
 impl<T, E, K> QueryConfig<T, E, K> {
+    /// The query function gets its own copy of the query keys, so they can be moved into the returned future.
     pub fn new<Q, F>(query_keys: Vec<K>, query_fn: Q) -> Self
     where
-        Q: 'static + Fn(Vec<K>) -> F,
-        F: 'static + Future<Output = QueryResult<T, E>>,
+        Q: 'static + Fn(Vec<K>) -> F + MaybeSend + MaybeSync,
+        F: 'static + Future<Output = QueryResult<T, E>> + MaybeSend,
+    {
+        Self::new_with_abort_signal(query_keys, move |q, _| query_fn(q))
+    }
+
+    /// Same as [QueryConfig::new] but the query function also gets a [QueryAbortSignal],
+    /// so it can stop the work it started once its fetch is abandoned.
+    ///
+    /// ## Example:
+    ///
+    /// ```no_run
+    /// QueryConfig::new_with_abort_signal(vec![QueryKeys::Report(id)], |keys, signal| async move {
+    ///     let job = api::start_report(keys).await;
+    ///     select! {
+    ///         report = job.wait().fuse() => report.into(),
+    ///         _ = signal.aborted().fuse() => {
+    ///             job.cancel().await;
+    ///             QueryResult::Loading(None)
+    ///         }
+    ///     }
+    /// })
+    /// ```
+    pub fn new_with_abort_signal<Q, F>(query_keys: Vec<K>, query_fn: Q) -> Self
+    where
+        Q: 'static + Fn(Vec<K>, QueryAbortSignal) -> F + MaybeSend + MaybeSync,
+        F: 'static + Future<Output = QueryResult<T, E>> + MaybeSend,
     {
         Self {
-            query_fn: Arc::new(Box::new(move |q| {
-                let fut = query_fn(q);
+            query_fn: Arc::new(Box::new(move |q, signal, _| {
+                let fut = query_fn(q, signal);
                 Box::new(fut)
             })),
+            stream_fn: None,
             initial_value: None,
+            initial_updated_at: None,
+            placeholder_data: None,
             registry_entry: RegistryEntry {
                 query_keys,
-                query_fn_id: TypeId::of::<F>(),
+                query_fn_id: QueryFnId::of::<F>(),
             },
+            is_equal: None,
+            loading_on_refetch: false,
+            stale_time: None,
+            retry: None,
+            retry_if: None,
+            retry_delay: None,
+            meta: Arc::default(),
+            tags: Arc::default(),
+            refetch_interval: None,
+            refetch_interval_in_background: false,
+            debounce: None,
+            debounce_invalidations: true,
+            min_fetch_interval: None,
+            keep_data_on_error: false,
+            max_age: None,
+            refetch_on_mount: RefetchOnMount::default(),
+            fetch_on_mount: true,
+            network_mode: NetworkMode::default(),
+            enabled: None,
+            subscription: None,
+            throw_error: None,
+            throw_on_refetch_error: true,
+            notify_on: NotifyOn::default(),
+            entry_callbacks: EntryCallbacks::default(),
+            listener_filter: None,
+            prefetch_next: None,
         }
     }
 
+    /// Same as [QueryConfig::new] but the query function also gets a copy of the given context,
+    /// e.g the locale or an API client, which is not part of the identity of the query unlike its keys.
+    /// See [use_query_with_context] to keep it up to date with the props of a component.
+    ///
+    /// ## Example:
+    ///
+    /// ```no_run
+    /// QueryConfig::new_with_context(vec![QueryKeys::Article(id)], locale, |keys, locale| async move {
+    ///     api::fetch_article(keys, &locale).await.into()
+    /// })
+    /// ```
+    pub fn new_with_context<C, Q, F>(query_keys: Vec<K>, context: C, query_fn: Q) -> Self
+    where
+        C: 'static + Clone + MaybeSend + MaybeSync,
+        Q: 'static + Fn(Vec<K>, C) -> F + MaybeSend + MaybeSync,
+        F: 'static + Future<Output = QueryResult<T, E>> + MaybeSend,
+    {
+        Self::new(query_keys, move |q| query_fn(q, context.clone()))
+    }
+
+    /// Same as [QueryConfig::new] but the query function also gets a copy of the data currently cached, if any,
+    /// e.g to make a conditional request with the `ETag` of the response it was fetched from.
+    ///
+    /// When the data didn't change, e.g the server answered `304 Not Modified`, return the previous value:
+    /// the results are compared as with [QueryConfig::compare_results], so it's kept and considered fresh again
+    /// without notifying the listeners.
+    ///
+    /// ## Example:
+    ///
+    /// ```no_run
+    /// QueryConfig::new_with_previous(vec![QueryKeys::Feed], |keys, previous: Option<Feed>| async move {
+    ///     let etag = previous.as_ref().map(|feed| feed.etag.clone());
+    ///     match api::fetch_feed(keys, etag).await {
+    ///         Ok(Response::NotModified) => previous.map_or(QueryResult::Loading(None), QueryResult::Ok),
+    ///         Ok(Response::Modified(feed)) => QueryResult::Ok(feed),
+    ///         Err(err) => QueryResult::Err(err),
+    ///     }
+    /// })
+    /// ```
+    pub fn new_with_previous<Q, F>(query_keys: Vec<K>, query_fn: Q) -> Self
+    where
+        T: 'static + Clone + PartialEq,
+        E: 'static + PartialEq,
+        Q: 'static + Fn(Vec<K>, Option<T>) -> F + MaybeSend + MaybeSync,
+        F: 'static + Future<Output = QueryResult<T, E>> + MaybeSend,
+    {
+        let mut config = Self::new(query_keys, |_| async { QueryResult::Loading(None) });
+        config.query_fn = Arc::new(Box::new(
+            move |q, _, cached: &RwLock<CachedResult<T, E>>| {
+                let previous = sync::read(cached).data().cloned();
+                let fut = query_fn(q, previous);
+                Box::new(fut)
+            },
+        ));
+        config.registry_entry.query_fn_id = QueryFnId::of::<F>();
+        config.compare_results(true)
+    }
+
+    /// Same as [QueryConfig::new] but with a query function returning a [Result].
+    pub fn from_result_fn<Q, F>(query_keys: Vec<K>, query_fn: Q) -> Self
+    where
+        T: 'static,
+        E: 'static,
+        K: 'static,
+        Q: 'static + Fn(Vec<K>) -> F + MaybeSend + MaybeSync,
+        F: 'static + Future<Output = Result<T, E>> + MaybeSend,
+    {
+        Self::new(query_keys, move |q| {
+            let fut = query_fn(q);
+            async move { QueryResult::from(fut.await) }
+        })
+    }
+
+    /// Create the configuration of a query whose function returns a [Stream] instead of a single result,
+    /// e.g for server-sent events, so every value it yields is cached and its listeners notified as it arrives.
+    ///
+    /// There is one subscription to the stream per cached entry, torn down once the entry is removed,
+    /// e.g when its last listener unmounts, and replaced with a new one when it's invalidated.
+    /// If the stream ends on its own, it's subscribed to again once the value goes stale.
+    pub fn from_stream_fn<Q, S>(query_keys: Vec<K>, stream_fn: Q) -> Self
+    where
+        T: 'static,
+        E: 'static,
+        K: 'static,
+        Q: 'static + Fn(Vec<K>) -> S + MaybeSend + MaybeSync,
+        S: 'static + Stream<Item = QueryResult<T, E>> + MaybeSend,
+    {
+        let mut config = Self::new(query_keys, |_| async { QueryResult::Loading(None) });
+        config.stream_fn = Some(Arc::new(Box::new(move |q| Box::pin(stream_fn(q)))));
+        config.registry_entry.query_fn_id = QueryFnId::of::<S>();
+        config
+    }
+
     /// Set the initial value of the query.
+    /// It's stale, so it's fetched right away, unless given when it was updated with [QueryConfig::initial_updated_at].
     pub fn initial(mut self, initial_value: QueryResult<T, E>) -> Self {
         self.initial_value = Some(initial_value);
         self
     }
+
+    /// Set when the [QueryConfig::initial] value was updated, e.g when it was loaded from disk,
+    /// so it's only fetched once it goes stale, like any fetched value.
+    /// Time is measured with the [Clock](crate::prelude::Clock) of the [UseQueryClient].
+    pub fn initial_updated_at(mut self, initial_updated_at: Instant) -> Self {
+        self.initial_updated_at = Some(initial_updated_at);
+        self
+    }
+
+    /// Set the data to show while the query has no result yet, e.g skeleton rows.
+    /// Unlike [QueryConfig::initial], it is never stored in the cache,
+    /// so it doesn't count as a cached result and is only surfaced by [UseQuery::result_or_placeholder].
+    pub fn placeholder_data(mut self, placeholder_data: impl FnOnce() -> T + 'static) -> Self {
+        self.placeholder_data = Some(Box::new(placeholder_data));
+        self
+    }
+
+    /// Change the cached result to `Loading` while refetching, keeping the previous value if there was any.
+    /// By default the cached result is kept as it is and only [CachedResult::is_fetching] changes.
+    pub fn loading_on_refetch(mut self, loading_on_refetch: bool) -> Self {
+        self.loading_on_refetch = loading_on_refetch;
+        self
+    }
+
+    /// Identify the query function with the given id instead of its type, so queries with the same keys and id
+    /// share their cache entry even if their functions are different closures, e.g written in different components.
+    /// Queries sharing an id are expected to fetch the same data.
+    pub fn with_id(mut self, id: impl Into<Cow<'static, str>>) -> Self {
+        self.registry_entry.query_fn_id = QueryFnId::Explicit(id.into());
+        self
+    }
+
+    /// Set how long the result is considered fresh, and therefore not refetched, after being updated.
+    /// Overrides the default of the [UseQueryClient].
+    pub fn stale_time(mut self, stale_time: Duration) -> Self {
+        self.stale_time = Some(stale_time);
+        self
+    }
+
+    /// Set how many times the query function is called again when it returns an error.
+    /// Overrides the default of the [UseQueryClient].
+    pub fn retry(mut self, retry: u32) -> Self {
+        self.retry = Some(retry);
+        self
+    }
+
+    /// Set how long to wait before calling the query function again when it returns an error,
+    /// e.g with a [Jitter](crate::prelude::Jitter) so clients failing together don't retry together.
+    /// Overrides the default of the [UseQueryClient], which retries right away.
+    ///
+    /// ## Example:
+    ///
+    /// ```no_run
+    /// QueryConfig::new(vec![QueryKeys::User(id)], fetch_user)
+    ///     .retry(5)
+    ///     .retry_delay(
+    ///         ExponentialBackoff::new(Duration::from_millis(200), Duration::from_secs(30))
+    ///             .with_jitter(Jitter::Full),
+    ///     )
+    /// ```
+    pub fn retry_delay(mut self, retry_delay: ExponentialBackoff) -> Self {
+        self.retry_delay = Some(retry_delay);
+        self
+    }
+
+    /// Only retry the errors for which `retry_if` returns `true`, given the error, how many attempts failed so far
+    /// and the [QueryConfig::meta] of the query, e.g to retry timeouts but not authorization errors.
+    /// Other errors settle right away. It is only consulted while there are retries left, see [QueryConfig::retry].
+    pub fn retry_if(
+        mut self,
+        retry_if: impl Fn(&E, u32, &QueryMeta) -> bool + MaybeSend + MaybeSync + 'static,
+    ) -> Self {
+        self.retry_if = Some(Arc::new(Box::new(retry_if)));
+        self
+    }
+
+    /// Attach inert data to the query, e.g a human-readable name or the feature it belongs to.
+    /// It's given to the client callbacks, see [UseQueryClient::on_error], to [QueryConfig::retry_if]
+    /// and shown in the snapshots of the cache, but it doesn't make the query a different one:
+    /// the first listener of a cache entry sets it.
+    ///
+    /// ## Example:
+    ///
+    /// ```no_run
+    /// QueryConfig::new(vec![QueryKeys::Invoices], fetch_invoices)
+    ///     .meta(HashMap::from([("name".to_string(), "Invoices".to_string())]))
+    /// ```
+    pub fn meta(mut self, meta: QueryMeta) -> Self {
+        self.meta = Arc::new(meta);
+        self
+    }
+
+    /// Tag the query, so it can be invalidated along with other queries with the same tag
+    /// regardless of their query keys, e.g everything about billing, see [UseQueryClient::invalidate_tag].
+    /// Tags are never given to the query function, and like [QueryConfig::meta] the first listener
+    /// of a cache entry sets them.
+    ///
+    /// ## Example:
+    ///
+    /// ```no_run
+    /// let invoices = use_query_config(cx, || {
+    ///     QueryConfig::new(vec![QueryKeys::Invoices(customer_id)], fetch_invoices)
+    ///         .tags(vec![QueryKeys::Billing])
+    /// });
+    ///
+    /// client.invalidate_tag(QueryKeys::Billing);
+    /// ```
+    pub fn tags(mut self, tags: Vec<K>) -> Self {
+        self.tags = Arc::new(tags);
+        self
+    }
+
+    /// Refetch the query every `refetch_interval` while the component is mounted.
+    /// It pauses while the app is hidden, see [QueryConfig::refetch_interval_in_background].
+    /// Time is measured with the [Clock](crate::prelude::Clock) of the [UseQueryClient].
+    pub fn refetch_interval(mut self, refetch_interval: Duration) -> Self {
+        self.refetch_interval = Some(Arc::new(Box::new(move |_| Some(refetch_interval))));
+        self
+    }
+
+    /// Same as [QueryConfig::refetch_interval] but with an interval derived from the latest result, errors included,
+    /// e.g to poll a job only while it's running. It's evaluated whenever a fetch settles:
+    /// `Some` refetches the query after the given interval, and `None` stops polling
+    /// until another fetch settles, e.g after an invalidation.
+    ///
+    /// ## Example:
+    ///
+    /// ```no_run
+    /// QueryConfig::new(vec![QueryKeys::Job(id)], fetch_job).refetch_interval_fn(|result| match result {
+    ///     QueryResult::Ok(job) if job.is_running() => Some(Duration::from_secs(1)),
+    ///     QueryResult::Err(_) => Some(Duration::from_secs(10)),
+    ///     _ => None,
+    /// })
+    /// ```
+    pub fn refetch_interval_fn(
+        mut self,
+        refetch_interval: impl Fn(&QueryResult<T, E>) -> Option<Duration>
+            + MaybeSend
+            + MaybeSync
+            + 'static,
+    ) -> Self {
+        self.refetch_interval = Some(Arc::new(Box::new(refetch_interval)));
+        self
+    }
+
+    /// Keep refetching at the [QueryConfig::refetch_interval] while the app is hidden.
+    /// By default it pauses, and once the app is visible again it refetches right away if the value went stale.
+    /// See [QueryClientConfig::visibility_manager](crate::prelude::QueryClientConfig::visibility_manager).
+    pub fn refetch_interval_in_background(mut self, refetch_interval_in_background: bool) -> Self {
+        self.refetch_interval_in_background = refetch_interval_in_background;
+        self
+    }
+
+    /// Wait `debounce` before fetching, and start waiting again if the query is triggered again in the meantime,
+    /// e.g by a search as you type query mounted on every keystroke, so it only fetches once the user stops typing.
+    /// The previous result, or `Loading(None)` for a new entry, is kept while waiting.
+    /// Time is measured with the [Clock](crate::prelude::Clock) of the [UseQueryClient].
+    pub fn debounce(mut self, debounce: Duration) -> Self {
+        self.debounce = Some(debounce);
+        self
+    }
+
+    /// Whether invalidations also wait for the [QueryConfig::debounce]. Enabled by default,
+    /// disable it to refetch right away when the query is invalidated.
+    pub fn debounce_invalidations(mut self, debounce_invalidations: bool) -> Self {
+        self.debounce_invalidations = debounce_invalidations;
+        self
+    }
+
+    /// Wait at least `min_fetch_interval` between the end of a fetch and the start of the next one.
+    /// Triggers arriving sooner, e.g invalidations, leave the result stale and are collapsed into a single
+    /// fetch once the interval elapses, which notifies the listeners as usual.
+    /// Unlike [QueryConfig::stale_time], which decides whether a result needs to be fetched again at all,
+    /// it only delays the fetches that are needed.
+    pub fn min_fetch_interval(mut self, min_fetch_interval: Duration) -> Self {
+        self.min_fetch_interval = Some(min_fetch_interval);
+        self
+    }
+
+    /// Keep the successful value when a refetch fails, e.g so a dashboard doesn't blank out on a failed refresh.
+    /// The result stays `Ok` but stale, so it's retried, and the error is available with [CachedResult::error]
+    /// and [CachedResult::has_newer_error]. Listeners are still notified so they can show it.
+    /// Errors before there is any successful value are stored as usual.
+    pub fn keep_data_on_error(mut self, keep_data_on_error: bool) -> Self {
+        self.keep_data_on_error = keep_data_on_error;
+        self
+    }
+
+    /// Stop showing the successful value once it's older than `max_age`, even if refetching it fails,
+    /// e.g for quotes that are wrong past some point. The result reads as `Loading(None)`,
+    /// or as the error if it failed, until a fetch succeeds again.
+    /// It can't be shorter than the [QueryConfig::stale_time], so fresh values never expire.
+    pub fn max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// Set whether the query is fetched when a component using it mounts and there is a cached result already.
+    /// By default it's only fetched if the result is stale. The first fetch of the query always happens.
+    pub fn refetch_on_mount(mut self, refetch_on_mount: RefetchOnMount) -> Self {
+        self.refetch_on_mount = refetch_on_mount;
+        self
+    }
+
+    /// Call a callback with the value of every successful fetch of the query, once it's cached,
+    /// whatever triggered the fetch and whether or not the listeners are notified, e.g to log it.
+    /// Unlike [UseQueryClient::on_success] it only runs for this query.
+    ///
+    /// Like its other options, the callbacks of a query are the ones of the configuration that created its cache entry:
+    /// when several components use the same query, those given by the others don't run.
+    ///
+    /// ## Example:
+    ///
+    /// ```no_run
+    /// QueryConfig::new(vec![QueryKeys::Cart], fetch_cart)
+    ///     .on_success(|cart| log::info!(target: "checkout", "{} items in the cart", cart.len()))
+    /// ```
+    pub fn on_success(mut self, on_success: impl Fn(&T) + MaybeSend + MaybeSync + 'static) -> Self {
+        self.entry_callbacks
+            .on_success
+            .push(Arc::new(Box::new(on_success)));
+        self
+    }
+
+    /// Call a callback with the error of every failed fetch of the query, once it's cached, see [QueryConfig::on_success].
+    pub fn on_error(mut self, on_error: impl Fn(&E) + MaybeSend + MaybeSync + 'static) -> Self {
+        self.entry_callbacks
+            .on_error
+            .push(Arc::new(Box::new(on_error)));
+        self
+    }
+
+    /// Call a callback with the result of every fetch of the query, successful or not, once it's cached,
+    /// see [QueryConfig::on_success].
+    pub fn on_settled(
+        mut self,
+        on_settled: impl Fn(&QueryResult<T, E>) + MaybeSend + MaybeSync + 'static,
+    ) -> Self {
+        self.entry_callbacks
+            .on_settled
+            .push(Arc::new(Box::new(on_settled)));
+        self
+    }
+
+    /// Set which changes of the query re-render the components using it. By default they are re-rendered on every change,
+    /// e.g [NotifyOn::DataChanged] avoids re-rendering an expensive component that doesn't show the loading state.
+    pub fn notify_on(mut self, notify_on: NotifyOn) -> Self {
+        self.notify_on = notify_on;
+        self
+    }
+
+    /// Whether the query is fetched for the first time as soon as a component using it mounts. Enabled by default,
+    /// disable it for lazy queries that only start fetching once [UseQuery::trigger] is called, e.g to export a report.
+    /// Until then [CachedResult::is_idle] is `true`, and invalidating or refetching it on an interval does nothing.
+    pub fn fetch_on_mount(mut self, fetch_on_mount: bool) -> Self {
+        self.fetch_on_mount = fetch_on_mount;
+        self
+    }
+
+    /// Set how the query behaves while the app is offline. By default it's fetched anyway, see [NetworkMode].
+    ///
+    /// ## Example:
+    ///
+    /// ```no_run
+    /// QueryConfig::new(vec![QueryKeys::Notes], fetch_notes).network_mode(NetworkMode::OfflineFirst)
+    /// ```
+    pub fn network_mode(mut self, network_mode: NetworkMode) -> Self {
+        self.network_mode = network_mode;
+        self
+    }
+
+    /// Only fetch the query while `enabled` returns `true`. It's checked before every fetch,
+    /// and on every render of the component, so the query starts fetching once it gets enabled.
+    /// Invalidating a disabled query does nothing.
+    ///
+    /// ## Example:
+    ///
+    /// Fetch the projects of a user once the user is fetched:
+    ///
+    /// ```no_run
+    /// let client = use_query_client::<QueryValue, QueryError, QueryKeys>(cx);
+    /// let user = use_query(cx, || vec![QueryKeys::User(id)], fetch_user);
+    /// let projects = use_query_config(cx, || {
+    ///     let client = client.clone();
+    ///     // `fetch_user_projects` reads the user from the cache with `get_query_data`
+    ///     QueryConfig::new(vec![QueryKeys::UserProjects(id)], fetch_user_projects)
+    ///         .enabled_fn(move || client.get_query_data(&[QueryKeys::User(id)]).is_some())
+    /// });
+    /// ```
+    pub fn enabled_fn(
+        mut self,
+        enabled: impl Fn() -> bool + MaybeSend + MaybeSync + 'static,
+    ) -> Self {
+        self.enabled = Some(Arc::new(Box::new(enabled)));
+        self
+    }
+
+    /// Prefetch the next page of a paginated query in the **background** once a page is fetched,
+    /// so going to it is instant. `next_keys` gets the value of the page and returns the query keys of the next one,
+    /// if any, e.g from its cursor, which are fetched with the same query function and options.
+    ///
+    /// Only one page ahead is prefetched, the prefetched page doesn't prefetch the one after it until a component uses it,
+    /// and nothing is fetched if the next page is fresh in the cache already.
+    ///
+    /// ## Example:
+    ///
+    /// ```no_run
+    /// QueryConfig::new(vec![QueryKeys::Posts(cursor)], fetch_posts)
+    ///     .prefetch_next(|page: &QueryValue| page.next_cursor().map(|cursor| vec![QueryKeys::Posts(cursor)]))
+    /// ```
+    pub fn prefetch_next(
+        mut self,
+        next_keys: impl Fn(&T) -> Option<Vec<K>> + MaybeSend + MaybeSync + 'static,
+    ) -> Self {
+        self.prefetch_next = Some(Arc::new(Box::new(next_keys)));
+        self
+    }
+
+    /// Keep the value of a [QueryConfig::from_stream_fn] query live for as long as anything listens to it,
+    /// e.g with a websocket. It's never stale, so it's never refetched by time nor invalidated,
+    /// there is a single subscription per cached entry no matter how many components listen to it,
+    /// and it's only torn down once the last listener unmounts, subscribing again on the next mount.
+    ///
+    /// ## Example:
+    ///
+    /// ```no_run
+    /// let config = QueryConfig::from_stream_fn(vec![QueryKeys::Chat(id)], watch_chat)
+    ///     .subscription(SubscriptionEnd::Resubscribe(Duration::from_secs(1)));
+    /// ```
+    pub fn subscription(mut self, on_end: SubscriptionEnd<E>) -> Self {
+        self.subscription = Some(on_end);
+        self.stale_time = Some(Duration::MAX);
+        self
+    }
+
+    /// Expose a transformation of the cached value to the component, while the raw value stays in the cache.
+    /// The component is only re-rendered by a new value if its transformation changed.
+    /// Use it with [use_query_select].
+    pub fn select<U>(
+        self,
+        select: impl Fn(&T) -> U + 'static + MaybeSend + MaybeSync,
+    ) -> SelectQueryConfig<T, E, K, U> {
+        SelectQueryConfig {
+            config: self,
+            select: Box::new(select),
+        }
+    }
+}
+
+impl<T, E, K> QueryConfig<T, E, K>
+where
+    T: 'static + PartialEq,
+    E: 'static + PartialEq,
+{
+    /// Whether to compare the new result of a fetch with the cached one,
+    /// so listeners are not notified when nothing changed. Disabled by default,
+    /// as it requires the value and the error to implement [PartialEq].
+    pub fn compare_results(mut self, compare_results: bool) -> Self {
+        self.is_equal = compare_results.then(|| {
+            let is_equal: Arc<Box<QueryEqFn<T, E>>> = Arc::new(Box::new(|a, b| a == b));
+            is_equal
+        });
+        self
+    }
+}
+
+impl<T, E, K> QueryConfig<T, E, K>
+where
+    E: 'static + Clone + Debug,
+{
+    /// Throw the error of the query to the nearest `ErrorBoundary` once it settles to one,
+    /// instead of only returning it from [UseQuery::result]. Disabled by default.
+    ///
+    /// ## Example:
+    ///
+    /// ```no_run
+    /// fn App(cx: Scope) -> Element {
+    ///     render!(
+    ///         ErrorBoundary {
+    ///             handle_error: |error: CapturedError| render!( p { "Something went wrong: {error}" } ),
+    ///             UserProfile {}
+    ///             UserProjects {}
+    ///         }
+    ///     )
+    /// }
+    ///
+    /// fn UserProfile(cx: Scope) -> Element {
+    ///     let user = use_query_config(cx, || {
+    ///         QueryConfig::new(vec![QueryKeys::User(0)], fetch_user).throw_on_error(true)
+    ///     });
+    ///     // No need to handle `Err` here
+    ///     let name = user.result().data().map(|user| user.name.clone()).unwrap_or_default();
+    ///     render!( p { "{name}" } )
+    /// }
+    /// ```
+    pub fn throw_on_error(mut self, throw_on_error: bool) -> Self {
+        let throw_error: ThrowErrorFn<E> = |cx, err| {
+            cx.throw(err.clone());
+        };
+        self.throw_error = throw_on_error.then_some(throw_error);
+        self
+    }
+
+    /// Whether to also throw the errors of refetches while there is a previous value to show,
+    /// see [QueryConfig::throw_on_error]. Enabled by default.
+    pub fn throw_on_refetch_error(mut self, throw_on_refetch_error: bool) -> Self {
+        self.throw_on_refetch_error = throw_on_refetch_error;
+        self
+    }
+}
+
+/// The configuration for a query listener that exposes a transformation of the cached value.
+/// See [QueryConfig::select].
+pub struct SelectQueryConfig<T, E, K, U> {
+    config: QueryConfig<T, E, K>,
+    select: Box<SelectFn<T, U>>,
+}
+
+#[cfg(not(feature = "sync"))]
+type SelectFn<T, U> = dyn Fn(&T) -> U;
+#[cfg(feature = "sync")]
+type SelectFn<T, U> = dyn Fn(&T) -> U + Send + Sync;
+
+/// The transformation of a [UseSelectedQuery], along with its last result.
+struct Selection<T, U> {
+    select: Box<SelectFn<T, U>>,
+    /// The last transformed value, and when the value it was transformed from was cached
+    selected: Lock<Option<(Option<Instant>, U)>>,
+}
+
+impl<T, U: Clone + PartialEq> Selection<T, U> {
+    /// Transform the cached value, reusing the last transformation as long as the cached value didn't change.
+    /// Also returns whether the transformed value differs from the last one.
+    fn select<E>(&self, result: &CachedResult<T, E>) -> (Option<U>, bool) {
+        let Some(data) = result.data() else {
+            return (None, self.selected.borrow_mut().take().is_some());
+        };
+        let data_updated_at = result.data_updated_at();
+        let mut selected = self.selected.borrow_mut();
+
+        if let Some((selected_at, selected)) = &*selected {
+            if *selected_at == data_updated_at {
+                return (Some(selected.clone()), false);
+            }
+        }
+
+        let data = (self.select)(data);
+        let has_changed = selected
+            .as_ref()
+            .map_or(true, |(_, selected)| *selected != data);
+        *selected = Some((data_updated_at, data.clone()));
+        (Some(data), has_changed)
+    }
+}
+
+/// A query listener that exposes a transformation of the cached value.
+///
+/// The transformation only runs again when the cached value changes,
+/// and the component is only re-rendered by a new value if its transformation is different,
+/// e.g a component showing a single field of a large settings object ignores the changes to the other fields.
+/// The changes of the loading and error states still re-render it, unless disabled with [QueryConfig::notify_on].
+pub struct UseSelectedQuery<T, E, K: Eq + Hash, U> {
+    query: UseQuery<T, E, K>,
+    selection: Shared<Selection<T, U>>,
+}
+
+impl<T, E, K: Eq + Hash, U: Clone + PartialEq> UseSelectedQuery<T, E, K, U> {
+    /// Get the underlying query listener, with the raw cached value.
+    pub fn query(&self) -> &UseQuery<T, E, K> {
+        &self.query
+    }
+
+    /// Get the current transformed result from the query.
+    pub fn result(&self) -> QueryResult<U, E>
+    where
+        E: Clone,
+    {
+        let result = self.query.result();
+        let data = self.select_data(&result);
+        match (result.value(), data) {
+            (QueryResult::Ok(_), Some(data)) => QueryResult::Ok(data),
+            (QueryResult::Err(err), _) => QueryResult::Err(err.clone()),
+            (QueryResult::Loading(Some(_)), data) => QueryResult::Loading(data),
+            _ => QueryResult::Loading(None),
+        }
+    }
+
+    /// Get the transformed last successful value, even if a later fetch failed.
+    pub fn data(&self) -> Option<U> {
+        self.select_data(&self.query.result())
+    }
+
+    fn select_data(&self, result: &CachedResult<T, E>) -> Option<U> {
+        self.selection.select(result).0
+    }
 }
 
 /// Register a query listener with the given configuration.
@@ -90,46 +1190,59 @@ pub fn use_query_config<T, E, K>(
     config: impl FnOnce() -> QueryConfig<T, E, K>,
 ) -> &UseQuery<T, E, K>
 where
-    T: 'static + PartialEq + Clone,
-    E: 'static + PartialEq + Clone,
+    T: 'static + Clone,
+    E: 'static + Clone,
     K: 'static + Eq + Hash + Clone,
 {
     let client = use_query_client(cx);
-    cx.use_hook(|| {
-        let config = config();
-        let registry_entry = config.registry_entry.clone();
-        let mut queries_registry = client.queries_registry.borrow_mut();
-
-        // Create a group of listeners for the given [RegistryEntry] key.
-        let query_listeners =
-            queries_registry
-                .entry(registry_entry.clone())
-                .or_insert(QueryListeners {
-                    listeners: HashSet::default(),
-                    value: QueryValue::new(RwLock::new(CachedResult::new(
-                        config.initial_value.unwrap_or_default(),
-                    ))),
-                    query_fn: config.query_fn.clone(),
-                });
-
-        // Register this listener's scope
-        query_listeners.listeners.insert(cx.scope_id());
+    let query = cx.use_hook(|| UseQuery::new(cx, client, config()));
+    query.fetch_once_enabled(cx);
+    query.throw_if_errored(cx);
+    query
+}
 
-        // Asynchronously initialize the query value
-        cx.spawn({
-            to_owned![client, registry_entry];
-            async move {
-                client.run_new_query(&registry_entry).await;
-            }
+/// Register a query listener that exposes a transformation of the cached value.
+/// See [UseSelectedQuery] on how to use it.
+///
+/// ## Example:
+///
+/// ```no_run
+/// let user_name = use_query_select(cx, || {
+///     QueryConfig::new(vec![QueryKeys::User(id)], fetch_user).select(|user| user.name.clone())
+/// });
+/// ```
+pub fn use_query_select<T, E, K, U>(
+    cx: &ScopeState,
+    config: impl FnOnce() -> SelectQueryConfig<T, E, K, U>,
+) -> &UseSelectedQuery<T, E, K, U>
+where
+    T: 'static + Clone,
+    E: 'static + Clone,
+    K: 'static + Eq + Hash + Clone,
+    U: 'static + Clone + PartialEq + MaybeSend + MaybeSync,
+{
+    let client = use_query_client(cx);
+    let query = cx.use_hook(|| {
+        let SelectQueryConfig { mut config, select } = config();
+        let selection = Shared::new(Selection {
+            select,
+            selected: Lock::default(),
         });
 
-        UseQuery {
-            client: client.clone(),
-            value: query_listeners.value.clone(),
-            registry_entry: registry_entry.clone(),
-            scope_id: cx.scope_id(),
+        // Only re-render the component once the transformation of a new value changes
+        config.listener_filter = Some(Arc::new(Box::new({
+            let selection = selection.clone();
+            move |result: &CachedResult<T, E>| selection.select(result).1
+        })));
+
+        UseSelectedQuery {
+            query: UseQuery::new(cx, client, config),
+            selection,
         }
-    })
+    });
+    query.query.fetch_once_enabled(cx);
+    query.query.throw_if_errored(cx);
+    query
 }
 
 /// Register a query listener with the given combination of **query keys** and **query function**.
@@ -140,17 +1253,276 @@ where
 /// ```no_run
 /// let users_query = use_query(cx, || vec![QueryKeys::User(id)], fetch_user);
 /// ```
+///
+/// The query function can also be a closure returning an `async` block, no boxing needed:
+///
+/// ```no_run
+/// let users_query = use_query(cx, || vec![QueryKeys::User(id)], move |keys| {
+///     let token = token.clone();
+///     async move { api::fetch_user(keys, token).await.into() }
+/// });
+/// ```
+///
+/// Results are cached by query keys and function, so every closure gets its own cache entries,
+/// even if the same code is written in another component, unless they are given an id with [QueryConfig::with_id].
+///
+/// The query is fetched with the closure of the last render, so the values it captures are never stale.
 pub fn use_query<T, E, K, Q, F>(
     cx: &ScopeState,
     query_keys: impl FnOnce() -> Vec<K>,
     query_fn: Q,
 ) -> &UseQuery<T, E, K>
 where
-    T: 'static + PartialEq + Clone,
-    E: 'static + PartialEq + Clone,
+    T: 'static + Clone,
+    E: 'static + Clone,
     K: 'static + Eq + Hash + Clone,
-    Q: 'static + Fn(Vec<K>) -> F,
-    F: 'static + Future<Output = QueryResult<T, E>>,
+    Q: 'static + Fn(Vec<K>) -> F + MaybeSend + MaybeSync,
+    F: 'static + Future<Output = QueryResult<T, E>> + MaybeSend,
 {
-    use_query_config(cx, || QueryConfig::new(query_keys(), query_fn))
+    let mut query_fn = Some(query_fn);
+    let query = use_query_config(cx, || {
+        QueryConfig::new(query_keys(), query_fn.take().unwrap())
+    });
+    if let Some(query_fn) = query_fn {
+        query.refresh_query_fn(QueryConfig::new(Vec::new(), query_fn));
+    }
+    query
+}
+
+/// Same as [use_query] but the query function also gets a copy of the given context, see [QueryConfig::new_with_context].
+/// The context of the last render is used, so it can change without changing the cache entry of the query.
+///
+/// ## Example:
+///
+/// ```no_run
+/// let article = use_query_with_context(
+///     cx,
+///     || vec![QueryKeys::Article(id)],
+///     cx.props.locale.clone(),
+///     |keys, locale| async move { api::fetch_article(keys, &locale).await.into() },
+/// );
+/// ```
+pub fn use_query_with_context<T, E, K, C, Q, F>(
+    cx: &ScopeState,
+    query_keys: impl FnOnce() -> Vec<K>,
+    context: C,
+    query_fn: Q,
+) -> &UseQuery<T, E, K>
+where
+    T: 'static + Clone,
+    E: 'static + Clone,
+    K: 'static + Eq + Hash + Clone,
+    C: 'static + Clone + MaybeSend + MaybeSync,
+    Q: 'static + Fn(Vec<K>, C) -> F + MaybeSend + MaybeSync,
+    F: 'static + Future<Output = QueryResult<T, E>> + MaybeSend,
+{
+    let mut query_fn = Some((context, query_fn));
+    let query = use_query_config(cx, || {
+        let (context, query_fn) = query_fn.take().unwrap();
+        QueryConfig::new_with_context(query_keys(), context, query_fn)
+    });
+    if let Some((context, query_fn)) = query_fn {
+        query.refresh_query_fn(QueryConfig::new_with_context(Vec::new(), context, query_fn));
+    }
+    query
+}
+
+/// Same as [use_query] but the query function also gets a copy of the data currently cached, if any,
+/// see [QueryConfig::new_with_previous].
+///
+/// ## Example:
+///
+/// ```no_run
+/// let feed = use_query_with_previous(cx, || vec![QueryKeys::Feed], |keys, previous| async move {
+///     let etag = previous.as_ref().map(|feed: &Feed| feed.etag.clone());
+///     match api::fetch_feed(keys, etag).await {
+///         Ok(Response::NotModified) => previous.map_or(QueryResult::Loading(None), QueryResult::Ok),
+///         Ok(Response::Modified(feed)) => QueryResult::Ok(feed),
+///         Err(err) => QueryResult::Err(err),
+///     }
+/// });
+/// ```
+pub fn use_query_with_previous<T, E, K, Q, F>(
+    cx: &ScopeState,
+    query_keys: impl FnOnce() -> Vec<K>,
+    query_fn: Q,
+) -> &UseQuery<T, E, K>
+where
+    T: 'static + Clone + PartialEq,
+    E: 'static + Clone + PartialEq,
+    K: 'static + Eq + Hash + Clone,
+    Q: 'static + Fn(Vec<K>, Option<T>) -> F + MaybeSend + MaybeSync,
+    F: 'static + Future<Output = QueryResult<T, E>> + MaybeSend,
+{
+    let mut query_fn = Some(query_fn);
+    let query = use_query_config(cx, || {
+        QueryConfig::new_with_previous(query_keys(), query_fn.take().unwrap())
+    });
+    if let Some(query_fn) = query_fn {
+        query.refresh_query_fn(QueryConfig::new_with_previous(Vec::new(), query_fn));
+    }
+    query
+}
+
+/// Same as [use_query] but with a query function returning a [Result].
+///
+/// ## Example:
+///
+/// ```no_run
+/// async fn fetch_user(keys: Vec<QueryKeys>) -> Result<QueryValue, QueryError> {
+///     api::fetch_user(keys).await
+/// }
+///
+/// let users_query = use_query_result(cx, || vec![QueryKeys::User(id)], fetch_user);
+/// ```
+pub fn use_query_result<T, E, K, Q, F>(
+    cx: &ScopeState,
+    query_keys: impl FnOnce() -> Vec<K>,
+    query_fn: Q,
+) -> &UseQuery<T, E, K>
+where
+    T: 'static + Clone,
+    E: 'static + Clone,
+    K: 'static + Eq + Hash + Clone,
+    Q: 'static + Fn(Vec<K>) -> F + MaybeSend + MaybeSync,
+    F: 'static + Future<Output = Result<T, E>> + MaybeSend,
+{
+    let mut query_fn = Some(query_fn);
+    let query = use_query_config(cx, || {
+        QueryConfig::from_result_fn(query_keys(), query_fn.take().unwrap())
+    });
+    if let Some(query_fn) = query_fn {
+        query.refresh_query_fn(QueryConfig::from_result_fn(Vec::new(), query_fn));
+    }
+    query
+}
+
+/// Register a query listener whose function returns a [Stream], see [QueryConfig::from_stream_fn].
+///
+/// ## Example:
+///
+/// ```no_run
+/// fn watch_prices(keys: Vec<QueryKeys>) -> impl Stream<Item = QueryResult<Prices, QueryError>> {
+///     api::price_events(keys).map(QueryResult::from)
+/// }
+///
+/// let prices_query = use_query_stream(cx, || vec![QueryKeys::Prices], watch_prices);
+/// ```
+pub fn use_query_stream<T, E, K, Q, S>(
+    cx: &ScopeState,
+    query_keys: impl FnOnce() -> Vec<K>,
+    stream_fn: Q,
+) -> &UseQuery<T, E, K>
+where
+    T: 'static + Clone,
+    E: 'static + Clone,
+    K: 'static + Eq + Hash + Clone,
+    Q: 'static + Fn(Vec<K>) -> S + MaybeSend + MaybeSync,
+    S: 'static + Stream<Item = QueryResult<T, E>> + MaybeSend,
+{
+    use_query_config(cx, || QueryConfig::from_stream_fn(query_keys(), stream_fn))
+}
+
+/// Register a query listener kept live by a subscription to a [Stream], see [QueryConfig::subscription].
+///
+/// ## Example:
+///
+/// ```no_run
+/// let chat_query = use_subscription(
+///     cx,
+///     || vec![QueryKeys::Chat(id)],
+///     watch_chat,
+///     SubscriptionEnd::Resubscribe(Duration::from_secs(1)),
+/// );
+/// ```
+pub fn use_subscription<T, E, K, Q, S>(
+    cx: &ScopeState,
+    query_keys: impl FnOnce() -> Vec<K>,
+    stream_fn: Q,
+    on_end: SubscriptionEnd<E>,
+) -> &UseQuery<T, E, K>
+where
+    T: 'static + Clone,
+    E: 'static + Clone,
+    K: 'static + Eq + Hash + Clone,
+    Q: 'static + Fn(Vec<K>) -> S + MaybeSend + MaybeSync,
+    S: 'static + Stream<Item = QueryResult<T, E>> + MaybeSend,
+{
+    use_query_config(cx, || {
+        QueryConfig::from_stream_fn(query_keys(), stream_fn).subscription(on_end)
+    })
+}
+
+/// Register a query listener with a single **query key**, given to the query function as is.
+/// It shares the cache with the queries registered with [use_query] for the same key and function.
+///
+/// ## Example:
+///
+/// ```no_run
+/// async fn fetch_user(key: QueryKeys) -> QueryResult<QueryValue, QueryError> {
+///     ...
+/// }
+///
+/// let user_query = use_query_single(cx, || QueryKeys::User(id), fetch_user);
+/// ```
+pub fn use_query_single<T, E, K, Q, F>(
+    cx: &ScopeState,
+    query_key: impl FnOnce() -> K,
+    query_fn: Q,
+) -> &UseQuery<T, E, K>
+where
+    T: 'static + Clone,
+    E: 'static + Clone,
+    K: 'static + Eq + Hash + Clone,
+    Q: 'static + Fn(K) -> F + MaybeSend + MaybeSync,
+    F: 'static + Future<Output = QueryResult<T, E>> + MaybeSend,
+{
+    let query_fn = move |keys: Vec<K>| {
+        query_fn(
+            keys.into_iter()
+                .next()
+                .expect("Single queries have one key"),
+        )
+    };
+    let mut query_fn = Some(query_fn);
+    let query = use_query_config(cx, || {
+        QueryConfig::new(vec![query_key()], query_fn.take().unwrap())
+    });
+    if let Some(query_fn) = query_fn {
+        query.refresh_query_fn(QueryConfig::new(Vec::new(), query_fn));
+    }
+    query
+}
+
+/// Register a query listener without **query keys**, identified by the given id instead,
+/// e.g for the current user. Invalidate it with [UseQueryClient::invalidate_unit_query].
+///
+/// ## Example:
+///
+/// ```no_run
+/// let current_user = use_query_unit(cx, "current_user", fetch_current_user);
+///
+/// client.invalidate_unit_query("current_user");
+/// ```
+pub fn use_query_unit<'a, T, E, K, Q, F>(
+    cx: &'a ScopeState,
+    id: &'static str,
+    query_fn: Q,
+) -> &'a UseQuery<T, E, K>
+where
+    T: 'static + Clone,
+    E: 'static + Clone,
+    K: 'static + Eq + Hash + Clone,
+    Q: 'static + Fn() -> F + MaybeSend + MaybeSync,
+    F: 'static + Future<Output = QueryResult<T, E>> + MaybeSend,
+{
+    let query_fn = move |_: Vec<K>| query_fn();
+    let mut query_fn = Some(query_fn);
+    let query = use_query_config(cx, || {
+        QueryConfig::new(Vec::new(), query_fn.take().unwrap()).with_id(id)
+    });
+    if let Some(query_fn) = query_fn {
+        query.refresh_query_fn(QueryConfig::new(Vec::new(), query_fn));
+    }
+    query
 }
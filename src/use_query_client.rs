@@ -1,50 +1,954 @@
 use dioxus::prelude::*;
 use futures_util::{
-    stream::{FuturesUnordered, StreamExt},
+    future::{join_all, poll_fn, select, Either},
+    stream::{self, AbortHandle, Abortable, FuturesUnordered, StreamExt},
     Future,
 };
-use instant::Instant;
 use std::{
     any::TypeId,
+    borrow::Cow,
     collections::{HashMap, HashSet},
     hash::Hash,
-    rc::Rc,
-    sync::{Arc, RwLock},
+    pin::Pin,
+    sync::{Arc, RwLock, RwLockReadGuard},
+    task::{Poll, Waker},
+    time::Duration,
 };
 
-use crate::{cached_result::CachedResult, result::QueryResult};
+#[cfg(debug_assertions)]
+use crate::query_diagnostics::RegistrationConflict;
+use crate::{
+    abort_signal::QueryAbortSignal,
+    cache_events::{CacheEventsSubscription, CacheObservers, QueryCacheEvent},
+    cached_result::{CachedResult, DataSource, STALE_TIME},
+    clock::{Clock, Instant, SharedClock},
+    fetch_limiter::FetchLimiter,
+    hydration::{DehydratedQuery, DehydratedState, HydratedQueries, HydratedQuery},
+    manager_subscription::ManagerSubscription,
+    metrics::{QueryMetrics, QueryMetricsSnapshot},
+    online_manager::{OnlineManager, OnlineStatus, SharedOnlineManager},
+    persister::{PendingWrites, QueryPersister},
+    queries_registry::QueriesRegistry,
+    query_client::QueryClient,
+    query_diagnostics::QueryFnName,
+    query_handle::QueryHandle,
+    query_watcher::{QueryWatcher, QueryWatchers},
+    rate_limiter::RateLimiter,
+    result::QueryResult,
+    retry_backoff::ExponentialBackoff,
+    settle_notify::SettleNotify,
+    snapshot::{QueryEntrySnapshot, QueryEntryStatus},
+    sync::{self, Counter, Lock, MaybeSend, MaybeSync, Shared},
+    use_query::{
+        NetworkMode, NotifyOn, QueryChange, QueryConfig, QueryMeta, RefetchOnMount, SubscriptionEnd,
+    },
+    use_query_status::QueryStatus,
+    visibility_manager::{SharedVisibilityManager, VisibilityManager},
+};
+
+/// How long an entry with no listeners (e.g. a prefetched one) is kept in the cache.
+const CACHE_TIME: u64 = 5 * 60 * 1000;
+
+/// Default options for the queries of a [UseQueryClient].
+/// Each query can still override them through its [QueryConfig].
+#[derive(Clone, Debug, PartialEq)]
+pub struct QueryClientConfig {
+    pub(crate) stale_time: Duration,
+    pub(crate) retry: u32,
+    pub(crate) retry_delay: Option<ExponentialBackoff>,
+    pub(crate) clock: SharedClock,
+    pub(crate) max_entries: Option<usize>,
+    pub(crate) online_manager: SharedOnlineManager,
+    pub(crate) visibility_manager: SharedVisibilityManager,
+    pub(crate) fetch_limiter: Option<FetchLimiter>,
+    pub(crate) rate_limiter: Option<RateLimiter>,
+}
+
+impl Default for QueryClientConfig {
+    fn default() -> Self {
+        Self {
+            stale_time: Duration::from_millis(STALE_TIME),
+            retry: 0,
+            retry_delay: None,
+            clock: SharedClock::default(),
+            max_entries: None,
+            online_manager: SharedOnlineManager::default(),
+            visibility_manager: SharedVisibilityManager::default(),
+            fetch_limiter: None,
+            rate_limiter: None,
+        }
+    }
+}
+
+impl QueryClientConfig {
+    /// Set how long query results are considered fresh, and therefore not refetched, after being updated.
+    pub fn stale_time(mut self, stale_time: Duration) -> Self {
+        self.stale_time = stale_time;
+        self
+    }
+
+    /// Set how many times a query function is called again when it returns an error.
+    pub fn retry(mut self, retry: u32) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Set how long to wait before calling a query function again when it returns an error.
+    /// It's called again right away by default.
+    pub fn retry_delay(mut self, retry_delay: ExponentialBackoff) -> Self {
+        self.retry_delay = Some(retry_delay);
+        self
+    }
+
+    /// Set the source of the current time, e.g a [MockClock](crate::prelude::MockClock) in tests.
+    pub fn clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = SharedClock::new(clock);
+        self
+    }
 
+    /// Limit how many entries the cache keeps. Once there are more, the least recently used entries
+    /// without listeners are evicted, while the ones with listeners are always kept. Unlimited by default.
+    pub fn max_entries(mut self, max_entries: usize) -> Self {
+        self.max_entries = Some(max_entries);
+        self
+    }
+
+    /// Limit how many query functions run at once, e.g to protect a rate-limited backend.
+    /// The fetches over the limit wait for a slot while their queries stay loading. Unlimited by default.
+    ///
+    /// The clients created with this configuration, e.g the ones of a [QueryClient], share the same limit.
+    pub fn max_concurrent_fetches(mut self, max_concurrent_fetches: usize) -> Self {
+        self.fetch_limiter = Some(FetchLimiter::new(max_concurrent_fetches));
+        self
+    }
+
+    /// Limit how often query functions are called, e.g to stay under the quota of a third-party API,
+    /// with a bucket of `capacity` calls that gets a call back every `refill_interval`.
+    /// Every call goes through it, including retries, prefetches, invalidations and refetches on an interval.
+    /// The calls over the limit wait in the order they were made while their queries stay fetching. Unlimited by default.
+    ///
+    /// The clients created with this configuration, e.g the ones of a [QueryClient], share the same bucket.
+    ///
+    /// ## Example:
+    ///
+    /// ```no_run
+    /// // 10 calls per second, in bursts of up to 10
+    /// QueryClientConfig::default().rate_limit(10, Duration::from_millis(100))
+    /// ```
+    pub fn rate_limit(mut self, capacity: usize, refill_interval: Duration) -> Self {
+        self.rate_limiter = Some(RateLimiter::new(capacity, refill_interval));
+        self
+    }
+
+    /// Set the source of truth for whether the app is online, e.g a
+    /// [NavigatorOnlineManager](crate::prelude::NavigatorOnlineManager) with the `web` feature.
+    /// The app is always considered online by default.
+    pub fn online_manager(mut self, online_manager: impl OnlineManager + 'static) -> Self {
+        self.online_manager = SharedOnlineManager::new(online_manager);
+        self
+    }
+
+    /// Set the source of truth for whether the app is visible, e.g a
+    /// [DocumentVisibilityManager](crate::prelude::DocumentVisibilityManager) with the `web` feature.
+    /// The app is always considered visible by default.
+    pub fn visibility_manager(
+        mut self,
+        visibility_manager: impl VisibilityManager + 'static,
+    ) -> Self {
+        self.visibility_manager = SharedVisibilityManager::new(visibility_manager);
+        self
+    }
+}
+
+/// Create a [UseQueryClient] with the default options and provide it to the descendants of this component.
+/// Meant to be called once near the root, so the client is set up before any query runs.
+///
+/// Calling it again below an initialized client of the same types returns that client instead,
+/// use [provide_query_client] to provide a separate one.
 pub fn use_init_query_client<T, E, K>(cx: &ScopeState) -> &UseQueryClient<T, E, K>
 where
-    T: 'static + Clone,
-    E: 'static + Clone,
-    K: 'static + Clone,
+    T: 'static + Clone + MaybeSend + MaybeSync,
+    E: 'static + Clone + MaybeSend + MaybeSync,
+    K: 'static + Eq + Hash + Clone + MaybeSend + MaybeSync,
+{
+    use_init_query_client_with_config(cx, QueryClientConfig::default)
+}
+
+/// Same as [use_init_query_client] but with default options for all the queries.
+///
+/// ## Example:
+///
+/// ```no_run
+/// use_init_query_client_with_config::<QueryValue, QueryError, QueryKeys>(cx, || {
+///     QueryClientConfig::default()
+///         .stale_time(Duration::from_secs(30))
+///         .retry(3)
+/// });
+/// ```
+pub fn use_init_query_client_with_config<T, E, K>(
+    cx: &ScopeState,
+    config: impl FnOnce() -> QueryClientConfig,
+) -> &UseQueryClient<T, E, K>
+where
+    T: 'static + Clone + MaybeSend + MaybeSync,
+    E: 'static + Clone + MaybeSend + MaybeSync,
+    K: 'static + Eq + Hash + Clone + MaybeSend + MaybeSync,
 {
-    use_context_provider(cx, || UseQueryClient {
-        queries_registry: Rc::default(),
-        scheduler: cx.schedule_update_any(),
+    use_init_query_client_with(cx, || {
+        UseQueryClient::new(cx.schedule_update_any()).with_config(config())
     })
 }
 
-/// Get access to the [UseQueryClient].
+/// Same as [use_init_query_client] but with the client built by the given closure,
+/// e.g to collect its metrics or persist its cache.
+///
+/// ## Example:
+///
+/// ```no_run
+/// use_init_query_client_with::<QueryValue, QueryError, QueryKeys>(cx, || {
+///     UseQueryClient::new(cx.schedule_update_any())
+///         .with_config(QueryClientConfig::default().retry(3))
+///         .with_metrics(InMemoryMetrics::default())
+/// });
+/// ```
+pub fn use_init_query_client_with<T, E, K>(
+    cx: &ScopeState,
+    client: impl FnOnce() -> UseQueryClient<T, E, K>,
+) -> &UseQueryClient<T, E, K>
+where
+    T: 'static + Clone + MaybeSend + MaybeSync,
+    E: 'static + Clone + MaybeSend + MaybeSync,
+    K: 'static + Eq + Hash + Clone + MaybeSend + MaybeSync,
+{
+    // The first initialization wins
+    if let Some(client) = cx.use_hook(|| cx.consume_context::<UseQueryClient<T, E, K>>()) {
+        return client;
+    }
+    provide_query_client(cx, client)
+}
+
+/// Provide the given [UseQueryClient] to the descendants of this component,
+/// instead of the one provided by any of its ancestors.
+/// Useful to keep the cache of a self-contained widget apart from the rest of the app.
+///
+/// ## Example:
+///
+/// ```no_run
+/// provide_query_client(cx, || UseQueryClient::<QueryValue, QueryError, QueryKeys>::new(cx.schedule_update_any()));
+/// ```
+pub fn provide_query_client<T, E, K>(
+    cx: &ScopeState,
+    client: impl FnOnce() -> UseQueryClient<T, E, K>,
+) -> &UseQueryClient<T, E, K>
+where
+    T: 'static + Clone + MaybeSend + MaybeSync,
+    E: 'static + Clone + MaybeSend + MaybeSync,
+    K: 'static + Eq + Hash + Clone + MaybeSend + MaybeSync,
+{
+    let client = use_context_provider(cx, client);
+    use_context_provider(cx, || {
+        let client = client.clone();
+        QueryInvalidator(Shared::new(move |keys: &[K], except: &[Vec<K>]| {
+            client.invalidate_queries_except(keys, except)
+        }))
+    });
+    client
+}
+
+/// Get access to the [UseQueryClient] provided by the closest ancestor,
+/// or else the one of the [QueryClient] provided by an ancestor, if it serves these types.
+///
+/// If none was provided, a client with the default options is created and shared with the whole app.
+/// Mutations can't invalidate the queries of such a client, so prefer initializing it with [use_init_query_client].
 pub fn use_query_client<T, E, K>(cx: &ScopeState) -> &UseQueryClient<T, E, K>
 where
     T: 'static + Clone,
     E: 'static + Clone,
     K: 'static + Clone,
 {
-    use_context(cx).unwrap()
+    cx.use_hook(|| {
+        cx.consume_context::<UseQueryClient<T, E, K>>()
+            .or_else(|| cx.consume_context::<QueryClient<K>>()?.get::<T, E>())
+            .unwrap_or_else(|| {
+                cx.provide_root_context(
+                    UseQueryClient::new(cx.schedule_update_any())
+                        .with_config(QueryClientConfig::default()),
+                )
+            })
+    })
 }
 
-pub(crate) type QueryFn<T, E, K> = dyn Fn(Vec<K>) -> Box<dyn Future<Output = QueryResult<T, E>>>;
+/// Also gets the cached result of the entry, to read the previous value, see [QueryConfig::new_with_previous]
+#[cfg(not(feature = "sync"))]
+pub(crate) type QueryFn<T, E, K> = dyn Fn(
+    Vec<K>,
+    QueryAbortSignal,
+    &RwLock<CachedResult<T, E>>,
+) -> Box<dyn Future<Output = QueryResult<T, E>>>;
+/// Also gets the cached result of the entry, to read the previous value, see [QueryConfig::new_with_previous]
+#[cfg(feature = "sync")]
+pub(crate) type QueryFn<T, E, K> = dyn Fn(
+        Vec<K>,
+        QueryAbortSignal,
+        &RwLock<CachedResult<T, E>>,
+    ) -> Box<dyn Future<Output = QueryResult<T, E>> + Send>
+    + Send
+    + Sync;
 
-pub(crate) type QueryValue<T> = Arc<RwLock<T>>;
+#[cfg(not(feature = "sync"))]
+pub(crate) type StreamFn<T, E, K> =
+    dyn Fn(Vec<K>) -> stream::LocalBoxStream<'static, QueryResult<T, E>>;
+#[cfg(feature = "sync")]
+pub(crate) type StreamFn<T, E, K> =
+    dyn Fn(Vec<K>) -> stream::BoxStream<'static, QueryResult<T, E>> + Send + Sync;
+
+#[cfg(not(feature = "sync"))]
+pub(crate) type RefetchIntervalFn<T, E> = dyn Fn(&QueryResult<T, E>) -> Option<Duration>;
+#[cfg(feature = "sync")]
+pub(crate) type RefetchIntervalFn<T, E> =
+    dyn Fn(&QueryResult<T, E>) -> Option<Duration> + Send + Sync;
+
+#[cfg(not(feature = "sync"))]
+pub(crate) type QueryEqFn<T, E> = dyn Fn(&QueryResult<T, E>, &QueryResult<T, E>) -> bool;
+#[cfg(feature = "sync")]
+pub(crate) type QueryEqFn<T, E> =
+    dyn Fn(&QueryResult<T, E>, &QueryResult<T, E>) -> bool + Send + Sync;
+
+/// Serves the result of a fetch instead of the query function, along with how long to wait before,
+/// or `None` to call the query function. See [UseQueryClient::with_mock](crate::prelude::UseQueryClient::with_mock)
+#[cfg(not(feature = "sync"))]
+pub(crate) type FetchInterceptor<T, E, K> = dyn Fn(&[K]) -> Option<(QueryResult<T, E>, Duration)>;
+#[cfg(feature = "sync")]
+pub(crate) type FetchInterceptor<T, E, K> =
+    dyn Fn(&[K]) -> Option<(QueryResult<T, E>, Duration)> + Send + Sync;
 
+/// Runs the background tasks of a client outside of any component, see [UseQueryClient::with_spawner].
+#[cfg(not(feature = "sync"))]
+pub(crate) type SpawnFn = dyn Fn(Pin<Box<dyn Future<Output = ()>>>);
+#[cfg(feature = "sync")]
+pub(crate) type SpawnFn = dyn Fn(Pin<Box<dyn Future<Output = ()>>>) + Send + Sync;
+
+#[cfg(not(feature = "sync"))]
+pub(crate) type RetryIfFn<E> = dyn Fn(&E, u32, &QueryMeta) -> bool;
+#[cfg(feature = "sync")]
+pub(crate) type RetryIfFn<E> = dyn Fn(&E, u32, &QueryMeta) -> bool + Send + Sync;
+
+#[cfg(not(feature = "sync"))]
+pub(crate) type ListenerFilterFn<T, E> = dyn Fn(&CachedResult<T, E>) -> bool;
+#[cfg(feature = "sync")]
+pub(crate) type ListenerFilterFn<T, E> = dyn Fn(&CachedResult<T, E>) -> bool + Send + Sync;
+
+#[cfg(not(feature = "sync"))]
+pub(crate) type PrefetchNextFn<T, K> = dyn Fn(&T) -> Option<Vec<K>>;
+#[cfg(feature = "sync")]
+pub(crate) type PrefetchNextFn<T, K> = dyn Fn(&T) -> Option<Vec<K>> + Send + Sync;
+
+#[cfg(not(feature = "sync"))]
+pub(crate) type EnabledFn = dyn Fn() -> bool;
+#[cfg(feature = "sync")]
+pub(crate) type EnabledFn = dyn Fn() -> bool + Send + Sync;
+
+#[cfg(not(feature = "sync"))]
+pub(crate) type OnSuccessFn<T, K> = dyn Fn(&T, &[K], &QueryMeta);
+#[cfg(feature = "sync")]
+pub(crate) type OnSuccessFn<T, K> = dyn Fn(&T, &[K], &QueryMeta) + Send + Sync;
+
+#[cfg(not(feature = "sync"))]
+pub(crate) type OnErrorFn<E, K> = dyn Fn(&E, &[K], &QueryMeta);
+#[cfg(feature = "sync")]
+pub(crate) type OnErrorFn<E, K> = dyn Fn(&E, &[K], &QueryMeta) + Send + Sync;
+
+#[cfg(not(feature = "sync"))]
+pub(crate) type OnSettledFn<T, E, K> = dyn Fn(&QueryResult<T, E>, &[K], &QueryMeta);
+#[cfg(feature = "sync")]
+pub(crate) type OnSettledFn<T, E, K> = dyn Fn(&QueryResult<T, E>, &[K], &QueryMeta) + Send + Sync;
+
+/// Invalidates the queries with any of the given keys, except the entries with the given query keys
+#[cfg(not(feature = "sync"))]
+pub(crate) type InvalidateFn<K> = dyn Fn(&[K], &[Vec<K>]);
+/// Invalidates the queries with any of the given keys, except the entries with the given query keys
+#[cfg(feature = "sync")]
+pub(crate) type InvalidateFn<K> = dyn Fn(&[K], &[Vec<K>]) + Send + Sync;
+
+/// Invalidates queries of the closest [UseQueryClient] knowing only the type of its keys,
+/// see [MutationConfig::invalidates](crate::prelude::MutationConfig::invalidates).
 #[derive(Clone)]
+pub(crate) struct QueryInvalidator<K>(pub(crate) Shared<InvalidateFn<K>>);
+
+/// Callbacks called after every fetch of any query of a client.
+pub(crate) struct QueryCallbacks<T, E, K> {
+    pub(crate) on_success: Vec<Shared<OnSuccessFn<T, K>>>,
+    pub(crate) on_error: Vec<Shared<OnErrorFn<E, K>>>,
+    pub(crate) on_settled: Vec<Shared<OnSettledFn<T, E, K>>>,
+}
+
+impl<T, E, K> Default for QueryCallbacks<T, E, K> {
+    fn default() -> Self {
+        Self {
+            on_success: Vec::new(),
+            on_error: Vec::new(),
+            on_settled: Vec::new(),
+        }
+    }
+}
+
+#[cfg(not(feature = "sync"))]
+pub(crate) type EntrySuccessFn<T> = dyn Fn(&T);
+#[cfg(feature = "sync")]
+pub(crate) type EntrySuccessFn<T> = dyn Fn(&T) + Send + Sync;
+
+#[cfg(not(feature = "sync"))]
+pub(crate) type EntryErrorFn<E> = dyn Fn(&E);
+#[cfg(feature = "sync")]
+pub(crate) type EntryErrorFn<E> = dyn Fn(&E) + Send + Sync;
+
+#[cfg(not(feature = "sync"))]
+pub(crate) type EntrySettledFn<T, E> = dyn Fn(&QueryResult<T, E>);
+#[cfg(feature = "sync")]
+pub(crate) type EntrySettledFn<T, E> = dyn Fn(&QueryResult<T, E>) + Send + Sync;
+
+/// Callbacks called after every fetch of a single entry, once its result is cached,
+/// see [QueryConfig::on_success].
+pub(crate) struct EntryCallbacks<T, E> {
+    pub(crate) on_success: Vec<Arc<Box<EntrySuccessFn<T>>>>,
+    pub(crate) on_error: Vec<Arc<Box<EntryErrorFn<E>>>>,
+    pub(crate) on_settled: Vec<Arc<Box<EntrySettledFn<T, E>>>>,
+}
+
+impl<T, E> Default for EntryCallbacks<T, E> {
+    fn default() -> Self {
+        Self {
+            on_success: Vec::new(),
+            on_error: Vec::new(),
+            on_settled: Vec::new(),
+        }
+    }
+}
+
+impl<T, E> Clone for EntryCallbacks<T, E> {
+    fn clone(&self) -> Self {
+        Self {
+            on_success: self.on_success.clone(),
+            on_error: self.on_error.clone(),
+            on_settled: self.on_settled.clone(),
+        }
+    }
+}
+
+impl<T, E> EntryCallbacks<T, E> {
+    fn is_empty(&self) -> bool {
+        self.on_success.is_empty() && self.on_error.is_empty() && self.on_settled.is_empty()
+    }
+
+    /// Get a copy of the result of a fetch to run the callbacks with once it's cached, if there are any.
+    fn keep(&self, value: &QueryResult<T, E>) -> Option<QueryResult<T, E>>
+    where
+        T: Clone,
+        E: Clone,
+    {
+        (!self.is_empty()).then(|| value.clone())
+    }
+
+    /// Run the callbacks for the result of a fetch, in order of registration.
+    fn run(&self, value: &QueryResult<T, E>) {
+        match value {
+            QueryResult::Ok(data) => self.on_success.iter().for_each(|f| f(data)),
+            QueryResult::Err(err) => self.on_error.iter().for_each(|f| f(err)),
+            QueryResult::Loading(_) => {}
+        }
+        self.on_settled.iter().for_each(|f| f(value));
+    }
+}
+
+/// Invalidations merged until the debounce window closes, see [UseQueryClient::set_invalidation_debounce].
+pub(crate) struct InvalidationBatch<K> {
+    pub(crate) debounce: Option<Duration>,
+    /// Union of the keys invalidated during the current window
+    pub(crate) keys: Vec<K>,
+    /// Whether a task is already waiting for the current window to close
+    pub(crate) is_scheduled: bool,
+}
+
+impl<K> Default for InvalidationBatch<K> {
+    fn default() -> Self {
+        Self {
+            debounce: None,
+            keys: Vec::new(),
+            is_scheduled: false,
+        }
+    }
+}
+
+/// Entries whose fetches were held back while the client was paused, see [UseQueryClient::pause_all].
+pub(crate) struct PausedQueries<K> {
+    pub(crate) is_paused: bool,
+    /// Entries to revalidate once resumed, each of them once however many fetches it missed
+    pub(crate) pending: HashSet<RegistryEntry<K>>,
+}
+
+impl<K> Default for PausedQueries<K> {
+    fn default() -> Self {
+        Self {
+            is_paused: false,
+            pending: HashSet::new(),
+        }
+    }
+}
+
+/// Options of an invalidation, see [UseQueryClient::invalidate_queries_with].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct InvalidateOptions {
+    /// How many of the invalidated queries are refetched at the same time, all of them if `None`.
+    /// Ignored by [InvalidateMode::Sequential].
+    /// When limited, the listeners are notified as each query finishes instead of once all of theirs have.
+    pub max_concurrency: Option<usize>,
+    pub mode: InvalidateMode,
+}
+
+/// How the queries of an invalidation are refetched, see [InvalidateOptions].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum InvalidateMode {
+    /// Refetch them at the same time, up to [InvalidateOptions::max_concurrency]
+    #[default]
+    Concurrent,
+    /// Refetch them one after another, in the order their cache entries were created.
+    /// Each one is cached and its listeners notified before the next one starts,
+    /// e.g for query functions that must not run at the same time.
+    Sequential,
+}
+
+/// The cached result of one of the queries of a prefetch, see [UseQueryClient::prefetch_queries].
+#[derive(Clone, Debug, PartialEq)]
+pub struct PrefetchOutcome<T, E, K> {
+    pub query_keys: Vec<K>,
+    pub result: QueryResult<T, E>,
+}
+
+/// What an invalidation did with one of the entries it matched,
+/// see [UseQueryClient::manual_invalidate_queries].
+#[derive(Clone, Debug, PartialEq)]
+pub struct InvalidationOutcome<K, E> {
+    pub query_keys: Vec<K>,
+    pub status: InvalidationStatus<E>,
+}
+
+/// Whether an entry matched by an invalidation was refetched, see [InvalidationOutcome].
+#[derive(Clone, Debug, PartialEq)]
+pub enum InvalidationStatus<E> {
+    /// Refetched in `duration`, with the error if it failed
+    Refetched {
+        error: Option<E>,
+        duration: Duration,
+    },
+    /// Not refetched, as nothing listens to it, but marked as stale so it's fetched once something does
+    Unobserved,
+    /// Not fetched, as it's a lazy query that was never triggered, see [QueryConfig::fetch_on_mount]
+    Idle,
+    /// Not fetched, as it's disabled, see [QueryConfig::enabled_fn]
+    Disabled,
+    /// Refetched by the query itself once allowed to,
+    /// see [QueryConfig::debounce] and [QueryConfig::min_fetch_interval]
+    Deferred,
+    /// Subscribed to its stream again, see [QueryConfig::from_stream_fn]
+    Resubscribed,
+    /// Not refetched, as it's kept up to date by its subscription, see [QueryConfig::subscription]
+    Live,
+    /// Its refetch was aborted by a newer fetch of the query, whose result is kept instead
+    Superseded,
+    /// Not refetched, as the app is offline or the client is paused, but fetched once it's back online
+    /// or resumed, see [NetworkMode::OfflineFirst] and [UseQueryClient::pause_all]
+    Paused,
+}
+
+/// How waiting for a query to settle ended, see [UseQueryClient::wait_for].
+#[derive(Clone, Debug, PartialEq)]
+pub enum WaitOutcome<T, E> {
+    /// It settled with a value
+    Ok(T),
+    /// It settled with an error
+    Err(E),
+    /// It didn't settle in time
+    TimedOut,
+}
+
+/// When an entry was last fetched, see [QueryConfig::min_fetch_interval].
+#[derive(Default)]
+pub(crate) struct FetchThrottle {
+    pub(crate) last_fetch: Option<Instant>,
+    /// Whether a trailing fetch is already waiting for the interval to elapse
+    pub(crate) has_trailing_fetch: bool,
+}
+
+/// The fetch in flight of an entry, see [UseQueryClient::fetch_entry].
+pub(crate) struct InFlightFetch {
+    handle: AbortHandle,
+    signal: QueryAbortSignal,
+}
+
+impl InFlightFetch {
+    /// Let the query function know the fetch is abandoned, and drop its future.
+    pub(crate) fn abort(self) {
+        self.signal.abort();
+        self.handle.abort();
+    }
+}
+
+/// Re-renders the given scope.
+#[cfg(not(feature = "sync"))]
+pub type Scheduler = dyn Fn(ScopeId);
+/// Re-renders the given scope.
+#[cfg(feature = "sync")]
+pub type Scheduler = dyn Fn(ScopeId) + Send + Sync;
+
+pub(crate) type QueryValue<T> = Arc<RwLock<T>>;
+
+/// How a component listening to an entry wants to be notified of its changes.
+pub(crate) struct QueryListener<T, E> {
+    pub(crate) notify_on: NotifyOn,
+    /// Whether a new value is relevant to the component, see [use_query_select](crate::prelude::use_query_select)
+    pub(crate) filter: Option<Arc<Box<ListenerFilterFn<T, E>>>>,
+}
+
+impl<T, E> Clone for QueryListener<T, E> {
+    fn clone(&self) -> Self {
+        Self {
+            notify_on: self.notify_on,
+            filter: self.filter.clone(),
+        }
+    }
+}
+
+impl<T, E> QueryListener<T, E> {
+    /// Whether the listener is notified of the given change, now that the entry has the given result.
+    pub(crate) fn accepts(&self, change: QueryChange, result: &CachedResult<T, E>) -> bool {
+        self.notify_on.accepts(change)
+            && (change != QueryChange::Data
+                || self.filter.as_ref().map_or(true, |filter| filter(result)))
+    }
+}
+
+/// The state of an entry of the cache. Its clones are handles to the same state,
+/// so the registry only needs to be locked to find an entry, not while fetching it or notifying its listeners.
 pub(crate) struct QueryListeners<T, E, K> {
     pub(crate) value: QueryValue<CachedResult<T, E>>,
-    pub(crate) listeners: HashSet<ScopeId>,
-    pub(crate) query_fn: Arc<Box<QueryFn<T, E, K>>>,
+    /// Shared with the clones of the entry, so getting the entry doesn't copy the map.
+    /// Each listener is only notified of the changes it asked for, see [QueryConfig::notify_on]
+    pub(crate) listeners: Shared<Lock<HashMap<ScopeId, QueryListener<T, E>>>>,
+    /// Listeners that are not components, see [QueryHandle]
+    pub(crate) detached_listeners: Shared<Counter>,
+    /// Components that stopped listening for now, which keep the entry around, see [UseQuery::pause](crate::prelude::UseQuery::pause)
+    pub(crate) paused_listeners: Shared<Lock<HashMap<ScopeId, QueryListener<T, E>>>>,
+    /// Replaced whenever a component using it re-renders with a new closure, so it doesn't capture stale values
+    pub(crate) query_fn: Shared<Lock<Arc<Box<QueryFn<T, E, K>>>>>,
+    /// Subscribed to instead of calling the query function, see [QueryConfig::from_stream_fn]
+    pub(crate) stream_fn: Option<Arc<Box<StreamFn<T, E, K>>>>,
+    /// Tears down the current subscription to the stream, if any
+    pub(crate) stream: Shared<Lock<Option<AbortHandle>>>,
+    /// Aborts the fetch in flight, if any, once a newer one starts or the entry is discarded
+    pub(crate) in_flight: Shared<Lock<Option<InFlightFetch>>>,
+    /// What to do once the stream ends, if it's kept live, see [QueryConfig::subscription]
+    pub(crate) subscription: Option<Arc<SubscriptionEnd<E>>>,
+    /// Used to skip notifying the listeners when a fetch returns the same result
+    pub(crate) is_equal: Option<Arc<Box<QueryEqFn<T, E>>>>,
+    /// Change to `Loading` when refetching instead of only marking the cached result as fetching
+    pub(crate) loading_on_refetch: bool,
+    /// How many times the query function is called again when it returns an error
+    pub(crate) retry: u32,
+    /// Whether an error is worth retrying, see [QueryConfig::retry_if]
+    pub(crate) retry_if: Option<Arc<Box<RetryIfFn<E>>>>,
+    /// How long to wait before retrying, see [QueryConfig::retry_delay]
+    pub(crate) retry_delay: Option<ExponentialBackoff>,
+    /// Inert data about the query, see [QueryConfig::meta]
+    pub(crate) meta: Arc<QueryMeta>,
+    /// Groups the query belongs to, see [QueryConfig::tags]
+    pub(crate) tags: Arc<Vec<K>>,
+    /// Bumped whenever a fetch starts or the cached value is discarded,
+    /// so only the most recently started fetch writes its result.
+    pub(crate) generation: Shared<Counter>,
+    /// When the entry was last read or written, in uses of the client, see [QueryClientConfig::max_entries]
+    pub(crate) last_used: Shared<Counter>,
+    /// How long to wait for other triggers before fetching, see [QueryConfig::debounce]
+    pub(crate) debounce: Option<Duration>,
+    /// Whether invalidations wait for the debounce too, see [QueryConfig::debounce_invalidations]
+    pub(crate) debounce_invalidations: bool,
+    /// Bumped whenever a debounced fetch is triggered, so only the last trigger of a window fetches
+    pub(crate) triggers: Shared<Counter>,
+    /// Minimum time between the fetches, see [QueryConfig::min_fetch_interval]
+    pub(crate) min_fetch_interval: Option<Duration>,
+    pub(crate) throttle: Shared<Lock<FetchThrottle>>,
+    /// Position of the entry in the order the entries were created, see [InvalidateMode::Sequential]
+    pub(crate) created: usize,
+    /// Whether the entry is fetched before being triggered, see [QueryConfig::fetch_on_mount]
+    pub(crate) fetch_on_mount: bool,
+    /// Whether the entry is fetched while offline, see [QueryConfig::network_mode]
+    pub(crate) network_mode: NetworkMode,
+    /// Whether the entry can be fetched right now, see [QueryConfig::enabled_fn]
+    pub(crate) enabled: Option<Arc<Box<EnabledFn>>>,
+    /// Gets the keys of the page to prefetch once this one is fetched, see [QueryConfig::prefetch_next]
+    pub(crate) prefetch_next: Option<Arc<Box<PrefetchNextFn<T, K>>>>,
+    /// Woken whenever a result is written to the entry, see [UseQuery::wait](crate::prelude::UseQuery::wait)
+    pub(crate) settled: Shared<SettleNotify>,
+    /// The callbacks of the configuration that created the entry, see [QueryConfig::on_success]
+    pub(crate) entry_callbacks: Arc<EntryCallbacks<T, E>>,
+}
+
+/// Every field is either shared or cheap to copy, so the value types don't need to be cloneable.
+impl<T, E, K> Clone for QueryListeners<T, E, K> {
+    fn clone(&self) -> Self {
+        Self {
+            value: self.value.clone(),
+            listeners: self.listeners.clone(),
+            detached_listeners: self.detached_listeners.clone(),
+            paused_listeners: self.paused_listeners.clone(),
+            query_fn: self.query_fn.clone(),
+            stream_fn: self.stream_fn.clone(),
+            stream: self.stream.clone(),
+            in_flight: self.in_flight.clone(),
+            subscription: self.subscription.clone(),
+            is_equal: self.is_equal.clone(),
+            loading_on_refetch: self.loading_on_refetch,
+            retry: self.retry,
+            retry_if: self.retry_if.clone(),
+            retry_delay: self.retry_delay.clone(),
+            meta: self.meta.clone(),
+            tags: self.tags.clone(),
+            generation: self.generation.clone(),
+            last_used: self.last_used.clone(),
+            debounce: self.debounce,
+            debounce_invalidations: self.debounce_invalidations,
+            triggers: self.triggers.clone(),
+            min_fetch_interval: self.min_fetch_interval,
+            throttle: self.throttle.clone(),
+            created: self.created,
+            fetch_on_mount: self.fetch_on_mount,
+            network_mode: self.network_mode,
+            enabled: self.enabled.clone(),
+            prefetch_next: self.prefetch_next.clone(),
+            settled: self.settled.clone(),
+            entry_callbacks: self.entry_callbacks.clone(),
+        }
+    }
+}
+
+impl<T, E, K> QueryListeners<T, E, K> {
+    /// Abort any fetch still in flight and tear down the subscription to the stream,
+    /// e.g once the entry is removed.
+    pub(crate) fn discard(&self) {
+        self.generation.increment();
+        let fetch = self.in_flight.borrow_mut().take();
+        if let Some(fetch) = fetch {
+            fetch.abort();
+        }
+        if let Some(stream) = self.stream.borrow_mut().take() {
+            stream.abort();
+        }
+    }
+
+    /// Check if anything is following the entry right now, so it's worth fetching.
+    /// Paused listeners aren't, see [UseQuery::pause](crate::prelude::UseQuery::pause).
+    pub(crate) fn is_observed(&self) -> bool {
+        !self.listeners.borrow().is_empty() || self.detached_listeners.get() > 0
+    }
+
+    /// Check if nothing listens to the entry, not even a paused listener, so it can be removed.
+    pub(crate) fn is_unused(&self) -> bool {
+        !self.is_observed() && self.paused_listeners.borrow().is_empty()
+    }
+
+    /// Check if the entry is subscribed to its stream, see [QueryConfig::from_stream_fn].
+    pub(crate) fn is_streaming(&self) -> bool {
+        self.stream.borrow().is_some()
+    }
+}
+
+impl<T: Clone, E: Clone, K> QueryListeners<T, E, K> {
+    /// Create the listeners of a new entry,
+    /// falling back to the client defaults for the options not set in the query configuration.
+    pub(crate) fn from_config(config: &QueryConfig<T, E, K>, defaults: &QueryClientConfig) -> Self {
+        let mut cached_result = CachedResult::new(config.initial_value.clone().unwrap_or_default());
+        if config.initial_value.is_some() {
+            cached_result.data_source = Some(DataSource::Initial);
+        }
+        cached_result.stale_time = config.stale_time.unwrap_or(defaults.stale_time);
+        cached_result.keep_data_on_error = config.keep_data_on_error;
+        if let (Some(initial_value), Some(updated_at)) =
+            (&config.initial_value, config.initial_updated_at)
+        {
+            // Only fetch it once it's stale
+            cached_result.instant = Some(updated_at);
+            if initial_value.is_ok() {
+                cached_result.data_instant = Some(updated_at);
+            }
+            cached_result.has_been_queried = true;
+        }
+        cached_result.max_age = config
+            .max_age
+            .map(|max_age| max_age.max(cached_result.stale_time));
+        cached_result.clock = defaults.clock.clone();
+
+        Self {
+            value: QueryValue::new(RwLock::new(cached_result)),
+            listeners: Shared::default(),
+            detached_listeners: Shared::default(),
+            paused_listeners: Shared::default(),
+            query_fn: Shared::new(Lock::new(config.query_fn.clone())),
+            stream_fn: config.stream_fn.clone(),
+            stream: Shared::default(),
+            in_flight: Shared::default(),
+            subscription: config.subscription.clone().map(Arc::new),
+            is_equal: config.is_equal.clone(),
+            loading_on_refetch: config.loading_on_refetch,
+            retry: config.retry.unwrap_or(defaults.retry),
+            retry_if: config.retry_if.clone(),
+            retry_delay: config
+                .retry_delay
+                .clone()
+                .or_else(|| defaults.retry_delay.clone()),
+            meta: config.meta.clone(),
+            tags: config.tags.clone(),
+            generation: Shared::default(),
+            last_used: Shared::default(),
+            debounce: config.debounce,
+            debounce_invalidations: config.debounce_invalidations,
+            triggers: Shared::default(),
+            min_fetch_interval: config.min_fetch_interval,
+            throttle: Shared::default(),
+            created: 0,
+            fetch_on_mount: config.fetch_on_mount,
+            network_mode: config.network_mode,
+            enabled: config.enabled.clone(),
+            prefetch_next: config.prefetch_next.clone(),
+            settled: Shared::default(),
+            entry_callbacks: Arc::new(config.entry_callbacks.clone()),
+        }
+    }
+
+    /// Create the listeners of the entry of the next page, with the same options as this one,
+    /// except that it doesn't prefetch the page after it, see [QueryConfig::prefetch_next].
+    pub(crate) fn for_next_page(&self) -> Self {
+        let mut cached_result = CachedResult::new(QueryResult::default());
+        {
+            let value = sync::read(&self.value);
+            cached_result.stale_time = value.stale_time;
+            cached_result.keep_data_on_error = value.keep_data_on_error;
+            cached_result.max_age = value.max_age;
+            cached_result.clock = value.clock.clone();
+        }
+
+        Self {
+            value: QueryValue::new(RwLock::new(cached_result)),
+            listeners: Shared::default(),
+            detached_listeners: Shared::default(),
+            paused_listeners: Shared::default(),
+            query_fn: Shared::new(Lock::new(self.query_fn.borrow().clone())),
+            stream_fn: None,
+            stream: Shared::default(),
+            in_flight: Shared::default(),
+            subscription: None,
+            is_equal: self.is_equal.clone(),
+            loading_on_refetch: self.loading_on_refetch,
+            retry: self.retry,
+            retry_if: self.retry_if.clone(),
+            retry_delay: self.retry_delay.clone(),
+            meta: self.meta.clone(),
+            tags: self.tags.clone(),
+            generation: Shared::default(),
+            last_used: Shared::default(),
+            debounce: None,
+            debounce_invalidations: self.debounce_invalidations,
+            triggers: Shared::default(),
+            min_fetch_interval: self.min_fetch_interval,
+            throttle: Shared::default(),
+            created: 0,
+            fetch_on_mount: true,
+            network_mode: self.network_mode,
+            enabled: self.enabled.clone(),
+            prefetch_next: None,
+            settled: Shared::default(),
+            entry_callbacks: self.entry_callbacks.clone(),
+        }
+    }
+
+    /// Call the query function, calling it again on errors as many times as configured.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) async fn fetch(
+        query_fn: &QueryFn<T, E, K>,
+        query_keys: Vec<K>,
+        cached: &RwLock<CachedResult<T, E>>,
+        retry: u32,
+        retry_if: Option<&RetryIfFn<E>>,
+        retry_delay: Option<&ExponentialBackoff>,
+        clock: &SharedClock,
+        meta: &QueryMeta,
+        signal: &QueryAbortSignal,
+        limiter: Option<&FetchLimiter>,
+        rate_limiter: Option<&RateLimiter>,
+        interceptor: Option<&FetchInterceptor<T, E, K>>,
+    ) -> QueryResult<T, E>
+    where
+        K: Clone,
+    {
+        let mut attempt = 0;
+        loop {
+            if let Some(rate_limiter) = rate_limiter {
+                rate_limiter.acquire(clock).await;
+            }
+            let permit = match limiter {
+                Some(limiter) => Some(limiter.acquire().await),
+                None => None,
+            };
+            let value = match interceptor.and_then(|interceptor| interceptor(&query_keys)) {
+                Some((value, delay)) => {
+                    if !delay.is_zero() {
+                        Box::into_pin(clock.sleep(delay)).await;
+                    }
+                    value
+                }
+                None => {
+                    let fut = (query_fn)(query_keys.clone(), signal.clone(), cached);
+                    let fut = Box::into_pin(fut);
+                    fut.await
+                }
+            };
+            drop(permit);
+            if let QueryResult::Err(err) = &value {
+                // Only consulted while there are retries left
+                let is_retryable = attempt < retry
+                    && match retry_if {
+                        Some(retry_if) => retry_if(err, attempt + 1, meta),
+                        None => true,
+                    };
+                if is_retryable {
+                    attempt += 1;
+                    if let Some(retry_delay) = retry_delay {
+                        Box::into_pin(clock.sleep(retry_delay.delay(attempt))).await;
+                    }
+                    continue;
+                }
+            }
+            return value;
+        }
+    }
+}
+
+/// Run the given task in the **background**, in the current component, or with the given spawner outside of components.
+/// Returns `false` if neither is there, in which case it's dropped.
+fn spawn_with(
+    spawner: &Lock<Option<Arc<Box<SpawnFn>>>>,
+    task: impl Future<Output = ()> + 'static,
+) -> bool {
+    if current_scope_id().is_some() {
+        return spawn(task).is_some();
+    }
+    // Cloned out first so the spawner is free to use the client
+    let spawner = spawner.borrow().clone();
+    match spawner {
+        Some(spawner) => {
+            spawner(Box::pin(task));
+            true
+        }
+        None => false,
+    }
 }
 
 /// Query listeners are grouped by their query keys and query functions
@@ -52,160 +956,3892 @@ pub(crate) struct QueryListeners<T, E, K> {
 #[derive(PartialEq, Eq, Hash, Clone)]
 pub(crate) struct RegistryEntry<K> {
     pub(crate) query_keys: Vec<K>,
-    pub(crate) query_fn_id: TypeId,
+    pub(crate) query_fn_id: QueryFnId,
+}
+
+/// Identifies the query function of an entry.
+#[derive(PartialEq, Eq, Hash, Clone, Debug)]
+pub(crate) enum QueryFnId {
+    /// The type of the future returned by the function, so each function has its own entries
+    Type(TypeId, QueryFnName),
+    /// Given with [QueryConfig::with_id], so entries can be shared by different functions
+    Explicit(Cow<'static, str>),
 }
 
-pub(crate) type QueriesRegistry<T, E, K> = HashMap<RegistryEntry<K>, QueryListeners<T, E, K>>;
+impl QueryFnId {
+    /// Identify a query function by the given type.
+    pub(crate) fn of<F: 'static>() -> Self {
+        Self::Type(TypeId::of::<F>(), QueryFnName::of::<F>())
+    }
+}
 
 /// Manage the queries of your application.
 #[derive(Clone)]
 pub struct UseQueryClient<T, E, K> {
-    pub(crate) queries_registry: Rc<RefCell<QueriesRegistry<T, E, K>>>,
-    pub(crate) scheduler: Arc<dyn Fn(ScopeId)>,
+    pub(crate) config: QueryClientConfig,
+    pub(crate) queries_registry: Shared<Lock<QueriesRegistry<T, E, K>>>,
+    /// Scopes that only read the cached values of some query keys, see [use_query_data](crate::prelude::use_query_data)
+    pub(crate) data_listeners: Shared<Lock<HashMap<Vec<K>, HashSet<ScopeId>>>>,
+    /// Scopes that follow whether queries are being fetched, see [use_is_fetching](crate::prelude::use_is_fetching)
+    pub(crate) fetching_listeners: Shared<Lock<HashSet<ScopeId>>>,
+    /// Scopes that follow the status of some query keys, with the status they last rendered,
+    /// see [use_query_status](crate::prelude::use_query_status)
+    pub(crate) status_listeners: Shared<Lock<HashMap<Vec<K>, HashMap<ScopeId, QueryStatus>>>>,
+    /// Tasks that follow the cached values of some query keys, see [UseQueryClient::watch]
+    pub(crate) watchers: Shared<Lock<QueryWatchers<K>>>,
+    pub(crate) callbacks: Shared<Lock<QueryCallbacks<T, E, K>>>,
+    /// Bumped whenever an entry is used, to know which ones were used least recently
+    pub(crate) uses: Shared<Counter>,
+    /// Bumped whenever an entry is created, to know in which order they were
+    pub(crate) created_entries: Shared<Counter>,
+    /// Results waiting for an entry with their query keys to be created, see [UseQueryClient::hydrate]
+    pub(crate) hydrated_queries: Shared<Lock<HydratedQueries<T, K>>>,
+    /// Tasks waiting for the app to be visible again, see [QueryClientConfig::visibility_manager]
+    pub(crate) visibility_waiters: Shared<Lock<Vec<Waker>>>,
+    /// Fetches waiting for the app to be back online, see [NetworkMode::OfflineFirst]
+    pub(crate) online_waiters: Shared<Lock<Vec<Waker>>>,
+    /// Subscriptions to the online and visibility managers of the config, dropped along with the client
+    pub(crate) manager_subscriptions: Shared<Lock<Vec<ManagerSubscription>>>,
+    /// Fetches held back while the client is paused, see [UseQueryClient::pause_all]
+    pub(crate) paused: Shared<Lock<PausedQueries<K>>>,
+    /// Observers of the changes in the cache, see [UseQueryClient::subscribe_cache_events]
+    pub(crate) cache_observers: Shared<Lock<CacheObservers<T, E, K>>>,
+    /// Keys waiting to be invalidated together, see [UseQueryClient::set_invalidation_debounce]
+    pub(crate) invalidation_batch: Shared<Lock<InvalidationBatch<K>>>,
+    /// Collects how the cache performs, see [UseQueryClient::with_metrics]
+    pub(crate) metrics: Option<Shared<dyn QueryMetrics<K>>>,
+    /// Serves scripted results instead of the query functions, see [UseQueryClient::with_mock](crate::prelude::UseQueryClient::with_mock)
+    pub(crate) interceptor: Option<Arc<Box<FetchInterceptor<T, E, K>>>>,
+    /// Runs the background tasks started outside of any component, see [UseQueryClient::with_spawner].
+    /// Shared so the tasks started by the cache observers use it even if it's set after them
+    pub(crate) spawner: Shared<Lock<Option<Arc<Box<SpawnFn>>>>>,
+    /// Suspicious registrations seen so far, see [UseQueryClient::registration_conflicts](crate::prelude::UseQueryClient::registration_conflicts)
+    #[cfg(debug_assertions)]
+    pub(crate) registration_conflicts: Shared<Lock<Vec<RegistrationConflict<K>>>>,
+    /// Puts the query keys of every query in a canonical order, see [UseQueryClient::with_unordered_keys]
+    pub(crate) keys_normalizer: Option<fn(&mut [K])>,
+    /// Formats the query keys in the `tracing` events, see [UseQueryClient::with_debug_keys]
+    #[cfg(feature = "tracing")]
+    pub(crate) keys_formatter: Option<fn(&[K]) -> String>,
+    pub(crate) scheduler: Arc<Scheduler>,
 }
 
-impl<T, E, K> UseQueryClient<T, E, K>
-where
-    T: 'static + Clone,
-    E: 'static + Clone,
-    K: 'static + PartialEq + Eq + Hash + Clone,
-{
-    pub(crate) fn get_entry(&self, entry: &RegistryEntry<K>) -> QueryListeners<T, E, K> {
-        let registry = self.queries_registry.borrow();
-        registry.get(entry).unwrap().clone()
+impl<T, E, K> UseQueryClient<T, E, K> {
+    /// Create a new client with an empty cache, see [provide_query_client].
+    /// The scheduler is used to re-render the listeners, e.g `cx.schedule_update_any()`.
+    pub fn new(scheduler: Arc<Scheduler>) -> Self {
+        Self {
+            config: QueryClientConfig::default(),
+            queries_registry: Shared::default(),
+            data_listeners: Shared::default(),
+            fetching_listeners: Shared::default(),
+            status_listeners: Shared::default(),
+            watchers: Shared::default(),
+            callbacks: Shared::default(),
+            uses: Shared::default(),
+            created_entries: Shared::default(),
+            hydrated_queries: Shared::default(),
+            visibility_waiters: Shared::default(),
+            online_waiters: Shared::default(),
+            manager_subscriptions: Shared::default(),
+            paused: Shared::default(),
+            cache_observers: Shared::default(),
+            invalidation_batch: Shared::default(),
+            metrics: None,
+            interceptor: None,
+            spawner: Shared::default(),
+            #[cfg(debug_assertions)]
+            registration_conflicts: Shared::default(),
+            keys_normalizer: None,
+            #[cfg(feature = "tracing")]
+            keys_formatter: None,
+            scheduler,
+        }
     }
 
-    pub(crate) async fn run_new_query(&self, entry: &RegistryEntry<K>) {
-        let QueryListeners {
-            value,
-            query_fn,
-            listeners,
-            ..
-        } = self.get_entry(entry);
-
-        let is_fresh = value.read().unwrap().is_fresh();
-        let is_loading = value.read().unwrap().is_loading();
-        let has_been_mutated = value.read().unwrap().has_been_mutated();
-        let has_been_queried = value.read().unwrap().has_been_queried();
+    /// Create a new client that is not tied to any component, so nothing gets re-rendered.
+    /// Useful to test query functions and the cache outside of Dioxus, see [UseQueryClient::register_query].
+    /// Its background tasks need a spawner, see [UseQueryClient::with_spawner].
+    pub fn new_detached() -> Self {
+        Self::new(Arc::new(|_| {}))
+    }
 
-        if (!is_fresh && !is_loading) || !has_been_queried {
-            // Only change to `Loading` if had been changed at some point
-            if has_been_mutated {
-                let cached_value: Option<T> = value.read().unwrap().clone().into();
-                *value.write().unwrap() = CachedResult {
-                    value: QueryResult::Loading(cached_value),
-                    instant: Some(Instant::now()),
-                    has_been_queried: true,
-                };
-                for listener in listeners {
-                    (self.scheduler)(listener);
-                }
-            }
+    /// Set the default options for the queries of this client.
+    pub fn with_config(mut self, config: QueryClientConfig) -> Self
+    where
+        T: 'static,
+        E: 'static,
+        K: 'static,
+    {
+        self.config = config;
 
-            // Mark as queried
-            value.write().unwrap().has_been_queried = true;
+        // Let the cache observers know when the app goes online or offline,
+        // and resume the fetches paused while it was offline
+        let cache_observers = self.cache_observers.clone();
+        let online_waiters = self.online_waiters.clone();
+        let online_subscription =
+            self.config
+                .online_manager
+                .subscribe(Box::new(move |is_online| {
+                    if is_online {
+                        let waiters = std::mem::take(&mut *online_waiters.borrow_mut());
+                        for waiter in waiters {
+                            waiter.wake();
+                        }
+                    }
+                    let observers = cache_observers.borrow().to_vec();
+                    for observer in observers {
+                        observer(&QueryCacheEvent::OnlineStatusChanged {
+                            status: is_online.into(),
+                        });
+                    }
+                }));
 
-            // Fetch the result
-            let fut = (query_fn)(entry.query_keys.clone());
-            let fut = Box::into_pin(fut);
-            let new_value = fut.await;
-            *value.write().unwrap() = CachedResult {
-                value: new_value,
-                instant: Some(Instant::now()),
-                has_been_queried: true,
-            };
+        // Resume the tasks waiting for the app to be visible
+        let visibility_waiters = self.visibility_waiters.clone();
+        let visibility_subscription =
+            self.config
+                .visibility_manager
+                .subscribe(Box::new(move |is_visible| {
+                    if is_visible {
+                        let waiters = std::mem::take(&mut *visibility_waiters.borrow_mut());
+                        for waiter in waiters {
+                            waiter.wake();
+                        }
+                    }
+                }));
 
-            // Get the listeners again in case they changed
-            let QueryListeners { listeners, .. } = self.get_entry(entry);
+        // Replaces the subscriptions to the managers of a previous config
+        *self.manager_subscriptions.borrow_mut() =
+            vec![online_subscription, visibility_subscription];
+        self
+    }
 
-            for listener in listeners {
-                (self.scheduler)(listener);
+    /// Wait until the app is visible, according to the [VisibilityManager] of the client.
+    pub(crate) async fn wait_until_visible(&self) {
+        poll_fn(|cx| {
+            if self.config.visibility_manager.is_visible() {
+                return Poll::Ready(());
             }
-        } else {
-            for listener in listeners {
-                (self.scheduler)(listener);
+            self.visibility_waiters
+                .borrow_mut()
+                .push(cx.waker().clone());
+            Poll::Pending
+        })
+        .await
+    }
+
+    /// Wait until the app is online, according to the [OnlineManager] of the client.
+    pub(crate) async fn wait_until_online(&self) {
+        poll_fn(|cx| {
+            if self.config.online_manager.status().is_online() {
+                return Poll::Ready(());
             }
+            self.online_waiters.borrow_mut().push(cx.waker().clone());
+            Poll::Pending
+        })
+        .await
+    }
+
+    /// Run the background tasks of the client with the given function when they are started outside of any component,
+    /// e.g on a client created with [UseQueryClient::new_detached].
+    ///
+    /// Methods like [UseQueryClient::invalidate_queries], [UseQueryClient::resume_all] or
+    /// [UseQueryClient::remove_queries_matching] do their work in a task spawned in the current component.
+    /// Without a component nor a spawner that work is skipped, use their `manual_` variants to await it instead.
+    ///
+    /// ## Example:
+    ///
+    /// ```no_run
+    /// let client = UseQueryClient::<QueryValue, QueryError, QueryKeys>::new_detached()
+    ///     .with_spawner(|task| {
+    ///         tokio::task::spawn_local(task);
+    ///     });
+    /// ```
+    pub fn with_spawner(
+        self,
+        spawner: impl Fn(Pin<Box<dyn Future<Output = ()>>>) + MaybeSend + MaybeSync + 'static,
+    ) -> Self {
+        *self.spawner.borrow_mut() = Some(Arc::new(Box::new(spawner)));
+        self
+    }
+
+    /// Check if a task can be run in the **background**, see [UseQueryClient::spawn_task].
+    pub(crate) fn can_spawn(&self) -> bool {
+        self.spawner.borrow().is_some() || current_scope_id().is_some()
+    }
+
+    /// Run the given task in the **background**, in the current component,
+    /// or with the spawner of the client outside of components, see [UseQueryClient::with_spawner].
+    /// Returns `false` if it couldn't be started, in which case it's dropped.
+    pub(crate) fn spawn_task(&self, task: impl Future<Output = ()> + 'static) -> bool {
+        let is_spawned = spawn_with(&self.spawner, task);
+        if !is_spawned {
+            query_warning!(
+                "a background task of the query client was skipped, as there is no component nor spawner to run it"
+            );
         }
+        is_spawned
     }
 
-    pub(crate) async fn invalidate_queries_inner(
-        queries_registry: Rc<RefCell<QueriesRegistry<T, E, K>>>,
-        scheduler: Arc<dyn Fn(ScopeId)>,
-        keys_to_invalidate: &[K],
-    ) {
-        let tasks = FuturesUnordered::new();
-        for (
-            RegistryEntry { query_keys, .. },
-            QueryListeners {
-                value,
-                listeners,
-                query_fn,
-            },
-        ) in queries_registry.borrow().iter()
-        {
-            let mut query_listeners = HashSet::<ScopeId>::default();
+    /// Check if the app is online, according to the [OnlineManager] of the client.
+    pub fn online_status(&self) -> OnlineStatus {
+        self.config.online_manager.status()
+    }
 
-            // Add the listeners of this `query_keys` when at least one of the keys match
-            if query_keys.iter().any(|k| keys_to_invalidate.contains(k)) {
-                for listener in listeners {
-                    query_listeners.insert(*listener);
-                }
-            }
+    /// Check if the queries are paused, see [UseQueryClient::pause_all].
+    pub fn is_paused(&self) -> bool {
+        self.paused.borrow().is_paused
+    }
 
-            // Save the group of listeners
-            if !query_listeners.is_empty() {
-                // Only change to `Loading` if had been changed at some point
-                let cached_value: Option<T> = value.read().unwrap().clone().into();
-                *value.write().unwrap() = CachedResult {
-                    value: QueryResult::Loading(cached_value),
-                    instant: Some(Instant::now()),
-                    has_been_queried: true,
-                };
-                for listener in &query_listeners {
-                    (scheduler)(*listener);
-                }
+    /// Ignore the order of the query keys, so the same keys listed in a different order share the same cache entry.
+    /// The keys are sorted before being used, so query functions get them sorted too.
+    pub fn with_unordered_keys(mut self) -> Self
+    where
+        K: Ord,
+    {
+        self.keys_normalizer = Some(<[K]>::sort);
+        self
+    }
 
-                to_owned![query_fn, query_keys, query_listeners, value, scheduler];
+    /// Collect how the cache performs with the given collector, e.g [InMemoryMetrics](crate::prelude::InMemoryMetrics):
+    /// how often queries are served from the cache instead of being fetched, and how long fetches take.
+    /// Without a collector nothing is measured.
+    pub fn with_metrics(mut self, metrics: impl QueryMetrics<K> + 'static) -> Self {
+        let metrics: Shared<dyn QueryMetrics<K>> = Shared::new(metrics);
+        self.metrics = Some(metrics);
+        self
+    }
 
-                tasks.push(Box::pin(async move {
-                    // Fetch the result
-                    let fut = (query_fn)(query_keys.clone());
-                    let fut = Box::into_pin(fut);
-                    let new_value = fut.await;
-                    *value.write().unwrap() = CachedResult {
-                        value: new_value,
-                        instant: Some(Instant::now()),
-                        has_been_queried: true,
-                    };
+    /// Get what the metrics collector of the client gathered so far, see [UseQueryClient::with_metrics].
+    /// `None` without a collector, or if it doesn't keep them.
+    pub fn metrics_snapshot(&self) -> Option<QueryMetricsSnapshot<K>> {
+        self.metrics.as_ref().and_then(|metrics| metrics.snapshot())
+    }
 
-                    for listener in query_listeners {
-                        scheduler(listener);
-                    }
-                }));
-            }
+    /// Put the given query keys in the canonical order of the client, see [UseQueryClient::with_unordered_keys].
+    pub(crate) fn normalize_keys(&self, keys: &mut [K]) {
+        if let Some(keys_normalizer) = self.keys_normalizer {
+            keys_normalizer(keys);
         }
+    }
 
-        tasks.count().await;
+    /// Include the query keys in the `tracing` events, otherwise only their number is.
+    #[cfg(feature = "tracing")]
+    pub fn with_debug_keys(mut self) -> Self
+    where
+        K: std::fmt::Debug,
+    {
+        self.keys_formatter = Some(|keys| format!("{keys:?}"));
+        self
     }
 
-    /// Invalidate a single query.
-    /// It will run alone, after previous queries have finished.
-    pub fn invalidate_query(&self, key_to_invalidate: K) {
-        let queries_registry = self.queries_registry.clone();
-        let scheduler = self.scheduler.clone();
-        spawn(async move {
-            Self::invalidate_queries_inner(queries_registry, scheduler, &[key_to_invalidate]).await;
-        });
+    /// Format the query keys for the `tracing` events.
+    #[cfg(feature = "tracing")]
+    pub(crate) fn fmt_keys(&self, keys: &[K]) -> String {
+        match self.keys_formatter {
+            Some(keys_formatter) => keys_formatter(keys),
+            None => format!("[{} keys]", keys.len()),
+        }
     }
 
-    /// Invalidate a group of queries.
-    /// They will all run concurrently, after previous queries have finished.
-    pub fn invalidate_queries(&self, keys_to_invalidate: &[K]) {
-        let queries_registry = self.queries_registry.clone();
-        let scheduler = self.scheduler.clone();
-        let keys_to_invalidate = keys_to_invalidate.to_vec();
-        spawn(async move {
-            Self::invalidate_queries_inner(queries_registry, scheduler, &keys_to_invalidate).await;
-        });
+    /// Register a callback called after every successful fetch of any query, in order of registration.
+    ///
+    /// ## Example:
+    ///
+    /// ```no_run
+    /// client.on_success(|value, keys, meta| println!("Fetched {keys:?} {:?}", meta.get("name")));
+    /// ```
+    ///
+    /// The callbacks also get the metadata of the query, see [QueryConfig::meta].
+    pub fn on_success(
+        &self,
+        on_success: impl Fn(&T, &[K], &QueryMeta) + MaybeSend + MaybeSync + 'static,
+    ) {
+        self.callbacks
+            .borrow_mut()
+            .on_success
+            .push(Shared::new(on_success));
+    }
+
+    /// Register a callback called after every failed fetch of any query, in order of registration.
+    pub fn on_error(
+        &self,
+        on_error: impl Fn(&E, &[K], &QueryMeta) + MaybeSend + MaybeSync + 'static,
+    ) {
+        self.callbacks
+            .borrow_mut()
+            .on_error
+            .push(Shared::new(on_error));
+    }
+
+    /// Register a callback called after every fetch of any query, successful or not, in order of registration.
+    pub fn on_settled(
+        &self,
+        on_settled: impl Fn(&QueryResult<T, E>, &[K], &QueryMeta) + MaybeSend + MaybeSync + 'static,
+    ) {
+        self.callbacks
+            .borrow_mut()
+            .on_settled
+            .push(Shared::new(on_settled));
+    }
+
+    /// Run the callbacks for the result of a fetch.
+    /// They are cloned out first so they are free to use the client.
+    pub(crate) fn run_callbacks(
+        &self,
+        value: &QueryResult<T, E>,
+        query_keys: &[K],
+        meta: &QueryMeta,
+    ) {
+        let (on_success, on_error, on_settled) = {
+            let callbacks = self.callbacks.borrow();
+            (
+                callbacks.on_success.clone(),
+                callbacks.on_error.clone(),
+                callbacks.on_settled.clone(),
+            )
+        };
+
+        match value {
+            QueryResult::Ok(data) => on_success.iter().for_each(|f| f(data, query_keys, meta)),
+            QueryResult::Err(err) => on_error.iter().for_each(|f| f(err, query_keys, meta)),
+            QueryResult::Loading(_) => {}
+        }
+        on_settled.iter().for_each(|f| f(value, query_keys, meta));
+    }
+
+    /// Subscribe to the changes in the cache: entries being added, updated or removed.
+    /// Useful to persist the cache or to build devtools.
+    /// The observer stays subscribed until the returned [CacheEventsSubscription] is dropped.
+    ///
+    /// ## Example:
+    ///
+    /// ```no_run
+    /// let subscription = client.subscribe_cache_events(|event| match event {
+    ///     QueryCacheEvent::Updated { query_keys, result } => println!("{query_keys:?} is now {result:?}"),
+    ///     _ => {}
+    /// });
+    /// ```
+    pub fn subscribe_cache_events(
+        &self,
+        observer: impl Fn(&QueryCacheEvent<'_, T, E, K>) + MaybeSend + MaybeSync + 'static,
+    ) -> CacheEventsSubscription<T, E, K> {
+        let id = self
+            .cache_observers
+            .borrow_mut()
+            .insert(Shared::new(observer));
+        CacheEventsSubscription {
+            observers: self.cache_observers.clone(),
+            id,
+        }
+    }
+
+    /// Run the cache observers with the given event.
+    /// They are cloned out first so they are free to use the client.
+    pub(crate) fn emit_cache_event(&self, event: QueryCacheEvent<'_, T, E, K>) {
+        let observers = self.cache_observers.borrow().to_vec();
+        for observer in observers {
+            observer(&event);
+        }
+    }
+
+    /// Let the cache observers know the cached value of the given query keys changed.
+    /// The value is only cloned if there is any observer.
+    pub(crate) fn emit_cache_updated(
+        &self,
+        query_keys: &[K],
+        value: &QueryValue<CachedResult<T, E>>,
+    ) where
+        T: Clone,
+        E: Clone,
+    {
+        if self.cache_observers.borrow().is_empty() {
+            return;
+        }
+        let result = sync::read(&value).value().clone();
+        self.emit_cache_event(QueryCacheEvent::Updated {
+            query_keys,
+            result: &result,
+        });
+    }
+}
+
+impl<T, E, K: Eq + Hash> UseQueryClient<T, E, K> {
+    pub(crate) fn get_entry(&self, entry: &RegistryEntry<K>) -> Option<QueryListeners<T, E, K>> {
+        let registry = self.queries_registry.borrow();
+        registry.get(entry).cloned()
+    }
+
+    /// Get the entry with exactly the given query keys, looked up in the index of the cache.
+    /// If multiple query functions are cached with the same keys, the most recently updated one is used.
+    pub(crate) fn get_entry_with_keys(&self, query_keys: &[K]) -> Option<QueryListeners<T, E, K>> {
+        let registry = self.queries_registry.borrow();
+        registry
+            .with_keys(query_keys)
+            .map(|(_, query_listeners)| query_listeners)
+            .max_by_key(|query_listeners| sync::read(&query_listeners.value).updated_at())
+            .cloned()
+    }
+
+    /// Unregister a listener of the given entry, either a component or a [QueryHandle].
+    /// The entry is removed once it has no listeners left.
+    pub(crate) fn unregister_listener(
+        &self,
+        registry_entry: &RegistryEntry<K>,
+        scope_id: Option<ScopeId>,
+    ) {
+        let Some(query_listeners) = self.get_entry(registry_entry) else {
+            return;
+        };
+
+        // Remove this listener
+        match scope_id {
+            Some(scope_id) => {
+                query_listeners.listeners.borrow_mut().remove(&scope_id);
+                query_listeners
+                    .paused_listeners
+                    .borrow_mut()
+                    .remove(&scope_id);
+            }
+            None => {
+                let detached_listeners = &query_listeners.detached_listeners;
+                detached_listeners.decrement();
+            }
+        }
+
+        // Clear the queries registry of this listener if it was the last one,
+        // checking again once locked in case a listener was registered in the meantime
+        let removed = {
+            let mut queries_registry = self.queries_registry.borrow_mut();
+            self.is_entry_unused(registry_entry, &query_listeners)
+                && queries_registry.remove(registry_entry).is_some()
+        };
+        if removed {
+            query_listeners.discard();
+            self.emit_cache_event(QueryCacheEvent::Removed {
+                query_keys: &registry_entry.query_keys,
+            });
+            self.notify_data_listeners(&registry_entry.query_keys);
+            self.notify_fetching_listeners();
+        }
+    }
+
+    /// Check if nothing listens to the given entry nor watches its query keys, so it can be removed.
+    pub(crate) fn is_entry_unused(
+        &self,
+        registry_entry: &RegistryEntry<K>,
+        query_listeners: &QueryListeners<T, E, K>,
+    ) -> bool {
+        query_listeners.is_unused()
+            && !self
+                .watchers
+                .borrow()
+                .contains_key(&registry_entry.query_keys)
+    }
+
+    /// Call the given function to fetch the given entry from now on, see [QueryListeners::query_fn].
+    pub(crate) fn set_query_fn(
+        &self,
+        registry_entry: &RegistryEntry<K>,
+        query_fn: Arc<Box<QueryFn<T, E, K>>>,
+    ) {
+        if let Some(query_listeners) = self.get_entry(registry_entry) {
+            *query_listeners.query_fn.borrow_mut() = query_fn;
+        }
+    }
+
+    /// Stop re-rendering a component listening to the given entry, keeping the entry around until it resumes.
+    pub(crate) fn pause_listener(&self, registry_entry: &RegistryEntry<K>, scope_id: ScopeId) {
+        if let Some(query_listeners) = self.get_entry(registry_entry) {
+            if let Some(listener) = query_listeners.listeners.borrow_mut().remove(&scope_id) {
+                query_listeners
+                    .paused_listeners
+                    .borrow_mut()
+                    .insert(scope_id, listener);
+            }
+        }
+    }
+
+    /// Re-render a paused component listening to the given entry again, see [UseQueryClient::pause_listener].
+    pub(crate) fn resume_listener(&self, registry_entry: &RegistryEntry<K>, scope_id: ScopeId) {
+        if let Some(query_listeners) = self.get_entry(registry_entry) {
+            if let Some(listener) = query_listeners
+                .paused_listeners
+                .borrow_mut()
+                .remove(&scope_id)
+            {
+                query_listeners
+                    .listeners
+                    .borrow_mut()
+                    .insert(scope_id, listener);
+            }
+        }
+    }
+
+    /// Mark an entry as the most recently used one.
+    pub(crate) fn mark_used(&self, last_used: &Counter) {
+        last_used.set(self.uses.increment());
+    }
+
+    /// Mark the given entry as the most recently used one, if it still exists.
+    pub(crate) fn mark_entry_used(&self, registry_entry: &RegistryEntry<K>) {
+        if let Some(query_listeners) = self.get_entry(registry_entry) {
+            self.mark_used(&query_listeners.last_used);
+        }
+    }
+
+    /// Read the cached value of the given entry, marking it as used and discarding it if it expired.
+    pub(crate) fn read_entry<'a>(
+        &self,
+        registry_entry: &RegistryEntry<K>,
+        value: &'a QueryValue<CachedResult<T, E>>,
+    ) -> RwLockReadGuard<'a, CachedResult<T, E>> {
+        self.mark_entry_used(registry_entry);
+        let result = sync::read(value);
+        if !result.is_expired() {
+            return result;
+        }
+        drop(result);
+
+        // The caller might still hold a guard of an earlier read, which can't be waited for,
+        // in which case the value is expired by the next read instead
+        if let Some(mut result) = sync::try_write(value) {
+            result.expire();
+        }
+        sync::read(value)
+    }
+
+    /// Notify the tasks watching the given query keys, see [UseQueryClient::watch].
+    pub(crate) fn notify_watchers(&self, query_keys: &[K]) {
+        if let Some(watchers) = self.watchers.borrow().get(query_keys) {
+            for watcher in watchers {
+                watcher.borrow_mut().notify();
+            }
+        }
+    }
+
+    /// Notify the scopes reading the cached values of the given query keys, and the tasks watching them.
+    pub(crate) fn notify_data_listeners(&self, query_keys: &[K]) {
+        self.notify_watchers(query_keys);
+        self.notify_status_listeners();
+
+        let data_listeners = match self.data_listeners.borrow().get(query_keys) {
+            Some(data_listeners) => data_listeners.clone(),
+            None => return,
+        };
+
+        for listener in data_listeners {
+            (self.scheduler)(listener);
+        }
+    }
+
+    /// Get the status of the query with the given keys.
+    /// If multiple query functions are cached with the same keys, the most recently updated one is used.
+    pub(crate) fn query_status(&self, query_keys: &[K]) -> QueryStatus {
+        self.get_entry_with_keys(query_keys)
+            .map(|query_listeners| QueryStatus::of(&sync::read(&query_listeners.value)))
+            .unwrap_or(QueryStatus::NotRegistered)
+    }
+
+    /// Notify the scopes following the status of some query keys, only if it changed since they last rendered it.
+    fn notify_status_listeners(&self) {
+        let mut changed_listeners = Vec::new();
+        {
+            let mut status_listeners = self.status_listeners.borrow_mut();
+            for (query_keys, listeners) in status_listeners.iter_mut() {
+                let status = self.query_status(query_keys);
+                for (scope_id, last_status) in listeners.iter_mut() {
+                    if *last_status != status {
+                        *last_status = status;
+                        changed_listeners.push(*scope_id);
+                    }
+                }
+            }
+        }
+
+        for listener in changed_listeners {
+            (self.scheduler)(listener);
+        }
+    }
+
+    /// Notify the scopes following whether queries are being fetched.
+    pub(crate) fn notify_fetching_listeners(&self) {
+        self.notify_status_listeners();
+        let fetching_listeners = self.fetching_listeners.borrow().clone();
+        for listener in fetching_listeners {
+            (self.scheduler)(listener);
+        }
+    }
+
+    /// Get a snapshot of every entry in the cache, e.g to debug why a query is refetching.
+    /// It doesn't include the cached values, see [UseQueryClient::snapshot_with_values].
+    pub fn snapshot(&self) -> Vec<QueryEntrySnapshot<K>>
+    where
+        K: Clone,
+    {
+        self.snapshot_entries(|_| None)
+    }
+
+    /// Same as [UseQueryClient::snapshot] but including the cached values formatted with [Debug](std::fmt::Debug).
+    pub fn snapshot_with_values(&self) -> Vec<QueryEntrySnapshot<K>>
+    where
+        T: std::fmt::Debug,
+        E: std::fmt::Debug,
+        K: Clone,
+    {
+        self.snapshot_entries(|cached_result| Some(format!("{:?}", cached_result.value())))
+    }
+
+    fn snapshot_entries(
+        &self,
+        debug_value: impl Fn(&CachedResult<T, E>) -> Option<String>,
+    ) -> Vec<QueryEntrySnapshot<K>>
+    where
+        K: Clone,
+    {
+        self.queries_registry
+            .borrow()
+            .iter()
+            .map(|(entry, query_listeners)| {
+                let cached_result = sync::read(&query_listeners.value);
+                QueryEntrySnapshot {
+                    query_keys: entry.query_keys.clone(),
+                    status: QueryEntryStatus::of(&cached_result),
+                    is_fetching: cached_result.is_fetching(),
+                    updated_at: cached_result.updated_at(),
+                    listener_count: query_listeners.listeners.borrow().len()
+                        + query_listeners.detached_listeners.get(),
+                    meta: query_listeners.meta.as_ref().clone(),
+                    debug_value: debug_value(&cached_result),
+                }
+            })
+            .collect()
+    }
+
+    /// Get how many queries whose keys satisfy the given predicate are being fetched.
+    pub(crate) fn count_fetching(&self, is_matching: impl Fn(&[K]) -> bool) -> usize {
+        self.queries_registry
+            .borrow()
+            .iter()
+            .filter(|(entry, QueryListeners { value, .. })| {
+                is_matching(&entry.query_keys) && sync::read(&value).is_fetching()
+            })
+            .count()
+    }
+}
+
+impl<T, E, K> UseQueryClient<T, E, K>
+where
+    T: 'static + Clone,
+    E: 'static + Clone,
+    K: 'static + PartialEq + Eq + Hash + Clone,
+{
+    /// Register a listener of the entry of the given configuration, either a component or a [QueryHandle],
+    /// creating the entry if there is none yet.
+    pub(crate) fn register_listener(
+        &self,
+        config: &QueryConfig<T, E, K>,
+        scope_id: Option<ScopeId>,
+    ) -> QueryValue<CachedResult<T, E>> {
+        let registry_entry = &config.registry_entry;
+        let mut queries_registry = self.queries_registry.borrow_mut();
+        let is_new_entry = !queries_registry.contains_key(registry_entry);
+        #[cfg(debug_assertions)]
+        self.check_registration(&queries_registry, config);
+
+        // Create a group of listeners for the given [RegistryEntry] key.
+        let query_listeners = queries_registry
+            .get_or_insert_with(registry_entry.clone(), || self.new_query_listeners(config));
+        // Entries prefetched as the next page of another query start prefetching their own once they are used
+        if query_listeners.prefetch_next.is_none() {
+            query_listeners.prefetch_next = config.prefetch_next.clone();
+        }
+
+        // Register this listener
+        match scope_id {
+            Some(scope_id) => {
+                query_listeners.listeners.borrow_mut().insert(
+                    scope_id,
+                    QueryListener {
+                        notify_on: config.notify_on,
+                        filter: config.listener_filter.clone(),
+                    },
+                );
+            }
+            None => {
+                let detached_listeners = &query_listeners.detached_listeners;
+                detached_listeners.increment();
+            }
+        }
+
+        self.mark_used(&query_listeners.last_used);
+        let value = query_listeners.value.clone();
+        drop(queries_registry);
+
+        query_event!(
+            keys = %self.fmt_keys(&registry_entry.query_keys),
+            new_entry = is_new_entry,
+            component = scope_id.is_some(),
+            "query listener registered"
+        );
+
+        // Let the observers and the scopes reading these query keys know there is a value for them now
+        if is_new_entry {
+            self.emit_cache_event(QueryCacheEvent::EntryAdded {
+                query_keys: &registry_entry.query_keys,
+            });
+            self.notify_data_listeners(&registry_entry.query_keys);
+            self.evict_least_recently_used();
+        }
+
+        value
+    }
+
+    /// Register a query without any component, see [QueryHandle].
+    ///
+    /// ## Example:
+    ///
+    /// ```no_run
+    /// let client = UseQueryClient::<QueryValue, QueryError, QueryKeys>::new_detached();
+    /// let user = client.register_query(vec![QueryKeys::User(0)], fetch_user);
+    /// assert_eq!(user.fetch().await, QueryResult::Ok(QueryValue::UserName("Marc".to_string())));
+    /// ```
+    pub fn register_query<Q, F>(&self, query_keys: Vec<K>, query_fn: Q) -> QueryHandle<T, E, K>
+    where
+        Q: 'static + Fn(Vec<K>) -> F + MaybeSend + MaybeSync,
+        F: 'static + Future<Output = QueryResult<T, E>> + MaybeSend,
+    {
+        self.register_query_config(QueryConfig::new(query_keys, query_fn))
+    }
+
+    /// Register a query with the given configuration without any component, see [QueryHandle].
+    pub fn register_query_config(&self, mut config: QueryConfig<T, E, K>) -> QueryHandle<T, E, K> {
+        self.normalize_keys(&mut config.registry_entry.query_keys);
+        let value = self.register_listener(&config, None);
+        QueryHandle {
+            client: self.clone(),
+            value,
+            registry_entry: config.registry_entry,
+        }
+    }
+
+    /// Follow the cached value of the given query keys outside of any component, see [QueryWatcher].
+    pub fn watch(&self, query_keys: &[K]) -> QueryWatcher<T, E, K> {
+        let mut query_keys = query_keys.to_vec();
+        self.normalize_keys(&mut query_keys);
+        let state = Shared::<Lock<_>>::default();
+        self.watchers
+            .borrow_mut()
+            .entry(query_keys.clone())
+            .or_default()
+            .push(state.clone());
+        QueryWatcher {
+            client: self.clone(),
+            query_keys,
+            state,
+            seen: 0,
+        }
+    }
+
+    /// Evict the least recently used entries without listeners while the cache has more than
+    /// [QueryClientConfig::max_entries].
+    pub(crate) fn evict_least_recently_used(&self) {
+        let Some(max_entries) = self.config.max_entries else {
+            return;
+        };
+
+        let mut evicted_entries = Vec::new();
+        let mut evicted_listeners = Vec::new();
+        {
+            let mut queries_registry = self.queries_registry.borrow_mut();
+            while queries_registry.len() > max_entries {
+                let least_recently_used = queries_registry
+                    .iter()
+                    .filter(|(entry, query_listeners)| self.is_entry_unused(entry, query_listeners))
+                    .min_by_key(|(_, query_listeners)| query_listeners.last_used.get())
+                    .map(|(entry, _)| entry.clone());
+                let Some(entry) = least_recently_used else {
+                    break;
+                };
+                evicted_listeners.extend(queries_registry.remove(&entry));
+                evicted_entries.push(entry);
+            }
+        }
+        for query_listeners in evicted_listeners {
+            query_listeners.discard();
+        }
+
+        for entry in evicted_entries {
+            query_event!(keys = %self.fmt_keys(&entry.query_keys), "query entry evicted");
+            self.emit_cache_event(QueryCacheEvent::Evicted {
+                query_keys: &entry.query_keys,
+            });
+            self.notify_data_listeners(&entry.query_keys);
+        }
+    }
+
+    /// Create the listeners of a new entry, seeded with the hydrated result of its query keys if there is one.
+    fn new_query_listeners(&self, config: &QueryConfig<T, E, K>) -> QueryListeners<T, E, K> {
+        let mut query_listeners = QueryListeners::from_config(config, &self.config);
+        query_listeners.created = self.created_entries.increment();
+        if config.initial_value.is_some() {
+            return query_listeners;
+        }
+
+        let hydrated_query = self
+            .hydrated_queries
+            .borrow_mut()
+            .remove(&config.registry_entry.query_keys);
+        if let Some(HydratedQuery {
+            data,
+            age,
+            hydrated_at,
+            is_stale,
+        }) = hydrated_query
+        {
+            let now = self.config.clock.now();
+            let age = age + self.config.clock.elapsed(hydrated_at);
+            let mut value = sync::write(&query_listeners.value);
+            // Results older than the max age are not shown, e.g when restored from a storage long after they were persisted
+            if value.max_age.is_some_and(|max_age| age > max_age) {
+                query_event!(
+                    keys = %self.fmt_keys(&config.registry_entry.query_keys),
+                    "hydrated result dropped, older than the max age"
+                );
+            } else {
+                // The clock might not go back that far, e.g after a restart, so it's marked as stale explicitly
+                let updated_at = now.checked_sub(age).unwrap_or(now);
+                value.value = QueryResult::Ok(data);
+                value.instant = Some(updated_at);
+                value.data_instant = Some(updated_at);
+                value.data_source = Some(DataSource::Restored);
+                // Only fetch it again once it's stale
+                value.has_been_queried = true;
+                value.has_been_invalidated = is_stale || age >= value.stale_time;
+            }
+        }
+        query_listeners
+    }
+
+    /// Get the successful results in the cache, e.g to send them along with the HTML rendered on the server.
+    /// See [UseQueryClient::hydrate].
+    pub fn dehydrate(&self) -> DehydratedState<T, K> {
+        let queries = self
+            .queries_registry
+            .borrow()
+            .iter()
+            .filter_map(|(entry, QueryListeners { value, .. })| {
+                let value = sync::read(&value);
+                match (value.value(), value.updated_at()) {
+                    (QueryResult::Ok(data), Some(updated_at)) => Some(DehydratedQuery {
+                        query_keys: entry.query_keys.clone(),
+                        data: data.clone(),
+                        age: self.config.clock.elapsed(updated_at),
+                    }),
+                    _ => None,
+                }
+            })
+            .collect();
+        DehydratedState { queries }
+    }
+
+    /// Seed the cache with the results of a [DehydratedState], e.g the ones fetched while rendering on the server.
+    /// Queries registered afterwards with the same keys start with these results
+    /// and are not fetched until they become stale.
+    /// Call it before the components using those queries are rendered.
+    ///
+    /// ## Example:
+    ///
+    /// ```no_run
+    /// let client = use_init_query_client::<QueryValue, QueryError, QueryKeys>(cx);
+    /// cx.use_hook(|| client.hydrate(serde_json::from_str(&state).unwrap()));
+    /// ```
+    pub fn hydrate(&self, state: DehydratedState<T, K>) {
+        self.seed_queries(state.queries, false);
+    }
+
+    /// Store results to seed the entries created afterwards with the same keys.
+    pub(crate) fn seed_queries(&self, queries: Vec<DehydratedQuery<T, K>>, is_stale: bool) {
+        let now = self.config.clock.now();
+        let mut hydrated_queries = self.hydrated_queries.borrow_mut();
+        for DehydratedQuery {
+            query_keys,
+            data,
+            age,
+        } in queries
+        {
+            let mut query_keys = query_keys;
+            self.normalize_keys(&mut query_keys);
+            hydrated_queries.insert(
+                query_keys,
+                HydratedQuery {
+                    data,
+                    age,
+                    hydrated_at: now,
+                    is_stale,
+                },
+            );
+        }
+    }
+
+    /// Persist the successful results of the queries with the given [QueryPersister],
+    /// restoring the ones it already stored. Restored results are shown right away
+    /// but they are stale, so they are refetched in the background,
+    /// unless they are older than the [QueryConfig::max_age] of their query, which drops them instead.
+    ///
+    /// Changes are written in batches, once the [QueryPersister::debounce] future resolves.
+    /// Results of entries removed from the cache stay persisted, unless they were reset by
+    /// [UseQueryClient::remove_queries] while still having listeners.
+    ///
+    /// ## Example:
+    ///
+    /// ```no_run
+    /// provide_query_client(cx, || {
+    ///     UseQueryClient::<QueryValue, QueryError, QueryKeys>::new(cx.schedule_update_any())
+    ///         .with_persister(LocalStoragePersister::new("queries:"))
+    /// });
+    /// ```
+    pub fn with_persister(self, persister: impl QueryPersister<T, K> + 'static) -> Self
+    where
+        T: MaybeSend + MaybeSync,
+        K: MaybeSend + MaybeSync,
+    {
+        self.seed_queries(persister.restore_all(), true);
+
+        let persister = Shared::new(persister);
+        let pending_writes = Shared::new(Lock::new(PendingWrites::<T, K>::default()));
+        let spawner = self.spawner.clone();
+        let clock = self.config.clock.clone();
+        self.cache_observers.borrow_mut().insert(Shared::new(
+            move |event: &QueryCacheEvent<'_, T, E, K>| {
+                let QueryCacheEvent::Updated { query_keys, result } = event else {
+                    return;
+                };
+                // The result was just updated, so its age is counted from now
+                let data = match result {
+                    QueryResult::Ok(data) => Some((data.clone(), clock.now())),
+                    QueryResult::Loading(None) => None,
+                    _ => return,
+                };
+
+                let is_flush_scheduled = {
+                    let mut pending_writes = pending_writes.borrow_mut();
+                    let is_flush_scheduled = !pending_writes.is_empty();
+                    pending_writes.insert(query_keys.to_vec(), data);
+                    is_flush_scheduled
+                };
+                if is_flush_scheduled {
+                    return;
+                }
+
+                let flush = {
+                    let persister = persister.clone();
+                    let pending_writes = pending_writes.clone();
+                    let clock = clock.clone();
+                    async move {
+                        Box::into_pin(persister.debounce()).await;
+                        let writes = std::mem::take(&mut *pending_writes.borrow_mut());
+                        for (query_keys, data) in writes {
+                            match data {
+                                Some((data, updated_at)) => persister.persist(DehydratedQuery {
+                                    query_keys,
+                                    data,
+                                    age: clock.elapsed(updated_at),
+                                }),
+                                None => persister.remove(&query_keys),
+                            }
+                        }
+                    }
+                };
+                // Otherwise the writes would stay pending, and every later one would wait for them
+                if !spawn_with(&spawner, flush) {
+                    pending_writes.borrow_mut().clear();
+                    query_warning!(
+                        "query results weren't persisted, there is no component nor spawner"
+                    );
+                }
+            },
+        ));
+        self
+    }
+
+    /// Notify the current listeners of the given entry of a change, if it still exists.
+    pub(crate) fn notify_listeners(&self, entry: &RegistryEntry<K>, change: QueryChange) {
+        let Some(QueryListeners {
+            value, listeners, ..
+        }) = self.get_entry(entry)
+        else {
+            return;
+        };
+        self.notify_entry_listeners(&entry.query_keys, &listeners, &value, change);
+    }
+
+    /// Notify the given listeners of an entry of a change, through a handle kept from before.
+    pub(crate) fn notify_entry_listeners(
+        &self,
+        query_keys: &[K],
+        listeners: &Lock<HashMap<ScopeId, QueryListener<T, E>>>,
+        value: &QueryValue<CachedResult<T, E>>,
+        change: QueryChange,
+    ) {
+        query_event!(
+            keys = %self.fmt_keys(query_keys),
+            listeners = listeners.borrow().len(),
+            change = ?change,
+            "query listeners notified"
+        );
+        self.schedule_listeners(&listeners.borrow(), change, value);
+        self.notify_data_listeners(query_keys);
+    }
+
+    /// Re-render the given listeners that want to be notified of the change to the value of their entry.
+    fn schedule_listeners(
+        &self,
+        listeners: &HashMap<ScopeId, QueryListener<T, E>>,
+        change: QueryChange,
+        value: &QueryValue<CachedResult<T, E>>,
+    ) {
+        let result = sync::read(value);
+        for (scope_id, listener) in listeners {
+            if listener.accepts(change, &result) {
+                (self.scheduler)(*scope_id);
+            }
+        }
+    }
+
+    /// Call the query function of an entry and run the callbacks with its result.
+    /// The fetch in flight of the entry, if any, is aborted as its result would be discarded anyway,
+    /// and this one is in turn aborted if a newer one starts. Returns `None` once aborted.
+    #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+    #[allow(clippy::too_many_arguments)]
+    async fn fetch_entry(
+        &self,
+        query_fn: &QueryFn<T, E, K>,
+        query_keys: &[K],
+        cached: &RwLock<CachedResult<T, E>>,
+        retry: u32,
+        retry_if: Option<&RetryIfFn<E>>,
+        retry_delay: Option<&ExponentialBackoff>,
+        meta: &QueryMeta,
+        in_flight: &Lock<Option<InFlightFetch>>,
+        reason: &'static str,
+    ) -> Option<QueryResult<T, E>> {
+        query_event!(keys = %self.fmt_keys(query_keys), reason, "query fetch started");
+        #[cfg(feature = "tracing")]
+        let fetch_start = crate::clock::Instant::now();
+
+        let (handle, registration) = AbortHandle::new_pair();
+        let signal = QueryAbortSignal::default();
+        let previous_fetch = in_flight.borrow_mut().replace(InFlightFetch {
+            handle,
+            signal: signal.clone(),
+        });
+        if let Some(previous_fetch) = previous_fetch {
+            query_event!(keys = %self.fmt_keys(query_keys), "query fetch superseded");
+            previous_fetch.abort();
+        }
+
+        let metrics_start = self.metrics.as_ref().map(|_| self.config.clock.now());
+        let value = Abortable::new(
+            QueryListeners::fetch(
+                query_fn,
+                query_keys.to_vec(),
+                cached,
+                retry,
+                retry_if,
+                retry_delay,
+                &self.config.clock,
+                meta,
+                &signal,
+                self.config.fetch_limiter.as_ref(),
+                self.config.rate_limiter.as_ref(),
+                self.interceptor.as_deref().map(Box::as_ref),
+            ),
+            registration,
+        )
+        .await
+        .ok()?;
+
+        // It can't be abandoned anymore
+        {
+            let mut in_flight = in_flight.borrow_mut();
+            if in_flight
+                .as_ref()
+                .is_some_and(|in_flight| in_flight.signal == signal)
+            {
+                in_flight.take();
+            }
+        }
+
+        if let (Some(metrics), Some(metrics_start)) = (&self.metrics, metrics_start) {
+            let duration = self.config.clock.elapsed(metrics_start);
+            metrics.record_fetch(query_keys, duration, value.is_ok());
+        }
+
+        query_event!(
+            keys = %self.fmt_keys(query_keys),
+            duration_ms = fetch_start.elapsed().as_millis() as u64,
+            ok = value.is_ok(),
+            "query fetch finished"
+        );
+        self.run_callbacks(&value, query_keys, meta);
+        Some(value)
+    }
+
+    /// Prefetch the next page of the given entry in the **background**, unless it's fresh or being fetched already,
+    /// see [QueryConfig::prefetch_next].
+    fn prefetch_next_page(&self, entry: &RegistryEntry<K>) {
+        let Some(query_listeners) = self.get_entry(entry) else {
+            return;
+        };
+        let Some(prefetch_next) = &query_listeners.prefetch_next else {
+            return;
+        };
+        let next_keys = match sync::read(&query_listeners.value).value() {
+            QueryResult::Ok(data) => prefetch_next(data),
+            _ => None,
+        };
+        let Some(mut query_keys) = next_keys else {
+            return;
+        };
+        self.normalize_keys(&mut query_keys);
+        let next_entry = RegistryEntry {
+            query_keys,
+            query_fn_id: entry.query_fn_id.clone(),
+        };
+        if next_entry == *entry {
+            return;
+        }
+
+        let is_new_entry = {
+            let mut queries_registry = self.queries_registry.borrow_mut();
+            if let Some(next_listeners) = queries_registry.get(&next_entry) {
+                let value = sync::read(&next_listeners.value);
+                if value.is_fresh() || value.is_fetching() {
+                    return;
+                }
+                false
+            } else {
+                let mut next_listeners = query_listeners.for_next_page();
+                next_listeners.created = self.created_entries.increment();
+                queries_registry.insert(next_entry.clone(), next_listeners);
+                true
+            }
+        };
+        if is_new_entry {
+            self.emit_cache_event(QueryCacheEvent::EntryAdded {
+                query_keys: &next_entry.query_keys,
+            });
+        }
+        self.mark_entry_used(&next_entry);
+        self.evict_least_recently_used();
+
+        query_event!(keys = %self.fmt_keys(&next_entry.query_keys), "next page prefetch started");
+        let client = self.clone();
+        self.spawn_task(async move {
+            client.run_new_query(&next_entry).await;
+        });
+    }
+
+    /// Get the entries whose keys satisfy the given predicate.
+    /// The registry is only borrowed to clone them, so the caller is free
+    /// to run any code (which might use the client again) while working with them.
+    pub(crate) fn get_entries_matching(
+        &self,
+        is_matching: impl Fn(&[K]) -> bool,
+    ) -> Vec<(RegistryEntry<K>, QueryListeners<T, E, K>)> {
+        self.get_entries_where(|entry| is_matching(&entry.query_keys))
+    }
+
+    /// Same as [UseQueryClient::get_entries_matching] but matching the whole entry, query function id included.
+    pub(crate) fn get_entries_where(
+        &self,
+        is_matching: impl Fn(&RegistryEntry<K>) -> bool,
+    ) -> Vec<(RegistryEntry<K>, QueryListeners<T, E, K>)> {
+        let entries = self
+            .queries_registry
+            .borrow()
+            .iter()
+            .map(|(entry, query_listeners)| (entry.clone(), query_listeners.clone()))
+            .collect::<Vec<_>>();
+        entries
+            .into_iter()
+            .filter(|(entry, _)| is_matching(entry))
+            .collect()
+    }
+
+    /// Same as [UseQueryClient::get_entries_where] but only among the entries whose query keys contain
+    /// any of the given keys, which are looked up in the index of the cache instead of going through all of it.
+    pub(crate) fn get_entries_with_any_key(
+        &self,
+        keys: &[K],
+        is_matching: impl Fn(&RegistryEntry<K>) -> bool,
+    ) -> Vec<(RegistryEntry<K>, QueryListeners<T, E, K>)> {
+        let entries = self
+            .queries_registry
+            .borrow()
+            .with_any_key(keys)
+            .map(|(entry, query_listeners)| (entry.clone(), query_listeners.clone()))
+            .collect::<Vec<_>>();
+        entries
+            .into_iter()
+            .filter(|(entry, _)| is_matching(entry))
+            .collect()
+    }
+
+    /// Same as [UseQueryClient::get_entries_with_any_key] but only the entries whose query keys are exactly the given ones,
+    /// one per query function.
+    pub(crate) fn get_entries_with_keys(
+        &self,
+        query_keys: &[K],
+    ) -> Vec<(RegistryEntry<K>, QueryListeners<T, E, K>)> {
+        self.queries_registry
+            .borrow()
+            .with_keys(query_keys)
+            .map(|(entry, query_listeners)| (entry.clone(), query_listeners.clone()))
+            .collect()
+    }
+
+    /// Refetch the entry after the interval given by `interval` for its latest result, for as long as the returned future is polled.
+    /// The interval is evaluated again whenever a fetch settles, and polling stops while it's `None`.
+    /// Unless `in_background`, it pauses while the app is hidden and
+    /// once visible again it refetches right away, but only if the value went stale.
+    pub(crate) async fn run_refetch_interval(
+        &self,
+        entry: &RegistryEntry<K>,
+        interval: &RefetchIntervalFn<T, E>,
+        in_background: bool,
+    ) {
+        loop {
+            let Some(QueryListeners { value, settled, .. }) = self.get_entry(entry) else {
+                return;
+            };
+            let settles = settled.settles();
+            let next_poll = {
+                let value = sync::read(&value);
+                if value.is_fetching() {
+                    None
+                } else {
+                    interval(value.value())
+                }
+            };
+
+            // Wait for the next fetch to settle while it's being fetched or not polled,
+            // and restart the timer if one settles in the meantime, e.g after an invalidation
+            let Some(next_poll) = next_poll else {
+                settled.settled_since(settles).await;
+                continue;
+            };
+            let timer = Box::into_pin(self.config.clock.sleep(next_poll));
+            if let Either::Right(_) = select(timer, Box::pin(settled.settled_since(settles))).await
+            {
+                continue;
+            }
+
+            let Some(query_listeners) = self.get_entry(entry) else {
+                return;
+            };
+            // Don't poll while every listener is paused
+            if !query_listeners.is_observed() {
+                continue;
+            }
+
+            if !in_background && !self.config.visibility_manager.is_visible() {
+                self.wait_until_visible().await;
+                self.run_new_query(entry).await;
+                continue;
+            }
+
+            sync::write(&query_listeners.value).has_been_invalidated = true;
+            self.run_new_query(entry).await;
+        }
+    }
+
+    /// Fetch the entry for a component that just mounted, depending on its [RefetchOnMount] mode.
+    pub(crate) async fn run_mount_query(
+        &self,
+        entry: &RegistryEntry<K>,
+        refetch_on_mount: RefetchOnMount,
+    ) {
+        if let Some(QueryListeners { value, .. }) = self.get_entry(entry) {
+            let mut value = sync::write(&value);
+            if value.has_been_queried() {
+                match refetch_on_mount {
+                    RefetchOnMount::Always => value.has_been_invalidated = true,
+                    RefetchOnMount::IfStale => {}
+                    RefetchOnMount::Never => return,
+                }
+            }
+        }
+        self.run_new_query(entry).await;
+    }
+
+    /// Fetch the entry even if it's fresh, and from then on like any other entry if it was a lazy one.
+    /// Forced fetches happen even while the client is paused, see [UseQueryClient::pause_all].
+    pub(crate) async fn trigger_query(&self, entry: &RegistryEntry<K>, force: bool) {
+        if let Some(QueryListeners { value, .. }) = self.get_entry(entry) {
+            let mut value = sync::write(&value);
+            value.has_been_queried = true;
+            value.has_been_invalidated = true;
+        }
+        self.run_query(entry, force).await;
+    }
+
+    pub(crate) async fn run_new_query(&self, entry: &RegistryEntry<K>) {
+        self.run_query(entry, false).await;
+    }
+
+    /// Record the entry to be revalidated once the client is resumed, if it's paused.
+    fn hold_if_paused(&self, entry: &RegistryEntry<K>) -> bool {
+        let mut paused = self.paused.borrow_mut();
+        if paused.is_paused {
+            paused.pending.insert(entry.clone());
+        }
+        paused.is_paused
+    }
+
+    async fn run_query(&self, entry: &RegistryEntry<K>, force: bool) {
+        let Some(query_listeners) = self.get_entry(entry) else {
+            return;
+        };
+
+        // Paused clients revalidate the entry once resumed instead
+        if !force && self.hold_if_paused(entry) {
+            return;
+        }
+
+        // Live streams keep the value up to date, they are only subscribed to again when invalidated,
+        // or never if they are subscriptions
+        if query_listeners.is_streaming()
+            && (query_listeners.subscription.is_some()
+                || !sync::read(&query_listeners.value).has_been_invalidated)
+        {
+            return;
+        }
+
+        let QueryListeners {
+            value,
+            query_fn,
+            stream_fn,
+            stream,
+            in_flight,
+            subscription,
+            listeners,
+            is_equal,
+            loading_on_refetch,
+            retry,
+            retry_if,
+            retry_delay,
+            generation,
+            last_used,
+            debounce,
+            triggers,
+            min_fetch_interval,
+            throttle,
+            fetch_on_mount,
+            network_mode,
+            enabled,
+            meta,
+            prefetch_next,
+            settled,
+            entry_callbacks,
+            ..
+        } = query_listeners;
+
+        // Lazy queries wait to be triggered, and disabled ones to be enabled
+        if !fetch_on_mount && sync::read(&value).is_idle() {
+            return;
+        }
+        if enabled.is_some_and(|enabled| !enabled()) {
+            return;
+        }
+
+        // Wait for the window to close, giving up if another trigger arrived in the meantime
+        if let Some(debounce) = debounce {
+            let trigger = triggers.increment();
+            Box::into_pin(self.config.clock.sleep(debounce)).await;
+            if triggers.get() != trigger {
+                return;
+            }
+        }
+
+        // Defer stale fetches until the interval since the last one elapses,
+        // collapsing the triggers in between into a single trailing fetch
+        if let Some(min_fetch_interval) = min_fetch_interval {
+            let remaining = throttle
+                .borrow()
+                .last_fetch
+                .and_then(|last_fetch| {
+                    min_fetch_interval.checked_sub(self.config.clock.elapsed(last_fetch))
+                })
+                .filter(|remaining| !remaining.is_zero());
+            if let Some(remaining) = remaining {
+                if !sync::read(&value).is_fresh() {
+                    if std::mem::replace(&mut throttle.borrow_mut().has_trailing_fetch, true) {
+                        return;
+                    }
+                    Box::into_pin(self.config.clock.sleep(remaining)).await;
+                    throttle.borrow_mut().has_trailing_fetch = false;
+                }
+            }
+        }
+
+        // Serve the cached value while offline, and fetch it once the app is back online,
+        // unless another task is already waiting to
+        if network_mode == NetworkMode::OfflineFirst && !self.online_status().is_online() {
+            let is_pausing = {
+                let mut value = sync::write(&value);
+                let should_fetch =
+                    (!value.is_fresh() && !value.is_fetching()) || !value.has_been_queried();
+                if should_fetch && value.is_offline_paused {
+                    return;
+                }
+                value.is_offline_paused = should_fetch;
+                should_fetch
+            };
+            if is_pausing {
+                query_event!(keys = %self.fmt_keys(&entry.query_keys), "query fetch paused while offline");
+                self.schedule_listeners(&listeners.borrow(), QueryChange::Loading, &value);
+                self.wait_until_online().await;
+                sync::write(&value).is_offline_paused = false;
+            }
+        }
+
+        // Check the cached value, mark it as fetching and start a new generation under a single lock,
+        // so concurrent validations of the same entry can't both decide to fetch it,
+        // and a result cached in the meantime isn't overwritten by an older fetch
+        let fetch_state = {
+            let mut value = sync::write(&value);
+            let should_fetch =
+                (!value.is_fresh() && !value.is_fetching()) || !value.has_been_queried();
+            should_fetch.then(|| {
+                let has_been_mutated = value.has_been_mutated();
+                let has_been_queried = value.has_been_queried();
+                // Mark as queried and fetching, only change to `Loading` if had been changed at some point
+                value.set_fetching(has_been_mutated && loading_on_refetch);
+                let fetch_generation = generation.increment();
+                (has_been_mutated, has_been_queried, fetch_generation)
+            })
+        };
+
+        if let Some(metrics) = &self.metrics {
+            match fetch_state {
+                Some(_) => metrics.record_cache_miss(&entry.query_keys),
+                None => metrics.record_cache_hit(&entry.query_keys),
+            }
+        }
+
+        if let Some((has_been_mutated, has_been_queried, fetch_generation)) = fetch_state {
+            self.notify_fetching_listeners();
+            if has_been_mutated {
+                self.schedule_listeners(&listeners.borrow(), QueryChange::Loading, &value);
+                self.notify_data_listeners(&entry.query_keys);
+            }
+
+            // Fetch the result
+            if let Some(stream_fn) = stream_fn {
+                self.run_stream(
+                    entry,
+                    &stream_fn,
+                    &stream,
+                    subscription.as_deref(),
+                    fetch_generation,
+                )
+                .await;
+                return;
+            }
+            let reason = if has_been_queried { "stale" } else { "initial" };
+            let query_fn = query_fn.borrow().clone();
+            let Some(new_value) = self
+                .fetch_entry(
+                    query_fn.as_ref(),
+                    &entry.query_keys,
+                    &value,
+                    retry,
+                    retry_if.as_deref().map(Box::as_ref),
+                    retry_delay.as_ref(),
+                    &meta,
+                    &in_flight,
+                    reason,
+                )
+                .await
+            else {
+                return;
+            };
+            throttle.borrow_mut().last_fetch = Some(self.config.clock.now());
+
+            // Discard the result if a newer fetch was started or the cached value was removed in the meantime
+            if generation.get() != fetch_generation {
+                return;
+            }
+
+            let settled_value = entry_callbacks.keep(&new_value);
+            let has_changed =
+                sync::write(&value).set_value(new_value, is_equal.as_deref().map(Box::as_ref));
+            settled.notify();
+            if let Some(settled_value) = settled_value {
+                entry_callbacks.run(&settled_value);
+            }
+            self.mark_used(&last_used);
+            query_event!(
+                keys = %self.fmt_keys(&entry.query_keys),
+                changed = has_changed,
+                "query result cached"
+            );
+            self.notify_fetching_listeners();
+            if prefetch_next.is_some() {
+                self.prefetch_next_page(entry);
+            }
+            if !has_changed {
+                // Still let the watchers know the fetch settled
+                self.notify_watchers(&entry.query_keys);
+                return;
+            }
+            self.emit_cache_updated(&entry.query_keys, &value);
+
+            // The listeners are shared with the registry, so they are the current ones
+            let change = QueryChange::of(sync::read(&value).value());
+            self.notify_entry_listeners(&entry.query_keys, &listeners, &value, change);
+        } else {
+            self.schedule_listeners(&listeners.borrow(), QueryChange::Loading, &value);
+        }
+    }
+
+    /// Subscribe to the stream of an entry, replacing its current subscription,
+    /// and cache every value it yields until it ends or is torn down.
+    /// Subscriptions either subscribe again once the stream ends or cache their error.
+    async fn run_stream(
+        &self,
+        entry: &RegistryEntry<K>,
+        stream_fn: &StreamFn<T, E, K>,
+        stream: &Lock<Option<AbortHandle>>,
+        subscription: Option<&SubscriptionEnd<E>>,
+        stream_generation: usize,
+    ) {
+        loop {
+            self.subscribe_stream(entry, stream_fn, stream, stream_generation)
+                .await;
+
+            let Some(QueryListeners {
+                value,
+                is_equal,
+                generation,
+                meta,
+                settled,
+                entry_callbacks,
+                ..
+            }) = self.get_entry(entry)
+            else {
+                return;
+            };
+            // Torn down, rather than ended on its own
+            if generation.get() != stream_generation {
+                return;
+            }
+            query_event!(keys = %self.fmt_keys(&entry.query_keys), "query stream ended");
+
+            match subscription {
+                Some(SubscriptionEnd::Resubscribe(delay)) => {
+                    Box::into_pin(self.config.clock.sleep(*delay)).await;
+                    if self.get_entry(entry).is_none() || generation.get() != stream_generation {
+                        return;
+                    }
+                }
+                Some(SubscriptionEnd::Fail(error)) => {
+                    stream.borrow_mut().take();
+                    let new_value = QueryResult::Err(error.clone());
+                    self.run_callbacks(&new_value, &entry.query_keys, &meta);
+                    let settled_value = entry_callbacks.keep(&new_value);
+                    sync::write(&value).set_value(new_value, is_equal.as_deref().map(Box::as_ref));
+                    settled.notify();
+                    if let Some(settled_value) = settled_value {
+                        entry_callbacks.run(&settled_value);
+                    }
+                    self.emit_cache_updated(&entry.query_keys, &value);
+                    self.notify_fetching_listeners();
+                    self.notify_listeners(entry, QueryChange::Error);
+                    return;
+                }
+                // Subscribed to again the next time the entry goes stale
+                None => {
+                    stream.borrow_mut().take();
+                    sync::write(&value).is_fetching = false;
+                    settled.notify();
+                    self.notify_fetching_listeners();
+                    self.notify_listeners(entry, QueryChange::Loading);
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Cache every value the stream of an entry yields, until it ends or is torn down.
+    async fn subscribe_stream(
+        &self,
+        entry: &RegistryEntry<K>,
+        stream_fn: &StreamFn<T, E, K>,
+        stream: &Lock<Option<AbortHandle>>,
+        stream_generation: usize,
+    ) {
+        let (handle, registration) = AbortHandle::new_pair();
+        if let Some(previous_stream) = stream.borrow_mut().replace(handle) {
+            previous_stream.abort();
+        }
+        query_event!(keys = %self.fmt_keys(&entry.query_keys), "query stream subscribed");
+
+        let mut values = Abortable::new(stream_fn(entry.query_keys.clone()), registration);
+        while let Some(new_value) = values.next().await {
+            let Some(QueryListeners {
+                value,
+                is_equal,
+                generation,
+                last_used,
+                meta,
+                settled,
+                entry_callbacks,
+                ..
+            }) = self.get_entry(entry)
+            else {
+                return;
+            };
+
+            // Stop once a newer fetch was started or the cached value was removed
+            if generation.get() != stream_generation {
+                return;
+            }
+
+            self.run_callbacks(&new_value, &entry.query_keys, &meta);
+            let settled_value = entry_callbacks.keep(&new_value);
+            let has_changed =
+                sync::write(&value).set_value(new_value, is_equal.as_deref().map(Box::as_ref));
+            settled.notify();
+            if let Some(settled_value) = settled_value {
+                entry_callbacks.run(&settled_value);
+            }
+            self.mark_used(&last_used);
+            self.notify_fetching_listeners();
+            if has_changed {
+                self.emit_cache_updated(&entry.query_keys, &value);
+                let change = QueryChange::of(sync::read(&value).value());
+                self.notify_listeners(entry, change);
+            }
+        }
+    }
+
+    pub(crate) async fn invalidate_queries_inner(
+        &self,
+        is_matching: impl Fn(&[K]) -> bool,
+        refetch_unobserved: bool,
+        silent: bool,
+    ) -> Vec<InvalidationOutcome<K, E>> {
+        self.invalidate_entries_inner(
+            |entry| is_matching(&entry.query_keys),
+            refetch_unobserved,
+            silent,
+            InvalidateOptions::default(),
+        )
+        .await
+    }
+
+    /// Same as [UseQueryClient::invalidate_queries_inner] but matching the whole entry, query function id included.
+    pub(crate) async fn invalidate_entries_inner(
+        &self,
+        is_matching: impl Fn(&RegistryEntry<K>) -> bool,
+        refetch_unobserved: bool,
+        silent: bool,
+        options: InvalidateOptions,
+    ) -> Vec<InvalidationOutcome<K, E>> {
+        let entries = self.get_entries_where(is_matching);
+        self.invalidate_listed_entries(entries, refetch_unobserved, silent, options)
+            .await
+    }
+
+    /// Invalidate the queries whose keys contain any of the given keys,
+    /// found in the index of the cache instead of matching every entry, see [UseQueryClient::get_entries_with_any_key].
+    pub(crate) async fn invalidate_keys_inner(
+        &self,
+        keys_to_invalidate: &[K],
+        is_matching: impl Fn(&RegistryEntry<K>) -> bool,
+        refetch_unobserved: bool,
+        silent: bool,
+        options: InvalidateOptions,
+    ) -> Vec<InvalidationOutcome<K, E>> {
+        let entries = self.get_entries_with_any_key(keys_to_invalidate, is_matching);
+        self.invalidate_listed_entries(entries, refetch_unobserved, silent, options)
+            .await
+    }
+
+    async fn invalidate_listed_entries(
+        &self,
+        mut entries: Vec<(RegistryEntry<K>, QueryListeners<T, E, K>)>,
+        refetch_unobserved: bool,
+        silent: bool,
+        options: InvalidateOptions,
+    ) -> Vec<InvalidationOutcome<K, E>> {
+        // How many of the invalidated queries each listener is still waiting for,
+        // and whether any of those that already finished changed in a way it wants to be notified of
+        let pending_listeners = Lock::new(HashMap::<ScopeId, (usize, bool)>::default());
+        // Limited batches notify the listeners as each query finishes, as the next ones might take a while
+        let notify_each =
+            options.mode == InvalidateMode::Sequential || options.max_concurrency.is_some();
+        // The listeners that want to be notified of the invalidated queries starting to fetch
+        let mut loading_listeners = HashSet::<ScopeId>::default();
+        let mut tasks = Vec::<Pin<Box<dyn Future<Output = InvalidationOutcome<K, E>> + '_>>>::new();
+        let mut outcomes = Vec::new();
+
+        if options.mode == InvalidateMode::Sequential {
+            entries.sort_by_key(|(_, query_listeners)| query_listeners.created);
+        }
+
+        for (
+            entry,
+            QueryListeners {
+                value,
+                listeners,
+                detached_listeners,
+                query_fn,
+                is_equal,
+                loading_on_refetch,
+                retry,
+                retry_if,
+                retry_delay,
+                generation,
+                last_used,
+                debounce,
+                debounce_invalidations,
+                min_fetch_interval,
+                fetch_on_mount,
+                network_mode,
+                enabled,
+                stream_fn,
+                in_flight,
+                subscription,
+                meta,
+                prefetch_next,
+                settled,
+                entry_callbacks,
+                ..
+            },
+        ) in entries
+        {
+            // The listeners waiting for this query, as they were when it got invalidated
+            let listeners = listeners
+                .borrow()
+                .iter()
+                .map(|(scope_id, listener)| (*scope_id, listener.clone()))
+                .collect::<Vec<_>>();
+
+            // Queries without listeners are only refetched if requested,
+            // otherwise they are left stale so they are fetched once a component uses them again
+            if !refetch_unobserved && listeners.is_empty() && detached_listeners.get() == 0 {
+                sync::write(&value).has_been_invalidated = true;
+                outcomes.push(InvalidationOutcome {
+                    query_keys: entry.query_keys,
+                    status: InvalidationStatus::Unobserved,
+                });
+                continue;
+            }
+            if !fetch_on_mount && sync::read(&value).is_idle() {
+                outcomes.push(InvalidationOutcome {
+                    query_keys: entry.query_keys,
+                    status: InvalidationStatus::Idle,
+                });
+                continue;
+            }
+            if enabled.is_some_and(|enabled| !enabled()) {
+                outcomes.push(InvalidationOutcome {
+                    query_keys: entry.query_keys,
+                    status: InvalidationStatus::Disabled,
+                });
+                continue;
+            }
+
+            if subscription.is_some() {
+                outcomes.push(InvalidationOutcome {
+                    query_keys: entry.query_keys,
+                    status: InvalidationStatus::Live,
+                });
+                continue;
+            }
+
+            // Paused clients refetch the query once resumed, notifying its own listeners
+            if self.hold_if_paused(&entry) {
+                sync::write(&value).has_been_invalidated = true;
+                outcomes.push(InvalidationOutcome {
+                    query_keys: entry.query_keys,
+                    status: InvalidationStatus::Paused,
+                });
+                continue;
+            }
+
+            // Queries that don't fetch while offline are fetched once the app is back online,
+            // notifying their own listeners
+            if network_mode == NetworkMode::OfflineFirst && !self.online_status().is_online() {
+                sync::write(&value).has_been_invalidated = true;
+                let client = self.clone();
+                let query_keys = entry.query_keys.clone();
+                self.spawn_task(async move {
+                    client.run_new_query(&entry).await;
+                });
+                outcomes.push(InvalidationOutcome {
+                    query_keys,
+                    status: InvalidationStatus::Paused,
+                });
+                continue;
+            }
+
+            // Streams are torn down and subscribed to again in the **background**, as they might never end,
+            // notifying their own listeners
+            if stream_fn.is_some() {
+                sync::write(&value).has_been_invalidated = true;
+                let client = self.clone();
+                let query_keys = entry.query_keys.clone();
+                self.spawn_task(async move {
+                    client.run_new_query(&entry).await;
+                });
+                outcomes.push(InvalidationOutcome {
+                    query_keys,
+                    status: InvalidationStatus::Resubscribed,
+                });
+                continue;
+            }
+
+            // Debounced and throttled queries are refetched once they are allowed to, notifying their own listeners
+            if (debounce.is_some() && debounce_invalidations) || min_fetch_interval.is_some() {
+                sync::write(&value).has_been_invalidated = true;
+                tasks.push(Box::pin(async move {
+                    self.run_new_query(&entry).await;
+                    InvalidationOutcome {
+                        query_keys: entry.query_keys,
+                        status: InvalidationStatus::Deferred,
+                    }
+                }));
+                continue;
+            }
+            let RegistryEntry {
+                query_keys,
+                query_fn_id,
+            } = entry;
+
+            // Mark it as fetching and start a new generation under a single lock, see [UseQueryClient::run_new_query]
+            let fetch_generation = {
+                let mut value = sync::write(&value);
+                value.set_fetching(loading_on_refetch && !silent);
+                generation.increment()
+            };
+            if !silent {
+                self.notify_data_listeners(&query_keys);
+            }
+            for (scope_id, listener) in &listeners {
+                pending_listeners
+                    .borrow_mut()
+                    .entry(*scope_id)
+                    .or_default()
+                    .0 += 1;
+                if listener.notify_on.accepts(QueryChange::Loading) {
+                    loading_listeners.insert(*scope_id);
+                }
+            }
+
+            let pending_listeners = &pending_listeners;
+
+            tasks.push(Box::pin(async move {
+                // Fetch the result
+                let started_at = self.config.clock.now();
+                let query_fn = query_fn.borrow().clone();
+                let new_value = self
+                    .fetch_entry(
+                        query_fn.as_ref(),
+                        &query_keys,
+                        &value,
+                        retry,
+                        retry_if.as_deref().map(Box::as_ref),
+                        retry_delay.as_ref(),
+                        &meta,
+                        &in_flight,
+                        "invalidation",
+                    )
+                    .await;
+                let status = match &new_value {
+                    Some(new_value) => InvalidationStatus::Refetched {
+                        error: new_value.err().cloned(),
+                        duration: self.config.clock.elapsed(started_at),
+                    },
+                    None => InvalidationStatus::Superseded,
+                };
+
+                // Discard the result if a newer fetch was started or the cached value was removed in the meantime
+                let is_current = new_value.is_some() && generation.get() == fetch_generation;
+                let settled_value = new_value
+                    .as_ref()
+                    .filter(|_| is_current)
+                    .and_then(|new_value| entry_callbacks.keep(new_value));
+                let has_changed = is_current
+                    && new_value.is_some_and(|new_value| {
+                        sync::write(&value)
+                            .set_value(new_value, is_equal.as_deref().map(Box::as_ref))
+                    });
+                if is_current {
+                    settled.notify();
+                }
+                if let Some(settled_value) = settled_value {
+                    entry_callbacks.run(&settled_value);
+                }
+                if is_current && prefetch_next.is_some() {
+                    self.prefetch_next_page(&RegistryEntry {
+                        query_keys: query_keys.clone(),
+                        query_fn_id,
+                    });
+                }
+                if has_changed {
+                    self.mark_used(&last_used);
+                }
+                query_event!(
+                    keys = %self.fmt_keys(&query_keys),
+                    changed = has_changed,
+                    "query result cached"
+                );
+                if has_changed {
+                    self.emit_cache_updated(&query_keys, &value);
+                    self.notify_data_listeners(&query_keys);
+                } else {
+                    self.notify_watchers(&query_keys);
+                }
+                self.notify_fetching_listeners();
+
+                // Notify the listeners once all their invalidated queries have finished, or right away if limited
+                let result = sync::read(&value);
+                let change = QueryChange::of(result.value());
+                let mut pending_listeners = pending_listeners.borrow_mut();
+                for (scope_id, listener) in listeners {
+                    if let Some((pending, changed)) = pending_listeners.get_mut(&scope_id) {
+                        *pending -= 1;
+                        *changed |= has_changed && listener.accepts(change, &result);
+                        if *changed && (*pending == 0 || notify_each) {
+                            (self.scheduler)(scope_id);
+                            *changed = false;
+                        }
+                    }
+                }
+
+                InvalidationOutcome { query_keys, status }
+            }));
+        }
+
+        self.notify_fetching_listeners();
+
+        // Notify each listener once, even if it listens to multiple of the invalidated queries
+        if !silent {
+            for listener in loading_listeners {
+                (self.scheduler)(listener);
+            }
+        }
+
+        // Each task caches its result as soon as it finishes, see above for when the listeners are notified
+        match (options.mode, options.max_concurrency) {
+            (InvalidateMode::Sequential, _) => {
+                for task in tasks {
+                    outcomes.push(task.await);
+                }
+            }
+            (InvalidateMode::Concurrent, Some(max_concurrency)) => {
+                outcomes.extend(
+                    stream::iter(tasks)
+                        .buffer_unordered(max_concurrency.max(1))
+                        .collect::<Vec<_>>()
+                        .await,
+                );
+            }
+            (InvalidateMode::Concurrent, None) => {
+                outcomes.extend(
+                    tasks
+                        .into_iter()
+                        .collect::<FuturesUnordered<_>>()
+                        .collect::<Vec<_>>()
+                        .await,
+                );
+            }
+        }
+        outcomes
+    }
+
+    /// Merge the invalidations made in the **background** within `debounce` of the first one into a single one,
+    /// e.g to avoid a burst of refetches when a server pushes many changes at once.
+    /// Queries invalidated several times during the window are only refetched once, when it closes.
+    /// Use [UseQueryClient::invalidate_queries_immediately] for the invalidations that can't wait.
+    ///
+    /// ## Example:
+    ///
+    /// ```no_run
+    /// client.set_invalidation_debounce(Some(Duration::from_millis(200)));
+    /// ```
+    pub fn set_invalidation_debounce(&self, debounce: Option<Duration>) {
+        self.invalidation_batch.borrow_mut().debounce = debounce;
+    }
+
+    /// Hold back every fetch until [UseQueryClient::resume_all] is called, e.g while a heavy modal is open.
+    /// Polling, refetches on focus or reconnect, invalidations and newly mounted queries don't fetch,
+    /// their entries are recorded instead and keep their cached results.
+    /// Fetches that were already running still finish.
+    ///
+    /// Use [UseQuery::force_trigger](crate::prelude::UseQuery::force_trigger) for the fetches
+    /// asked by the user, which can't wait.
+    /// Awaited fetches like [UseQueryClient::fetch_query] get the cached result of their query meanwhile.
+    ///
+    /// ## Example:
+    ///
+    /// ```no_run
+    /// let open_call = move |_| {
+    ///     client.pause_all();
+    ///     is_calling.set(true);
+    /// };
+    /// ```
+    pub fn pause_all(&self) {
+        let mut paused = self.paused.borrow_mut();
+        if paused.is_paused {
+            return;
+        }
+        paused.is_paused = true;
+        drop(paused);
+        self.emit_cache_event(QueryCacheEvent::PauseChanged { is_paused: true });
+    }
+
+    /// Resume the fetches held back since [UseQueryClient::pause_all], in the **background**.
+    /// Every entry that missed a fetch is revalidated once, unless it's fresh and wasn't invalidated meanwhile.
+    /// They are fetched concurrently, within the limits of [QueryClientConfig::max_concurrent_fetches]
+    /// and [QueryClientConfig::rate_limit] if given.
+    ///
+    /// Outside of any component and without a spawner, see [UseQueryClient::with_spawner],
+    /// the client stays paused, use [UseQueryClient::manual_resume_all] instead.
+    pub fn resume_all(&self) {
+        if !self.can_spawn() {
+            query_warning!("the query client can't be resumed in the background, as there is no component nor spawner");
+            return;
+        }
+        let Some(pending) = self.take_paused() else {
+            return;
+        };
+        let client = self.clone();
+        self.spawn_task(async move {
+            client.revalidate_entries(&pending).await;
+        });
+    }
+
+    /// Same as [UseQueryClient::resume_all], but resolves once the entries that missed a fetch are revalidated.
+    pub async fn manual_resume_all(&self) {
+        if let Some(pending) = self.take_paused() {
+            self.revalidate_entries(&pending).await;
+        }
+    }
+
+    /// Resume the client, getting the entries that missed a fetch, or `None` if it wasn't paused.
+    fn take_paused(&self) -> Option<HashSet<RegistryEntry<K>>> {
+        let pending = {
+            let mut paused = self.paused.borrow_mut();
+            if !paused.is_paused {
+                return None;
+            }
+            paused.is_paused = false;
+            std::mem::take(&mut paused.pending)
+        };
+        self.emit_cache_event(QueryCacheEvent::PauseChanged { is_paused: false });
+        Some(pending)
+    }
+
+    async fn revalidate_entries(&self, entries: &HashSet<RegistryEntry<K>>) {
+        let tasks = FuturesUnordered::new();
+        for entry in entries {
+            tasks.push(self.run_new_query(entry));
+        }
+        tasks.count().await;
+    }
+
+    /// Invalidate a single query, in the **background**.
+    /// Its fetch in flight, if any, is aborted and it's refetched along with
+    /// the others invalidated in the same window, see [UseQueryClient::set_invalidation_debounce].
+    pub fn invalidate_query(&self, key_to_invalidate: K) {
+        self.invalidate_queries(&[key_to_invalidate]);
+    }
+
+    /// Invalidate a group of queries, in the **background**.
+    /// Their fetches in flight are aborted and they are refetched concurrently, within
+    /// [QueryClientConfig::max_concurrent_fetches] if set, along with the others invalidated
+    /// in the same window, see [UseQueryClient::set_invalidation_debounce].
+    ///
+    /// Outside of any component it needs a spawner, see [UseQueryClient::with_spawner],
+    /// otherwise nothing is invalidated, use [UseQueryClient::manual_invalidate_queries] instead.
+    pub fn invalidate_queries(&self, keys_to_invalidate: &[K]) {
+        let debounce = {
+            let mut batch = self.invalidation_batch.borrow_mut();
+            let Some(debounce) = batch.debounce else {
+                drop(batch);
+                self.invalidate_queries_immediately(keys_to_invalidate);
+                return;
+            };
+            for key in keys_to_invalidate {
+                if !batch.keys.contains(key) {
+                    batch.keys.push(key.clone());
+                }
+            }
+            if batch.is_scheduled {
+                return;
+            }
+            // Without a task to close the window, the batch would never be invalidated
+            if !self.can_spawn() {
+                batch.keys.clear();
+                query_warning!("queries can't be invalidated in the background, as there is no component nor spawner");
+                return;
+            }
+            batch.is_scheduled = true;
+            debounce
+        };
+
+        // Invalidate the whole batch once the window closes
+        let client = self.clone();
+        self.spawn_task(async move {
+            Box::into_pin(client.config.clock.sleep(debounce)).await;
+            let keys_to_invalidate = {
+                let mut batch = client.invalidation_batch.borrow_mut();
+                batch.is_scheduled = false;
+                std::mem::take(&mut batch.keys)
+            };
+            client.manual_invalidate_queries(&keys_to_invalidate).await;
+        });
+    }
+
+    /// Invalidate a group of queries, in the **background**, without waiting for
+    /// the debounce window of the client, see [UseQueryClient::set_invalidation_debounce].
+    /// Any fetch in flight of those queries is aborted, as its result would be overwritten anyway.
+    pub fn invalidate_queries_immediately(&self, keys_to_invalidate: &[K]) {
+        let client = self.clone();
+        let keys_to_invalidate = keys_to_invalidate.to_vec();
+        self.spawn_task(async move {
+            client.manual_invalidate_queries(&keys_to_invalidate).await;
+        });
+    }
+
+    /// Invalidate a group of queries as [UseQueryClient::invalidate_queries] would,
+    /// except the entries with the given query keys, e.g because they were just updated.
+    pub(crate) fn invalidate_queries_except(&self, keys_to_invalidate: &[K], except: &[Vec<K>]) {
+        if except.is_empty() {
+            self.invalidate_queries(keys_to_invalidate);
+            return;
+        }
+        let keys_to_invalidate = keys_to_invalidate.to_vec();
+        let except = except.to_vec();
+        let client = self.clone();
+        self.spawn_task(async move {
+            client
+                .invalidate_keys_inner(
+                    &keys_to_invalidate,
+                    |entry| !except.contains(&entry.query_keys),
+                    false,
+                    false,
+                    InvalidateOptions::default(),
+                )
+                .await;
+        });
+    }
+
+    /// Invalidate the entries with exactly the given query keys, in the **background**.
+    pub(crate) fn invalidate_entries_with_keys(&self, entries: &[Vec<K>]) {
+        let mut query_keys = entries.to_vec();
+        for query_keys in &mut query_keys {
+            self.normalize_keys(query_keys);
+        }
+        let client = self.clone();
+        self.spawn_task(async move {
+            let entries = query_keys
+                .into_iter()
+                .collect::<HashSet<_>>()
+                .iter()
+                .flat_map(|query_keys| client.get_entries_with_keys(query_keys))
+                .collect();
+            client
+                .invalidate_listed_entries(entries, false, false, InvalidateOptions::default())
+                .await;
+        });
+    }
+
+    /// Invalidate a single query.
+    /// Resolves once it has been refetched, with what happened to each of the matching entries.
+    pub async fn manual_invalidate_query(
+        &self,
+        key_to_invalidate: K,
+    ) -> Vec<InvalidationOutcome<K, E>> {
+        self.invalidate_keys_inner(
+            &[key_to_invalidate],
+            |_| true,
+            false,
+            false,
+            InvalidateOptions::default(),
+        )
+        .await
+    }
+
+    /// Invalidate a group of queries.
+    /// They will all run concurrently, resolves once all of them have been refetched,
+    /// with what happened to each of the matching entries.
+    ///
+    /// ## Example:
+    ///
+    /// ```no_run
+    /// let outcomes = client.manual_invalidate_queries(&[QueryKeys::Users]).await;
+    /// let failed = outcomes.iter().filter(|outcome| {
+    ///     matches!(outcome.status, InvalidationStatus::Refetched { error: Some(_), .. })
+    /// });
+    /// ```
+    pub async fn manual_invalidate_queries(
+        &self,
+        keys_to_invalidate: &[K],
+    ) -> Vec<InvalidationOutcome<K, E>> {
+        self.invalidate_keys_inner(
+            keys_to_invalidate,
+            |_| true,
+            false,
+            false,
+            InvalidateOptions::default(),
+        )
+        .await
+    }
+
+    /// Invalidate a group of queries with the given options, in the **background**,
+    /// e.g to limit how many of them are refetched at the same time.
+    /// Unlike [UseQueryClient::invalidate_queries], it never waits for the debounce window of the client.
+    ///
+    /// ## Example:
+    ///
+    /// ```no_run
+    /// client.invalidate_queries_with(&[QueryKeys::Users], InvalidateOptions { max_concurrency: Some(6) });
+    /// ```
+    pub fn invalidate_queries_with(&self, keys_to_invalidate: &[K], options: InvalidateOptions) {
+        let client = self.clone();
+        let keys_to_invalidate = keys_to_invalidate.to_vec();
+        self.spawn_task(async move {
+            client
+                .manual_invalidate_queries_with(&keys_to_invalidate, options)
+                .await;
+        });
+    }
+
+    /// Invalidate a group of queries with the given options.
+    /// Resolves once all of them have been refetched, with what happened to each of the matching entries.
+    pub async fn manual_invalidate_queries_with(
+        &self,
+        keys_to_invalidate: &[K],
+        options: InvalidateOptions,
+    ) -> Vec<InvalidationOutcome<K, E>> {
+        self.invalidate_keys_inner(keys_to_invalidate, |_| true, false, false, options)
+            .await
+    }
+
+    /// Invalidate the queries with the given id and no keys, in the **background**, see [use_query_unit](crate::prelude::use_query_unit).
+    pub fn invalidate_unit_query(&self, id: &'static str) {
+        let client = self.clone();
+        self.spawn_task(async move {
+            client.manual_invalidate_unit_query(id).await;
+        });
+    }
+
+    /// Invalidate the queries with the given id and no keys, see [use_query_unit](crate::prelude::use_query_unit).
+    /// Resolves once they have been refetched, with what happened to each of them.
+    pub async fn manual_invalidate_unit_query(
+        &self,
+        id: &'static str,
+    ) -> Vec<InvalidationOutcome<K, E>> {
+        self.invalidate_entries_inner(
+            |entry| {
+                entry.query_keys.is_empty()
+                    && entry.query_fn_id == QueryFnId::Explicit(Cow::Borrowed(id))
+            },
+            false,
+            false,
+            InvalidateOptions::default(),
+        )
+        .await
+    }
+
+    /// Invalidate the queries tagged with the given tag, in the **background**, see [QueryConfig::tags].
+    pub fn invalidate_tag(&self, tag: K) {
+        let client = self.clone();
+        self.spawn_task(async move {
+            client.manual_invalidate_tag(&tag).await;
+        });
+    }
+
+    /// Invalidate the queries tagged with the given tag, see [QueryConfig::tags].
+    /// Their query keys don't matter. Resolves once they have been refetched, with what happened to each of them.
+    pub async fn manual_invalidate_tag(&self, tag: &K) -> Vec<InvalidationOutcome<K, E>> {
+        let tagged_entries = self
+            .queries_registry
+            .borrow()
+            .with_tag(tag)
+            .map(|(entry, query_listeners)| (entry.clone(), query_listeners.clone()))
+            .collect();
+        self.invalidate_listed_entries(tagged_entries, false, false, InvalidateOptions::default())
+            .await
+    }
+
+    /// Invalidate a group of queries silently, in the **background**.
+    /// The cached values are kept as they are while refetching, and listeners
+    /// are only notified once the new results arrive, if they are different.
+    pub fn invalidate_queries_silent(&self, keys_to_invalidate: &[K]) {
+        let client = self.clone();
+        let keys_to_invalidate = keys_to_invalidate.to_vec();
+        self.spawn_task(async move {
+            client
+                .invalidate_keys_inner(
+                    &keys_to_invalidate,
+                    |_| true,
+                    false,
+                    true,
+                    InvalidateOptions::default(),
+                )
+                .await;
+        });
+    }
+
+    /// Invalidate every query whose keys satisfy the given predicate.
+    /// Each one is refetched right away, aborting its own fetch in flight, with at most
+    /// [QueryClientConfig::max_concurrent_fetches] query functions running at once if set.
+    ///
+    /// ## Example:
+    ///
+    /// ```no_run
+    /// client.invalidate_queries_matching(|keys| {
+    ///     keys.iter().any(|k| matches!(k, QueryKeys::User(_)))
+    /// });
+    /// ```
+    pub fn invalidate_queries_matching(&self, predicate: impl Fn(&[K]) -> bool + 'static) {
+        let client = self.clone();
+        self.spawn_task(async move {
+            client
+                .invalidate_queries_inner(predicate, false, false)
+                .await;
+        });
+    }
+
+    /// Invalidate every query whose keys satisfy the given predicate, going through the whole cache.
+    /// Resolves once they have been refetched, with what happened to each of them.
+    /// Prefer [UseQueryClient::manual_invalidate_queries] to invalidate the queries with some keys,
+    /// which are found without going through the whole cache.
+    pub async fn manual_invalidate_queries_matching(
+        &self,
+        predicate: impl Fn(&[K]) -> bool,
+    ) -> Vec<InvalidationOutcome<K, E>> {
+        self.invalidate_queries_inner(predicate, false, false).await
+    }
+
+    /// Refetch a group of queries, including the ones that have no listeners.
+    /// The fetches they had in flight are dropped for the new ones.
+    pub fn refetch_queries(&self, keys_to_refetch: &[K]) {
+        let client = self.clone();
+        let keys_to_refetch = keys_to_refetch.to_vec();
+        self.spawn_task(async move {
+            client
+                .invalidate_keys_inner(
+                    &keys_to_refetch,
+                    |_| true,
+                    true,
+                    false,
+                    InvalidateOptions::default(),
+                )
+                .await;
+        });
+    }
+
+    /// Invalidate every query in the cache, aborting the fetches in flight.
+    /// On a large cache, consider [QueryClientConfig::max_concurrent_fetches] so they don't all start at once.
+    pub fn invalidate_all(&self) {
+        let client = self.clone();
+        self.spawn_task(async move {
+            client
+                .invalidate_queries_inner(|_| true, false, false)
+                .await;
+        });
+    }
+
+    /// Mark a group of queries as stale without refetching them.
+    /// They will be refetched the next time a listener is registered for them.
+    pub fn mark_queries_stale(&self, keys_to_invalidate: &[K]) {
+        for (_, QueryListeners { value, .. }) in
+            self.get_entries_with_any_key(keys_to_invalidate, |_| true)
+        {
+            sync::write(&value).has_been_invalidated = true;
+        }
+    }
+
+    /// Get a copy of the last successful cached value of the query with the given keys, `None` if there is none.
+    /// If multiple query functions are cached with the same keys, the most recently updated one is used.
+    pub fn get_query_data(&self, keys: &[K]) -> Option<T> {
+        let mut keys = keys.to_vec();
+        self.normalize_keys(&mut keys);
+        self.get_entry_with_keys(&keys)
+            .and_then(|QueryListeners { value, .. }| sync::read(&value).data().cloned())
+    }
+
+    /// Edit the cached value of the query with the given keys in place and notify its listeners.
+    /// Returns `false` without calling `f` if there is no value to edit (`Err` or `Loading(None)`).
+    /// If multiple query functions are cached with the same keys, only the most recently updated one is edited.
+    pub fn update_query_data(&self, keys: &[K], f: impl FnOnce(&mut T)) -> bool {
+        let mut keys = keys.to_vec();
+        self.normalize_keys(&mut keys);
+        let Some(query_listeners) = self.get_entry_with_keys(&keys) else {
+            return false;
+        };
+        self.write_entry_data(&keys, &query_listeners, f)
+    }
+
+    /// Edit the cached values of the queries with any of the given keys in place and notify their listeners,
+    /// see [UseQueryClient::update_query_data]. Returns the query keys of the entries that were edited,
+    /// and of the ones that had no value to edit.
+    pub(crate) fn update_matching_data(
+        &self,
+        keys: &[K],
+        f: impl Fn(&mut T),
+    ) -> (Vec<Vec<K>>, Vec<Vec<K>>) {
+        let mut updated = Vec::new();
+        let mut missed = Vec::new();
+        for (entry, query_listeners) in self.get_entries_with_any_key(keys, |_| true) {
+            if self.write_entry_data(&entry.query_keys, &query_listeners, &f) {
+                updated.push(entry.query_keys);
+            } else {
+                missed.push(entry.query_keys);
+            }
+        }
+        (updated, missed)
+    }
+
+    /// Edit the cached value of an entry in place, marking it fresh, and notify its listeners.
+    fn write_entry_data(
+        &self,
+        keys: &[K],
+        QueryListeners {
+            value,
+            listeners,
+            last_used,
+            ..
+        }: &QueryListeners<T, E, K>,
+        f: impl FnOnce(&mut T),
+    ) -> bool {
+        {
+            let mut value = sync::write(&value);
+            match &mut value.value {
+                QueryResult::Ok(data) | QueryResult::Loading(Some(data)) => f(data),
+                _ => return false,
+            }
+            let now = value.clock.now();
+            value.instant = Some(now);
+            value.data_instant = Some(now);
+            value.has_been_invalidated = false;
+        }
+
+        self.mark_used(last_used);
+        self.emit_cache_updated(keys, value);
+        self.schedule_listeners(&listeners.borrow(), QueryChange::Data, value);
+        self.notify_data_listeners(keys);
+
+        true
+    }
+
+    /// Replace the cached value of the query with the given keys and notify its listeners,
+    /// e.g to roll back an optimistic update. See [UseQueryClient::update_query_data].
+    pub fn set_query_data(&self, keys: &[K], data: T) -> bool {
+        self.update_query_data(keys, |cached| *cached = data)
+    }
+
+    /// Get the result of a query, fetching it unless its cached result is still fresh, e.g from a router loader.
+    /// The result is stored in the cache and the listeners of the query are notified as usual,
+    /// and if the query is already being fetched, e.g by a mounted component, its result is awaited instead.
+    ///
+    /// ## Example:
+    ///
+    /// ```no_run
+    /// let user = client.fetch_query(vec![QueryKeys::User(id)], fetch_user).await;
+    /// ```
+    pub async fn fetch_query<Q, F>(&self, query_keys: Vec<K>, query_fn: Q) -> QueryResult<T, E>
+    where
+        Q: 'static + Fn(Vec<K>) -> F + MaybeSend + MaybeSync,
+        F: 'static + Future<Output = QueryResult<T, E>> + MaybeSend,
+    {
+        self.fetch_query_config(QueryConfig::new(query_keys, query_fn))
+            .await
+    }
+
+    /// Same as [UseQueryClient::fetch_query] but with a query configuration.
+    pub async fn fetch_query_config(&self, mut config: QueryConfig<T, E, K>) -> QueryResult<T, E> {
+        self.normalize_keys(&mut config.registry_entry.query_keys);
+        let registry_entry = config.registry_entry.clone();
+
+        // Keeps the entry around while waiting for a fetch started somewhere else
+        let mut watcher = self.watch(&registry_entry.query_keys);
+        self.prefetch_query_config(config).await;
+        loop {
+            let Some(QueryListeners { value, .. }) = self.get_entry(&registry_entry) else {
+                return QueryResult::default();
+            };
+            let result = sync::read(&value);
+            if !result.is_fetching() {
+                return result.value().clone();
+            }
+            drop(result);
+            watcher.changed().await;
+        }
+    }
+
+    /// Get the last successful cached value of a query, only fetching it if there is none, see [UseQueryClient::fetch_query].
+    /// Unlike [UseQueryClient::fetch_query] a stale value is returned as is.
+    /// Only the value cached by the given query function is used, not the ones of other functions with the same keys.
+    pub async fn ensure_query_data<Q, F>(
+        &self,
+        query_keys: Vec<K>,
+        query_fn: Q,
+    ) -> QueryResult<T, E>
+    where
+        Q: 'static + Fn(Vec<K>) -> F + MaybeSend + MaybeSync,
+        F: 'static + Future<Output = QueryResult<T, E>> + MaybeSend,
+    {
+        let mut config = QueryConfig::new(query_keys, query_fn);
+        self.normalize_keys(&mut config.registry_entry.query_keys);
+        let data = self
+            .get_entry(&config.registry_entry)
+            .and_then(|QueryListeners { value, .. }| sync::read(&value).data().cloned());
+        match data {
+            Some(data) => QueryResult::Ok(data),
+            None => self.fetch_query_config(config).await,
+        }
+    }
+
+    /// Wait for the query with the given keys to settle, without fetching it, e.g in a route guard or a test.
+    /// It resolves right away if it has a value or an error already, otherwise with the next one it gets,
+    /// or with [WaitOutcome::TimedOut] once the timeout, if any, elapses first.
+    /// If multiple query functions are cached with the same keys, the most recently updated one is used.
+    ///
+    /// ## Example:
+    ///
+    /// ```no_run
+    /// match client.wait_for(&[QueryKeys::User(id)], Some(Duration::from_secs(5))).await {
+    ///     WaitOutcome::Ok(user) => export(user),
+    ///     WaitOutcome::Err(_) | WaitOutcome::TimedOut => show_error(),
+    /// }
+    /// ```
+    pub async fn wait_for(&self, keys: &[K], timeout: Option<Duration>) -> WaitOutcome<T, E> {
+        // Watch the keys before reading them, so a result cached in between isn't missed
+        let mut watcher = self.watch(keys);
+        let settled = async move {
+            let mut cached = watcher.cached();
+            loop {
+                match cached.map(|cached_result| cached_result.value) {
+                    Some(QueryResult::Ok(data)) => return WaitOutcome::Ok(data),
+                    Some(QueryResult::Err(error)) => return WaitOutcome::Err(error),
+                    _ => cached = watcher.changed().await,
+                }
+            }
+        };
+
+        let Some(timeout) = timeout else {
+            return settled.await;
+        };
+        let timer = Box::into_pin(self.config.clock.sleep(timeout));
+        match select(Box::pin(settled), timer).await {
+            Either::Left((outcome, _)) => outcome,
+            Either::Right(_) => WaitOutcome::TimedOut,
+        }
+    }
+
+    /// Remove the entries that have no listeners and haven't been updated for the cache time (5 minutes) right away,
+    /// instead of waiting for their retention timer. Entries being fetched are kept.
+    /// Returns how many entries were removed.
+    pub fn gc(&self) -> usize {
+        self.gc_entries(|_, value| {
+            value
+                .instant
+                .map(|instant| {
+                    self.config.clock.elapsed(instant) >= Duration::from_millis(CACHE_TIME)
+                })
+                .unwrap_or_default()
+        })
+    }
+
+    /// Remove the entries whose keys satisfy the given predicate and have no listeners, no matter how recently
+    /// they were updated, e.g everything about a workspace the user just left. Entries being fetched are kept.
+    /// Returns how many entries were removed.
+    ///
+    /// ## Example:
+    ///
+    /// ```no_run
+    /// client.gc_matching(|keys| keys.contains(&QueryKeys::Workspace(workspace_id)));
+    /// ```
+    pub fn gc_matching(&self, is_matching: impl Fn(&[K]) -> bool) -> usize {
+        self.gc_entries(|query_keys, _| is_matching(query_keys))
+    }
+
+    /// Remove the entries without listeners nor fetches in flight for which `should_remove` returns `true`.
+    fn gc_entries(&self, should_remove: impl Fn(&[K], &CachedResult<T, E>) -> bool) -> usize {
+        let removed_entries =
+            self.queries_registry
+                .borrow_mut()
+                .remove_where(|entry, query_listeners| {
+                    self.is_entry_unused(entry, query_listeners) && {
+                        let value = sync::read(&query_listeners.value);
+                        !value.is_fetching() && should_remove(&entry.query_keys, &value)
+                    }
+                });
+
+        for (entry, query_listeners) in &removed_entries {
+            self.collected(entry, query_listeners);
+        }
+        removed_entries.len()
+    }
+
+    /// Collect the given entry once it was left unused for the cache time (5 minutes),
+    /// e.g a prefetched one that never got a listener. Only that entry is checked, not the whole cache.
+    fn schedule_gc(&self, registry_entry: RegistryEntry<K>) {
+        let client = self.clone();
+        self.spawn_task(async move {
+            Box::into_pin(client.config.clock.sleep(Duration::from_millis(CACHE_TIME))).await;
+            let removed = {
+                let mut queries_registry = client.queries_registry.borrow_mut();
+                let is_expired =
+                    queries_registry
+                        .get(&registry_entry)
+                        .is_some_and(|query_listeners| {
+                            client.is_entry_unused(&registry_entry, query_listeners) && {
+                                let value = sync::read(&query_listeners.value);
+                                !value.is_fetching()
+                                    && value.instant.is_some_and(|instant| {
+                                        client.config.clock.elapsed(instant)
+                                            >= Duration::from_millis(CACHE_TIME)
+                                    })
+                            }
+                        });
+                is_expired
+                    .then(|| queries_registry.remove(&registry_entry))
+                    .flatten()
+            };
+            if let Some(query_listeners) = removed {
+                client.collected(&registry_entry, &query_listeners);
+            }
+        });
+    }
+
+    /// Tear down an entry removed by the garbage collection and notify whoever follows it.
+    fn collected(&self, entry: &RegistryEntry<K>, query_listeners: &QueryListeners<T, E, K>) {
+        query_listeners.discard();
+        query_event!(keys = %self.fmt_keys(&entry.query_keys), "query entry collected");
+        self.emit_cache_event(QueryCacheEvent::Removed {
+            query_keys: &entry.query_keys,
+        });
+        self.notify_data_listeners(&entry.query_keys);
+    }
+
+    /// Fetch a query and store its result in the cache without requiring any listener,
+    /// so a component mounted later with the same keys and function can render it right away.
+    /// Prefetched entries that never get a listener are removed from the cache eventually.
+    pub async fn prefetch_query<Q, F>(&self, query_keys: Vec<K>, query_fn: Q)
+    where
+        Q: 'static + Fn(Vec<K>) -> F + MaybeSend + MaybeSync,
+        F: 'static + Future<Output = QueryResult<T, E>> + MaybeSend,
+    {
+        self.prefetch_query_config(QueryConfig::new(query_keys, query_fn))
+            .await;
+    }
+
+    /// Same as [UseQueryClient::prefetch_query] but with a query configuration,
+    /// e.g so the prefetched result stays fresh for as long as the listeners of the query expect.
+    pub async fn prefetch_query_config(&self, mut config: QueryConfig<T, E, K>) {
+        self.normalize_keys(&mut config.registry_entry.query_keys);
+
+        let (registry_entry, is_new_entry) = {
+            let mut queries_registry = self.queries_registry.borrow_mut();
+            let is_new_entry = !queries_registry.contains_key(&config.registry_entry);
+            queries_registry.get_or_insert_with(config.registry_entry.clone(), || {
+                self.new_query_listeners(&config)
+            });
+            (config.registry_entry, is_new_entry)
+        };
+        if is_new_entry {
+            self.emit_cache_event(QueryCacheEvent::EntryAdded {
+                query_keys: &registry_entry.query_keys,
+            });
+        }
+        self.mark_entry_used(&registry_entry);
+        self.evict_least_recently_used();
+
+        self.run_new_query(&registry_entry).await;
+        let is_unused = self
+            .get_entry(&registry_entry)
+            .is_some_and(|query_listeners| self.is_entry_unused(&registry_entry, &query_listeners));
+        if is_unused {
+            self.schedule_gc(registry_entry);
+        }
+    }
+
+    /// Prefetch the given queries at the same time, see [UseQueryClient::prefetch_query],
+    /// e.g to warm up the cache with what the first screen needs before its components mount.
+    /// Resolves once all of them settle, with their cached results in the same order.
+    ///
+    /// ## Example:
+    ///
+    /// ```no_run
+    /// fn App(cx: Scope) -> Element {
+    ///     let client = use_init_query_client::<QueryValue, QueryError, QueryKeys>(cx);
+    ///     cx.use_hook(|| {
+    ///         to_owned![client];
+    ///         cx.spawn(async move {
+    ///             client
+    ///                 .prefetch_queries(vec![
+    ///                     QueryConfig::new(vec![QueryKeys::CurrentUser], fetch_current_user),
+    ///                     QueryConfig::new(vec![QueryKeys::Notifications], fetch_notifications),
+    ///                     QueryConfig::new(vec![QueryKeys::Feed], fetch_feed),
+    ///                 ])
+    ///                 .await;
+    ///         })
+    ///     });
+    ///
+    ///     render!( Home {} )
+    /// }
+    /// ```
+    pub async fn prefetch_queries(
+        &self,
+        configs: Vec<QueryConfig<T, E, K>>,
+    ) -> Vec<PrefetchOutcome<T, E, K>> {
+        join_all(configs.into_iter().map(|mut config| async move {
+            self.normalize_keys(&mut config.registry_entry.query_keys);
+            let registry_entry = config.registry_entry.clone();
+            self.prefetch_query_config(config).await;
+            let result = self
+                .get_entry(&registry_entry)
+                .map(|query_listeners| sync::read(&query_listeners.value).value().clone())
+                .unwrap_or_default();
+            PrefetchOutcome {
+                query_keys: registry_entry.query_keys,
+                result,
+            }
+        }))
+        .await
+    }
+
+    /// Remove every query containing any of the given keys from the cache.
+    /// Queries that still have listeners are reset back to `Loading(None)` and fetched again in the **background**.
+    /// Outside of any component and without a spawner, see [UseQueryClient::with_spawner],
+    /// they are only fetched again once a listener fetches them.
+    pub fn remove_queries(&self, keys_to_remove: &[K]) {
+        self.remove_queries_matching(|query_keys| {
+            query_keys.iter().any(|k| keys_to_remove.contains(k))
+        });
+    }
+
+    /// Remove every query from the cache, see [UseQueryClient::remove_queries].
+    pub fn remove_all(&self) {
+        self.remove_queries_matching(|_| true);
+    }
+
+    pub(crate) fn remove_queries_matching(&self, predicate: impl Fn(&[K]) -> bool) {
+        let mut entries_to_refetch = Vec::new();
+        let mut listeners_to_notify = HashSet::<ScopeId>::default();
+
+        for (entry, query_listeners) in self.get_entries_matching(predicate) {
+            // Make sure in-flight fetches and streams don't bring the data back
+            query_listeners.discard();
+            self.notify_data_listeners(&entry.query_keys);
+
+            if self.is_entry_unused(&entry, &query_listeners) {
+                self.queries_registry.borrow_mut().remove(&entry);
+                self.emit_cache_event(QueryCacheEvent::Removed {
+                    query_keys: &entry.query_keys,
+                });
+                continue;
+            }
+
+            // Keep the entries that are still being listened to, but without their data,
+            // which every listener is notified of as their data is gone.
+            // Entries only paused listeners are left with are fetched once one resumes
+            let value = &query_listeners.value;
+            sync::write(value).reset();
+            self.emit_cache_updated(&entry.query_keys, value);
+            listeners_to_notify.extend(query_listeners.listeners.borrow().keys());
+            if query_listeners.is_observed() {
+                entries_to_refetch.push(entry);
+            }
+        }
+
+        for listener in listeners_to_notify {
+            (self.scheduler)(listener);
+        }
+        self.notify_fetching_listeners();
+
+        let client = self.clone();
+        self.spawn_task(async move {
+            let tasks = FuturesUnordered::new();
+            for entry in &entries_to_refetch {
+                tasks.push(client.run_new_query(entry));
+            }
+            tasks.count().await;
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::MockClock;
+    use futures_util::future::{ready, BoxFuture, FutureExt, Ready};
+    use std::{
+        collections::VecDeque,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Mutex,
+        },
+    };
+    use tokio::{
+        sync::oneshot,
+        task::{spawn_local, yield_now, LocalSet},
+    };
+
+    type TestClient = UseQueryClient<usize, String, &'static str>;
+
+    /// A client outside of any component, running its background tasks on the current `LocalSet`,
+    /// with a clock that only moves when told to.
+    fn client() -> (TestClient, MockClock) {
+        let clock = MockClock::default();
+        let client = TestClient::new_detached()
+            .with_config(QueryClientConfig::default().clock(clock.clone()))
+            .with_spawner(|task| {
+                spawn_local(task);
+            });
+        (client, clock)
+    }
+
+    /// A query function resolving right away with how many times it was called.
+    fn counting_fn(
+        calls: Arc<AtomicUsize>,
+    ) -> impl Fn(Vec<&'static str>) -> Ready<QueryResult<usize, String>> {
+        move |_| ready(QueryResult::Ok(calls.fetch_add(1, Ordering::SeqCst) + 1))
+    }
+
+    /// A query function whose calls resolve, in order, with the values sent to the returned senders.
+    fn gated_fn(
+        calls: usize,
+    ) -> (
+        VecDeque<oneshot::Sender<usize>>,
+        impl Fn(Vec<&'static str>) -> BoxFuture<'static, QueryResult<usize, String>>,
+    ) {
+        let (senders, receivers): (VecDeque<_>, VecDeque<_>) =
+            (0..calls).map(|_| oneshot::channel()).unzip();
+        let receivers = Mutex::new(receivers);
+        let query_fn = move |_: Vec<&'static str>| {
+            let receiver = receivers
+                .lock()
+                .unwrap()
+                .pop_front()
+                .expect("the query function was called more times than expected");
+            async move {
+                match receiver.await {
+                    Ok(value) => QueryResult::Ok(value),
+                    Err(_) => QueryResult::Err("gate dropped".to_string()),
+                }
+            }
+            .boxed()
+        };
+        (senders, query_fn)
+    }
+
+    /// Let the spawned tasks run until they are all waiting for something.
+    async fn settle() {
+        for _ in 0..10 {
+            yield_now().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn removing_an_entry_while_it_is_fetched_does_not_panic() {
+        LocalSet::new()
+            .run_until(async {
+                let (client, _) = client();
+                let (mut gates, query_fn) = gated_fn(1);
+                let handle = client.register_query(vec!["user"], query_fn);
+                let entry = handle.registry_entry.clone();
+                let fetch = spawn_local({
+                    let client = client.clone();
+                    async move { client.run_new_query(&entry).await }
+                });
+                settle().await;
+                assert!(handle.result().is_fetching());
+
+                // The last listener unmounts mid-fetch, then the query function resolves
+                drop(handle);
+                let _ = gates.pop_front().unwrap().send(1);
+
+                fetch.await.expect("the fetch panicked");
+                assert_eq!(client.queries_registry.borrow().len(), 0);
+                assert_eq!(client.get_query_data(&["user"]), None);
+            })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn query_functions_can_use_the_client_running_them() {
+        LocalSet::new()
+            .run_until(async {
+                let (client, _) = client();
+                let b_calls = Arc::new(AtomicUsize::new(0));
+                let b = client.register_query(vec!["b"], counting_fn(b_calls.clone()));
+                b.fetch().await;
+
+                // Reads the cache and invalidates another query while the client is fetching this one
+                let a = client.register_query(vec!["a"], {
+                    let client = client.clone();
+                    move |_: Vec<&'static str>| {
+                        client.invalidate_queries(&["b"]);
+                        ready(match client.get_query_data(&["b"]) {
+                            Some(b) => QueryResult::Ok(b),
+                            None => QueryResult::Err("b isn't cached".to_string()),
+                        })
+                    }
+                });
+                assert_eq!(a.fetch().await, QueryResult::Ok(1));
+                let outcomes = client.manual_invalidate_queries(&["a", "b"]).await;
+                assert_eq!(outcomes.len(), 2);
+
+                // Both invalidations started by `a` refetch `b` once they run
+                settle().await;
+                assert_eq!(b_calls.load(Ordering::SeqCst), 4);
+                assert_eq!(b.result().value(), &QueryResult::Ok(4));
+            })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn keys_in_any_order_share_their_entry() {
+        LocalSet::new()
+            .run_until(async {
+                let (client, _) = client();
+                let client = client.with_unordered_keys();
+                let calls = Arc::new(AtomicUsize::new(0));
+                let first =
+                    client.register_query(vec!["settings", "user"], counting_fn(calls.clone()));
+                let second =
+                    client.register_query(vec!["user", "settings"], counting_fn(calls.clone()));
+                assert_eq!(client.queries_registry.borrow().len(), 1);
+
+                assert_eq!(first.fetch().await, QueryResult::Ok(1));
+                assert_eq!(second.fetch().await, QueryResult::Ok(1));
+                assert_eq!(client.get_query_data(&["user", "settings"]), Some(1));
+                assert_eq!(client.get_query_data(&["settings", "user"]), Some(1));
+
+                let outcomes = client.manual_invalidate_query("user").await;
+                assert_eq!(outcomes.len(), 1);
+                assert_eq!(calls.load(Ordering::SeqCst), 2);
+                assert_eq!(second.result().value(), &QueryResult::Ok(2));
+            })
+            .await;
+    }
+
+    /// A query function failing with the given errors in order, then with the last one.
+    fn failing_fn(
+        errors: &'static [&'static str],
+        calls: Arc<AtomicUsize>,
+    ) -> impl Fn(Vec<&'static str>) -> Ready<QueryResult<usize, String>> {
+        move |_| {
+            let call = calls.fetch_add(1, Ordering::SeqCst);
+            ready(QueryResult::Err(
+                errors[call.min(errors.len() - 1)].to_string(),
+            ))
+        }
+    }
+
+    #[tokio::test]
+    async fn only_the_errors_accepted_by_retry_if_are_retried() {
+        LocalSet::new()
+            .run_until(async {
+                let (client, _) = client();
+                let calls = Arc::new(AtomicUsize::new(0));
+                let attempts = Arc::new(Mutex::new(Vec::new()));
+                let handle = client.register_query_config(
+                    QueryConfig::new(
+                        vec!["user"],
+                        failing_fn(&["timeout", "timeout", "unauthorized"], calls.clone()),
+                    )
+                    .retry(5)
+                    .retry_if({
+                        let attempts = attempts.clone();
+                        move |err: &String, attempt, _| {
+                            attempts.lock().unwrap().push(attempt);
+                            err == "timeout"
+                        }
+                    }),
+                );
+
+                let result = handle.fetch().await;
+                assert_eq!(result, QueryResult::Err("unauthorized".to_string()));
+                assert_eq!(calls.load(Ordering::SeqCst), 3);
+                assert_eq!(*attempts.lock().unwrap(), vec![1, 2, 3]);
+            })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn retry_if_is_not_consulted_once_the_retries_run_out() {
+        LocalSet::new()
+            .run_until(async {
+                let (client, _) = client();
+                let calls = Arc::new(AtomicUsize::new(0));
+                let attempts = Arc::new(Mutex::new(Vec::new()));
+                let handle = client.register_query_config(
+                    QueryConfig::new(vec!["user"], failing_fn(&["timeout"], calls.clone()))
+                        .retry(2)
+                        .retry_if({
+                            let attempts = attempts.clone();
+                            move |_: &String, attempt, _| {
+                                attempts.lock().unwrap().push(attempt);
+                                true
+                            }
+                        }),
+                );
+
+                let result = handle.fetch().await;
+                assert_eq!(result, QueryResult::Err("timeout".to_string()));
+                assert_eq!(calls.load(Ordering::SeqCst), 3);
+                assert_eq!(*attempts.lock().unwrap(), vec![1, 2]);
+            })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn a_panic_while_an_entry_is_locked_only_affects_that_entry() {
+        LocalSet::new()
+            .run_until(async {
+                let (client, _) = client();
+                let a_calls = Arc::new(AtomicUsize::new(0));
+                let b_calls = Arc::new(AtomicUsize::new(0));
+                let a = client.register_query(vec!["a"], counting_fn(a_calls.clone()));
+                let b = client.register_query(vec!["b"], counting_fn(b_calls.clone()));
+                a.fetch().await;
+                b.fetch().await;
+
+                // Poison the lock of `a` by panicking while editing its value
+                let edit = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                    client.update_query_data(&["a"], |value| {
+                        *value = 10;
+                        panic!("the edit failed halfway");
+                    })
+                }));
+                assert!(edit.is_err());
+
+                // `a` is still readable and can be refetched
+                assert_eq!(client.get_query_data(&["a"]), Some(10));
+                assert_eq!(a.result().value(), &QueryResult::Ok(10));
+                assert_eq!(client.manual_invalidate_query("a").await.len(), 1);
+                assert_eq!(a.result().value(), &QueryResult::Ok(2));
+
+                // `b` is unaffected
+                assert_eq!(client.manual_invalidate_query("b").await.len(), 1);
+                assert_eq!(b.result().value(), &QueryResult::Ok(2));
+                assert_eq!(client.get_query_data(&["b"]), Some(2));
+            })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn invalidations_report_what_they_did_with_each_entry() {
+        LocalSet::new()
+            .run_until(async {
+                let (client, _) = client();
+                let calls = Arc::new(AtomicUsize::new(0));
+                let refetched = client.register_query(vec!["x", "ok"], counting_fn(calls.clone()));
+                refetched.fetch().await;
+                let failed = client.register_query(
+                    vec!["x", "failing"],
+                    failing_fn(&["timeout"], Arc::default()),
+                );
+                failed.fetch().await;
+                client
+                    .prefetch_query(vec!["x", "unobserved"], counting_fn(calls.clone()))
+                    .await;
+                let _idle = client.register_query_config(
+                    QueryConfig::new(vec!["x", "idle"], counting_fn(calls.clone()))
+                        .fetch_on_mount(false),
+                );
+                let _disabled = client.register_query_config(
+                    QueryConfig::new(vec!["x", "disabled"], counting_fn(calls.clone()))
+                        .enabled_fn(|| false),
+                );
+
+                let mut outcomes = client.manual_invalidate_queries(&["x"]).await;
+                outcomes.sort_by_key(|outcome| outcome.query_keys.clone());
+                let statuses = outcomes
+                    .into_iter()
+                    .map(|outcome| (outcome.query_keys[1], outcome.status))
+                    .collect::<Vec<_>>();
+                assert_eq!(
+                    statuses,
+                    vec![
+                        ("disabled", InvalidationStatus::Disabled),
+                        (
+                            "failing",
+                            InvalidationStatus::Refetched {
+                                error: Some("timeout".to_string()),
+                                duration: Duration::ZERO,
+                            }
+                        ),
+                        ("idle", InvalidationStatus::Idle),
+                        (
+                            "ok",
+                            InvalidationStatus::Refetched {
+                                error: None,
+                                duration: Duration::ZERO,
+                            }
+                        ),
+                        ("unobserved", InvalidationStatus::Unobserved),
+                    ]
+                );
+
+                // Only the observed entries were refetched, the unobserved one is left stale
+                assert_eq!(refetched.result().value(), &QueryResult::Ok(3));
+                let unobserved = client
+                    .get_entry_with_keys(&["x", "unobserved"])
+                    .expect("the unobserved entry is kept");
+                assert!(!sync::read(&unobserved.value).is_fresh());
+            })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn values_expire_while_they_are_refetched() {
+        LocalSet::new()
+            .run_until(async {
+                let (client, clock) = client();
+                let (mut gates, query_fn) = gated_fn(2);
+                let handle = client.register_query_config(
+                    QueryConfig::new(vec!["quote"], query_fn).max_age(Duration::from_millis(500)),
+                );
+                let entry = handle.registry_entry.clone();
+                let fetch = |entry: RegistryEntry<&'static str>| {
+                    let client = client.clone();
+                    spawn_local(async move { client.run_new_query(&entry).await })
+                };
+
+                let first = fetch(entry.clone());
+                settle().await;
+                let _ = gates.pop_front().unwrap().send(1);
+                first.await.unwrap();
+                assert_eq!(handle.result().value(), &QueryResult::Ok(1));
+
+                // The value gets stale and is refetched, then expires before the refetch resolves
+                clock.advance(Duration::from_millis(200));
+                let refetch = fetch(entry);
+                settle().await;
+                assert!(handle.result().is_fetching());
+                clock.advance(Duration::from_millis(400));
+
+                // A read while an earlier guard is still held doesn't wait for it to expire the value
+                {
+                    let held = sync::read(&handle.value);
+                    assert!(held.is_expired());
+                    assert!(handle.result().is_expired());
+                }
+                let result = handle.result();
+                assert_eq!(result.value(), &QueryResult::Loading(None));
+                assert!(!result.is_expired());
+                assert!(result.is_fetching());
+                drop(result);
+
+                let _ = gates.pop_front().unwrap().send(2);
+                refetch.await.unwrap();
+                let result = handle.result();
+                assert_eq!(result.value(), &QueryResult::Ok(2));
+                assert!(!result.is_expired());
+            })
+            .await;
+    }
+
+    /// Mount a component fetching a query, then mount a second one while the result is fresh,
+    /// and unmount and remount it once the result is stale. Returns how many times the query was fetched.
+    async fn fetches_when_mounting(refetch_on_mount: RefetchOnMount) -> usize {
+        let (client, clock) = client();
+        let calls = Arc::new(AtomicUsize::new(0));
+        let mount = || {
+            let handle = client.register_query_config(
+                QueryConfig::new(vec!["user"], counting_fn(calls.clone()))
+                    .refetch_on_mount(refetch_on_mount),
+            );
+            let client = client.clone();
+            async move {
+                client
+                    .run_mount_query(&handle.registry_entry, refetch_on_mount)
+                    .await;
+                handle
+            }
+        };
+
+        let _first = mount().await;
+        let second = mount().await;
+        drop(second);
+        clock.advance(Duration::from_millis(200));
+        let _second = mount().await;
+        calls.load(Ordering::SeqCst)
+    }
+
+    #[tokio::test]
+    async fn mounting_always_refetches() {
+        LocalSet::new()
+            .run_until(async {
+                assert_eq!(fetches_when_mounting(RefetchOnMount::Always).await, 3);
+            })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn mounting_refetches_stale_results() {
+        LocalSet::new()
+            .run_until(async {
+                assert_eq!(fetches_when_mounting(RefetchOnMount::IfStale).await, 2);
+            })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn mounting_never_refetches_cached_results() {
+        LocalSet::new()
+            .run_until(async {
+                assert_eq!(fetches_when_mounting(RefetchOnMount::Never).await, 1);
+            })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn invalidated_entries_are_refetched_once_remounted() {
+        LocalSet::new()
+            .run_until(async {
+                let (client, _) = client();
+                let calls = Arc::new(AtomicUsize::new(0));
+                // Keeps the entry in the cache once its only component unmounts
+                let _watcher = client.watch(&["user"]);
+
+                let handle = client.register_query(vec!["user"], counting_fn(calls.clone()));
+                handle.fetch().await;
+                drop(handle);
+                assert_eq!(client.queries_registry.borrow().len(), 1);
+
+                let outcomes = client.manual_invalidate_query("user").await;
+                assert_eq!(
+                    outcomes
+                        .into_iter()
+                        .map(|outcome| outcome.status)
+                        .collect::<Vec<_>>(),
+                    vec![InvalidationStatus::Unobserved]
+                );
+                assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+                // The result is still fresh by its age, but it was invalidated
+                let handle = client.register_query(vec!["user"], counting_fn(calls.clone()));
+                client
+                    .run_mount_query(&handle.registry_entry, RefetchOnMount::IfStale)
+                    .await;
+                assert_eq!(calls.load(Ordering::SeqCst), 2);
+                assert_eq!(handle.result().value(), &QueryResult::Ok(2));
+            })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn invalidating_aborts_the_fetch_in_flight() {
+        LocalSet::new()
+            .run_until(async {
+                let (client, _) = client();
+                let (mut gates, query_fn) = gated_fn(2);
+                let handle = client.register_query(vec!["user"], query_fn);
+                let entry = handle.registry_entry.clone();
+                let fetch = spawn_local({
+                    let client = client.clone();
+                    async move { client.run_new_query(&entry).await }
+                });
+                settle().await;
+                let invalidation = spawn_local({
+                    let client = client.clone();
+                    async move { client.manual_invalidate_query("user").await }
+                });
+                settle().await;
+
+                // The first call was given up on, so it can't overwrite the newer result
+                fetch.await.unwrap();
+                assert!(gates.pop_front().unwrap().send(1).is_err());
+                let _ = gates.pop_front().unwrap().send(2);
+                let outcomes = invalidation.await.unwrap();
+                assert_eq!(
+                    outcomes
+                        .into_iter()
+                        .map(|outcome| outcome.status)
+                        .collect::<Vec<_>>(),
+                    vec![InvalidationStatus::Refetched {
+                        error: None,
+                        duration: Duration::ZERO,
+                    }]
+                );
+                assert_eq!(handle.result().value(), &QueryResult::Ok(2));
+            })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn invalidations_superseded_by_a_newer_one_report_it() {
+        LocalSet::new()
+            .run_until(async {
+                let (client, _) = client();
+                let (mut gates, query_fn) = gated_fn(2);
+                let handle = client.register_query(vec!["user"], query_fn);
+                let invalidate = || {
+                    let client = client.clone();
+                    spawn_local(async move {
+                        let outcomes = client.manual_invalidate_query("user").await;
+                        outcomes
+                            .into_iter()
+                            .map(|outcome| outcome.status)
+                            .collect::<Vec<_>>()
+                    })
+                };
+                let first = invalidate();
+                settle().await;
+                let second = invalidate();
+                settle().await;
+
+                assert_eq!(first.await.unwrap(), vec![InvalidationStatus::Superseded]);
+                assert!(gates.pop_front().unwrap().send(1).is_err());
+                let _ = gates.pop_front().unwrap().send(2);
+                assert!(matches!(
+                    second.await.unwrap()[..],
+                    [InvalidationStatus::Refetched { error: None, .. }]
+                ));
+                assert_eq!(handle.result().value(), &QueryResult::Ok(2));
+            })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn concurrent_validations_fetch_once() {
+        LocalSet::new()
+            .run_until(async {
+                let (client, _) = client();
+                // Calling it a second time panics
+                let (mut gates, query_fn) = gated_fn(1);
+                let handle = client.register_query(vec!["user"], query_fn);
+                let validate = || {
+                    let client = client.clone();
+                    let entry = handle.registry_entry.clone();
+                    spawn_local(async move { client.run_new_query(&entry).await })
+                };
+                let first = validate();
+                let second = validate();
+                settle().await;
+
+                let _ = gates.pop_front().unwrap().send(1);
+                first.await.expect("the first validation panicked");
+                second.await.expect("the second validation panicked");
+                assert_eq!(handle.result().value(), &QueryResult::Ok(1));
+            })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn the_next_fetch_uses_the_refreshed_query_function() {
+        LocalSet::new()
+            .run_until(async {
+                let (client, _) = client();
+                let returning =
+                    |value: usize| move |_: Vec<&'static str>| ready(QueryResult::Ok(value));
+                let handle = client.register_query(vec!["user"], returning(1));
+                assert_eq!(handle.fetch().await, QueryResult::Ok(1));
+
+                // A re-render with a closure capturing a newer value
+                client.set_query_fn(
+                    &handle.registry_entry,
+                    QueryConfig::new(Vec::new(), returning(2)).query_fn,
+                );
+                client.manual_invalidate_query("user").await;
+                assert_eq!(handle.result().value(), &QueryResult::Ok(2));
+                assert_eq!(client.queries_registry.borrow().len(), 1);
+            })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn only_the_callbacks_of_the_config_creating_the_entry_run() {
+        LocalSet::new()
+            .run_until(async {
+                let (client, _) = client();
+                let log = Arc::new(Mutex::new(Vec::new()));
+                let calls = Arc::new(AtomicUsize::new(0));
+                // Succeeds the first time, then fails
+                let query_fn = move |_: Vec<&'static str>| {
+                    ready(match calls.fetch_add(1, Ordering::SeqCst) {
+                        0 => QueryResult::Ok(1),
+                        _ => QueryResult::Err("timeout".to_string()),
+                    })
+                };
+                let logging = |name: &'static str| {
+                    let (success, error, settled) = (log.clone(), log.clone(), log.clone());
+                    QueryConfig::new(vec!["cart"], query_fn.clone())
+                        .on_success(move |value: &usize| {
+                            success
+                                .lock()
+                                .unwrap()
+                                .push(format!("{name} success {value}"))
+                        })
+                        .on_error(move |err: &String| {
+                            error.lock().unwrap().push(format!("{name} error {err}"))
+                        })
+                        .on_settled(move |result: &QueryResult<usize, String>| {
+                            settled
+                                .lock()
+                                .unwrap()
+                                .push(format!("{name} settled {result:?}"))
+                        })
+                };
+                let first = client.register_query_config(logging("first"));
+                let _second = client.register_query_config(logging("second"));
+                assert_eq!(client.queries_registry.borrow().len(), 1);
+
+                first.fetch().await;
+                client.manual_invalidate_query("cart").await;
+                assert_eq!(
+                    *log.lock().unwrap(),
+                    vec![
+                        "first success 1",
+                        "first settled Ok(1)",
+                        "first error timeout",
+                        "first settled Err(\"timeout\")",
+                    ]
+                );
+            })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn prefetched_entries_are_collected_once_left_unused() {
+        LocalSet::new()
+            .run_until(async {
+                let (client, clock) = client();
+                let calls = Arc::new(AtomicUsize::new(0));
+                client
+                    .prefetch_query(vec!["unused"], counting_fn(calls.clone()))
+                    .await;
+                client
+                    .prefetch_query(vec!["used"], counting_fn(calls.clone()))
+                    .await;
+                let _used = client.register_query(vec!["used"], counting_fn(calls.clone()));
+                settle().await;
+                assert_eq!(client.queries_registry.borrow().len(), 2);
+
+                clock.advance(Duration::from_millis(CACHE_TIME));
+                settle().await;
+                assert_eq!(client.get_query_data(&["unused"]), None);
+                assert_eq!(client.get_query_data(&["used"]), Some(2));
+                assert_eq!(client.queries_registry.borrow().len(), 1);
+            })
+            .await;
+    }
+
+    /// A persister keeping the written results in memory, waiting for the given clock to move before writing them.
+    #[derive(Clone, Default)]
+    struct MemoryPersister {
+        persisted: Arc<Mutex<Vec<DehydratedQuery<usize, &'static str>>>>,
+        debounce: Option<MockClock>,
+    }
+
+    impl QueryPersister<usize, &'static str> for MemoryPersister {
+        fn persist(&self, query: DehydratedQuery<usize, &'static str>) {
+            self.persisted.lock().unwrap().push(query);
+        }
+
+        fn remove(&self, _query_keys: &[&'static str]) {}
+
+        fn restore_all(&self) -> Vec<DehydratedQuery<usize, &'static str>> {
+            Vec::new()
+        }
+
+        fn debounce(&self) -> Box<dyn Future<Output = ()>> {
+            match &self.debounce {
+                Some(clock) => clock.sleep(Duration::from_secs(1)),
+                None => Box::new(async {}),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn results_that_cant_be_persisted_dont_hold_back_the_next_ones() {
+        LocalSet::new()
+            .run_until(async {
+                let persister = MemoryPersister::default();
+                let client = TestClient::new_detached()
+                    .with_config(QueryClientConfig::default().clock(MockClock::default()))
+                    .with_persister(persister.clone());
+                let calls = Arc::new(AtomicUsize::new(0));
+                let handle = client.register_query(vec!["user"], counting_fn(calls.clone()));
+
+                // Without a spawner the write is dropped
+                handle.fetch().await;
+                settle().await;
+                assert!(persister.persisted.lock().unwrap().is_empty());
+
+                // Set after the persister, it's still used for the next write
+                let client = client.with_spawner(|task| {
+                    spawn_local(task);
+                });
+                client.manual_invalidate_query("user").await;
+                settle().await;
+                let persisted = persister.persisted.lock().unwrap();
+                assert_eq!(
+                    persisted
+                        .iter()
+                        .map(|query| (query.query_keys.clone(), query.data))
+                        .collect::<Vec<_>>(),
+                    vec![(vec!["user"], 2)]
+                );
+            })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn persisted_results_keep_their_age_at_the_time_they_are_written() {
+        LocalSet::new()
+            .run_until(async {
+                let (client, clock) = client();
+                let persister = MemoryPersister {
+                    debounce: Some(clock.clone()),
+                    ..Default::default()
+                };
+                let client = client.with_persister(persister.clone());
+                let handle = client.register_query(vec!["user"], counting_fn(Arc::default()));
+                handle.fetch().await;
+                settle().await;
+                assert!(persister.persisted.lock().unwrap().is_empty());
+
+                clock.advance(Duration::from_millis(1500));
+                settle().await;
+                let persisted = persister.persisted.lock().unwrap();
+                assert_eq!(persisted.len(), 1);
+                assert_eq!(persisted[0].age, Duration::from_millis(1500));
+            })
+            .await;
+    }
+
+    /// A client like [client] whose scheduler records the components it re-renders.
+    fn rendering_client() -> (TestClient, Arc<Mutex<Vec<ScopeId>>>) {
+        let renders = Arc::new(Mutex::new(Vec::new()));
+        let client = TestClient::new(Arc::new({
+            let renders = renders.clone();
+            move |scope_id| renders.lock().unwrap().push(scope_id)
+        }))
+        .with_config(QueryClientConfig::default().clock(MockClock::default()))
+        .with_spawner(|task| {
+            spawn_local(task);
+        });
+        (client, renders)
+    }
+
+    #[tokio::test]
+    async fn limited_invalidations_rerender_as_each_query_finishes() {
+        LocalSet::new()
+            .run_until(async {
+                let (client, renders) = rendering_client();
+                let (mut gates, query_fn) = gated_fn(2);
+                let query_fn = Arc::new(query_fn);
+                for key in ["a", "b"] {
+                    let query_fn = query_fn.clone();
+                    let config = QueryConfig::new(vec!["user", key], move |keys| query_fn(keys));
+                    client.register_listener(&config, Some(ScopeId(1)));
+                }
+
+                let invalidation = spawn_local({
+                    let client = client.clone();
+                    async move {
+                        let options = InvalidateOptions {
+                            max_concurrency: Some(1),
+                            ..Default::default()
+                        };
+                        client
+                            .invalidate_keys_inner(&["user"], |_| true, false, false, options)
+                            .await
+                    }
+                });
+                settle().await;
+                renders.lock().unwrap().clear();
+
+                // The component sees the first result while the second query is still fetching
+                gates.pop_front().unwrap().send(1).unwrap();
+                settle().await;
+                assert_eq!(*renders.lock().unwrap(), vec![ScopeId(1)]);
+
+                gates.pop_front().unwrap().send(2).unwrap();
+                assert_eq!(invalidation.await.unwrap().len(), 2);
+                assert_eq!(*renders.lock().unwrap(), vec![ScopeId(1), ScopeId(1)]);
+            })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn sequential_invalidations_refetch_one_after_another_in_creation_order() {
+        LocalSet::new()
+            .run_until(async {
+                let (client, renders) = rendering_client();
+                let (mut gates, query_fn) = gated_fn(3);
+                let query_fn = Arc::new(query_fn);
+                let started = Arc::new(Mutex::new(Vec::new()));
+                // Created in another order than the one of their keys
+                for key in ["c", "a", "b"] {
+                    let query_fn = query_fn.clone();
+                    let started = started.clone();
+                    let config = QueryConfig::new(vec!["user", key], move |keys: Vec<_>| {
+                        started.lock().unwrap().push(keys[1]);
+                        query_fn(keys)
+                    });
+                    client.register_listener(&config, Some(ScopeId(1)));
+                }
+
+                let invalidation = spawn_local({
+                    let client = client.clone();
+                    async move {
+                        let options = InvalidateOptions {
+                            mode: InvalidateMode::Sequential,
+                            ..Default::default()
+                        };
+                        client
+                            .invalidate_keys_inner(&["user"], |_| true, false, false, options)
+                            .await
+                    }
+                });
+                settle().await;
+                assert_eq!(*started.lock().unwrap(), vec!["c"]);
+                renders.lock().unwrap().clear();
+
+                // Each query only starts once the previous one is cached and its listeners notified
+                for (value, expected) in [(1, vec!["c", "a"]), (2, vec!["c", "a", "b"])] {
+                    gates.pop_front().unwrap().send(value).unwrap();
+                    settle().await;
+                    assert_eq!(*started.lock().unwrap(), expected);
+                    assert_eq!(renders.lock().unwrap().len(), value);
+                }
+
+                gates.pop_front().unwrap().send(3).unwrap();
+                let refetched = invalidation
+                    .await
+                    .unwrap()
+                    .into_iter()
+                    .map(|outcome| outcome.query_keys[1])
+                    .collect::<Vec<_>>();
+                assert_eq!(refetched, vec!["c", "a", "b"]);
+                assert_eq!(renders.lock().unwrap().len(), 3);
+            })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn tags_are_looked_up_apart_from_query_keys() {
+        LocalSet::new()
+            .run_until(async {
+                let (client, _) = client();
+                let calls = Arc::new(AtomicUsize::new(0));
+                let _user = client.register_query_config(
+                    QueryConfig::new(vec!["user"], counting_fn(calls.clone())).tags(vec!["users"]),
+                );
+                let _post = client.register_query(vec!["post"], counting_fn(calls.clone()));
+
+                let outcomes = client.manual_invalidate_tag(&"users").await;
+                assert_eq!(
+                    outcomes
+                        .into_iter()
+                        .map(|outcome| outcome.query_keys)
+                        .collect::<Vec<_>>(),
+                    vec![vec!["user"]]
+                );
+                assert!(client.manual_invalidate_tag(&"user").await.is_empty());
+                assert_eq!(calls.load(Ordering::SeqCst), 1);
+            })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn listeners_registered_while_fetching_are_rerendered_by_the_same_fetch() {
+        LocalSet::new()
+            .run_until(async {
+                let (client, renders) = rendering_client();
+                // Panics if the second listener started another fetch
+                let (mut gates, query_fn) = gated_fn(1);
+                let config = QueryConfig::new(vec!["user"], query_fn);
+                client.register_listener(&config, Some(ScopeId(1)));
+                let fetch = spawn_local({
+                    let client = client.clone();
+                    let entry = config.registry_entry.clone();
+                    async move { client.run_new_query(&entry).await }
+                });
+                settle().await;
+
+                client.register_listener(&config, Some(ScopeId(2)));
+                // The entry is cloned without copying its listeners, so the fetch sees the new one
+                let (first, second) = (
+                    client.get_entry(&config.registry_entry).unwrap(),
+                    client.get_entry(&config.registry_entry).unwrap(),
+                );
+                assert!(Shared::ptr_eq(&first.listeners, &second.listeners));
+                renders.lock().unwrap().clear();
+
+                gates.pop_front().unwrap().send(1).unwrap();
+                fetch.await.unwrap();
+                let mut rendered = renders
+                    .lock()
+                    .unwrap()
+                    .iter()
+                    .map(|scope_id| scope_id.0)
+                    .collect::<Vec<_>>();
+                rendered.sort();
+                assert_eq!(rendered, vec![1, 2]);
+            })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn throttled_invalidations_collapse_into_one_trailing_fetch() {
+        LocalSet::new()
+            .run_until(async {
+                let (client, clock) = client();
+                let calls = Arc::new(AtomicUsize::new(0));
+                let user = client.register_query_config(
+                    QueryConfig::new(vec!["user"], counting_fn(calls.clone()))
+                        .min_fetch_interval(Duration::from_secs(1)),
+                );
+                user.fetch().await;
+
+                let invalidations = (0..3)
+                    .map(|_| {
+                        let client = client.clone();
+                        spawn_local(async move { client.manual_invalidate_query("user").await })
+                    })
+                    .collect::<Vec<_>>();
+                settle().await;
+                // Left stale until the interval elapses
+                assert_eq!(calls.load(Ordering::SeqCst), 1);
+                assert!(!user.result().is_fresh());
+
+                clock.advance(Duration::from_secs(1));
+                for invalidation in invalidations {
+                    invalidation.await.unwrap();
+                }
+                assert_eq!(calls.load(Ordering::SeqCst), 2);
+                assert_eq!(*user.result().value(), QueryResult::Ok(2));
+            })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn the_throttle_delays_stale_fetches_but_never_refetches_fresh_results() {
+        LocalSet::new()
+            .run_until(async {
+                let (client, clock) = client();
+                let calls = Arc::new(AtomicUsize::new(0));
+                let fresh = client.register_query_config(
+                    QueryConfig::new(vec!["fresh"], counting_fn(calls.clone()))
+                        .stale_time(Duration::from_secs(3600))
+                        .min_fetch_interval(Duration::from_secs(1)),
+                );
+                let stale = client.register_query_config(
+                    QueryConfig::new(vec!["stale"], counting_fn(calls.clone()))
+                        .stale_time(Duration::ZERO)
+                        .min_fetch_interval(Duration::from_secs(1)),
+                );
+                fresh.fetch().await;
+                stale.fetch().await;
+                assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+                // The stale result waits for the interval, the fresh one isn't fetched even once it's over
+                let refetch = spawn_local({
+                    let client = client.clone();
+                    let entry = stale.registry_entry.clone();
+                    async move { client.run_new_query(&entry).await }
+                });
+                settle().await;
+                assert_eq!(calls.load(Ordering::SeqCst), 2);
+                clock.advance(Duration::from_secs(1));
+                refetch.await.unwrap();
+                assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+                clock.advance(Duration::from_secs(1));
+                fresh.fetch().await;
+                assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+                // Once the interval is over, stale results are fetched right away
+                stale.fetch().await;
+                assert_eq!(calls.load(Ordering::SeqCst), 4);
+            })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn detached_clients_run_their_background_work_with_their_spawner() {
+        LocalSet::new()
+            .run_until(async {
+                let calls = Arc::new(AtomicUsize::new(0));
+                let without_spawner = TestClient::new_detached()
+                    .with_config(QueryClientConfig::default().clock(MockClock::default()));
+                let (with_spawner, _) = client();
+                // Without a spawner, the background invalidation is dropped
+                for (client, refetches) in [(&without_spawner, 0), (&with_spawner, 1)] {
+                    let user = client.register_query(vec!["user"], counting_fn(calls.clone()));
+                    calls.store(0, Ordering::SeqCst);
+                    user.fetch().await;
+                    // Still fresh
+                    user.fetch().await;
+                    assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+                    client.invalidate_queries_immediately(&["user"]);
+                    settle().await;
+                    assert_eq!(calls.load(Ordering::SeqCst), 1 + refetches);
+                    assert_eq!(*user.result().value(), QueryResult::Ok(1 + refetches));
+                }
+            })
+            .await;
+    }
+
+    /// An [OnlineManager] counting the functions subscribed to it.
+    #[derive(Clone, Default)]
+    struct CountingOnlineManager {
+        subscribed: Arc<AtomicUsize>,
+    }
+
+    impl OnlineManager for CountingOnlineManager {
+        fn is_online(&self) -> bool {
+            true
+        }
+
+        fn subscribe(
+            &self,
+            _on_change: Box<crate::online_manager::OnlineChangeFn>,
+        ) -> ManagerSubscription {
+            self.subscribed.fetch_add(1, Ordering::SeqCst);
+            let subscribed = self.subscribed.clone();
+            ManagerSubscription::new(move || {
+                subscribed.fetch_sub(1, Ordering::SeqCst);
+            })
+        }
+    }
+
+    #[test]
+    fn clients_unsubscribe_from_their_managers_once_dropped() {
+        let manager = CountingOnlineManager::default();
+        let config = QueryClientConfig::default().online_manager(manager.clone());
+        let client = TestClient::new_detached().with_config(config.clone());
+        assert_eq!(manager.subscribed.load(Ordering::SeqCst), 1);
+
+        // Configuring it again replaces the subscription
+        let client = client.with_config(config);
+        assert_eq!(manager.subscribed.load(Ordering::SeqCst), 1);
+
+        // Only once the last clone is gone
+        let clone = client.clone();
+        drop(client);
+        assert_eq!(manager.subscribed.load(Ordering::SeqCst), 1);
+        drop(clone);
+        assert_eq!(manager.subscribed.load(Ordering::SeqCst), 0);
     }
 }
@@ -0,0 +1,101 @@
+use dioxus::prelude::*;
+use std::hash::Hash;
+
+use crate::{
+    cached_result::{CachedResult, QueryState},
+    result::QueryResult,
+    sync,
+    use_query_client::{use_query_client, UseQueryClient},
+};
+
+/// A read-only listener of the cached value of some query keys.
+///
+/// It never fetches nor creates cache entries, it only reflects what other
+/// query listeners have cached for the same keys.
+pub struct UseQueryData<T, E, K: Eq + Hash> {
+    client: UseQueryClient<T, E, K>,
+    query_keys: Vec<K>,
+    scope_id: ScopeId,
+}
+
+impl<T, E, K> UseQueryData<T, E, K>
+where
+    T: 'static + Clone,
+    E: 'static + Clone,
+    K: 'static + Eq + Hash + Clone,
+{
+    /// Get a copy of the cached result, `None` if nothing is cached for these keys.
+    /// If multiple query functions are cached with the same keys, the most recently updated one is used.
+    pub fn cached(&self) -> Option<CachedResult<T, E>> {
+        self.client
+            .get_entries_with_keys(&self.query_keys)
+            .into_iter()
+            .map(|(_, query_listeners)| sync::read(&query_listeners.value).clone())
+            .max_by_key(|cached_result| cached_result.updated_at())
+    }
+
+    /// Get the cached result, `None` if nothing is cached for these keys.
+    pub fn result(&self) -> Option<QueryResult<T, E>> {
+        self.cached().map(|cached_result| cached_result.value)
+    }
+
+    /// Get the last successful cached value, `None` if there is none.
+    pub fn data(&self) -> Option<T> {
+        self.cached()
+            .and_then(|cached_result| cached_result.data().cloned())
+    }
+
+    /// Get a snapshot of the cached result, `None` if nothing is cached for these keys.
+    pub fn state(&self) -> Option<QueryState<T, E>> {
+        self.cached().map(|cached_result| cached_result.state())
+    }
+}
+
+impl<T, E, K: Eq + Hash> Drop for UseQueryData<T, E, K> {
+    fn drop(&mut self) {
+        let mut data_listeners = self.client.data_listeners.borrow_mut();
+        if let Some(listeners) = data_listeners.get_mut(&self.query_keys) {
+            listeners.remove(&self.scope_id);
+            if listeners.is_empty() {
+                data_listeners.remove(&self.query_keys);
+            }
+        }
+    }
+}
+
+/// Read the cached value of the given **query keys** without fetching it.
+/// The component is re-rendered whenever the cached value changes, even if it
+/// is only cached after this is called. See [UseQueryData] on how to use it.
+///
+/// ## Example:
+///
+/// ```no_run
+/// let unread_count = use_query_data(cx, || vec![QueryKeys::UnreadCount]);
+/// ```
+pub fn use_query_data<T, E, K>(
+    cx: &ScopeState,
+    query_keys: impl FnOnce() -> Vec<K>,
+) -> &UseQueryData<T, E, K>
+where
+    T: 'static + Clone,
+    E: 'static + Clone,
+    K: 'static + Eq + Hash + Clone,
+{
+    let client = use_query_client(cx);
+    cx.use_hook(|| {
+        let mut query_keys = query_keys();
+        client.normalize_keys(&mut query_keys);
+        client
+            .data_listeners
+            .borrow_mut()
+            .entry(query_keys.clone())
+            .or_default()
+            .insert(cx.scope_id());
+
+        UseQueryData {
+            client: client.clone(),
+            query_keys,
+            scope_id: cx.scope_id(),
+        }
+    })
+}
@@ -0,0 +1,178 @@
+use dioxus::prelude::*;
+use futures_util::Future;
+use std::{cell::RefCell, collections::HashSet, hash::Hash, rc::Rc, sync::RwLockReadGuard};
+
+use crate::{
+    cached_result::{CachedResult, QueryState},
+    query_handle::QueryHandle,
+    result::QueryResult,
+    sync::{MaybeSend, MaybeSync},
+    use_query::QueryConfig,
+    use_query_client::use_query_client,
+};
+
+/// A query listener that can be cheaply cloned and passed down to other components,
+/// created with [use_query_signal].
+///
+/// The component creating it is not re-rendered when the query changes,
+/// only the components that read it with [QuerySignal::read] are.
+/// Two signals are equal if they are clones of each other, so components taking one as a prop
+/// are not re-rendered along with their parent.
+pub struct QuerySignal<T, E, K: Eq + Hash> {
+    inner: Rc<QuerySignalInner<T, E, K>>,
+}
+
+struct QuerySignalInner<T, E, K: Eq + Hash> {
+    handle: QueryHandle<T, E, K>,
+    /// Scopes that read the signal, re-rendered whenever the query changes
+    subscribers: RefCell<HashSet<ScopeId>>,
+}
+
+impl<T, E, K: Eq + Hash> Clone for QuerySignal<T, E, K> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<T, E, K: Eq + Hash> PartialEq for QuerySignal<T, E, K> {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.inner, &other.inner)
+    }
+}
+
+impl<T, E, K: Eq + Hash + Clone> QuerySignal<T, E, K> {
+    /// Get the current result from the query, re-rendering the given component whenever it changes.
+    pub fn read(&self, cx: &ScopeState) -> RwLockReadGuard<CachedResult<T, E>> {
+        let scope_id = cx.scope_id();
+        if self.inner.subscribers.borrow_mut().insert(scope_id) {
+            self.inner
+                .handle
+                .client
+                .data_listeners
+                .borrow_mut()
+                .entry(self.inner.handle.registry_entry.query_keys.clone())
+                .or_default()
+                .insert(scope_id);
+        }
+        self.peek()
+    }
+
+    /// Get the current result from the query without re-rendering any component when it changes.
+    pub fn peek(&self) -> RwLockReadGuard<CachedResult<T, E>> {
+        self.inner.handle.result()
+    }
+
+    /// Get a snapshot of the query, re-rendering the given component whenever it changes.
+    pub fn state(&self, cx: &ScopeState) -> QueryState<T, E>
+    where
+        T: Clone,
+        E: Clone,
+    {
+        self.read(cx).state()
+    }
+}
+
+impl<T, E, K: Eq + Hash> Drop for QuerySignalInner<T, E, K> {
+    fn drop(&mut self) {
+        let query_keys = &self.handle.registry_entry.query_keys;
+        let mut data_listeners = self.handle.client.data_listeners.borrow_mut();
+        if let Some(listeners) = data_listeners.get_mut(query_keys) {
+            for subscriber in self.subscribers.borrow().iter() {
+                listeners.remove(subscriber);
+            }
+            if listeners.is_empty() {
+                data_listeners.remove(query_keys);
+            }
+        }
+    }
+}
+
+/// Register a query listener with the given configuration, exposed as a [QuerySignal].
+/// The query is fetched and polled like with [use_query_config](crate::prelude::use_query_config),
+/// for as long as the component creating it is mounted.
+pub fn use_query_signal_config<T, E, K>(
+    cx: &ScopeState,
+    config: impl FnOnce() -> QueryConfig<T, E, K>,
+) -> &QuerySignal<T, E, K>
+where
+    T: 'static + Clone,
+    E: 'static + Clone,
+    K: 'static + Eq + Hash + Clone,
+{
+    let client = use_query_client(cx);
+    cx.use_hook(|| {
+        let config = config();
+        let refetch_on_mount = config.refetch_on_mount;
+        let refetch_interval = config.refetch_interval;
+        let in_background = config.refetch_interval_in_background;
+        let handle = client.register_query_config(config);
+        let registry_entry = handle.registry_entry.clone();
+
+        // Asynchronously initialize the query value
+        cx.spawn({
+            to_owned![client, registry_entry];
+            async move {
+                client
+                    .run_mount_query(&registry_entry, refetch_on_mount)
+                    .await;
+            }
+        });
+
+        // Poll the query, the task is dropped along with the component
+        if let Some(refetch_interval) = refetch_interval {
+            cx.spawn({
+                to_owned![client, registry_entry];
+                async move {
+                    client
+                        .run_refetch_interval(&registry_entry, &**refetch_interval, in_background)
+                        .await;
+                }
+            });
+        }
+
+        QuerySignal {
+            inner: Rc::new(QuerySignalInner {
+                handle,
+                subscribers: RefCell::default(),
+            }),
+        }
+    })
+}
+
+/// Register a query listener exposed as a [QuerySignal], so only the components reading it are re-rendered.
+///
+/// ## Example:
+///
+/// ```no_run
+/// #[inline_props]
+/// fn UserName(cx: Scope, user: QuerySignal<User, QueryError, QueryKeys>) -> Element {
+///     // Only this component is re-rendered when the user is fetched
+///     let user = user.read(cx);
+///     let name = user.data().map(|user| user.name.as_str()).unwrap_or("Loading...");
+///     render!( p { "{name}" } )
+/// }
+///
+/// fn Profile(cx: Scope) -> Element {
+///     let user = use_query_signal(cx, || vec![QueryKeys::User(0)], fetch_user);
+///     render!(
+///         ExpensiveHeader {}
+///         UserName { user: user.clone() }
+///     )
+/// }
+/// ```
+pub fn use_query_signal<T, E, K, Q, F>(
+    cx: &ScopeState,
+    query_keys: impl FnOnce() -> Vec<K>,
+    query_fn: Q,
+) -> &QuerySignal<T, E, K>
+where
+    T: 'static + Clone,
+    E: 'static + Clone,
+    K: 'static + Eq + Hash + Clone,
+    Q: 'static + Fn(Vec<K>) -> F + MaybeSend + MaybeSync,
+    F: 'static + Future<Output = QueryResult<T, E>> + MaybeSend,
+{
+    use_query_signal_config(cx, || QueryConfig::new(query_keys(), query_fn))
+}
@@ -0,0 +1,104 @@
+use dioxus::prelude::*;
+use std::hash::Hash;
+
+use crate::{
+    cached_result::CachedResult,
+    result::QueryResult,
+    use_query_client::{use_query_client, UseQueryClient},
+};
+
+/// What a query is doing, without its value, see [use_query_status].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum QueryStatus {
+    /// Nothing is cached for the query keys, yet
+    NotRegistered,
+    /// It's a lazy query that wasn't triggered yet, see [QueryConfig::fetch_on_mount](crate::prelude::QueryConfig::fetch_on_mount)
+    Idle,
+    /// It's being fetched for the first time, or after its value was discarded
+    Loading,
+    /// It's being fetched again, with a result cached already
+    Refetching,
+    /// Its last fetch failed
+    Error,
+    /// Its last fetch succeeded
+    Success,
+}
+
+impl QueryStatus {
+    pub(crate) fn of<T, E>(cached_result: &CachedResult<T, E>) -> Self {
+        if cached_result.is_idle() {
+            return Self::Idle;
+        }
+        match cached_result.value() {
+            QueryResult::Loading(None) => Self::Loading,
+            QueryResult::Loading(Some(_)) => Self::Refetching,
+            _ if cached_result.is_fetching() => Self::Refetching,
+            QueryResult::Err(_) => Self::Error,
+            QueryResult::Ok(_) => Self::Success,
+        }
+    }
+
+    /// Check if the query is being fetched.
+    pub fn is_fetching(&self) -> bool {
+        matches!(self, Self::Loading | Self::Refetching)
+    }
+}
+
+struct StatusListener<T, E, K: Eq + Hash> {
+    client: UseQueryClient<T, E, K>,
+    query_keys: Vec<K>,
+    scope_id: ScopeId,
+}
+
+impl<T, E, K: Eq + Hash> Drop for StatusListener<T, E, K> {
+    fn drop(&mut self) {
+        let mut status_listeners = self.client.status_listeners.borrow_mut();
+        if let Some(listeners) = status_listeners.get_mut(&self.query_keys) {
+            listeners.remove(&self.scope_id);
+            if listeners.is_empty() {
+                status_listeners.remove(&self.query_keys);
+            }
+        }
+    }
+}
+
+/// Follow the status of the query with the given **query keys** without its value, e.g for a syncing indicator.
+/// It never fetches nor creates cache entries, and the component is only re-rendered when the status changes,
+/// not on every update of the value. It's [QueryStatus::NotRegistered] until a query with these keys is cached.
+///
+/// ## Example:
+///
+/// ```no_run
+/// let status = use_query_status::<QueryValue, QueryError, QueryKeys>(cx, || vec![QueryKeys::Inbox]);
+/// let is_syncing = status.is_fetching();
+/// ```
+pub fn use_query_status<T, E, K>(
+    cx: &ScopeState,
+    query_keys: impl FnOnce() -> Vec<K>,
+) -> QueryStatus
+where
+    T: 'static + Clone,
+    E: 'static + Clone,
+    K: 'static + Eq + Hash + Clone,
+{
+    let client = use_query_client::<T, E, K>(cx);
+    let listener = cx.use_hook(|| {
+        let mut query_keys = query_keys();
+        client.normalize_keys(&mut query_keys);
+        StatusListener {
+            client: client.clone(),
+            query_keys,
+            scope_id: cx.scope_id(),
+        }
+    });
+
+    // Remember what is rendered, so the component is only notified once it changes
+    let status = client.query_status(&listener.query_keys);
+    client
+        .status_listeners
+        .borrow_mut()
+        .entry(listener.query_keys.clone())
+        .or_default()
+        .insert(listener.scope_id, status);
+    status
+}
@@ -0,0 +1,104 @@
+use dioxus::prelude::*;
+use dioxus_router::prelude::{use_route, Routable};
+use std::time::Duration;
+
+use crate::{
+    clock::sleep,
+    sync::{Counter, Lock, Shared},
+    use_query_client::QueryInvalidator,
+};
+
+/// How long the route has to stay the same before invalidating, by default.
+const ROUTE_REFETCH_DEBOUNCE: u64 = 50;
+
+struct RouteRefetch {
+    /// The last route seen, as its path
+    route: Lock<Option<String>>,
+    /// Bumped on every navigation, so only the last one of a burst invalidates
+    navigations: Shared<Counter>,
+}
+
+/// Invalidate the queries matching the keys returned by `keys_for` whenever the route changes,
+/// e.g to revalidate the inbox when coming back to it.
+///
+/// It invalidates through the client provided with [use_init_query_client](crate::prelude::use_init_query_client)
+/// or [provide_shared_query_client](crate::prelude::provide_shared_query_client), in the **background**,
+/// once the route stays the same for 50ms, so a burst of redirects only invalidates the keys of the last route.
+/// Nothing is invalidated for the route the component was mounted with.
+///
+/// ## Example:
+///
+/// ```no_run
+/// use_route_refetch::<Route, QueryKeys>(cx, |route| match route {
+///     Route::Inbox {} => vec![QueryKeys::Inbox],
+///     Route::User { id } => vec![QueryKeys::User(*id)],
+///     _ => vec![],
+/// });
+/// ```
+pub fn use_route_refetch<R, K>(cx: &ScopeState, keys_for: impl Fn(&R) -> Vec<K>)
+where
+    R: 'static + Routable + Clone,
+    K: 'static + Clone,
+{
+    use_route_refetch_with_debounce(cx, Duration::from_millis(ROUTE_REFETCH_DEBOUNCE), keys_for)
+}
+
+/// Same as [use_route_refetch] but waiting for the route to stay the same for `debounce` before invalidating.
+pub fn use_route_refetch_with_debounce<R, K>(
+    cx: &ScopeState,
+    debounce: Duration,
+    keys_for: impl Fn(&R) -> Vec<K>,
+) where
+    R: 'static + Routable + Clone,
+    K: 'static + Clone,
+{
+    let QueryInvalidator(invalidate) = cx
+        .use_hook(|| cx.consume_context::<QueryInvalidator<K>>())
+        .as_ref()
+        .expect("Refetching on route changes needs a query client");
+    let refetch = cx.use_hook(|| RouteRefetch {
+        route: Lock::default(),
+        navigations: Shared::default(),
+    });
+
+    let Some(route) = use_route::<R>(cx) else {
+        return;
+    };
+    let path = route.to_string();
+    let previous = refetch.route.borrow_mut().replace(path.clone());
+    if previous.is_none() || previous.as_ref() == Some(&path) {
+        return;
+    }
+
+    let keys = keys_for(&route);
+    let navigation = refetch.navigations.increment();
+    if keys.is_empty() {
+        return;
+    }
+
+    query_event!(route = %path, keys = keys.len(), "route changed, invalidating queries");
+    let invalidate = invalidate.clone();
+    let navigations = refetch.navigations.clone();
+    cx.spawn(async move {
+        // Wait for the navigation to settle, giving up if another one happened in the meantime
+        Box::into_pin(sleep(debounce)).await;
+        if navigations.get() == navigation {
+            invalidate(&keys, &[]);
+        }
+    });
+}
+
+/// Invalidate the queries matching any of the given keys on every navigation, see [use_route_refetch].
+///
+/// ## Example:
+///
+/// ```no_run
+/// use_navigation_refetch::<Route, QueryKeys>(cx, &[QueryKeys::Notifications]);
+/// ```
+pub fn use_navigation_refetch<R, K>(cx: &ScopeState, keys: &[K])
+where
+    R: 'static + Routable + Clone,
+    K: 'static + Clone,
+{
+    use_route_refetch::<R, K>(cx, |_| keys.to_vec())
+}
@@ -0,0 +1,132 @@
+use std::fmt::Debug;
+
+use crate::{
+    manager_subscription::ManagerSubscription,
+    sync::{MaybeSend, MaybeSync, Shared},
+};
+
+#[cfg(not(feature = "sync"))]
+pub type VisibilityChangeFn = dyn Fn(bool);
+#[cfg(feature = "sync")]
+pub type VisibilityChangeFn = dyn Fn(bool) + Send + Sync;
+
+/// The source of truth for whether the app is visible to the user, e.g to pause the
+/// [QueryConfig::refetch_interval](crate::prelude::QueryConfig::refetch_interval) of the queries
+/// while the tab is in the background.
+/// See [QueryClientConfig::visibility_manager](crate::prelude::QueryClientConfig::visibility_manager).
+pub trait VisibilityManager: MaybeSend + MaybeSync {
+    /// Check if the app is visible right now.
+    fn is_visible(&self) -> bool;
+
+    /// Call `on_change` with the new visibility whenever it changes, until the returned subscription is dropped.
+    fn subscribe(&self, on_change: Box<VisibilityChangeFn>) -> ManagerSubscription;
+}
+
+/// A [VisibilityManager] that is always visible, used by default.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct AlwaysVisible;
+
+impl VisibilityManager for AlwaysVisible {
+    fn is_visible(&self) -> bool {
+        true
+    }
+
+    fn subscribe(&self, _on_change: Box<VisibilityChangeFn>) -> ManagerSubscription {
+        ManagerSubscription::empty()
+    }
+}
+
+/// The visibility manager of a client.
+#[derive(Clone)]
+pub(crate) struct SharedVisibilityManager(Shared<dyn VisibilityManager>);
+
+impl SharedVisibilityManager {
+    pub(crate) fn new(visibility_manager: impl VisibilityManager + 'static) -> Self {
+        Self(Shared::new(visibility_manager))
+    }
+
+    pub(crate) fn is_visible(&self) -> bool {
+        self.0.is_visible()
+    }
+
+    pub(crate) fn subscribe(&self, on_change: Box<VisibilityChangeFn>) -> ManagerSubscription {
+        self.0.subscribe(on_change)
+    }
+}
+
+impl Default for SharedVisibilityManager {
+    fn default() -> Self {
+        Self::new(AlwaysVisible)
+    }
+}
+
+impl Debug for SharedVisibilityManager {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("SharedVisibilityManager")
+    }
+}
+
+/// The visibility manager doesn't take part in the comparison of configurations.
+impl PartialEq for SharedVisibilityManager {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+#[cfg(feature = "web")]
+pub use document::DocumentVisibilityManager;
+
+#[cfg(feature = "web")]
+mod document {
+    use wasm_bindgen::{closure::Closure, JsCast};
+
+    use super::{VisibilityChangeFn, VisibilityManager};
+    use crate::{
+        manager_subscription::ManagerSubscription,
+        sync::{Lock, Shared},
+    };
+
+    /// A [VisibilityManager] following `document.hidden` and the `visibilitychange` event of the document,
+    /// only available with the `web` feature.
+    #[derive(Clone, Copy, Debug, Default)]
+    pub struct DocumentVisibilityManager;
+
+    impl VisibilityManager for DocumentVisibilityManager {
+        fn is_visible(&self) -> bool {
+            web_sys::window()
+                .and_then(|window| window.document())
+                .map(|document| !document.hidden())
+                .unwrap_or(true)
+        }
+
+        fn subscribe(&self, on_change: Box<VisibilityChangeFn>) -> ManagerSubscription {
+            let Some(document) = web_sys::window().and_then(|window| window.document()) else {
+                return ManagerSubscription::empty();
+            };
+            let on_change = Shared::new(Lock::new(Some(Shared::<VisibilityChangeFn>::from(
+                on_change,
+            ))));
+            let listener = Closure::<dyn Fn()>::new({
+                let on_change = on_change.clone();
+                move || {
+                    // Cloned out first so it's free to unsubscribe
+                    let on_change = on_change.borrow().clone();
+                    if let Some(on_change) = on_change {
+                        on_change(DocumentVisibilityManager.is_visible());
+                    }
+                }
+            });
+            document
+                .add_event_listener_with_callback(
+                    "visibilitychange",
+                    listener.as_ref().unchecked_ref(),
+                )
+                .ok();
+            // The listener stays on the document, but the function is dropped once unsubscribed
+            listener.forget();
+            ManagerSubscription::new(move || {
+                on_change.borrow_mut().take();
+            })
+        }
+    }
+}